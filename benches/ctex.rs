@@ -0,0 +1,155 @@
+use base64::{Engine as _, engine::general_purpose};
+#[cfg(feature = "parallel")]
+use base64::write::EncoderWriter;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde::Deserialize;
+use std::hint::black_box;
+#[cfg(feature = "parallel")]
+use std::io::Write;
+
+use pica_convert::pica_texture::synthetic::synthetic_texture;
+use pica_convert::pica_texture::{PicaTexture, TextureFormat};
+use pica_convert::serialization::ctex;
+
+/// The document model the ctex reader was built on before it switched to
+/// streaming, kept here as the baseline to compare against.
+#[derive(Deserialize)]
+struct NintendoWareIntermediateFile {
+    #[serde(rename = "GraphicsContentCtr")]
+    graphics_content: GraphicsContentCtr,
+}
+
+#[derive(Deserialize)]
+struct GraphicsContentCtr {
+    #[serde(rename = "Textures")]
+    textures: Textures,
+}
+
+#[derive(Deserialize)]
+struct Textures {
+    #[serde(rename = "ImageTextureCtr")]
+    image_texture: ImageTextureCtr,
+}
+
+#[derive(Deserialize)]
+struct ImageTextureCtr {
+    #[serde(rename = "@Width", default)]
+    width: u32,
+    #[serde(rename = "@Height", default)]
+    height: u32,
+    #[serde(rename = "@Format")]
+    format: TextureFormat,
+    #[serde(rename = "Images")]
+    images: Images,
+}
+
+#[derive(Deserialize)]
+struct Images {
+    #[serde(rename = "PixelBasedImageCtr")]
+    pixel_data: String,
+}
+
+fn deserialize_serde_xml_rs(bytes: &[u8]) -> PicaTexture {
+    let ctex: NintendoWareIntermediateFile = serde_xml_rs::from_reader(bytes).unwrap();
+    let texture = ctex.graphics_content.textures.image_texture;
+    let data = general_purpose::STANDARD.decode(texture.images.pixel_data).unwrap();
+    PicaTexture::try_new(texture.format, texture.width, texture.height, data).unwrap()
+}
+
+fn bench_serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ctex_serialize");
+
+    for size in [64u32, 256, 1024] {
+        let texture = synthetic_texture(&TextureFormat::RGBA8888, size, size, 0);
+
+        group.throughput(Throughput::Bytes(texture.data().len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &texture, |b, texture| {
+            b.iter(|| ctex::serialize_to_vec(black_box(texture)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+/// How pixel data was base64-encoded before it was split into chunks, kept
+/// here as the baseline to compare against.
+#[cfg(feature = "parallel")]
+fn encode_base64_encoder_writer(data: &[u8]) -> Vec<u8> {
+    let mut text = Vec::new();
+    let mut encoder = EncoderWriter::new(&mut text, &general_purpose::STANDARD);
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap();
+    drop(encoder);
+    text
+}
+
+#[cfg(feature = "parallel")]
+fn bench_base64(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ctex_base64");
+
+    // The pixel data of a 1024x1024 RGBA8888 texture. The rest of the
+    // document is a few hundred bytes, so serializing it is almost all base64.
+    let texture = synthetic_texture(&TextureFormat::RGBA8888, 1024, 1024, 0);
+    let text = encode_base64_encoder_writer(texture.data());
+    let document = ctex::serialize_to_vec(&texture).unwrap();
+    assert!(document.windows(text.len()).any(|window| window == text));
+
+    group.throughput(Throughput::Bytes(texture.data().len() as u64));
+    group.bench_with_input(BenchmarkId::new("encoder_writer", 1024), &texture, |b, texture| {
+        b.iter(|| encode_base64_encoder_writer(black_box(texture.data())))
+    });
+    group.bench_with_input(BenchmarkId::new("chunked", 1024), &texture, |b, texture| {
+        b.iter(|| ctex::serialize_to_vec(black_box(texture)).unwrap())
+    });
+
+    group.finish();
+}
+
+fn bench_deserialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ctex_deserialize");
+
+    for size in [64u32, 256, 1024] {
+        let texture = synthetic_texture(&TextureFormat::RGBA8888, size, size, 0);
+        let bytes = ctex::serialize_to_vec(&texture).unwrap();
+        assert_eq!(deserialize_serde_xml_rs(&bytes).data(), texture.data());
+
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("serde_xml_rs", size), &bytes, |b, bytes| {
+            b.iter(|| deserialize_serde_xml_rs(black_box(bytes)))
+        });
+        group.bench_with_input(BenchmarkId::new("streaming", size), &bytes, |b, bytes| {
+            b.iter(|| ctex::deserialize_from_slice(black_box(bytes)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_read_file(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ctex_read_file");
+
+    for size in [256u32, 1024] {
+        let texture = synthetic_texture(&TextureFormat::RGBA8888, size, size, 0);
+        let path = std::env::temp_dir().join(format!("pica_convert_bench_{}.ctex", size));
+        ctex::serialize(&texture, &path).unwrap();
+
+        group.throughput(Throughput::Bytes(std::fs::metadata(&path).unwrap().len()));
+        group.bench_with_input(BenchmarkId::new("buffered", size), &path, |b, path| {
+            b.iter(|| ctex::deserialize(path).unwrap())
+        });
+        // Both sizes are over the mapping threshold
+        group.bench_with_input(BenchmarkId::new("mapped", size), &path, |b, path| {
+            b.iter(|| ctex::deserialize_mapped(path).unwrap())
+        });
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    group.finish();
+}
+
+#[cfg(feature = "parallel")]
+criterion_group!(benches, bench_serialize, bench_base64, bench_deserialize, bench_read_file);
+#[cfg(not(feature = "parallel"))]
+criterion_group!(benches, bench_serialize, bench_deserialize, bench_read_file);
+criterion_main!(benches);
@@ -0,0 +1,30 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+use pica_convert::pica_texture::synthetic::synthetic_texture;
+use pica_convert::pica_texture::{BuiltinCodec, DecodeOptions, TextureCodec, TextureFormat};
+
+const SIZES: [u32; 3] = [64, 256, 1024];
+
+fn bench_decode(c: &mut Criterion) {
+    let options = DecodeOptions::default();
+
+    let mut group = c.benchmark_group("decode");
+
+    for format in TextureFormat::ALL.into_iter().filter(|f| f.is_available()) {
+        let codec = BuiltinCodec::for_format(&format);
+
+        for size in SIZES {
+            let texture = synthetic_texture(&format, size, size, 0);
+            group.throughput(Throughput::Elements(size as u64 * size as u64));
+            group.bench_with_input(BenchmarkId::new(format.name(), size), texture.data(), |b, data| {
+                b.iter(|| codec.decode(black_box(data), size, size, &options))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
@@ -0,0 +1,53 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::RgbaImage;
+use std::hint::black_box;
+
+use pica_convert::pica_texture::synthetic::synthetic_image;
+use pica_convert::pica_texture::{encode_with_codec, BuiltinCodec, EncodeOptions, TextureFormat};
+
+const SIZES: [u32; 3] = [64, 256, 1024];
+
+fn bench_encode(c: &mut Criterion) {
+    let options = EncodeOptions::default();
+
+    let mut group = c.benchmark_group("encode");
+
+    // ETC1 is covered per quality level by the etc1 bench, and shadow and gas textures have no encoder
+    let formats = TextureFormat::ALL.into_iter()
+        .filter(|f| !matches!(f, TextureFormat::ETC1 | TextureFormat::ETC1A4 | TextureFormat::Shadow | TextureFormat::Gas));
+
+    for format in formats {
+        let codec = BuiltinCodec::for_format(&format);
+
+        for size in SIZES {
+            let img = synthetic_image(size, size, 0);
+            group.throughput(Throughput::Elements(size as u64 * size as u64));
+            group.bench_with_input(BenchmarkId::new(format.name(), size), &img, |b, img| {
+                b.iter(|| encode_with_codec(codec, black_box(img), &options).unwrap())
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_encode_batch(c: &mut Criterion) {
+    // A directory's worth of same-sized textures
+    let images: Vec<RgbaImage> = (0..32).map(|seed| synthetic_image(256, 256, seed)).collect();
+    let options = EncodeOptions::default();
+
+    let mut group = c.benchmark_group("encode_batch");
+    group.throughput(Throughput::Elements(images.len() as u64));
+
+    for format in [TextureFormat::RGBA8888, TextureFormat::RGB565, TextureFormat::L4] {
+        let codec = BuiltinCodec::for_format(&format);
+        group.bench_with_input(BenchmarkId::from_parameter(format), &images, |b, images| {
+            b.iter(|| images.iter().map(|img| encode_with_codec(codec, black_box(img), &options).unwrap().len()).sum::<usize>())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_encode, bench_encode_batch);
+criterion_main!(benches);
@@ -0,0 +1,77 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+use pica_convert::pica_texture::etc1::{quality, Backend};
+use pica_convert::pica_texture::synthetic::synthetic_image;
+use pica_convert::pica_texture::{encode_with_codec, BuiltinCodec, EncodeOptions, TextureFormat};
+
+/// The backends this build has, so each bench compares them side by side.
+fn backends() -> impl Iterator<Item = Backend> {
+    Backend::ALL.into_iter().filter(Backend::is_available)
+}
+
+const QUALITIES: [(&str, i32); 3] = [
+    ("low", quality::LOW),
+    ("medium", quality::MEDIUM),
+    ("high", quality::HIGH),
+];
+
+fn bench_compress_block(c: &mut Criterion) {
+    // Every 4x4 block of a small image, so the blocks vary like real ones do
+    let img = synthetic_image(64, 64, 0);
+    let blocks: Vec<[u8; 64]> = (0..16).flat_map(|by| (0..16).map(move |bx| (bx, by)))
+        .map(|(bx, by)| {
+            let mut block = [0u8; 64];
+            for (i, pixel) in block.chunks_exact_mut(4).enumerate() {
+                pixel.copy_from_slice(&img.get_pixel(bx * 4 + i as u32 % 4, by * 4 + i as u32 / 4).0);
+            }
+            block
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("etc1_compress_block");
+    group.throughput(Throughput::Elements(blocks.len() as u64));
+
+    for backend in backends() {
+        for (name, quality) in QUALITIES {
+            group.bench_with_input(BenchmarkId::new(backend.name(), name), &blocks, |b, blocks| {
+                b.iter(|| {
+                    for block in blocks {
+                        black_box(backend.compress_block(black_box(block), quality, false));
+                    }
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("etc1_encode");
+    group.sample_size(10);
+
+    for format in [TextureFormat::ETC1, TextureFormat::ETC1A4] {
+        let codec = BuiltinCodec::for_format(&format);
+
+        for size in [64u32, 256] {
+            let img = synthetic_image(size, size, 0);
+            group.throughput(Throughput::Elements(size as u64 * size as u64));
+
+            for etc1_backend in backends() {
+                for (name, quality) in QUALITIES {
+                    let options = EncodeOptions { etc1_quality: quality, etc1_backend, ..Default::default() };
+                    let id = BenchmarkId::new(format!("{}/{}/{}", format.name(), etc1_backend.name(), name), size);
+                    group.bench_with_input(id, &img, |b, img| {
+                        b.iter(|| encode_with_codec(codec, black_box(img), &options).unwrap())
+                    });
+                }
+            }
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_compress_block, bench_encode);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+use pica_convert::pica_texture::util::{swizzle, swizzle_scalar, SwizzleMap};
+
+const SIZE: u32 = 1024;
+
+fn bench_swizzle(c: &mut Criterion) {
+    let mut group = c.benchmark_group("swizzle");
+
+    for bytes_per_pixel in [1, 2, 4] {
+        let data: Vec<u8> = (0..SIZE as usize * SIZE as usize * bytes_per_pixel).map(|i| (i * 31 % 251) as u8).collect();
+        group.throughput(Throughput::Bytes(data.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("scalar", bytes_per_pixel), &data, |b, data| {
+            b.iter(|| swizzle_scalar(black_box(data), SIZE, SIZE, bytes_per_pixel))
+        });
+        group.bench_with_input(BenchmarkId::new("simd", bytes_per_pixel), &data, |b, data| {
+            b.iter(|| swizzle(black_box(data), SIZE, SIZE, bytes_per_pixel))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_swizzle_map(c: &mut Criterion) {
+    let mut group = c.benchmark_group("swizzle_map");
+
+    // The encoders ask for one map per 8-row band
+    group.bench_function("new", |b| b.iter(|| SwizzleMap::new(black_box(SIZE), 8)));
+    group.bench_function("cached", |b| b.iter(|| SwizzleMap::get(black_box(SIZE), 8)));
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_swizzle, bench_swizzle_map);
+criterion_main!(benches);
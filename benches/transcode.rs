@@ -0,0 +1,86 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use pica_convert::pica_texture::synthetic::synthetic_texture;
+use pica_convert::pica_texture::{decode_texture, encode_texture, DecodeOptions, EncodeOptions, PicaTexture, PicaTextureRef, TextureFormat};
+use pica_convert::serialization::ctex;
+
+/// Counts every allocation, so the transcodes can be compared by how much
+/// they copy as well as how long they take.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const SIZES: [u32; 2] = [256, 1024];
+
+/// Decodes an RGBA8888 texture out of a larger dump, then re-encodes it as
+/// RGB565 and writes it to a ctex document.
+fn transcode(texture: PicaTextureRef<'_>) -> Vec<u8> {
+    let decoded = decode_texture(texture, &DecodeOptions::default()).unwrap();
+    let encoded = encode_texture(&decoded, &TextureFormat::RGB565, &EncodeOptions::default()).unwrap();
+    ctex::serialize_to_vec(&encoded).unwrap()
+}
+
+/// The same transcode, first copying the texture's data out of the dump
+/// into an owned [`PicaTexture`], as it had to be before views existed.
+fn transcode_owned(dump: &[u8], size: u32) -> Vec<u8> {
+    let texture = PicaTexture::try_new(TextureFormat::RGBA8888, size, size, dump.to_vec()).unwrap();
+    transcode(texture.view())
+}
+
+fn transcode_borrowed(dump: &[u8], size: u32) -> Vec<u8> {
+    transcode(PicaTextureRef::try_new(TextureFormat::RGBA8888, size, size, dump).unwrap())
+}
+
+/// Runs `f` once and returns how many allocations it made, and their total size.
+fn count_allocations(f: impl FnOnce()) -> (usize, usize) {
+    let (count, bytes) = (ALLOCATIONS.load(Ordering::Relaxed), ALLOCATED_BYTES.load(Ordering::Relaxed));
+    f();
+    (ALLOCATIONS.load(Ordering::Relaxed) - count, ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes)
+}
+
+fn bench_transcode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transcode");
+
+    for size in SIZES {
+        let dump = synthetic_texture(&TextureFormat::RGBA8888, size, size, 0).into_data();
+
+        let (owned_count, owned_bytes) = count_allocations(|| { black_box(transcode_owned(&dump, size)); });
+        let (borrowed_count, borrowed_bytes) = count_allocations(|| { black_box(transcode_borrowed(&dump, size)); });
+        eprintln!(
+            "transcode {0}x{0}: owned {1} allocations ({2} bytes), borrowed {3} allocations ({4} bytes)",
+            size, owned_count, owned_bytes, borrowed_count, borrowed_bytes,
+        );
+
+        group.throughput(Throughput::Bytes(dump.len() as u64));
+        group.bench_with_input(BenchmarkId::new("owned", size), &dump, |b, dump| {
+            b.iter(|| transcode_owned(black_box(dump), size))
+        });
+        group.bench_with_input(BenchmarkId::new("borrowed", size), &dump, |b, dump| {
+            b.iter(|| transcode_borrowed(black_box(dump), size))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_transcode);
+criterion_main!(benches);
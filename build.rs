@@ -1,12 +1,53 @@
 fn main() {
+    #[cfg(feature = "etc1")]
+    compile_etc1();
+
+    #[cfg(feature = "capi")]
+    generate_header();
+}
+
+/// Builds the rg_etc1 compressor and its C wrapper.
+///
+/// The compressor uses a little single precision float math, which compilers
+/// may otherwise fuse into FMAs or keep at extended precision depending on
+/// the target. Pinning both is meant to keep compressed output the same on
+/// every target.
+#[cfg(feature = "etc1")]
+fn compile_etc1() {
     cc::Build::new()
         .cpp(true)
         .file("rg_etc1/rg_etc1.cpp")
         .file("rg_etc1/wrapper.cpp")
         .include("rg_etc1")
+        .flag_if_supported("-ffp-contract=off")
+        .flag_if_supported("-fexcess-precision=standard")
+        .flag_if_supported("/fp:precise")
         .compile("rg_etc1_wrapper");
 
     println!("cargo:rerun-if-changed=rg_etc1/rg_etc1.cpp");
     println!("cargo:rerun-if-changed=rg_etc1/wrapper.cpp");
     println!("cargo:rerun-if-changed=rg_etc1/rg_etc1.h");
-}
\ No newline at end of file
+}
+
+/// Writes the C header for the `capi` functions to `target/<profile>/include`.
+#[cfg(feature = "capi")]
+fn generate_header() {
+    use std::{env, path::PathBuf};
+
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    // OUT_DIR is target/<profile>/build/<package>-<hash>/out
+    let include_dir = out_dir.ancestors().nth(3).unwrap().join("include");
+    std::fs::create_dir_all(&include_dir).unwrap();
+
+    cbindgen::Builder::new()
+        .with_src(PathBuf::from(&crate_dir).join("src/capi.rs"))
+        .with_config(cbindgen::Config::from_file("cbindgen.toml").unwrap())
+        .generate()
+        .expect("Unable to generate C bindings")
+        .write_to_file(include_dir.join("pica_convert.h"));
+
+    println!("cargo:rerun-if-changed=src/capi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
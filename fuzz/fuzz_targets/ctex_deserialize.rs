@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pica_convert::serialization::ctex;
+
+// Any input may be rejected, but none may panic
+fuzz_target!(|data: &[u8]| {
+    let _ = ctex::deserialize_from_slice(data);
+});
@@ -364,6 +364,7 @@ namespace rg_etc1
       // 0   1   2   3   -4  -3  -2  -1
    };
    
+   // Written only by pack_etc1_block_init(), and read-only afterwards.
    static uint8 g_quant5_tab[256+16];
 
    static const int g_etc1_inten_tables[cETC1IntenModifierValues][cETC1SelectorValues] = 
@@ -1943,7 +1944,7 @@ done:
       pack_params;
       RG_ETC1_ASSERT(g_etc1_inverse_lookup[0][255]);
             
-      static uint s_next_comp[4] = { 1, 2, 0, 1 };
+      static const uint s_next_comp[4] = { 1, 2, 0, 1 };
             
       uint best_error = cUINT32_MAX, best_i = 0;
       int best_x = 0, best_packed_c1 = 0, best_packed_c2 = 0;
@@ -2033,7 +2034,7 @@ found_perfect_match:
       RG_ETC1_ASSERT(g_etc1_inverse_lookup[0][255]);
 
       pack_params;
-      static uint s_next_comp[4] = { 1, 2, 0, 1 };
+      static const uint s_next_comp[4] = { 1, 2, 0, 1 };
 
       uint best_error = cUINT32_MAX, best_i = 0;
       int best_x = 0, best_packed_c1 = 0, best_packed_c2 = 0;
@@ -2143,7 +2144,7 @@ found_perfect_match:
    static void dither_block_555(color_quad_u8* dest, const color_quad_u8* block)
    {
       int err[8],*ep1 = err,*ep2 = err+4;
-      uint8 *quant = g_quant5_tab+8;
+      const uint8 *quant = g_quant5_tab+8;
 
       memset(dest, 0xFF, sizeof(color_quad_u8)*16);
 
@@ -17,6 +17,9 @@ typedef struct {
 } etc1_pack_params_c;
 
 // Initialize the packer
+// This fills rg_etc1's global lookup tables, so it must run once before any
+// other call. Afterwards the tables are only read, and compressing and
+// decompressing blocks is safe from any number of threads at once.
 void etc1_pack_init() {
     rg_etc1::pack_etc1_block_init();
 }
@@ -0,0 +1,103 @@
+//! Reading the frames of animated GIF and APNG images, so each can be
+//! encoded as its own texture.
+
+use std::{fs::File, io::BufReader, path::{Path, PathBuf}};
+
+use image::{AnimationDecoder, ImageFormat, ImageReader, ImageResult, RgbaImage};
+use image::codecs::{gif::GifDecoder, png::PngDecoder};
+use serde::{Deserialize, Serialize};
+
+/// One frame of an animation, composited onto the full canvas.
+pub struct AnimationFrame {
+    pub image: RgbaImage,
+    /// How long the frame is shown for, in milliseconds.
+    pub delay_ms: u32,
+}
+
+/// Reads every frame of an animated GIF or APNG image.
+///
+/// Returns `None` for images in other formats, and for animations with only
+/// one frame, which are the same as a still image.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be opened or its frames can't be decoded.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::animation::read_animation;
+/// let frames = read_animation("tests/fixtures/animation/three_frames.gif".as_ref()).unwrap().unwrap();
+/// assert_eq!(frames.len(), 3);
+/// assert_eq!(frames.iter().map(|frame| frame.delay_ms).collect::<Vec<_>>(), [100, 200, 300]);
+/// assert!(frames.iter().all(|frame| frame.image.dimensions() == (8, 8)));
+/// assert_eq!(frames[0].image.get_pixel(1, 0).0, [255, 0, 0, 255]);
+/// assert_eq!(frames[2].image.get_pixel(1, 0).0, [0, 0, 255, 255]);
+///
+/// // Still images aren't animations
+/// let still = std::env::temp_dir().join(format!("pica-convert-doc-still-{}.png", std::process::id()));
+/// image::RgbaImage::new(4, 4).save(&still).unwrap();
+/// assert!(read_animation(&still).unwrap().is_none());
+/// # std::fs::remove_file(&still).unwrap();
+/// ```
+pub fn read_animation(path: &Path) -> ImageResult<Option<Vec<AnimationFrame>>> {
+    let format = ImageReader::open(path)?.with_guessed_format()?.format();
+    let reader = || -> ImageResult<_> { Ok(BufReader::new(File::open(path)?)) };
+
+    let frames = match format {
+        Some(ImageFormat::Gif) => GifDecoder::new(reader()?)?.into_frames().collect_frames()?,
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(reader()?)?;
+            if !decoder.is_apng()? {
+                return Ok(None);
+            }
+            decoder.apng()?.into_frames().collect_frames()?
+        }
+        _ => return Ok(None),
+    };
+    if frames.len() < 2 {
+        return Ok(None);
+    }
+
+    Ok(Some(frames.into_iter().map(|frame| {
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        AnimationFrame {
+            delay_ms: (numerator as f64 / denominator.max(1) as f64).round() as u32,
+            image: frame.into_buffer(),
+        }
+    }).collect()))
+}
+
+/// Names the output for each of `count` frames after `output_path`, as
+/// `{stem}_{index}` with the index zero-padded to at least three digits.
+///
+/// # Example
+///
+/// ```
+/// # use std::path::PathBuf;
+/// # use pica_convert::animation::frame_paths;
+/// assert_eq!(frame_paths("out/intro.ctex".as_ref(), 2), [PathBuf::from("out/intro_000.ctex"), PathBuf::from("out/intro_001.ctex")]);
+/// assert_eq!(frame_paths("intro.ctex".as_ref(), 1200)[1199], PathBuf::from("intro_1199.ctex"));
+/// ```
+pub fn frame_paths(output_path: &Path, count: usize) -> Vec<PathBuf> {
+    let stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output_path.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+    let digits = count.saturating_sub(1).to_string().len().max(3);
+
+    (0..count).map(|index| output_path.with_file_name(format!("{}_{:0digits$}{}", stem, index, extension))).collect()
+}
+
+/// The frames an animation was encoded into, in order, with how long each is shown.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameList {
+    pub frames: Vec<FrameEntry>,
+}
+
+/// One encoded frame in a [`FrameList`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameEntry {
+    /// The frame's file name.
+    pub file: String,
+    /// How long the frame is shown for, in milliseconds.
+    pub delay_ms: u32,
+}
@@ -0,0 +1,503 @@
+//! Packing many small images into shared texture atlases.
+
+use std::fmt;
+
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::pica_texture::limits::MAX_DIMENSION;
+
+/// How [`pack`] lays rectangles out on atlas canvases.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackOptions {
+    /// The widest an atlas can be.
+    pub max_width: u32,
+    /// The tallest an atlas can be.
+    pub max_height: u32,
+    /// Empty pixels left between neighbouring rectangles, so filtering
+    /// doesn't bleed one into the next.
+    pub padding: u32,
+    /// Whether each atlas is grown to power of two dimensions.
+    pub power_of_two: bool,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self {
+            max_width: MAX_DIMENSION,
+            max_height: MAX_DIMENSION,
+            padding: 2,
+            power_of_two: true,
+        }
+    }
+}
+
+/// Where [`pack`] put one rectangle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Placement {
+    /// The index of the atlas the rectangle is on.
+    pub atlas: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Placement {
+    /// Returns whether the two placements share any pixels.
+    pub fn overlaps(&self, other: &Placement) -> bool {
+        self.atlas == other.atlas
+            && self.x < other.x + other.width && other.x < self.x + self.width
+            && self.y < other.y + other.height && other.y < self.y + self.height
+    }
+}
+
+/// The result of [`pack`]: where each rectangle went, and how big each atlas is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Packing {
+    /// One placement per rectangle, in the order they were given.
+    pub placements: Vec<Placement>,
+    /// The `(width, height)` of each atlas.
+    pub atlas_sizes: Vec<(u32, u32)>,
+}
+
+/// Errors from packing rectangles into atlases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AtlasError {
+    /// A rectangle is larger than an atlas can be, so it fits on none of them.
+    TooLarge {
+        index: usize,
+        width: u32,
+        height: u32,
+        max_width: u32,
+        max_height: u32,
+    },
+    /// A sprite's rectangle reaches outside the atlas it's on.
+    OutOfBounds {
+        name: String,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        atlas_width: u32,
+        atlas_height: u32,
+    },
+    /// A line of a CSV manifest couldn't be read. Lines count from 1.
+    InvalidCsv {
+        line: usize,
+        reason: String,
+    },
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AtlasError::TooLarge { index, width, height, max_width, max_height } => {
+                write!(f, "Image {} is {}x{}, larger than the {}x{} atlas limit", index, width, height, max_width, max_height)
+            }
+            AtlasError::OutOfBounds { name, x, y, width, height, atlas_width, atlas_height } => {
+                write!(f, "Sprite '{}' at ({}, {}) is {}x{}, which doesn't fit in the {}x{} atlas", name, x, y, width, height, atlas_width, atlas_height)
+            }
+            AtlasError::InvalidCsv { line, reason } => write!(f, "Manifest line {} {}", line, reason),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+/// A row of rectangles along the width of an atlas.
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// Where the next rectangle on the shelf goes.
+    x: u32,
+}
+
+/// Packs rectangles of the given `(width, height)` sizes onto as few atlases
+/// as it can.
+///
+/// This is a shelf packer: rectangles are placed tallest first, left to
+/// right along rows as tall as the first rectangle in them. A rectangle goes
+/// on the first shelf of the first atlas with room for it, and a new atlas is
+/// started when none has any. Ties are broken by input order, so the same
+/// sizes always pack the same way.
+///
+/// # Errors
+///
+/// Returns [`AtlasError::TooLarge`] if a rectangle doesn't fit on an empty atlas.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::atlas::{pack, AtlasError, PackOptions, Placement};
+/// let sizes = [(64, 32), (30, 30), (100, 10), (16, 64), (64, 64), (8, 8), (200, 40), (40, 200), (1, 1), (33, 17)];
+/// let options = PackOptions { max_width: 256, max_height: 256, padding: 2, ..Default::default() };
+///
+/// let packing = pack(&sizes, &options).unwrap();
+/// assert_eq!(packing, pack(&sizes, &options).unwrap());
+/// assert_eq!(packing.atlas_sizes, vec![(256, 256)]);
+///
+/// // Every rectangle is the size it asked for, inside its atlas, and clear of the rest by the padding
+/// let padded = |p: &Placement| Placement { width: p.width + 2, height: p.height + 2, ..*p };
+/// for (i, (placement, (width, height))) in packing.placements.iter().zip(sizes).enumerate() {
+///     assert_eq!((placement.width, placement.height), (width, height));
+///     let (atlas_width, atlas_height) = packing.atlas_sizes[placement.atlas];
+///     assert!(placement.x + width <= atlas_width && placement.y + height <= atlas_height);
+///
+///     for other in &packing.placements[i + 1..] {
+///         assert!(!padded(placement).overlaps(other) && !padded(other).overlaps(placement), "{:?} {:?}", placement, other);
+///     }
+/// }
+///
+/// // The tallest goes first, in the top left corner
+/// assert_eq!((packing.placements[7].x, packing.placements[7].y), (0, 0));
+///
+/// // What doesn't fit spills onto more atlases, each only as big as it needs
+/// let packing = pack(&[(100, 100); 5], &PackOptions { max_width: 256, max_height: 128, padding: 0, ..Default::default() }).unwrap();
+/// assert_eq!(packing.atlas_sizes, vec![(256, 128), (256, 128), (128, 128)]);
+/// assert_eq!(packing.placements.iter().map(|p| p.atlas).collect::<Vec<_>>(), vec![0, 0, 1, 1, 2]);
+///
+/// let err = pack(&[(8, 8), (300, 8)], &options).unwrap_err();
+/// assert_eq!(err, AtlasError::TooLarge { index: 1, width: 300, height: 8, max_width: 256, max_height: 256 });
+/// ```
+pub fn pack(sizes: &[(u32, u32)], options: &PackOptions) -> Result<Packing, AtlasError> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&index| (std::cmp::Reverse(sizes[index].1), std::cmp::Reverse(sizes[index].0), index));
+
+    let padding = options.padding;
+    let mut atlases: Vec<Vec<Shelf>> = Vec::new();
+    let mut placements = vec![None; sizes.len()];
+
+    for index in order {
+        let (width, height) = sizes[index];
+        if width > options.max_width || height > options.max_height {
+            return Err(AtlasError::TooLarge { index, width, height, max_width: options.max_width, max_height: options.max_height });
+        }
+
+        let fits_across = |x: u32| x + width <= options.max_width;
+        let next_shelf_y = |shelves: &[Shelf]| shelves.last().map_or(0, |shelf| shelf.y + shelf.height + padding);
+
+        // The first existing shelf with room, or a new shelf below the others
+        let spot = atlases.iter().enumerate().find_map(|(atlas, shelves)| {
+            shelves.iter().position(|shelf| height <= shelf.height && fits_across(shelf.x))
+                .map(|shelf| (atlas, Some(shelf)))
+                .or_else(|| (next_shelf_y(shelves) + height <= options.max_height).then_some((atlas, None)))
+        });
+        let (atlas, shelf) = spot.unwrap_or_else(|| {
+            atlases.push(Vec::new());
+            (atlases.len() - 1, None)
+        });
+
+        let shelves = &mut atlases[atlas];
+        let shelf = match shelf {
+            Some(shelf) => &mut shelves[shelf],
+            None => {
+                let y = next_shelf_y(shelves);
+                shelves.push(Shelf { y, height, x: 0 });
+                shelves.last_mut().unwrap()
+            }
+        };
+
+        placements[index] = Some(Placement { atlas, x: shelf.x, y: shelf.y, width, height });
+        shelf.x += width + padding;
+    }
+
+    let placements: Vec<Placement> = placements.into_iter().flatten().collect();
+
+    // Each atlas only needs to cover what's on it
+    let atlas_sizes = (0..atlases.len()).map(|atlas| {
+        let (width, height) = placements.iter()
+            .filter(|placement| placement.atlas == atlas)
+            .fold((1, 1), |(width, height), placement| (width.max(placement.x + placement.width), height.max(placement.y + placement.height)));
+        if options.power_of_two {
+            (width.next_power_of_two().min(options.max_width), height.next_power_of_two().min(options.max_height))
+        } else {
+            (width, height)
+        }
+    }).collect();
+
+    Ok(Packing { placements, atlas_sizes })
+}
+
+/// Where each image packed by [`build_atlases`] ended up, written alongside
+/// the atlases as JSON.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AtlasManifest {
+    /// The atlases, in index order.
+    pub atlases: Vec<AtlasEntry>,
+    /// Every packed image, sorted by name.
+    pub sprites: Vec<Sprite>,
+}
+
+/// One atlas in an [`AtlasManifest`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AtlasEntry {
+    /// The atlas's file name.
+    pub file: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// One image's place in an atlas.
+///
+/// Pixel and UV coordinates are both measured from the top left corner,
+/// the way the image was oriented before it was packed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sprite {
+    /// The name of the image, usually its file stem.
+    pub name: String,
+    /// The index of the atlas the image is on.
+    pub atlas: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// The image's corners as fractions of the atlas size.
+    pub uv: UvRect,
+}
+
+/// A rectangle in normalized texture coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UvRect {
+    pub u0: f64,
+    pub v0: f64,
+    pub u1: f64,
+    pub v1: f64,
+}
+
+/// Packs named images into atlases with [`pack`], copying each into place on
+/// a transparent canvas.
+///
+/// The manifest names each atlas with `file_name`, called with its index.
+///
+/// # Errors
+///
+/// Returns [`AtlasError::TooLarge`] if an image doesn't fit on an empty atlas.
+///
+/// # Example
+///
+/// ```
+/// # use image::{Rgba, RgbaImage};
+/// # use pica_convert::atlas::{build_atlases, PackOptions};
+/// let images = vec![
+///     ("red".to_string(), RgbaImage::from_pixel(40, 20, Rgba([255, 0, 0, 255]))),
+///     ("blue".to_string(), RgbaImage::from_pixel(10, 30, Rgba([0, 0, 255, 255]))),
+/// ];
+/// let (atlases, manifest) = build_atlases(&images, &PackOptions::default(), |index| format!("ui_{}.ctex", index)).unwrap();
+///
+/// assert_eq!(atlases.len(), 1);
+/// assert_eq!(atlases[0].dimensions(), (64, 32));
+/// assert_eq!(manifest.atlases[0].file, "ui_0.ctex");
+///
+/// // Sprites are listed by name, in pixels and UVs
+/// let blue = &manifest.sprites[0];
+/// assert_eq!((blue.name.as_str(), blue.x, blue.y, blue.width, blue.height), ("blue", 0, 0, 10, 30));
+/// let red = &manifest.sprites[1];
+/// assert_eq!((red.name.as_str(), red.x, red.y), ("red", 12, 0));
+/// assert_eq!((red.uv.u0, red.uv.v0, red.uv.u1, red.uv.v1), (12.0 / 64.0, 0.0, 52.0 / 64.0, 20.0 / 32.0));
+///
+/// assert_eq!(atlases[0].get_pixel(12, 0), &Rgba([255, 0, 0, 255]));
+/// assert_eq!(atlases[0].get_pixel(11, 0), &Rgba([0, 0, 0, 0]));
+///
+/// // The manifest round-trips through JSON
+/// let json = serde_json::to_string(&manifest).unwrap();
+/// assert_eq!(serde_json::from_str::<pica_convert::atlas::AtlasManifest>(&json).unwrap(), manifest);
+/// ```
+pub fn build_atlases(images: &[(String, RgbaImage)], options: &PackOptions, file_name: impl Fn(usize) -> String) -> Result<(Vec<RgbaImage>, AtlasManifest), AtlasError> {
+    let sizes: Vec<(u32, u32)> = images.iter().map(|(_, image)| image.dimensions()).collect();
+    let packing = pack(&sizes, options)?;
+
+    let mut atlases: Vec<RgbaImage> = packing.atlas_sizes.iter().map(|&(width, height)| RgbaImage::new(width, height)).collect();
+    for ((_, image), placement) in images.iter().zip(&packing.placements) {
+        image::imageops::replace(&mut atlases[placement.atlas], image, placement.x as i64, placement.y as i64);
+    }
+
+    let mut sprites: Vec<Sprite> = images.iter().zip(&packing.placements).map(|((name, _), placement)| {
+        let (atlas_width, atlas_height) = packing.atlas_sizes[placement.atlas];
+        Sprite {
+            name: name.clone(),
+            atlas: placement.atlas,
+            x: placement.x,
+            y: placement.y,
+            width: placement.width,
+            height: placement.height,
+            uv: UvRect {
+                u0: placement.x as f64 / atlas_width as f64,
+                v0: placement.y as f64 / atlas_height as f64,
+                u1: (placement.x + placement.width) as f64 / atlas_width as f64,
+                v1: (placement.y + placement.height) as f64 / atlas_height as f64,
+            },
+        }
+    }).collect();
+    sprites.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let manifest = AtlasManifest {
+        atlases: packing.atlas_sizes.iter().enumerate()
+            .map(|(index, &(width, height))| AtlasEntry { file: file_name(index), width, height })
+            .collect(),
+        sprites,
+    };
+    Ok((atlases, manifest))
+}
+
+impl AtlasManifest {
+    /// Parses a manifest from CSV, one sprite per line as
+    /// `name,atlas,x,y,width,height`, under a header line naming those columns.
+    ///
+    /// A CSV manifest doesn't list its atlases, so their sizes aren't known
+    /// and every sprite's UVs are left at zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AtlasError::InvalidCsv`] for a missing header, a line
+    /// without six fields, or a field that isn't a number.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::atlas::{AtlasError, AtlasManifest};
+    /// let manifest = AtlasManifest::from_csv("name,atlas,x,y,width,height\nbutton,0,0,0,32,16\nicon, 0, 34, 0, 16, 16\n").unwrap();
+    /// assert!(manifest.atlases.is_empty());
+    /// assert_eq!(manifest.sprites[1].name, "icon");
+    /// assert_eq!((manifest.sprites[1].x, manifest.sprites[1].width), (34, 16));
+    ///
+    /// let err = AtlasManifest::from_csv("name,atlas,x,y,width,height\nbutton,0,0,0,32\n").unwrap_err();
+    /// assert_eq!(err, AtlasError::InvalidCsv { line: 2, reason: "has 5 fields, expected 6".to_string() });
+    /// assert!(AtlasManifest::from_csv("button,0,0,0,32,16").is_err());
+    /// ```
+    pub fn from_csv(text: &str) -> Result<Self, AtlasError> {
+        const COLUMNS: [&str; 6] = ["name", "atlas", "x", "y", "width", "height"];
+
+        let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        let header: Vec<String> = lines.next().map(|(_, line)| line.split(',').map(|field| field.trim().to_ascii_lowercase()).collect()).unwrap_or_default();
+        if header != COLUMNS {
+            return Err(AtlasError::InvalidCsv { line: 1, reason: format!("should be the header '{}'", COLUMNS.join(",")) });
+        }
+
+        let sprites = lines.map(|(index, line)| {
+            let invalid = |reason: String| AtlasError::InvalidCsv { line: index + 1, reason };
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [name, atlas, x, y, width, height] = fields[..] else {
+                return Err(invalid(format!("has {} fields, expected 6", fields.len())));
+            };
+            let number = |column: &str, value: &str| value.parse::<u32>()
+                .map_err(|e| invalid(format!("has an invalid {} '{}': {}", column, value, e)));
+
+            Ok(Sprite {
+                name: name.to_string(),
+                atlas: number("atlas", atlas)? as usize,
+                x: number("x", x)?,
+                y: number("y", y)?,
+                width: number("width", width)?,
+                height: number("height", height)?,
+                uv: UvRect { u0: 0.0, v0: 0.0, u1: 0.0, v1: 0.0 },
+            })
+        }).collect::<Result<_, _>>()?;
+
+        Ok(Self { atlases: Vec::new(), sprites })
+    }
+
+    /// Returns the sprites on the atlas stored in `file`, or `None` if the
+    /// manifest doesn't list it.
+    ///
+    /// Manifests that don't list their atlases, like CSV ones, only give each
+    /// sprite's atlas index. The index is then taken from the end of the file
+    /// name, as [`build_atlases`] names atlases (`ui_1.ctex` is atlas 1). A
+    /// file name without one matches only when every sprite is on the same atlas.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::atlas::AtlasManifest;
+    /// let manifest = AtlasManifest::from_csv("name,atlas,x,y,width,height\nbutton,0,0,0,32,16\nicon,1,0,0,16,16\nstar,1,16,0,8,8\n").unwrap();
+    /// let names = |file| manifest.sprites_on(file).map(|sprites| sprites.iter().map(|sprite| sprite.name.as_str()).collect::<Vec<_>>());
+    /// assert_eq!(names("ui_0.ctex").unwrap(), ["button"]);
+    /// assert_eq!(names("ui_1.ctex").unwrap(), ["icon", "star"]);
+    /// assert!(names("ui_2.ctex").unwrap().is_empty());
+    /// assert!(names("ui.ctex").is_none());
+    ///
+    /// let single = AtlasManifest::from_csv("name,atlas,x,y,width,height\nbutton,0,0,0,32,16\n").unwrap();
+    /// assert_eq!(single.sprites_on("ui.ctex").unwrap().len(), 1);
+    /// ```
+    pub fn sprites_on(&self, file: &str) -> Option<Vec<&Sprite>> {
+        let atlas = match self.atlases.iter().position(|entry| entry.file == file) {
+            Some(atlas) => atlas,
+            None if !self.atlases.is_empty() => return None,
+            None => match atlas_index(file) {
+                Some(atlas) => atlas,
+                None => {
+                    let first = self.sprites.first().map_or(0, |sprite| sprite.atlas);
+                    if !self.sprites.iter().all(|sprite| sprite.atlas == first) {
+                        return None;
+                    }
+                    first
+                }
+            },
+        };
+        Some(self.sprites.iter().filter(|sprite| sprite.atlas == atlas).collect())
+    }
+}
+
+/// The atlas index at the end of a file's stem, as in `ui_1.ctex`.
+fn atlas_index(file: &str) -> Option<usize> {
+    let stem = std::path::Path::new(file).file_stem()?.to_str()?;
+    let (_, index) = stem.rsplit_once('_')?;
+    index.parse().ok()
+}
+
+/// Copies a sprite's rectangle out of a decoded atlas, given as
+/// `atlas_width * atlas_height` RGBA pixels.
+///
+/// # Errors
+///
+/// Returns [`AtlasError::OutOfBounds`] if the rectangle reaches outside the atlas.
+///
+/// # Example
+///
+/// Images packed with [`build_atlases`] come back out unchanged:
+///
+/// ```
+/// # use image::{Rgba, RgbaImage};
+/// # use pica_convert::atlas::{build_atlases, extract_region, AtlasError, PackOptions};
+/// let images: Vec<(String, RgbaImage)> = [(30, 20), (8, 50), (64, 64), (17, 3)].into_iter().enumerate()
+///     .map(|(i, (width, height))| (format!("sprite{}", i), RgbaImage::from_fn(width, height, |x, y| Rgba([x as u8, y as u8, i as u8, 255]))))
+///     .collect();
+/// let (atlases, manifest) = build_atlases(&images, &PackOptions::default(), |index| format!("ui_{}.ctex", index)).unwrap();
+///
+/// for sprite in manifest.sprites_on("ui_0.ctex").unwrap() {
+///     let atlas = &atlases[sprite.atlas];
+///     let region = extract_region(atlas.as_raw(), atlas.width(), atlas.height(), sprite).unwrap();
+///     let (_, original) = images.iter().find(|(name, _)| *name == sprite.name).unwrap();
+///     assert_eq!(&region, original);
+/// }
+/// assert!(manifest.sprites_on("other.ctex").is_none());
+///
+/// let mut outside = manifest.sprites[0].clone();
+/// outside.x = atlases[0].width() - 4;
+/// let err = extract_region(atlases[0].as_raw(), atlases[0].width(), atlases[0].height(), &outside).unwrap_err();
+/// assert!(matches!(err, AtlasError::OutOfBounds { .. }), "{}", err);
+/// ```
+pub fn extract_region(pixels: &[u8], atlas_width: u32, atlas_height: u32, sprite: &Sprite) -> Result<RgbaImage, AtlasError> {
+    let fits = |start: u32, size: u32, limit: u32| start.checked_add(size).is_some_and(|end| end <= limit);
+    let row_bytes = atlas_width as usize * 4;
+    if !fits(sprite.x, sprite.width, atlas_width) || !fits(sprite.y, sprite.height, atlas_height) || pixels.len() < row_bytes * atlas_height as usize {
+        return Err(AtlasError::OutOfBounds {
+            name: sprite.name.clone(),
+            x: sprite.x,
+            y: sprite.y,
+            width: sprite.width,
+            height: sprite.height,
+            atlas_width,
+            atlas_height,
+        });
+    }
+
+    let (x, width) = (sprite.x as usize * 4, sprite.width as usize * 4);
+    let region: Vec<u8> = pixels.chunks_exact(row_bytes)
+        .skip(sprite.y as usize)
+        .take(sprite.height as usize)
+        .flat_map(|row| &row[x..x + width])
+        .copied()
+        .collect();
+    Ok(RgbaImage::from_raw(sprite.width, sprite.height, region).expect("the region is whole rows of the sprite's width"))
+}
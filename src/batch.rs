@@ -0,0 +1,582 @@
+//! Planning which output file each input of a conversion is written to, and
+//! which inputs a rerun retries.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::stage::{Stage, StageError};
+
+/// An input that bulk processing skips, either while scanning for inputs or
+/// because it can't be given an output file of its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkippedInput {
+    /// A symbolic link, while links aren't being followed.
+    Symlink(PathBuf),
+    /// Something other than a regular file or directory, such as a FIFO or device.
+    SpecialFile(PathBuf),
+    /// A link to a directory that's already been scanned.
+    AlreadyScanned(PathBuf),
+    /// A link whose target can't be read, or a directory whose canonical
+    /// path can't be found.
+    Unreadable { path: PathBuf, reason: String },
+    /// The path has no file stem to name the output after.
+    NoStem(PathBuf),
+    /// An earlier input is already written to the same output path.
+    Collision {
+        input: PathBuf,
+        earlier: PathBuf,
+        output: PathBuf,
+    },
+}
+
+impl fmt::Display for SkippedInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkippedInput::Symlink(path) => {
+                write!(f, "Skipping '{}': it's a symbolic link (use --follow-symlinks to follow it)", path.display())
+            }
+            SkippedInput::SpecialFile(path) => write!(f, "Skipping '{}': it isn't a regular file", path.display()),
+            SkippedInput::AlreadyScanned(path) => {
+                write!(f, "Skipping '{}': it links to a directory that's already scanned", path.display())
+            }
+            SkippedInput::Unreadable { path, reason } => write!(f, "Skipping '{}': {}", path.display(), reason),
+            SkippedInput::NoStem(input) => {
+                write!(f, "Skipping '{}': it has no file name to name the output after", input.display())
+            }
+            SkippedInput::Collision { input, earlier, output } => {
+                write!(f, "Skipping '{}': '{}' is already written to '{}'", input.display(), earlier.display(), output.display())
+            }
+        }
+    }
+}
+
+/// How [`scan_inputs`] walks a directory.
+#[derive(Clone, Debug, Default)]
+pub struct ScanOptions {
+    /// Whether to scan subdirectories too.
+    pub recursive: bool,
+    /// Whether to follow symbolic links, rather than skipping them.
+    pub follow_symlinks: bool,
+}
+
+/// Lists the input files in `dir`.
+///
+/// Symbolic links are skipped unless [`ScanOptions::follow_symlinks`] is set,
+/// so links can't pull in files from outside the tree. Followed links to
+/// directories are only scanned if their target hasn't been already, which
+/// stops link cycles. FIFOs, devices and sockets are always skipped, and
+/// directories are only entered with [`ScanOptions::recursive`].
+///
+/// Each directory's entries are visited in sorted order, so the files come
+/// back in the same order every time.
+///
+/// # Errors
+///
+/// Returns an error if a directory can't be read.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(unix)] {
+/// # use std::{fs, os::unix::fs::symlink};
+/// # use pica_convert::batch::{scan_inputs, ScanOptions, SkippedInput};
+/// let root = std::env::temp_dir().join(format!("pica-convert-doc-scan-{}", std::process::id()));
+/// let outside = std::env::temp_dir().join(format!("pica-convert-doc-outside-{}", std::process::id()));
+/// fs::create_dir_all(root.join("sub")).unwrap();
+/// fs::create_dir_all(&outside).unwrap();
+/// for file in [root.join("b.png"), root.join("a.png"), root.join("sub/c.png"), outside.join("d.png")] {
+///     fs::write(file, b"").unwrap();
+/// }
+/// symlink(outside.join("d.png"), root.join("link.png")).unwrap();
+/// symlink(&root, root.join("sub/loop")).unwrap();
+/// symlink(root.join("missing.png"), root.join("broken.png")).unwrap();
+///
+/// // Links are skipped by default, and directories only entered when recursive
+/// let (files, skipped) = scan_inputs(&root, &ScanOptions::default()).unwrap();
+/// assert_eq!(files, [root.join("a.png"), root.join("b.png")]);
+/// assert_eq!(skipped, [SkippedInput::Symlink(root.join("broken.png")), SkippedInput::Symlink(root.join("link.png"))]);
+///
+/// // Following them, the cycle back to the root is only scanned once
+/// let options = ScanOptions { recursive: true, follow_symlinks: true };
+/// let (files, skipped) = scan_inputs(&root, &options).unwrap();
+/// assert_eq!(files, [root.join("a.png"), root.join("b.png"), root.join("link.png"), root.join("sub/c.png")]);
+/// assert!(matches!(&skipped[0], SkippedInput::Unreadable { path, .. } if *path == root.join("broken.png")));
+/// assert_eq!(skipped[1], SkippedInput::AlreadyScanned(root.join("sub/loop")));
+///
+/// // FIFOs are never read
+/// if std::process::Command::new("mkfifo").arg(root.join("fifo")).status().is_ok_and(|status| status.success()) {
+///     let (_, skipped) = scan_inputs(&root, &options).unwrap();
+///     assert!(skipped.contains(&SkippedInput::SpecialFile(root.join("fifo"))));
+/// }
+/// # fs::remove_dir_all(&root).unwrap();
+/// # fs::remove_dir_all(&outside).unwrap();
+/// # }
+/// ```
+pub fn scan_inputs(dir: &Path, options: &ScanOptions) -> io::Result<(Vec<PathBuf>, Vec<SkippedInput>)> {
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+    let mut visited = HashSet::from([fs::canonicalize(dir)?]);
+
+    scan_dir(dir, options, &mut visited, &mut files, &mut skipped)?;
+    Ok((files, skipped))
+}
+
+/// Scans one directory for [`scan_inputs`], recursing into subdirectories
+/// that aren't in `visited` yet.
+fn scan_dir(dir: &Path, options: &ScanOptions, visited: &mut HashSet<PathBuf>, files: &mut Vec<PathBuf>, skipped: &mut Vec<SkippedInput>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.path());
+
+    for entry in entries {
+        let path = entry.path();
+        let mut file_type = entry.file_type()?;
+
+        if file_type.is_symlink() {
+            if !options.follow_symlinks {
+                skipped.push(SkippedInput::Symlink(path));
+                continue;
+            }
+            match fs::metadata(&path) {
+                Ok(metadata) => file_type = metadata.file_type(),
+                Err(e) => {
+                    skipped.push(SkippedInput::Unreadable { path, reason: e.to_string() });
+                    continue;
+                }
+            }
+        }
+
+        if file_type.is_file() {
+            files.push(path);
+        } else if file_type.is_dir() {
+            if options.recursive {
+                let canonical = match fs::canonicalize(&path) {
+                    Ok(canonical) => canonical,
+                    Err(e) => {
+                        skipped.push(SkippedInput::Unreadable { path, reason: e.to_string() });
+                        continue;
+                    }
+                };
+                if !visited.insert(canonical) {
+                    skipped.push(SkippedInput::AlreadyScanned(path));
+                    continue;
+                }
+                scan_dir(&path, options, visited, files, skipped)?;
+            }
+        } else {
+            skipped.push(SkippedInput::SpecialFile(path));
+        }
+    }
+
+    Ok(())
+}
+
+/// Works out the output path in `output_dir` for each of `inputs`, named
+/// after its file stem with `extension`.
+///
+/// Names are kept as `OsStr`s throughout, so names that aren't valid UTF-8
+/// stay distinct. Inputs without a file stem, and inputs whose output path
+/// an earlier input already claimed, are returned as [`SkippedInput`]s
+/// instead, so every output is written by exactly one input.
+///
+/// # Example
+///
+/// ```
+/// # use std::path::{Path, PathBuf};
+/// # use pica_convert::batch::{output_paths, SkippedInput};
+/// let inputs: Vec<PathBuf> = ["in/grass.png", "in/.hidden", "in/..", "in/rock.png", "in/grass.jpg"].iter().map(PathBuf::from).collect();
+/// let (planned, skipped) = output_paths(&inputs, Path::new("out"), "ctex");
+///
+/// assert_eq!(planned, vec![
+///     (PathBuf::from("in/grass.png"), PathBuf::from("out/grass.ctex")),
+///     (PathBuf::from("in/.hidden"), PathBuf::from("out/.hidden.ctex")),
+///     (PathBuf::from("in/rock.png"), PathBuf::from("out/rock.ctex")),
+/// ]);
+/// assert_eq!(skipped, vec![
+///     SkippedInput::NoStem(PathBuf::from("in/..")),
+///     SkippedInput::Collision {
+///         input: PathBuf::from("in/grass.jpg"),
+///         earlier: PathBuf::from("in/grass.png"),
+///         output: PathBuf::from("out/grass.ctex"),
+///     },
+/// ]);
+/// ```
+///
+/// Names that would be mangled by a lossy conversion to UTF-8 don't collide:
+///
+/// ```
+/// # #[cfg(unix)] {
+/// # use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::{Path, PathBuf}};
+/// # use pica_convert::batch::output_paths;
+/// let inputs = [PathBuf::from(OsStr::from_bytes(b"\xFE.png")), PathBuf::from(OsStr::from_bytes(b"\xFF.png"))];
+/// assert_eq!(inputs[0].to_string_lossy(), inputs[1].to_string_lossy());
+///
+/// let (planned, skipped) = output_paths(&inputs, Path::new("out"), "png");
+/// assert_eq!(planned.len(), 2);
+/// assert!(skipped.is_empty());
+/// # }
+/// ```
+pub fn output_paths(inputs: &[PathBuf], output_dir: &Path, extension: &str) -> (Vec<(PathBuf, PathBuf)>, Vec<SkippedInput>) {
+    let mut planned = Vec::with_capacity(inputs.len());
+    let mut skipped = Vec::new();
+    let mut claimed: HashMap<PathBuf, &Path> = HashMap::new();
+
+    for input in inputs {
+        let Some(stem) = input.file_stem() else {
+            skipped.push(SkippedInput::NoStem(input.clone()));
+            continue;
+        };
+
+        let output = output_dir.join(output_name(stem, extension));
+
+        if let Some(earlier) = claimed.get(&output) {
+            skipped.push(SkippedInput::Collision { input: input.clone(), earlier: earlier.to_path_buf(), output });
+            continue;
+        }
+
+        claimed.insert(output.clone(), input);
+        planned.push((input.clone(), output));
+    }
+
+    (planned, skipped)
+}
+
+/// Joins a file stem and extension into a file name.
+fn output_name(stem: &std::ffi::OsStr, extension: &str) -> OsString {
+    let mut name = OsString::from(stem);
+    name.push(".");
+    name.push(extension);
+    name
+}
+
+/// Why an output path can't be written to.
+#[derive(Debug)]
+pub enum OutputPathError {
+    /// The output is a directory, and the input has no file stem to name the
+    /// output file after.
+    NoStem(PathBuf),
+    /// The directory doesn't exist, and creating it wasn't allowed.
+    MissingDirectory(PathBuf),
+    /// The path should be a directory but is something else.
+    NotADirectory(PathBuf),
+    /// The path can't be created or written to.
+    Unwritable { path: PathBuf, source: io::Error },
+}
+
+impl fmt::Display for OutputPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputPathError::NoStem(input) => {
+                write!(f, "Can't name an output file after '{}', as it has no file name", input.display())
+            }
+            OutputPathError::MissingDirectory(dir) => write!(f, "Output directory '{}' doesn't exist", dir.display()),
+            OutputPathError::NotADirectory(path) => write!(f, "'{}' isn't a directory", path.display()),
+            OutputPathError::Unwritable { path, source } => write!(f, "Can't write to '{}': {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for OutputPathError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OutputPathError::Unwritable { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Checks that files can be written in `dir`, creating it and its parents
+/// first if `mkdirs` is set.
+///
+/// # Errors
+///
+/// Returns an [`OutputPathError`] if `dir` is missing and `mkdirs` is unset,
+/// isn't a directory, or can't be written to.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::batch::{prepare_output_dir, OutputPathError};
+/// let root = std::env::temp_dir().join(format!("pica-convert-doc-dir-{}", std::process::id()));
+/// let nested = root.join("a/b/c");
+///
+/// assert!(matches!(prepare_output_dir(&nested, false), Err(OutputPathError::MissingDirectory(_))));
+/// prepare_output_dir(&nested, true).unwrap();
+/// assert!(nested.is_dir());
+/// assert_eq!(std::fs::read_dir(&nested).unwrap().count(), 0);
+///
+/// std::fs::write(root.join("file"), b"").unwrap();
+/// assert!(matches!(prepare_output_dir(&root.join("file"), true), Err(OutputPathError::NotADirectory(_))));
+/// # std::fs::remove_dir_all(&root).unwrap();
+/// ```
+pub fn prepare_output_dir(dir: &Path, mkdirs: bool) -> Result<(), OutputPathError> {
+    let unwritable = |source| OutputPathError::Unwritable { path: dir.to_path_buf(), source };
+
+    match fs::metadata(dir) {
+        Ok(metadata) if !metadata.is_dir() => return Err(OutputPathError::NotADirectory(dir.to_path_buf())),
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            if !mkdirs {
+                return Err(OutputPathError::MissingDirectory(dir.to_path_buf()));
+            }
+            fs::create_dir_all(dir).map_err(unwritable)?;
+        }
+        Err(e) => return Err(unwritable(e)),
+    }
+
+    // Permissions alone don't say whether a write will succeed, so try one
+    let probe = dir.join(format!(".pica-convert-probe-{}", std::process::id()));
+    OpenOptions::new().write(true).create_new(true).open(&probe).map_err(unwritable)?;
+    fs::remove_file(&probe).map_err(unwritable)
+}
+
+/// Works out and checks the file a single `input` is written to.
+///
+/// If `output` is an existing directory, the file is named after the input's
+/// file stem with `extension`, like [`output_paths`] names them. Otherwise
+/// `output` is the file, and its parent directory is checked, or created if
+/// `mkdirs` is set, with [`prepare_output_dir`]. `-`, for stdout, is returned
+/// as it is.
+///
+/// Nothing is written to an existing output file, so a failed conversion
+/// leaves it as it was.
+///
+/// # Errors
+///
+/// Returns an [`OutputPathError`] if the output file can't be written.
+///
+/// # Example
+///
+/// ```
+/// # use std::path::Path;
+/// # use pica_convert::batch::{prepare_output_file, OutputPathError};
+/// let root = std::env::temp_dir().join(format!("pica-convert-doc-file-{}", std::process::id()));
+/// let input = Path::new("textures/grass.png");
+///
+/// // Missing parent directories are created if allowed
+/// let nested = root.join("out/sub/grass.ctex");
+/// assert!(matches!(prepare_output_file(&nested, input, "ctex", false), Err(OutputPathError::MissingDirectory(_))));
+/// assert_eq!(prepare_output_file(&nested, input, "ctex", true).unwrap(), nested);
+/// assert!(root.join("out/sub").is_dir() && !nested.exists());
+///
+/// // Directories are filled in with the input's name
+/// assert_eq!(prepare_output_file(&root.join("out"), input, "ctex", false).unwrap(), root.join("out/grass.ctex"));
+/// assert!(matches!(prepare_output_file(&root.join("out"), Path::new(".."), "ctex", false), Err(OutputPathError::NoStem(_))));
+///
+/// // Files can't be made inside other files
+/// std::fs::write(root.join("file"), b"").unwrap();
+/// let err = prepare_output_file(&root.join("file/grass.ctex"), input, "ctex", true).unwrap_err();
+/// assert!(matches!(err, OutputPathError::NotADirectory(_)), "{}", err);
+///
+/// assert_eq!(prepare_output_file(Path::new("-"), input, "ctex", false).unwrap(), Path::new("-"));
+/// # std::fs::remove_dir_all(&root).unwrap();
+/// ```
+///
+/// Read-only destinations are caught too:
+///
+/// ```
+/// # #[cfg(unix)] {
+/// # use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+/// # use pica_convert::batch::{prepare_output_file, OutputPathError};
+/// let dir = std::env::temp_dir().join(format!("pica-convert-doc-readonly-{}", std::process::id()));
+/// fs::create_dir_all(&dir).unwrap();
+/// fs::write(dir.join("existing.ctex"), b"keep").unwrap();
+/// fs::set_permissions(dir.join("existing.ctex"), fs::Permissions::from_mode(0o444)).unwrap();
+/// fs::set_permissions(&dir, fs::Permissions::from_mode(0o555)).unwrap();
+///
+/// // Superusers can write anywhere, so only check when the directory really is read-only
+/// if fs::File::create(dir.join("probe")).is_err() {
+///     for output in [dir.join("new.ctex"), dir.join("existing.ctex")] {
+///         let err = prepare_output_file(&output, Path::new("grass.png"), "ctex", true).unwrap_err();
+///         assert!(matches!(err, OutputPathError::Unwritable { .. }), "{}", err);
+///     }
+/// }
+/// assert_eq!(fs::read(dir.join("existing.ctex")).unwrap(), b"keep");
+/// # fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+/// # fs::remove_dir_all(&dir).unwrap();
+/// # }
+/// ```
+pub fn prepare_output_file(output: &Path, input: &Path, extension: &str, mkdirs: bool) -> Result<PathBuf, OutputPathError> {
+    if output == Path::new("-") {
+        return Ok(output.to_path_buf());
+    }
+
+    if output.is_dir() {
+        let stem = input.file_stem().ok_or_else(|| OutputPathError::NoStem(input.to_path_buf()))?;
+        prepare_output_dir(output, false)?;
+        return Ok(output.join(output_name(stem, extension)));
+    }
+
+    let parent = output.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    prepare_output_dir(parent, mkdirs)?;
+
+    // Opening an existing file for writing, without truncating it, checks it can be replaced
+    if output.exists() {
+        OpenOptions::new().write(true).open(output)
+            .map_err(|source| OutputPathError::Unwritable { path: output.to_path_buf(), source })?;
+    }
+
+    Ok(output.to_path_buf())
+}
+
+/// An input a bulk run failed to convert, as listed in a [`FailureManifest`].
+///
+/// # Example
+///
+/// ```
+/// # use std::path::Path;
+/// # use pica_convert::batch::FailedInput;
+/// # use pica_convert::stage::{Stage, StageError};
+/// let failure = StageError::new(Stage::Load, "Failed to read texture:\n  unexpected end of file\n")
+///     .in_file(Path::new("in/grass.ctex"), Some(Path::new("out/grass.png")));
+/// assert_eq!(FailedInput::from(&failure).error, "Failed to read texture: unexpected end of file");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedInput {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    /// The stage the conversion stopped at.
+    pub stage: Stage,
+    /// The error that stopped it, on one line, with the lines of a longer
+    /// message joined by spaces.
+    pub error: String,
+}
+
+impl From<&StageError> for FailedInput {
+    fn from(e: &StageError) -> Self {
+        FailedInput {
+            input: e.input().map(Path::to_path_buf).unwrap_or_default(),
+            output: e.output().map(Path::to_path_buf).unwrap_or_default(),
+            stage: e.stage(),
+            error: e.cause().to_string().lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join(" "),
+        }
+    }
+}
+
+/// The inputs a bulk run failed to convert, so a later run can retry only
+/// those.
+///
+/// Written as JSON, the format is:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "mode": "encode",
+///   "failures": [
+///     {
+///       "input": "textures/grass.png",
+///       "output": "out/grass.ctex",
+///       "stage": "load",
+///       "error": "Failed to decode image: Invalid PNG signature."
+///     }
+///   ]
+/// }
+/// ```
+///
+/// `version` is [`FailureManifest::VERSION`], which only goes up if fields
+/// change or go away. `mode` is the mode the run converted in, and
+/// `failures` lists its failed inputs in the order it listed them, with
+/// paths as it was given them. `stage` is a [`Stage`]'s name.
+///
+/// # Example
+///
+/// ```
+/// # use std::path::Path;
+/// # use pica_convert::batch::{FailedInput, FailureManifest};
+/// # use pica_convert::stage::{Stage, StageError};
+/// let failure = StageError::new(Stage::Load, "Invalid PNG signature")
+///     .in_file(Path::new("textures/grass.png"), Some(Path::new("out/grass.ctex")));
+/// let manifest = FailureManifest::new("encode", [FailedInput::from(&failure)]);
+///
+/// let json = serde_json::to_string(&manifest).unwrap();
+/// assert_eq!(json, r#"{"version":1,"mode":"encode","failures":[{"input":"textures/grass.png","output":"out/grass.ctex","stage":"load","error":"Invalid PNG signature"}]}"#);
+/// assert_eq!(serde_json::from_str::<FailureManifest>(&json).unwrap(), manifest);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureManifest {
+    pub version: u32,
+    pub mode: String,
+    pub failures: Vec<FailedInput>,
+}
+
+impl FailureManifest {
+    /// The version of the format written.
+    pub const VERSION: u32 = 1;
+
+    /// The name a bulk run's manifest is written under in its output directory.
+    pub const FILE_NAME: &'static str = "pica-convert-failures.json";
+
+    pub fn new(mode: impl Into<String>, failures: impl IntoIterator<Item = FailedInput>) -> Self {
+        FailureManifest { version: Self::VERSION, mode: mode.into(), failures: failures.into_iter().collect() }
+    }
+
+    /// Checks a manifest that's been read can be retried in `mode`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if it's from a newer version of the format, or from
+    /// a run in another mode.
+    pub fn check(&self, mode: &str) -> Result<(), String> {
+        if self.version > Self::VERSION {
+            return Err(format!("Failure manifest version {} is newer than this build reads ({})", self.version, Self::VERSION));
+        }
+        if self.mode != mode {
+            return Err(format!("The failure manifest lists files that failed in {} mode, not {} mode", self.mode, mode));
+        }
+        Ok(())
+    }
+
+    /// Narrows `planned`, the inputs and outputs a run would convert, down to
+    /// the inputs this manifest lists, returning them with the listed inputs
+    /// that aren't among them.
+    ///
+    /// Inputs are matched by their canonical paths where they exist, so a
+    /// retry can spell them differently, such as `./in/grass.png` for
+    /// `in/grass.png`. The manifest keeps relative paths as they were given,
+    /// so they're resolved against the directory the retry runs in, which
+    /// should be the one the failed run ran in.
+    ///
+    /// # Example
+    ///
+    /// A run where some inputs fail is retried with only those:
+    ///
+    /// ```
+    /// # use std::path::{Path, PathBuf};
+    /// # use pica_convert::batch::{output_paths, FailedInput, FailureManifest};
+    /// # use pica_convert::stage::{Stage, StageError};
+    /// let inputs: Vec<PathBuf> = ["in/grass.png", "in/rock.png", "in/sand.png"].iter().map(PathBuf::from).collect();
+    /// let (planned, _) = output_paths(&inputs, Path::new("out"), "ctex");
+    ///
+    /// // Rock and sand fail the first run
+    /// let failures = planned.iter()
+    ///     .filter(|(input, _)| !input.ends_with("grass.png"))
+    ///     .map(|(input, output)| FailedInput::from(&StageError::new(Stage::Encode, "injected").in_file(input, Some(output))));
+    /// let manifest = FailureManifest::new("encode", failures);
+    /// assert!(manifest.check("encode").is_ok() && manifest.check("decode").is_err());
+    ///
+    /// // Sand has been deleted since
+    /// let (planned, _) = output_paths(&inputs[..2], Path::new("out"), "ctex");
+    /// let (retried, missing) = manifest.retry_plan(planned);
+    /// assert_eq!(retried, [(PathBuf::from("in/rock.png"), PathBuf::from("out/rock.ctex"))]);
+    /// assert_eq!(missing, [PathBuf::from("in/sand.png")]);
+    /// ```
+    pub fn retry_plan(&self, planned: Vec<(PathBuf, PathBuf)>) -> (Vec<(PathBuf, PathBuf)>, Vec<PathBuf>) {
+        let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+        let mut listed: HashMap<PathBuf, &Path> = self.failures.iter()
+            .map(|failure| (canonical(&failure.input), failure.input.as_path()))
+            .collect();
+        let retried = planned.into_iter()
+            .filter(|(input, _)| listed.remove(&canonical(input)).is_some())
+            .collect();
+
+        let mut missing: Vec<PathBuf> = listed.into_values().map(Path::to_path_buf).collect();
+        missing.sort();
+        (retried, missing)
+    }
+}
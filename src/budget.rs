@@ -0,0 +1,137 @@
+//! Bounding how much memory concurrent conversions hold at once.
+
+use std::sync::{Condvar, Mutex};
+
+use crate::pica_texture::TextureFormat;
+
+/// Estimates the peak memory, in bytes, of converting one `width` x `height`
+/// texture to or from `format`.
+///
+/// A conversion holds the image as RGBA8 pixels twice over (the loaded image
+/// and the copy being converted) alongside the texture data itself.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::budget::estimate_texture_cost;
+/// # use pica_convert::pica_texture::TextureFormat;
+/// // Two RGBA8 copies of the pixels, plus 1 MiB of RGBA8888 texture data
+/// assert_eq!(estimate_texture_cost(&TextureFormat::RGBA8888, 512, 512), 3 * 1024 * 1024);
+/// assert_eq!(estimate_texture_cost(&TextureFormat::L4, 512, 512), 2 * 1024 * 1024 + 128 * 1024);
+/// ```
+pub fn estimate_texture_cost(format: &TextureFormat, width: u32, height: u32) -> u64 {
+    let rgba = width as u64 * height as u64 * 4;
+    rgba.saturating_mul(2).saturating_add(format.expected_data_len(width, height) as u64)
+}
+
+/// A fixed amount of memory shared between concurrent tasks, handed out as
+/// [`MemoryPermit`]s.
+///
+/// Each task acquires a permit for its estimated cost before it starts, and
+/// releases it by dropping the permit, so the tasks in flight never add up
+/// to more than the limit. A task that costs more than the whole limit still
+/// gets to run, but only once nothing else is in flight.
+///
+/// # Example
+///
+/// ```
+/// # use std::sync::atomic::{AtomicU64, Ordering};
+/// # use pica_convert::budget::MemoryBudget;
+/// let budget = MemoryBudget::new(100);
+///
+/// let first = budget.try_acquire(60).unwrap();
+/// assert!(budget.try_acquire(50).is_none());
+/// let second = budget.try_acquire(40).unwrap();
+/// assert_eq!(budget.in_use(), 100);
+///
+/// drop(first);
+/// assert_eq!(budget.in_use(), 40);
+///
+/// // Oversized tasks wait for everything else to finish, then run alone
+/// assert!(budget.try_acquire(500).is_none());
+/// drop(second);
+/// let oversized = budget.try_acquire(500).unwrap();
+/// assert_eq!(budget.in_use(), 100);
+/// drop(oversized);
+///
+/// // Simulate a batch of workers with varying costs, tracking the peak
+/// let peak = AtomicU64::new(0);
+/// std::thread::scope(|scope| {
+///     for cost in [30, 70, 20, 90, 50, 10, 60, 40] {
+///         let (budget, peak) = (&budget, &peak);
+///         scope.spawn(move || {
+///             let _permit = budget.acquire(cost);
+///             peak.fetch_max(budget.in_use(), Ordering::SeqCst);
+///             std::thread::sleep(std::time::Duration::from_millis(5));
+///         });
+///     }
+/// });
+///
+/// assert!(peak.load(Ordering::SeqCst) <= 100);
+/// assert_eq!(budget.in_use(), 0);
+/// ```
+pub struct MemoryBudget {
+    limit: u64,
+    in_use: Mutex<u64>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    /// Creates a budget of `limit` bytes.
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            in_use: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Returns the total number of bytes in the budget.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Returns the number of bytes currently held by permits.
+    pub fn in_use(&self) -> u64 {
+        *self.in_use.lock().unwrap()
+    }
+
+    /// Acquires a permit for `cost` bytes if they're available right now.
+    pub fn try_acquire(&self, cost: u64) -> Option<MemoryPermit<'_>> {
+        let cost = cost.min(self.limit);
+
+        let mut in_use = self.in_use.lock().unwrap();
+        if *in_use + cost > self.limit {
+            return None;
+        }
+        *in_use += cost;
+
+        Some(MemoryPermit { budget: self, cost })
+    }
+
+    /// Acquires a permit for `cost` bytes, blocking until enough permits
+    /// have been released for them to fit.
+    pub fn acquire(&self, cost: u64) -> MemoryPermit<'_> {
+        let cost = cost.min(self.limit);
+
+        let mut in_use = self.released
+            .wait_while(self.in_use.lock().unwrap(), |in_use| *in_use + cost > self.limit)
+            .unwrap();
+        *in_use += cost;
+
+        MemoryPermit { budget: self, cost }
+    }
+}
+
+/// A share of a [`MemoryBudget`], returned to it when dropped.
+pub struct MemoryPermit<'a> {
+    budget: &'a MemoryBudget,
+    cost: u64,
+}
+
+impl Drop for MemoryPermit<'_> {
+    fn drop(&mut self) {
+        *self.budget.in_use.lock().unwrap() -= self.cost;
+        self.budget.released.notify_all();
+    }
+}
@@ -0,0 +1,292 @@
+//! C-compatible bindings for encoding and decoding textures.
+//!
+//! Every function returns a [`PicaStatus`] code, with [`PicaStatus::Ok`]
+//! (zero) on success and a negative value on failure. Buffers returned through
+//! `out_buf`/`out_len` are owned by this library and must be released with
+//! [`pica_free`]. No Rust panic ever unwinds into the caller; one is reported
+//! as [`PicaStatus::Panic`] instead.
+//!
+//! Building with the `capi` feature also writes a C header, `pica_convert.h`,
+//! to `target/<profile>/include`.
+
+use std::ffi::{c_char, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::pica_texture::{self, BuiltinCodec, DecodeOptions, EncodeOptions, TextureError, TextureFormat};
+
+/// Result codes returned by every `pica_*` function.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PicaStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullPointer = -1,
+    /// The format ID isn't a known PICA hardware format, or the format isn't available in this build.
+    UnknownFormat = -2,
+    /// A buffer length doesn't match the texture's dimensions and format.
+    InvalidLength = -3,
+    /// Encoding or decoding failed for another reason.
+    Failed = -4,
+    /// The library panicked; this is a bug.
+    Panic = -5,
+}
+
+/// Options for [`pica_encode`], mirroring [`EncodeOptions`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct PicaEncodeOptions {
+    /// ETC1 compression quality: 0 (low), 1 (medium) or 2 (high).
+    pub etc1_quality: i32,
+    /// Whether the ETC1 compressor should dither block colors.
+    pub etc1_dithering: bool,
+    /// Whether to mirror the image horizontally before encoding.
+    pub flip_horizontal: bool,
+}
+
+impl From<&PicaEncodeOptions> for EncodeOptions {
+    fn from(options: &PicaEncodeOptions) -> Self {
+        EncodeOptions {
+            etc1_quality: options.etc1_quality,
+            etc1_dithering: options.etc1_dithering,
+            flip_horizontal: options.flip_horizontal,
+            ..Default::default()
+        }
+    }
+}
+
+/// Options for [`pica_decode`], mirroring [`DecodeOptions`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct PicaDecodeOptions {
+    /// Whether to flip the decoded pixels vertically. They're decoded top row
+    /// first, so this is only needed for data that was stored upside down.
+    pub flip_vertical: bool,
+    /// Whether to mirror the decoded pixels horizontally.
+    pub flip_horizontal: bool,
+}
+
+impl From<&PicaDecodeOptions> for DecodeOptions {
+    fn from(options: &PicaDecodeOptions) -> Self {
+        DecodeOptions {
+            flip_vertical: options.flip_vertical,
+            flip_horizontal: options.flip_horizontal,
+            crop: None,
+        }
+    }
+}
+
+/// Encodes RGBA8 pixels into PICA texture data.
+///
+/// # Arguments
+///
+/// * `rgba` - `width * height * 4` bytes of row-major RGBA8 pixels.
+/// * `width` - The width of the image in pixels.
+/// * `height` - The height of the image in pixels.
+/// * `format_id` - The PICA hardware ID of the format to encode as.
+/// * `options` - The options to encode with, or null for the defaults.
+/// * `out_buf` - Receives the encoded data, to be released with [`pica_free`].
+/// * `out_len` - Receives the length of the encoded data.
+///
+/// # Safety
+///
+/// `rgba` must point to `width * height * 4` readable bytes, `options` must
+/// be null or point to a valid [`PicaEncodeOptions`], and `out_buf` and
+/// `out_len` must be valid for writes.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::capi::{pica_decode, pica_encode, pica_free, PicaStatus};
+/// let pixels: Vec<u8> = (0..16 * 16 * 4).map(|i| i as u8).collect();
+/// let mut encoded = std::ptr::null_mut();
+/// let mut encoded_len = 0;
+///
+/// // RGBA8888 is hardware format 0
+/// let status = unsafe { pica_encode(pixels.as_ptr(), 16, 16, 0, std::ptr::null(), &mut encoded, &mut encoded_len) };
+/// assert_eq!(status, PicaStatus::Ok);
+/// assert_eq!(encoded_len, 16 * 16 * 4);
+///
+/// let mut decoded = std::ptr::null_mut();
+/// let mut decoded_len = 0;
+/// let status = unsafe { pica_decode(encoded, encoded_len, 16, 16, 0, std::ptr::null(), &mut decoded, &mut decoded_len) };
+/// assert_eq!(status, PicaStatus::Ok);
+/// assert_eq!(unsafe { std::slice::from_raw_parts(decoded, decoded_len) }, &pixels[..]);
+///
+/// unsafe {
+///     pica_free(encoded, encoded_len);
+///     pica_free(decoded, decoded_len);
+/// }
+///
+/// // Errors are reported as status codes
+/// let status = unsafe { pica_encode(pixels.as_ptr(), 16, 16, 99, std::ptr::null(), &mut encoded, &mut encoded_len) };
+/// assert_eq!(status, PicaStatus::UnknownFormat);
+/// let status = unsafe { pica_encode(std::ptr::null(), 16, 16, 0, std::ptr::null(), &mut encoded, &mut encoded_len) };
+/// assert_eq!(status, PicaStatus::NullPointer);
+/// ```
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pica_encode(
+    rgba: *const u8,
+    width: u32,
+    height: u32,
+    format_id: u8,
+    options: *const PicaEncodeOptions,
+    out_buf: *mut *mut u8,
+    out_len: *mut usize,
+) -> PicaStatus {
+    guard(|| {
+        if rgba.is_null() || out_buf.is_null() || out_len.is_null() {
+            return PicaStatus::NullPointer;
+        }
+        let Some(format) = TextureFormat::from_hw_id(format_id).filter(TextureFormat::is_available) else {
+            return PicaStatus::UnknownFormat;
+        };
+        let Some(len) = rgba_len(width, height) else {
+            return PicaStatus::InvalidLength;
+        };
+
+        let options = match unsafe { options.as_ref() } {
+            Some(options) => EncodeOptions::from(options),
+            None => EncodeOptions::default(),
+        };
+        let pixels = unsafe { std::slice::from_raw_parts(rgba, len) };
+
+        match pica_texture::encode_rgba_buffer(pixels, width, height, &format, &options) {
+            Ok(texture) => unsafe { give_buffer(texture.into_data(), out_buf, out_len) },
+            Err(_) => PicaStatus::Failed,
+        }
+    })
+}
+
+/// Decodes PICA texture data into RGBA8 pixels.
+///
+/// # Arguments
+///
+/// * `data` - The encoded texture data.
+/// * `data_len` - The length of `data` in bytes.
+/// * `width` - The width of the texture in pixels.
+/// * `height` - The height of the texture in pixels.
+/// * `format_id` - The PICA hardware ID of the format the data is encoded in.
+/// * `options` - The options to decode with, or null for the defaults.
+/// * `out_buf` - Receives `width * height * 4` bytes of RGBA8 pixels, to be released with [`pica_free`].
+/// * `out_len` - Receives the length of the decoded pixels.
+///
+/// # Safety
+///
+/// `data` must point to `data_len` readable bytes, `options` must be null or
+/// point to a valid [`PicaDecodeOptions`], and `out_buf` and `out_len` must
+/// be valid for writes.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::capi::{pica_decode, PicaStatus};
+/// let data = vec![0u8; 10];
+/// let mut decoded = std::ptr::null_mut();
+/// let mut decoded_len = 0;
+///
+/// // Too little L8 data for an 8x8 texture
+/// let status = unsafe { pica_decode(data.as_ptr(), data.len(), 8, 8, 7, std::ptr::null(), &mut decoded, &mut decoded_len) };
+/// assert_eq!(status, PicaStatus::InvalidLength);
+/// assert!(decoded.is_null());
+/// ```
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pica_decode(
+    data: *const u8,
+    data_len: usize,
+    width: u32,
+    height: u32,
+    format_id: u8,
+    options: *const PicaDecodeOptions,
+    out_buf: *mut *mut u8,
+    out_len: *mut usize,
+) -> PicaStatus {
+    guard(|| {
+        if data.is_null() || out_buf.is_null() || out_len.is_null() {
+            return PicaStatus::NullPointer;
+        }
+        let Some(format) = TextureFormat::from_hw_id(format_id).filter(TextureFormat::is_available) else {
+            return PicaStatus::UnknownFormat;
+        };
+        if rgba_len(width, height).is_none() {
+            return PicaStatus::InvalidLength;
+        }
+
+        let options = match unsafe { options.as_ref() } {
+            Some(options) => DecodeOptions::from(options),
+            None => DecodeOptions::default(),
+        };
+        let data = unsafe { std::slice::from_raw_parts(data, data_len) };
+
+        match pica_texture::decode_with_codec(BuiltinCodec::for_format(&format), data, width, height, &options) {
+            Ok(pixels) => unsafe { give_buffer(pixels, out_buf, out_len) },
+            Err(e) if matches!(e.downcast_ref(), Some(TextureError::DataLengthMismatch { .. })) => PicaStatus::InvalidLength,
+            Err(_) => PicaStatus::Failed,
+        }
+    })
+}
+
+/// Releases a buffer returned by [`pica_encode`] or [`pica_decode`].
+///
+/// # Safety
+///
+/// `buf` and `len` must be exactly as returned by this library, and the
+/// buffer must not be used or freed again afterwards. A null `buf` is ignored.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pica_free(buf: *mut u8, len: usize) {
+    if buf.is_null() {
+        return;
+    }
+    let _ = catch_unwind(AssertUnwindSafe(|| {
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(buf, len)) });
+    }));
+}
+
+/// Returns a static, NUL-terminated description of a status code.
+///
+/// # Example
+///
+/// ```
+/// # use std::ffi::CStr;
+/// # use pica_convert::capi::{pica_status_message, PicaStatus};
+/// let message = unsafe { CStr::from_ptr(pica_status_message(PicaStatus::UnknownFormat as i32)) };
+/// assert_eq!(message.to_str().unwrap(), "unknown texture format ID");
+/// ```
+#[unsafe(no_mangle)]
+pub extern "C" fn pica_status_message(status: i32) -> *const c_char {
+    let message: &'static CStr = match status {
+        0 => c"success",
+        -1 => c"a required pointer was null",
+        -2 => c"unknown texture format ID",
+        -3 => c"buffer length does not match the texture dimensions",
+        -4 => c"encoding or decoding failed",
+        -5 => c"internal error",
+        _ => c"unknown status code",
+    };
+    message.as_ptr()
+}
+
+/// Runs `f`, turning a panic into [`PicaStatus::Panic`] so it never crosses the FFI boundary.
+fn guard(f: impl FnOnce() -> PicaStatus) -> PicaStatus {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(PicaStatus::Panic)
+}
+
+/// Returns the length of a `width` x `height` RGBA8 buffer, or `None` if it overflows.
+fn rgba_len(width: u32, height: u32) -> Option<usize> {
+    (width as usize).checked_mul(height as usize)?.checked_mul(4)
+}
+
+/// Hands ownership of `data` to the caller through the output pointers.
+///
+/// # Safety
+///
+/// `out_buf` and `out_len` must be valid for writes.
+unsafe fn give_buffer(data: Vec<u8>, out_buf: *mut *mut u8, out_len: *mut usize) -> PicaStatus {
+    let data = data.into_boxed_slice();
+    let len = data.len();
+    unsafe {
+        *out_buf = Box::into_raw(data) as *mut u8;
+        *out_len = len;
+    }
+    PicaStatus::Ok
+}
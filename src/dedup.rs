@@ -0,0 +1,159 @@
+//! Finding inputs with the same contents, so bulk runs can convert each
+//! distinct input once.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use xxhash_rust::xxh3::Xxh3;
+
+/// Inputs with identical contents, as indices into the list they were found in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// The first of the inputs, which is the one to convert.
+    pub first: usize,
+    /// The rest, in the order they were given, which can be written from the first's result.
+    pub duplicates: Vec<usize>,
+}
+
+/// Hashes the contents of the file at `path` with 128-bit XXH3.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read.
+pub fn content_hash(path: &Path) -> io::Result<u128> {
+    let mut reader = File::open(path)?;
+    let mut hasher = Xxh3::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        match reader.read(&mut buffer)? {
+            0 => return Ok(hasher.digest128()),
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+}
+
+/// Returns whether the files at `a` and `b` hold the same bytes.
+///
+/// # Errors
+///
+/// Returns an error if either file can't be read.
+pub fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+
+    let (mut a, mut b) = (BufReader::new(File::open(a)?), BufReader::new(File::open(b)?));
+    loop {
+        let (chunk_a, chunk_b) = (a.fill_buf()?, b.fill_buf()?);
+        let len = chunk_a.len().min(chunk_b.len());
+        if len == 0 {
+            return Ok(chunk_a.len() == chunk_b.len());
+        }
+        if chunk_a[..len] != chunk_b[..len] {
+            return Ok(false);
+        }
+        a.consume(len);
+        b.consume(len);
+    }
+}
+
+/// Groups `paths` by their files' contents, with [`content_hash`].
+///
+/// See [`group_by_hash`].
+///
+/// # Example
+///
+/// ```
+/// # use std::fs;
+/// # use pica_convert::dedup::{group_by_content, DuplicateGroup};
+/// let dir = std::env::temp_dir().join(format!("pica-convert-doc-dedup-{}", std::process::id()));
+/// fs::create_dir_all(&dir).unwrap();
+/// let paths = ["grass.png", "rock.png", "grass_copy.png", "missing.png", "grass_again.png"].map(|name| dir.join(name));
+/// fs::write(&paths[0], b"grass").unwrap();
+/// fs::write(&paths[1], b"rock").unwrap();
+/// fs::write(&paths[2], b"grass").unwrap();
+/// fs::write(&paths[4], b"grass").unwrap();
+///
+/// assert_eq!(group_by_content(&paths), [
+///     DuplicateGroup { first: 0, duplicates: vec![2, 4] },
+///     DuplicateGroup { first: 1, duplicates: vec![] },
+///     DuplicateGroup { first: 3, duplicates: vec![] },
+/// ]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn group_by_content<P: AsRef<Path> + Sync>(paths: &[P]) -> Vec<DuplicateGroup> {
+    group_by_hash(paths, content_hash)
+}
+
+/// Groups `paths` by their files' contents, using `hash` to find the files
+/// that might match.
+///
+/// Files are hashed in parallel, on rayon's global pool with the `parallel`
+/// feature. A shared hash only makes files candidates: each is compared in
+/// full with the first file of a group before joining it, so a collision
+/// can't put different files together. Files that can't be read are left in
+/// groups of their own, to fail when they're converted.
+///
+/// Groups come back in the order of their first file, and every path is in
+/// exactly one group.
+///
+/// # Example
+///
+/// A hash that always collides still only groups identical files:
+///
+/// ```
+/// # use std::fs;
+/// # use pica_convert::dedup::{group_by_hash, DuplicateGroup};
+/// let dir = std::env::temp_dir().join(format!("pica-convert-doc-collide-{}", std::process::id()));
+/// fs::create_dir_all(&dir).unwrap();
+/// let paths = ["a.png", "b.png", "c.png", "d.png"].map(|name| dir.join(name));
+/// for (path, contents) in paths.iter().zip([&b"same"[..], b"diff", b"same", b"diff"]) {
+///     fs::write(path, contents).unwrap();
+/// }
+///
+/// assert_eq!(group_by_hash(&paths, |_| Ok(0)), [
+///     DuplicateGroup { first: 0, duplicates: vec![2] },
+///     DuplicateGroup { first: 1, duplicates: vec![3] },
+/// ]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn group_by_hash<P, H>(paths: &[P], hash: impl Fn(&Path) -> io::Result<H> + Sync) -> Vec<DuplicateGroup>
+where
+    P: AsRef<Path> + Sync,
+    H: Eq + Hash + Send,
+{
+    #[cfg(feature = "parallel")]
+    let hashes: Vec<Option<H>> = paths.par_iter().map(|path| hash(path.as_ref()).ok()).collect();
+    #[cfg(not(feature = "parallel"))]
+    let hashes: Vec<Option<H>> = paths.iter().map(|path| hash(path.as_ref()).ok()).collect();
+
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    // The groups whose first file has each hash, usually just one
+    let mut by_hash: HashMap<H, Vec<usize>> = HashMap::new();
+
+    for (index, hash) in hashes.into_iter().enumerate() {
+        let Some(hash) = hash else {
+            groups.push(DuplicateGroup { first: index, duplicates: Vec::new() });
+            continue;
+        };
+
+        let candidates = by_hash.entry(hash).or_default();
+        let matching = candidates.iter().copied().find(|&group| {
+            same_content(paths[groups[group].first].as_ref(), paths[index].as_ref()).unwrap_or(false)
+        });
+        match matching {
+            Some(group) => groups[group].duplicates.push(index),
+            None => {
+                candidates.push(groups.len());
+                groups.push(DuplicateGroup { first: index, duplicates: Vec::new() });
+            }
+        }
+    }
+
+    groups
+}
@@ -1,2 +1,18 @@
+pub mod animation;
+pub mod atlas;
+pub mod batch;
+pub mod budget;
 pub mod pica_texture;
-pub mod serialization;
\ No newline at end of file
+pub mod progress;
+pub mod serialization;
+pub mod sidecar;
+pub mod stage;
+
+#[cfg(feature = "cli")]
+pub mod dedup;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -1,147 +1,1612 @@
-use std::{fs, path::{Path, PathBuf}};
+use std::{fs::{self, File}, io::{self, BufReader, Read, Write}, path::{Path, PathBuf}};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use clap::Parser;
+use clap::{CommandFactory, Parser, ValueEnum, ValueHint};
 use image::{DynamicImage, GenericImageView, ImageReader};
 
-use pica_convert::{pica_texture, serialization};
+use pica_convert::animation;
+use pica_convert::atlas;
+use pica_convert::batch::{self, FailedInput, FailureManifest};
+use pica_convert::budget::{estimate_texture_cost, MemoryBudget, MemoryPermit};
+use pica_convert::dedup;
+use pica_convert::pica_texture;
+use pica_convert::progress::OutputQueue;
+use pica_convert::progress;
+use pica_convert::serialization::{ContainerRegistry, NamedTexture, TextureContainer, TextureResult};
+use pica_convert::serialization::ctex::{self, CreateInfo, Ctex, CtexOptions, EditData, PixelEncoding};
+use pica_convert::sidecar::TextureSidecar;
+use pica_convert::stage::{AtStage, Stage, StageError};
 
 use rayon::prelude::*;
 
-#[derive(Parser, Clone)]
+#[derive(Parser)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 struct Cli {
-    #[arg(short = 'i', long, help = "Input image file or directory")]
-    input_path: String,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    #[arg(short = 'm', long, value_enum, help = "Operation mode: encode or decode")]
+    #[command(flatten)]
+    convert: Option<ConvertArgs>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Print a completion script for a shell to stdout
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(clap::Args)]
+struct ConvertArgs {
+    #[arg(short = 'i', long, value_hint = ValueHint::AnyPath, help = "Input image file or directory")]
+    input_path: PathBuf,
+
+    #[arg(long, value_enum, default_value_t = InputFormat::Container, help = "How input textures are stored: in a container, detected from the file, or as a raw dump of texture data with no header, described by --width, --height and --output-format, and --layout if it's stored in rows (decode mode only; '-' reads the dump from stdin)")]
+    input_format: InputFormat,
+
+    #[arg(long, help = "Width of a raw input texture in pixels (with --input-format raw)")]
+    width: Option<u32>,
+
+    #[arg(long, help = "Height of a raw input texture in pixels (with --input-format raw)")]
+    height: Option<u32>,
+
+    #[arg(short = 'm', long, value_enum, help = "Operation mode: encode, decode, info, atlas to pack a directory of images into shared textures, split to cut an atlas back into images, or patch to paste an image over part of a texture")]
     mode: Mode,
 
-    #[arg(short = 'f', long, help = "Output texture format")]
-    output_format: Option<pica_texture::TextureFormat>,
+    #[arg(short = 'f', long, value_parser = OutputFormatParser, help = "Output texture format, or 'keep' to reuse the format, mip levels and name of the texture the input was decoded from (see --sidecar and --reference); with --input-format raw, the format of the dump")]
+    output_format: Option<OutputFormat>,
+
+    #[arg(long, help = "Also write each decoded texture's format, mip levels and name to {stem}.texture.json beside it, for '--output-format keep' (decode mode only)")]
+    sidecar: bool,
+
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, help = "Texture to take the format, mip levels and name from instead of a sidecar; --output-format and --name still win (encode mode only)")]
+    reference: Option<PathBuf>,
+
+    #[arg(long, value_enum, help = "Order encoded pixels are stored in: tiled as the PICA samples them, or linear rows for engines that tile textures when uploading them; recorded in the ctex file, and kept by '--output-format keep' [default: tiled]. When decoding, overrides what the container records, for raw dumps and files stored in rows without saying so (not ETC1 or ETC1A4)")]
+    layout: Option<pica_texture::TextureLayout>,
+
+    #[arg(long, value_enum, help = "Compressor to encode ETC1 and ETC1A4 with: rg_etc1, or the plain Rust one; each is only there if the build's cargo features include it [default: rg_etc1 if built, else rust]")]
+    etc1_backend: Option<pica_texture::etc1::Backend>,
+
+    #[arg(short = 'r', long, help = "Resize image to the nearest power of two, at most 1024x1024, if it doesn't fit already")]
+    resize: bool,
+
+    #[arg(long, value_enum, default_value_t = ResizeMode::Scale, requires = "resize", help = "How --resize fits the image: scale it, or pad it with transparent pixels and record its original size")]
+    resize_mode: ResizeMode,
+
+    #[arg(long, help = "Decode padded textures at their full size instead of cropping them to the recorded original size")]
+    keep_padding: bool,
+
+    #[arg(long, help = "Accept textures that aren't powers of two or are larger than 1024x1024")]
+    allow_nonstandard: bool,
+
+    #[arg(long, help = "Mirror the image horizontally when encoding or decoding")]
+    mirror: bool,
+
+    #[arg(short = 'o', long, value_hint = ValueHint::AnyPath, help = "Output file or directory ('-' writes an encoded texture to stdout)")]
+    output_path: Option<PathBuf>,
+
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set, value_name = "BOOL", help = "Create missing output directories")]
+    mkdirs: bool,
+
+    #[arg(short = 'c', long, help = "Container to write when encoding, by extension (defaults to the output file's extension, or ctex)")]
+    container: Option<String>,
+
+    #[arg(long, help = "Print texture info as JSON (info mode only)")]
+    json: bool,
+
+    #[arg(long, help = "Read each encoded file back, decode it and fail it if any channel its format stores is further from the source than the format allows (exact for 8-bit channels)")]
+    verify: bool,
+
+    #[arg(long, value_name = "NxM", value_parser = parse_tile_count, help = "Also write the decoded texture repeated N times across and M down, to spot seams in tiling textures (decode mode, or encode mode with --verify)")]
+    tile_preview: Option<(u32, u32)>,
+
+    #[arg(long, value_name = "RRGGBB|checker[:SIZE]", help = "Blend decoded textures over a solid color or a checkerboard with SIZE pixel squares, so the output is fully opaque (decode mode only)")]
+    background: Option<pica_texture::Background>,
+
+    #[arg(long, help = "Write decoded pixels as headerless RGBA8 bytes instead of a PNG, to the output path or to stdout with '-o -', printing their dimensions to stderr (decode mode only)")]
+    raw_output: bool,
+
+    #[arg(long, help = "Also list the frames of animated inputs and their delays in a JSON file named after the output (encode mode only)")]
+    frame_delays: bool,
+
+    #[arg(long, help = "Print the estimated encoded size in every format instead of encoding (encode mode only)")]
+    estimate: bool,
+
+    #[arg(long, help = "Number of worker threads, shared by bulk processing and tile encoding (defaults to one per CPU core)")]
+    threads: Option<usize>,
+
+    #[arg(long, value_name = "MIB", help = "Limit the estimated memory held by files being processed at once in bulk mode, in MiB")]
+    max_memory: Option<u64>,
+
+    #[arg(long, help = "Encode each distinct input once in bulk mode, writing inputs with the same bytes from the first one's output under their own names (encode mode only)")]
+    dedup: bool,
+
+    #[arg(long, value_name = "MANIFEST", value_hint = ValueHint::FilePath, help = "Convert only the inputs listed in the failure manifest an earlier bulk run wrote, with the current options, then list this run's failures in it, or remove it if there are none")]
+    retry_failed: Option<PathBuf>,
+
+    #[arg(long, help = "Print each file's messages in input order in bulk mode, rather than as each file finishes, so logs of runs can be compared")]
+    ordered_output: bool,
+
+    #[arg(long, help = "Memory-map large input textures instead of reading them through a buffer (decode and info modes)")]
+    mmap: bool,
+
+    #[arg(long, help = "Process symbolic links in input directories instead of skipping them")]
+    follow_symlinks: bool,
+
+    #[arg(long, help = "Report each failure over several lines, with its stage, input and output paths, and every underlying cause")]
+    verbose_errors: bool,
+
+    #[arg(long, help = "Write an EditData block into ctex files, keeping the one in the file being replaced if it has one")]
+    edit_data: bool,
+
+    #[arg(long, default_value_t = 2, value_name = "SPACES", help = "Spaces to indent each level of ctex XML by, or 0 to write it on one line")]
+    indent: usize,
+
+    #[arg(long, value_enum, ignore_case = true, default_value_t = PixelEncoding::Base64, help = "Text encoding for ctex pixel data")]
+    encoding: PixelEncoding,
+
+    #[arg(long, value_name = "COLS", num_args = 0..=1, default_missing_value = "76", help = "Wrap ctex pixel data into lines of COLS characters (76 if not given)")]
+    wrap_base64: Option<usize>,
+
+    #[arg(long, help = "Read ctex files with more pixel data than their texture needs, ignoring the extra with a warning")]
+    lenient: bool,
+
+    #[arg(long, value_name = "PATH", help = "Path attribute to store in ctex files; '{stem}' expands to the input file's stem (defaults to the output path, or its file name if absolute)")]
+    ctex_path: Option<String>,
+
+    #[arg(long, conflicts_with = "ctex_path", help = "Leave directories out of every path written into ctex files")]
+    strip_paths: bool,
+
+    #[arg(long, value_name = "VERSION", help = "GraphicsContentCtr Version to write into ctex files (defaults to the replaced file's, or 1.3.0)")]
+    ctex_version: Option<String>,
+
+    #[arg(long, value_name = "NAMESPACE", help = "GraphicsContentCtr Namespace to write into ctex files (defaults to the replaced file's, or empty)")]
+    ctex_namespace: Option<String>,
+
+    #[arg(long, default_value_t = 2, value_name = "PIXELS", help = "Empty pixels to leave between images packed into an atlas (atlas mode only)")]
+    padding: u32,
+
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::AnyPath, help = "Atlas manifest, JSON or CSV, or a directory to find each atlas's manifest in by stem (split mode only; defaults to beside the atlas)")]
+    manifest: Option<PathBuf>,
+
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, help = "Image to paste over the texture (patch mode only)")]
+    image: Option<PathBuf>,
+
+    #[arg(long, value_name = "X,Y", value_parser = parse_region, help = "Pixel offset of the image's top left corner in the texture, ideally a multiple of 8 (patch mode only)")]
+    region: Option<(u32, u32)>,
+
+    #[arg(long, help = "Texture name to store in the output container; '{stem}' expands to the input file's stem (defaults to the output file's stem)")]
+    name: Option<String>,
+}
 
-    #[arg(short = 'r', long, help = "Resize image to nearest power of two if not already")]
+/// Settings that apply to every file in a run, derived once from the command line.
+struct SharedOptions {
+    output_format: Option<pica_texture::TextureFormat>,
+    keep_format: bool,
+    layout: Option<pica_texture::TextureLayout>,
+    sidecar: bool,
+    reference: Option<PathBuf>,
     resize: bool,
+    resize_mode: ResizeMode,
+    keep_padding: bool,
+    allow_nonstandard: bool,
+    container: Option<String>,
+    json: bool,
+    verify: bool,
+    tile_preview: Option<(u32, u32)>,
+    background: Option<pica_texture::Background>,
+    raw_output: bool,
+    frame_delays: bool,
+    mmap: bool,
+    verbose_errors: bool,
+    edit_data: bool,
+    name: Option<String>,
+    indent: usize,
+    encoding: PixelEncoding,
+    wrap: usize,
+    lenient: bool,
+    ctex_path: Option<String>,
+    strip_paths: bool,
+    ctex_version: Option<String>,
+    ctex_namespace: Option<String>,
+    encode_options: pica_texture::EncodeOptions,
+    decode_options: pica_texture::DecodeOptions,
+}
 
-    #[arg(short = 'o', long, help = "Output file or directory")]
-    output_path: String,
+impl From<&ConvertArgs> for SharedOptions {
+    fn from(args: &ConvertArgs) -> Self {
+        Self {
+            output_format: match &args.output_format {
+                Some(OutputFormat::Format(format)) => Some(*format),
+                Some(OutputFormat::Keep) | None => None,
+            },
+            keep_format: matches!(args.output_format, Some(OutputFormat::Keep)),
+            layout: args.layout,
+            sidecar: args.sidecar,
+            reference: args.reference.clone(),
+            resize: args.resize,
+            resize_mode: args.resize_mode,
+            keep_padding: args.keep_padding,
+            allow_nonstandard: args.allow_nonstandard,
+            container: args.container.clone(),
+            json: args.json,
+            verify: args.verify,
+            tile_preview: args.tile_preview,
+            background: args.background,
+            raw_output: args.raw_output,
+            frame_delays: args.frame_delays,
+            mmap: args.mmap,
+            verbose_errors: args.verbose_errors,
+            edit_data: args.edit_data,
+            name: args.name.clone(),
+            indent: args.indent,
+            encoding: args.encoding,
+            wrap: args.wrap_base64.unwrap_or(0),
+            lenient: args.lenient,
+            ctex_path: args.ctex_path.clone(),
+            strip_paths: args.strip_paths,
+            ctex_version: args.ctex_version.clone(),
+            ctex_namespace: args.ctex_namespace.clone(),
+            encode_options: pica_texture::EncodeOptions {
+                flip_horizontal: args.mirror,
+                layout: args.layout.unwrap_or_default(),
+                etc1_backend: args.etc1_backend.unwrap_or_default(),
+                ..Default::default()
+            },
+            decode_options: pica_texture::DecodeOptions {
+                flip_horizontal: args.mirror,
+                ..Default::default()
+            },
+        }
+    }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Cli::parse();
+    let cli = Cli::parse();
+    let args = match (cli.command, cli.convert) {
+        (Some(Command::Completions { shell }), _) => {
+            clap_complete::generate(shell, &mut Cli::command(), "pica-convert", &mut io::stdout());
+            return Ok(());
+        }
+        (None, Some(args)) => args,
+        (None, None) => unreachable!("clap requires the input path and mode without a subcommand"),
+    };
+
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()?;
+    }
+
+    if let Some(version) = args.ctex_version.as_deref().filter(|version| !ctex::KNOWN_VERSIONS.contains(version)) {
+        eprintln!("Warning: ctex version '{}' isn't a known one ({}), so readers may reject it", version, ctex::KNOWN_VERSIONS.join(", "));
+    }
+
+    let options = SharedOptions::from(&args);
+
+    if args.raw_output && !matches!(args.mode, Mode::Decode) {
+        return Err("--raw-output takes decode mode".into());
+    }
+    if matches!(args.mode, Mode::Decode) && args.output_path.as_deref() == Some(Path::new("-")) {
+        if !args.raw_output {
+            return Err("Decoded images can only be written to stdout with --raw-output".into());
+        }
+        if args.sidecar {
+            return Err("--sidecar can't be used when writing to stdout".into());
+        }
+    }
+
+    if args.layout.is_some() && !matches!(args.mode, Mode::Encode | Mode::Decode | Mode::Atlas) {
+        return Err("--layout takes encode, decode or atlas mode".into());
+    }
+
+    if matches!(args.input_format, InputFormat::Raw) {
+        return decode_raw_file(&args, &options);
+    }
+    if args.width.is_some() || args.height.is_some() {
+        return Err("--width and --height describe raw input, so they take --input-format raw".into());
+    }
+
+    let input_metadata = fs::metadata(&args.input_path)
+        .at_stage(Stage::Load)
+        .map_err(|e| file_failure(e.in_file(&args.input_path, None), &options))?;
+
+    if matches!(args.mode, Mode::Atlas) {
+        if !input_metadata.is_dir() {
+            return Err("Atlas mode takes a directory of images".into());
+        }
+        let output_path = args.output_path.as_deref().ok_or("Output path is required for atlas packing.")?;
+        return pack_atlases(&args, output_path, &options);
+    }
+
+    if matches!(args.mode, Mode::Patch) {
+        if input_metadata.is_dir() {
+            return Err("Patch mode takes a single texture file".into());
+        }
+        return patch_file(&args, &options);
+    }
+
+    if matches!(args.mode, Mode::Split) {
+        let output_dir = args.output_path.as_deref().ok_or("Output directory is required for atlas splitting.")?;
+        batch::prepare_output_dir(output_dir, args.mkdirs).map_err(|e| e.to_string())?;
+        return split_atlases(&args, output_dir, input_metadata.is_dir(), &options);
+    }
+
+    if args.tile_preview.is_some() {
+        let previewed = match args.mode {
+            Mode::Decode => true,
+            Mode::Encode => args.verify,
+            _ => false,
+        };
+        if !previewed {
+            return Err("--tile-preview takes decode mode, or encode mode with --verify".into());
+        }
+        if args.output_path.as_deref() == Some(Path::new("-")) {
+            return Err("--tile-preview can't be used when writing to stdout".into());
+        }
+    }
+
+    if args.background.is_some() && !matches!(args.mode, Mode::Decode) {
+        return Err("--background takes decode mode".into());
+    }
+
+    if args.sidecar && !matches!(args.mode, Mode::Decode) {
+        return Err("--sidecar takes decode mode".into());
+    }
+
+    if args.reference.is_some() && !matches!(args.mode, Mode::Encode) {
+        return Err("--reference takes encode mode".into());
+    }
+
+    if args.dedup && !matches!(args.mode, Mode::Encode) {
+        return Err("--dedup takes encode mode".into());
+    }
+    if args.dedup && options.keep_format {
+        return Err("--dedup can't be used with '--output-format keep', as each image's sidecar can give it a different format".into());
+    }
+
+    if args.retry_failed.is_some() && !(input_metadata.is_dir() && matches!(args.mode, Mode::Encode | Mode::Decode)) {
+        return Err("--retry-failed takes a directory of inputs in encode or decode mode, like the run that wrote the manifest".into());
+    }
+
+    if args.estimate {
+        if input_metadata.is_dir() || !matches!(args.mode, Mode::Encode) {
+            return Err("--estimate takes a single input image in encode mode".into());
+        }
+        return print_estimates(&args.input_path, args.output_path.as_deref(), &options);
+    }
+
+    if input_metadata.is_dir() && matches!(args.mode, Mode::Info) {
+        // Bulk info, printed in a stable order
+        let files = scan_inputs(&args)?;
 
-    let input_metadata = fs::metadata(&args.input_path)?;
-    
-    if input_metadata.is_dir() {
+        for path in files {
+            if let Err(e) = texture_info(&path, &options) {
+                eprintln!("{}", report(&e.in_file(&path, None), &options));
+            }
+        }
+        return Ok(());
+    } else if input_metadata.is_dir() {
         // Bulk processing
 
-        let output_dir = Path::new(&args.output_path);
-        fs::create_dir_all(output_dir)?;
-
-        let files: Vec<PathBuf> = fs::read_dir(&args.input_path)?
-            .filter_map(|entry| entry.ok().map(|e| e.path()))
-            .filter(|p| p.is_file())
-            .collect();
-
-        files.par_iter().for_each(|path| {
-            let file_stem = path.file_stem().unwrap().to_string_lossy();
-
-            let output_file = match args.mode {
-                // TODO: Allow file type to be specified somehow
-                Mode::Encode => output_dir.join(format!("{}.ctex", file_stem)),
-                Mode::Decode => output_dir.join(format!("{}.png", file_stem)),
-            };
-
-            let file_args = Cli {
-                input_path: path.to_string_lossy().to_string(),
-                output_path: output_file.to_string_lossy().to_string(),
-                ..args.clone()
-            };
-
-            if let Err(e) = match file_args.mode {
-                Mode::Encode => encode_texture(file_args.clone()),
-                Mode::Decode => decode_texture(file_args.clone()),
-            } {
-                eprintln!("Failed to process '{}': {}", path.display(), e);
+        let output_path = args.output_path.as_ref().ok_or("Output path is required for bulk processing.")?;
+        let output_dir = output_path.as_path();
+        batch::prepare_output_dir(output_dir, args.mkdirs).map_err(|e| e.to_string())?;
+
+        let registry = ContainerRegistry::default();
+        let output_extension = match args.mode {
+            Mode::Encode => output_container(&registry, &options, None)?.extensions()[0],
+            _ => decoded_extension(&options),
+        };
+
+        let mut files = scan_inputs(&args)?;
+        if matches!(args.mode, Mode::Encode) {
+            // Sidecars sit beside the images they describe
+            files.retain(|path| !TextureSidecar::is_sidecar(path));
+        }
+        // Earlier runs' failure manifests aren't inputs either
+        files.retain(|path| path.file_name() != Some(FailureManifest::FILE_NAME.as_ref()));
+
+        // Every output is claimed by one input up front, so none are overwritten mid-run
+        let (files, skipped) = batch::output_paths(&files, output_dir, output_extension);
+        for skip in &skipped {
+            eprintln!("{}", skip);
+        }
+
+        // A retry converts only the inputs its manifest lists, and updates that manifest
+        let mode = args.mode.to_possible_value().expect("modes aren't hidden").get_name().to_string();
+        let manifest_path = args.retry_failed.clone().unwrap_or_else(|| output_dir.join(FailureManifest::FILE_NAME));
+        let files = match &args.retry_failed {
+            Some(retry_path) => {
+                let manifest = read_failure_manifest(retry_path, &mode)?;
+                let (retried, missing) = manifest.retry_plan(files);
+                for input in missing {
+                    eprintln!("Skipping '{}': it's listed in '{}', but isn't among the inputs", input.display(), retry_path.display());
+                }
+                eprintln!("Retrying {} of {} failed files", retried.len(), manifest.failures.len());
+                retried
+            }
+            None => files,
+        };
+
+        // With --dedup, only the first of each set of identical inputs is
+        // converted, and the rest are written from its output afterwards
+        let (queued, duplicates): (Vec<_>, Vec<_>) = if args.dedup {
+            let groups = dedup::group_by_content(&files.iter().map(|(path, _)| path).collect::<Vec<_>>());
+            let duplicates = groups.iter()
+                .flat_map(|group| group.duplicates.iter().map(|&duplicate| (&files[group.first], duplicate)))
+                .collect();
+            (groups.iter().map(|group| group.first).collect(), duplicates)
+        } else {
+            ((0..files.len()).collect(), Vec::new())
+        };
+
+        // Decoding counts textures, since one file can hold several
+        let decoded = AtomicUsize::new(0);
+        let total = AtomicUsize::new(0);
+        let failed_files = AtomicUsize::new(0);
+        let failed_verification = AtomicUsize::new(0);
+        let failures: Mutex<Vec<(usize, FailedInput)>> = Mutex::new(Vec::new());
+        let deduplicated = AtomicUsize::new(0);
+        // The files each converted input was written to, or the stage it failed at, for its duplicates
+        let converted: Mutex<HashMap<PathBuf, Result<Vec<PathBuf>, Stage>>> = Mutex::new(HashMap::new());
+        // Each file's messages are printed together once it's done
+        let output = OutputQueue::new(io::stderr(), args.ordered_output);
+
+        let finish = |index: usize, outcome: FileOutcome| {
+            let (path, output_file) = &files[index];
+            let mut log = outcome.log;
+            if let Err(e) = outcome.result {
+                let e = e.in_file(path, Some(output_file));
+                let counter = if e.cause().is::<VerifyError>() { &failed_verification } else { &failed_files };
+                counter.fetch_add(1, Ordering::Relaxed);
+                log.push(report(&e, &options));
+                failures.lock().unwrap().push((index, FailedInput::from(&e)));
             }
+            // Nothing else can be done with a log that can't be written
+            let _ = output.finish(index, log);
+        };
+
+        let process = |index: usize| {
+            let (path, output_file) = &files[index];
+            let outcome = FileOutcome::capture(|| match args.mode {
+                Mode::Encode => encode_texture(path, output_file, &options),
+                Mode::Decode => decode_texture(path, output_file, &options).map(|count| {
+                    decoded.fetch_add(count.decoded, Ordering::Relaxed);
+                    total.fetch_add(count.total, Ordering::Relaxed);
+                    Vec::new()
+                }),
+                Mode::Info | Mode::Atlas | Mode::Split | Mode::Patch => unreachable!(),
+            });
+            if args.dedup {
+                let written = outcome.result.as_ref().cloned().map_err(|e| e.stage());
+                converted.lock().unwrap().insert(path.clone(), written);
+            }
+            finish(index, outcome);
+        };
+
+        match args.max_memory {
+            None => queued.par_iter().for_each(|&index| process(index)),
+            Some(max_memory) => {
+                let budget = MemoryBudget::new(max_memory.saturating_mul(1024 * 1024));
+
+                // Files are started one at a time as the budget allows, rather than all being queued up front
+                rayon::scope(|scope| {
+                    for &index in &queued {
+                        // Files that can't be sized are run alone, and report their error when processed
+                        let cost = estimate_file_cost(&files[index].0, &args.mode, &options, &registry).unwrap_or(budget.limit());
+                        let permit = acquire_in_pool(&budget, cost);
+
+                        let process = &process;
+                        scope.spawn(move |_| {
+                            process(index);
+                            drop(permit);
+                        });
+                    }
+                });
+            }
+        }
+
+        duplicates.par_iter().for_each(|&((first, first_output), index)| {
+            let (path, output_file) = &files[index];
+            // Every first input has finished by now
+            let written = converted.lock().unwrap()[first.as_path()].clone();
+            let outcome = FileOutcome::capture(|| match written {
+                Ok(outputs) if outputs == [first_output.clone()] => write_duplicate(first_output, path, output_file, &options)
+                    .map(|()| {
+                        deduplicated.fetch_add(1, Ordering::Relaxed);
+                        vec![output_file.clone()]
+                    }),
+                // Animations are written to a file per frame, so each copy is encoded itself
+                Ok(_) => encode_texture(path, output_file, &options),
+                Err(stage) => Err(StageError::new(stage, format!("It has the same contents as '{}', which failed", first.display()))),
+            });
+            finish(index, outcome);
         });
+        let _ = output.into_inner();
+
+        let mut failures = failures.into_inner().unwrap();
+        failures.sort_by_key(|(index, _)| *index);
+        save_failure_manifest(&manifest_path, &FailureManifest::new(mode, failures.into_iter().map(|(_, failure)| failure)))?;
+
+        if args.dedup {
+            eprintln!("Files written from an identical input's output: {} of {}", deduplicated.into_inner(), files.len());
+        }
+        if matches!(args.mode, Mode::Decode) {
+            print_decode_summary(TextureCount { decoded: decoded.into_inner(), total: total.into_inner() }, failed_files.into_inner());
+        } else if options.verify {
+            print_verify_summary(files.len(), failed_files.into_inner(), failed_verification.into_inner());
+        }
     } else {
-        // Single file
+        // Single file, with the output checked before any pixels are loaded
         match args.mode {
-            Mode::Encode => encode_texture(args)?,
-            Mode::Decode => decode_texture(args)?,
+            Mode::Encode => {
+                let output_path = args.output_path.as_deref().ok_or("Output path is required for encoding.")?;
+                let extension = output_container(&ContainerRegistry::default(), &options, Some(output_path))?.extensions()[0];
+                let output_path = batch::prepare_output_file(output_path, &args.input_path, extension, args.mkdirs).map_err(|e| e.to_string())?;
+                encode_texture(&args.input_path, &output_path, &options)
+                    .map_err(|e| file_failure(e.in_file(&args.input_path, Some(&output_path)), &options))?;
+            }
+            Mode::Decode => {
+                let output_path = args.output_path.as_deref().ok_or("Output path is required for decoding.")?;
+                let output_path = batch::prepare_output_file(output_path, &args.input_path, decoded_extension(&options), args.mkdirs).map_err(|e| e.to_string())?;
+                let count = decode_texture(&args.input_path, &output_path, &options)
+                    .map_err(|e| file_failure(e.in_file(&args.input_path, Some(&output_path)), &options))?;
+                if count.total > 1 {
+                    print_decode_summary(count, 0);
+                }
+                if count.decoded < count.total {
+                    return Err(format!("{} of {} textures in '{}' couldn't be decoded", count.total - count.decoded, count.total, args.input_path.display()).into());
+                }
+            }
+            Mode::Atlas | Mode::Split | Mode::Patch => unreachable!(),
+            Mode::Info => {
+                return texture_info(&args.input_path, &options)
+                    .map_err(|e| file_failure(e.in_file(&args.input_path, None), &options));
+            }
         };
     }
 
-    println!("Conversion complete");
+    eprintln!("Conversion complete");
 
     Ok(())
 }
 
-fn encode_texture(args: Cli) -> Result<(), Box<dyn std::error::Error>> {
+/// Lists the files in the input directory, reporting the entries it skips.
+fn scan_inputs(args: &ConvertArgs) -> io::Result<Vec<PathBuf>> {
+    let scan_options = batch::ScanOptions { follow_symlinks: args.follow_symlinks, ..Default::default() };
+    let (files, skipped) = batch::scan_inputs(&args.input_path, &scan_options)?;
+    for skip in &skipped {
+        eprintln!("{}", skip);
+    }
+    Ok(files)
+}
+
+/// Estimates the memory needed to process a file in bulk mode, from its
+/// dimensions and format, without loading its pixels.
+fn estimate_file_cost(path: &Path, mode: &Mode, options: &SharedOptions, registry: &ContainerRegistry) -> Result<u64, Box<dyn std::error::Error>> {
+    match mode {
+        Mode::Encode => {
+            let format = options.output_format.as_ref().ok_or("Output format is required for encoding.")?;
+            let (mut width, mut height) = ImageReader::open(path)?.with_guessed_format()?.into_dimensions()?;
+            if options.resize {
+                (width, height) = pica_texture::DimensionLimits::default().resize_target(width, height);
+            }
+            Ok(estimate_texture_cost(format, width, height))
+        }
+        Mode::Decode => {
+            let infos = registry.detect(path)?.read_info(&mut BufReader::new(File::open(path)?))?;
+            for info in &infos {
+                check_dimensions(info.width, info.height, options)?;
+            }
+            Ok(infos.iter().map(|info| estimate_texture_cost(&info.format, info.width, info.height)).sum())
+        }
+        Mode::Info | Mode::Atlas | Mode::Split | Mode::Patch => Ok(0),
+    }
+}
+
+/// Acquires a permit from `budget` on a rayon worker thread.
+///
+/// While the budget is exhausted, this thread helps run the files already in
+/// flight, only blocking once there's nothing left for it to pick up. With a
+/// single thread, that's what lets the spawned files run at all.
+fn acquire_in_pool(budget: &MemoryBudget, cost: u64) -> MemoryPermit<'_> {
+    loop {
+        if let Some(permit) = budget.try_acquire(cost) {
+            return permit;
+        }
+        if rayon::yield_now() != Some(rayon::Yield::Executed) {
+            return budget.acquire(cost);
+        }
+    }
+}
+
+/// Names an encoded texture from `--name`, or the texture the input was
+/// decoded from, or after the output file, or the input when writing to stdout.
+fn texture_name(options: &SharedOptions, source: Option<&TextureSidecar>, input_path: &Path, output_path: &Path) -> String {
+    let stem = |path: &Path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match (&options.name, source) {
+        (Some(template), _) => template.replace("{stem}", &stem(input_path)),
+        (None, Some(source)) => source.name.clone(),
+        (None, None) if output_path == Path::new("-") => stem(input_path),
+        (None, None) => stem(output_path),
+    };
+    ctex::sanitize_name(&name).into_owned()
+}
+
+/// Encodes the image at `input_path` and writes it to `output_path`, or an
+/// animation to a file per frame beside it, returning the files written.
+fn encode_texture(input_path: &Path, output_path: &Path, options: &SharedOptions) -> Result<Vec<PathBuf>, StageError> {
+    // Animations are encoded a frame at a time
+    if let Some(frames) = animation::read_animation(input_path).map_err(|e| format!("Failed to decode image: {}", e)).at_stage(Stage::Load)? {
+        return encode_frames(frames, input_path, output_path, options);
+    }
+
     // Load image
-    let mut img: DynamicImage = match ImageReader::open(&args.input_path) {
+    let img: DynamicImage = match ImageReader::open(input_path) {
         Ok(reader) => match reader.decode() {
             Ok(image) => image,
             Err(e) => {
-                return Err(format!("Failed to decode image: {}", e).into());
+                return Err(StageError::new(Stage::Load, format!("Failed to decode image: {}", e)));
             }
         },
         Err(e) => {
-            return Err(format!("Failed to open image file '{}': {}", args.input_path, e).into());
+            return Err(StageError::new(Stage::Load, format!("Failed to open image file '{}': {}", input_path.display(), e)));
         }
     };
 
-    // Check input texture is power of two
+    encode_image(img, input_path, output_path, options)?;
+    Ok(vec![output_path.to_path_buf()])
+}
+
+/// Encodes each frame of an animation to its own numbered file beside
+/// `output_path`, and with `--frame-delays`, lists them with their delays
+/// in `{stem}.json`.
+///
+/// Every frame is the animation's full canvas, so resizing treats them all
+/// the same.
+fn encode_frames(frames: Vec<animation::AnimationFrame>, input_path: &Path, output_path: &Path, options: &SharedOptions) -> Result<Vec<PathBuf>, StageError> {
+    if output_path == Path::new("-") {
+        return Err(StageError::new(Stage::Validate, format!("'{}' is animated, so its {} frames can't all be written to stdout", input_path.display(), frames.len())));
+    }
+
+    let frame_paths = animation::frame_paths(output_path, frames.len());
+    progress!("Encoding {} frames of '{}'", frames.len(), input_path.display());
+
+    let mut frame_list = animation::FrameList { frames: Vec::new() };
+    for (frame, frame_path) in frames.into_iter().zip(&frame_paths) {
+        encode_image(DynamicImage::ImageRgba8(frame.image), input_path, frame_path, options)
+            .map_err(|e| e.in_file(input_path, Some(frame_path)))?;
+        frame_list.frames.push(animation::FrameEntry {
+            file: frame_path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            delay_ms: frame.delay_ms,
+        });
+    }
+
+    if options.frame_delays {
+        let list_path = output_path.with_extension("json");
+        let list = serde_json::to_string_pretty(&frame_list).at_stage(Stage::Serialize)?;
+        fs::write(&list_path, list).at_stage(Stage::Serialize)?;
+        progress!("Frame delays written to '{}'", list_path.display());
+    }
+
+    Ok(frame_paths)
+}
+
+/// Resizes and checks a loaded image, encodes it and writes it to `output_path`.
+fn encode_image(mut img: DynamicImage, input_path: &Path, output_path: &Path, options: &SharedOptions) -> Result<(), StageError> {
+    // Resize to fit the hardware limits if asked, then check the final dimensions
+    let limits = pica_texture::DimensionLimits::default();
     let (width, height) = img.dimensions();
-    if (width & (width - 1)) != 0 || (height & (height - 1)) != 0 {
-        if args.resize {
-            let new_width = width.next_power_of_two();
-            let new_height = height.next_power_of_two();
-            println!("Resizing image '{}' from {}x{} to {}x{}", args.input_path, width, height, new_width, new_height);
-            img = img.resize_exact(new_width, new_height, image::imageops::FilterType::Lanczos3);
-        } else {
-            return Err("Image dimensions are not power of two".into());
+    let mut original_dimensions = None;
+    if options.resize {
+        let (new_width, new_height) = limits.resize_target(width, height);
+        if (new_width, new_height) != (width, height) {
+            match options.resize_mode {
+                ResizeMode::Scale => {
+                    progress!("Resizing image '{}' from {}x{} to {}x{}", input_path.display(), width, height, new_width, new_height);
+                    img = img.resize_exact(new_width, new_height, image::imageops::FilterType::Lanczos3);
+                }
+                ResizeMode::Pad if new_width < width || new_height < height => {
+                    return Err(StageError::new(Stage::Validate, format!("Image dimensions {}x{} are too large to pad to {}x{} (use --resize-mode scale)", width, height, new_width, new_height)));
+                }
+                ResizeMode::Pad => {
+                    progress!("Padding image '{}' from {}x{} to {}x{}", input_path.display(), width, height, new_width, new_height);
+                    let mut padded = image::RgbaImage::new(new_width, new_height);
+                    image::imageops::replace(&mut padded, &img.to_rgba8(), 0, 0);
+                    img = DynamicImage::ImageRgba8(padded);
+                    original_dimensions = Some((width, height));
+                }
+            }
         }
     }
 
-    if width > 1024 || height > 1024 {
-        return Err("Image dimensions must not exceed 1024x1024".into());
+    if let Err(e) = check_dimensions(img.width(), img.height(), options) {
+        let hint = if options.resize { "" } else { " (use --resize to fit them, or --allow-nonstandard to keep them)" };
+        return Err(StageError::new(Stage::Validate, format!("{}{}", e, hint)));
     }
 
-    // Encode texture
-    let output_format = match args.output_format.as_ref() {
+    // Encode texture, with explicit flags winning over the texture the input was decoded from
+    let source = source_texture(input_path, options).at_stage(Stage::Load)?;
+    let output_format = match options.output_format.as_ref().or(source.as_ref().map(|source| &source.format)) {
         Some(fmt) => fmt,
         None => {
-            return Err("Output format is required for encoding.".into());
+            return Err(StageError::new(Stage::Validate, "Output format is required for encoding."));
         }
     };
-    let encoded_texture = match pica_texture::encode_texture(&img, output_format) {
+    let mip_levels = source.as_ref().map_or(1, |source| source.mip_levels)
+        .min(pica_texture::max_mip_levels(img.width(), img.height()));
+    let encode_options = pica_texture::EncodeOptions {
+        layout: options.layout.or(source.as_ref().map(|source| source.layout)).unwrap_or_default(),
+        ..options.encode_options.clone()
+    };
+    if encode_options.layout == pica_texture::TextureLayout::Linear && !output_format.has_linear_layout() {
+        return Err(StageError::new(Stage::Validate, pica_texture::TextureError::LinearUnsupported(*output_format)));
+    }
+    let encoded = if mip_levels > 1 {
+        pica_texture::encode_mip_chain(&img, output_format, mip_levels, &encode_options)
+    } else {
+        pica_texture::encode_texture(&img, output_format, &encode_options)
+    };
+    let mut encoded_texture = match encoded {
         Ok(tex) => tex,
         Err(e) => {
-            return Err(format!("Failed to encode texture: {}", e).into());
+            return Err(StageError::new(Stage::Encode, format!("Failed to encode texture: {}", e)));
         }
     };
+    if let Some((width, height)) = original_dimensions {
+        encoded_texture = encoded_texture.with_original_dimensions(width, height).at_stage(Stage::Encode)?;
+    }
+
+    // Verify the encoded texture before writing it
+    if options.verify {
+        let report = pica_texture::verify_texture(&img.to_rgba8(), &encoded_texture, &encode_options).at_stage(Stage::Verify)?;
+        progress!("Round trip for '{}': max error {}, MSE {:.3}", input_path.display(), report.max_error, report.mse);
+
+        // Uncompressed formats only lose what quantizing each channel to its bits costs
+        if !output_format.is_compressed() {
+            let checks = pica_texture::verify::verify_tolerance(&img.to_rgba8(), &encoded_texture, &encode_options).at_stage(Stage::Verify)?;
+            let failed: Vec<String> = checks.iter().filter(|check| !check.passed()).map(ToString::to_string).collect();
+            if !failed.is_empty() {
+                return Err(StageError::new(Stage::Verify, format!("Round trip verification failed: {} should be lossless at its bit depth but {}", output_format, failed.join(", "))));
+            }
+        }
+
+        if let Some(tiles) = options.tile_preview {
+            let decoded = pica_texture::decode_texture(&encoded_texture, &options.decode_options).at_stage(Stage::Decode)?;
+            write_tile_preview(&decoded.to_rgba8(), output_path, tiles).at_stage(Stage::Save)?;
+        }
+    }
 
     // Write file
-    // TODO: For now this assumes we are writing a CTEX file
-    serialization::ctex::serialize(encoded_texture, args.output_path.clone());
-    println!("Encoded file written to '{}'", args.output_path);
+    let registry = output_registry(options, input_path, output_path);
+    let container = output_container(&registry, options, Some(output_path)).at_stage(Stage::Serialize)?;
+    let textures = [NamedTexture::new(texture_name(options, source.as_ref(), input_path, output_path), encoded_texture)];
+
+    let written = if output_path == Path::new("-") {
+        let mut bytes = Vec::new();
+        container.write(&mut bytes, &textures).at_stage(Stage::Serialize)?;
+        io::stdout().lock().write_all(&bytes).at_stage(Stage::Serialize)?;
+        progress!("Encoded file written to stdout");
+        options.verify.then(|| container.read_slice(&bytes))
+    } else {
+        container.write_file(output_path, &textures).at_stage(Stage::Serialize)?;
+        progress!("Encoded file written to '{}'", output_path.display());
+        options.verify.then(|| container.read_file(output_path))
+    };
+
+    // Check what was written, as read back through the container
+    if let Some(written) = written {
+        let texture = written.map_err(|e| VerifyError::new(output_path, format!("couldn't be read back: {}", e)))
+            .at_stage(Stage::Verify)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| VerifyError::new(output_path, "holds no textures when read back"))
+            .at_stage(Stage::Verify)?
+            .texture;
+        let checks = pica_texture::verify::verify_tolerance(&img.to_rgba8(), &texture, &encode_options)
+            .map_err(|e| VerifyError::new(output_path, format!("couldn't be decoded: {}", e)))
+            .at_stage(Stage::Verify)?;
+        let failed: Vec<String> = checks.iter().filter(|check| !check.passed()).map(ToString::to_string).collect();
+        if !failed.is_empty() {
+            return Err(StageError::new(Stage::Verify, VerifyError::new(output_path, format!("doesn't match the source as {}: {}", texture.format(), failed.join(", ")))));
+        }
+    }
     Ok(())
 }
 
-fn decode_texture(args: Cli) -> Result<(), Box<dyn std::error::Error>> {
-    // TODO: For now this assumes we are decoding a CTEX file
-    let encoded_texture = serialization::ctex::deserialize(args.input_path)?;
+/// Writes an input to `output_path` from `first_output`, the output of an
+/// earlier input with the same contents, renaming its texture for this one
+/// and copying its tile preview.
+fn write_duplicate(first_output: &Path, input_path: &Path, output_path: &Path, options: &SharedOptions) -> Result<(), StageError> {
+    let registry = output_registry(options, input_path, output_path);
+    let container = output_container(&registry, options, Some(output_path)).at_stage(Stage::Serialize)?;
+    let source = source_texture(input_path, options).at_stage(Stage::Load)?;
+    let mut textures = container.read_file(first_output).at_stage(Stage::Load)?;
+    for named in &mut textures {
+        named.name = texture_name(options, source.as_ref(), input_path, output_path);
+    }
 
-    let dec_texture = pica_texture::decode_texture(&encoded_texture)?;
+    container.write_file(output_path, &textures).at_stage(Stage::Serialize)?;
+    progress!("Encoded file written to '{}' from '{}', encoded from the same image", output_path.display(), first_output.display());
 
-    dec_texture.save(args.output_path.clone())?;
-    println!("Decoded file written to '{}'", args.output_path);
+    if options.verify {
+        let written = container.read_file(output_path)
+            .map_err(|e| VerifyError::new(output_path, format!("couldn't be read back: {}", e)))
+            .at_stage(Stage::Verify)?;
+        if written.iter().map(|named| &named.texture).ne(textures.iter().map(|named| &named.texture)) {
+            return Err(StageError::new(Stage::Verify, VerifyError::new(output_path, format!("doesn't hold the texture in '{}' when read back", first_output.display()))));
+        }
 
+        if let Some(tiles) = options.tile_preview {
+            fs::copy(tile_preview_path(first_output, tiles), tile_preview_path(output_path, tiles)).at_stage(Stage::Save)?;
+        }
+    }
     Ok(())
 }
 
+/// What converting one file in a bulk run came to: the files it wrote, or
+/// why it failed, with the progress messages it logged along the way.
+struct FileOutcome {
+    result: Result<Vec<PathBuf>, StageError>,
+    log: Vec<String>,
+}
+
+impl FileOutcome {
+    /// Runs a file's conversion, collecting its messages rather than printing them.
+    fn capture(convert: impl FnOnce() -> Result<Vec<PathBuf>, StageError>) -> Self {
+        let (result, log) = progress::capture(convert);
+        FileOutcome { result, log }
+    }
+}
+
+/// Formats a file's failure for a bulk run's log: on one line, or with
+/// `--verbose-errors`, over several.
+fn report(e: &StageError, options: &SharedOptions) -> String {
+    if options.verbose_errors { format!("{:#}", e) } else { e.to_string() }
+}
+
+/// Turns a file's failure into the error a single-file run exits with.
+fn file_failure(e: StageError, options: &SharedOptions) -> Box<dyn std::error::Error> {
+    // Errors exit through their Debug form, which for a StageError is the verbose one
+    if options.verbose_errors { e.into() } else { e.to_string().into() }
+}
+
+/// An encoded file that doesn't hold what was encoded when read back with
+/// `--verify`, kept apart from encode errors so bulk runs can count it.
+#[derive(Debug)]
+struct VerifyError {
+    message: String,
+}
+
+impl VerifyError {
+    fn new(output_path: &Path, problem: impl std::fmt::Display) -> Self {
+        let output = if output_path == Path::new("-") { "stdout".to_string() } else { format!("'{}'", output_path.display()) };
+        VerifyError { message: format!("Verification failed: {} {}", output, problem) }
+    }
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Finds the texture the input image was decoded from: the first texture in
+/// `--reference` if given, otherwise with `--output-format keep`, the
+/// sidecar beside the image.
+fn source_texture(input_path: &Path, options: &SharedOptions) -> Result<Option<TextureSidecar>, Box<dyn std::error::Error>> {
+    if let Some(reference) = &options.reference {
+        let named = read_textures(reference, options)?.into_iter()
+            .next()
+            .ok_or_else(|| format!("No textures found in '{}'", reference.display()))?
+            .map_err(|e| format!("Failed to read reference '{}': {}", reference.display(), e))?;
+        return Ok(Some(TextureSidecar::from_texture(&named)));
+    }
+    if !options.keep_format {
+        return Ok(None);
+    }
+
+    let sidecar_path = TextureSidecar::path_for(input_path);
+    let text = fs::read_to_string(&sidecar_path)
+        .map_err(|e| format!("'--output-format keep' needs the sidecar '{}' or a --reference texture: {}", sidecar_path.display(), e))?;
+    let sidecar = serde_json::from_str(&text)
+        .map_err(|e| format!("Failed to read sidecar '{}': {}", sidecar_path.display(), e))?;
+    Ok(Some(sidecar))
+}
+
+/// Packs every image in the input directory into atlases, each encoded like
+/// a single texture, and writes a JSON manifest of where each image went.
+///
+/// `output_path` names the atlases: `ui.ctex` gives `ui_0.ctex`, `ui_1.ctex`
+/// and so on, with the manifest in `ui.json`. An output directory gives them
+/// the input directory's name.
+fn pack_atlases(args: &ConvertArgs, output_path: &Path, options: &SharedOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let format = options.output_format.as_ref().ok_or("Output format is required for atlas packing.")?;
+
+    let extension = output_container(&ContainerRegistry::default(), options, Some(output_path))?.extensions()[0];
+    let output_path = batch::prepare_output_file(output_path, &args.input_path, extension, args.mkdirs).map_err(|e| e.to_string())?;
+    let stem = output_path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+
+    // Images are named by file stem, so only the first of any two sharing one is packed
+    let mut images: Vec<(String, image::RgbaImage)> = Vec::new();
+    for path in scan_inputs(args)? {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        if images.iter().any(|(packed, _)| *packed == name) {
+            eprintln!("Skipping '{}': an image named '{}' is already packed", path.display(), name);
+            continue;
+        }
+        match ImageReader::open(&path).map_err(image::ImageError::from).and_then(|reader| reader.with_guessed_format()?.decode()) {
+            Ok(image) => images.push((name, image.to_rgba8())),
+            Err(e) => eprintln!("Failed to process '{}': {}", path.display(), e),
+        }
+    }
+    if images.is_empty() {
+        return Err(format!("No images found in '{}'", args.input_path.display()).into());
+    }
+
+    let pack_options = atlas::PackOptions { padding: args.padding, ..Default::default() };
+    let (atlases, manifest) = atlas::build_atlases(&images, &pack_options, |index| format!("{}_{}.{}", stem, index, extension))?;
+
+    for (canvas, entry) in atlases.into_iter().zip(&manifest.atlases) {
+        let atlas_path = output_path.with_file_name(&entry.file);
+        let texture = pica_texture::encode_texture(&DynamicImage::ImageRgba8(canvas), format, &options.encode_options)?;
+
+        let registry = output_registry(options, &args.input_path, &atlas_path);
+        let container = output_container(&registry, options, Some(&atlas_path))?;
+        let name = atlas_path.file_stem().unwrap_or_default().to_string_lossy();
+        container.write_file(&atlas_path, &[NamedTexture::new(ctex::sanitize_name(&name), texture)])?;
+        eprintln!("Atlas written to '{}'", atlas_path.display());
+    }
+
+    let manifest_path = output_path.with_extension("json");
+    fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)?;
+    eprintln!("Packed {} images into {} atlases, listed in '{}'", manifest.sprites.len(), manifest.atlases.len(), manifest_path.display());
+
+    Ok(())
+}
+
+/// Cuts atlases back into the images packed into them, writing each sprite
+/// their manifest lists on them to `{name}.png` in `output_dir`.
+///
+/// Given a directory, every texture file in it is split, each with its own
+/// manifest. Sprites already written by an earlier atlas in the run are
+/// skipped rather than overwritten.
+fn split_atlases(args: &ConvertArgs, output_dir: &Path, bulk: bool, options: &SharedOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if !bulk {
+        let count = split_atlas(&args.input_path, args.manifest.as_deref(), output_dir, options, &mut Default::default())
+            .map_err(|e| format!("Failed to process '{}': {}", args.input_path.display(), e))?;
+        eprintln!("Split {} sprites out of '{}'", count, args.input_path.display());
+        return Ok(());
+    }
+
+    // Manifests and other files sit beside the atlases, so only texture files are split
+    let registry = input_registry(options);
+    let atlases: Vec<PathBuf> = scan_inputs(args)?.into_iter()
+        .filter(|path| registry.for_path(path).is_some())
+        .collect();
+    if atlases.is_empty() {
+        return Err(format!("No atlases found in '{}'", args.input_path.display()).into());
+    }
+
+    let mut written = std::collections::HashSet::new();
+    let mut count = 0;
+    for path in &atlases {
+        match split_atlas(path, args.manifest.as_deref(), output_dir, options, &mut written) {
+            Ok(sprites) => count += sprites,
+            Err(e) => eprintln!("Failed to process '{}': {}", path.display(), e),
+        }
+    }
+    eprintln!("Split {} sprites out of {} atlases", count, atlases.len());
+
+    Ok(())
+}
+
+/// Decodes one atlas and writes out the sprites on it, returning how many
+/// were written. `written` holds the names already taken in the run.
+fn split_atlas(atlas_path: &Path, manifest: Option<&Path>, output_dir: &Path, options: &SharedOptions, written: &mut std::collections::HashSet<String>) -> Result<usize, Box<dyn std::error::Error>> {
+    let manifest_path = find_manifest(atlas_path, manifest)
+        .ok_or_else(|| format!("No manifest found for '{}' (use --manifest to give one)", atlas_path.display()))?;
+    let manifest = read_manifest(&manifest_path)?;
+
+    let file_name = atlas_path.file_name().unwrap_or_default().to_string_lossy();
+    let sprites = manifest.sprites_on(&file_name)
+        .ok_or_else(|| format!("'{}' doesn't list an atlas named '{}'", manifest_path.display(), file_name))?;
+
+    let texture = read_texture(atlas_path, options)?;
+    let (width, height) = texture.dimensions();
+    if let Err(e) = check_dimensions(width, height, options) {
+        return Err(format!("{} (use --allow-nonstandard to decode it anyway)", e).into());
+    }
+    let pixels = pica_texture::decode_texture(&texture, &options.decode_options)?.into_rgba8();
+
+    // Every rectangle is checked before anything is written
+    let regions = sprites.iter()
+        .map(|sprite| atlas::extract_region(pixels.as_raw(), pixels.width(), pixels.height(), sprite))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut count = 0;
+    for (sprite, region) in sprites.iter().zip(regions) {
+        let name = &sprite.name;
+        if name.is_empty() || name.contains(['/', '\\', ':']) || matches!(name.as_str(), "." | "..") {
+            eprintln!("Skipping sprite '{}' in '{}': its name can't be used as a file name", name, atlas_path.display());
+            continue;
+        }
+        if !written.insert(name.clone()) {
+            eprintln!("Skipping sprite '{}' in '{}': a sprite with that name was already written", name, atlas_path.display());
+            continue;
+        }
+        region.save(output_dir.join(format!("{}.png", name)))?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Finds the manifest for an atlas: `manifest` itself if it's a file,
+/// otherwise `{stem}.json` or `{stem}.csv` in `manifest`, or beside the
+/// atlas if it isn't given.
+///
+/// Atlases written by the packing mode are numbered, so `ui_0.ctex` also
+/// finds `ui.json`.
+fn find_manifest(atlas_path: &Path, manifest: Option<&Path>) -> Option<PathBuf> {
+    if let Some(path) = manifest.filter(|path| path.is_file()) {
+        return Some(path.to_path_buf());
+    }
+    let dir = manifest.or_else(|| atlas_path.parent()).unwrap_or(Path::new(""));
+
+    let stem = atlas_path.file_stem().unwrap_or_default().to_string_lossy();
+    let unnumbered = stem.rsplit_once('_')
+        .filter(|(_, index)| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()))
+        .map(|(base, _)| base);
+
+    [Some(stem.as_ref()), unnumbered].into_iter().flatten()
+        .flat_map(|stem| ["json", "csv"].map(|extension| dir.join(format!("{}.{}", stem, extension))))
+        .find(|path| path.is_file())
+}
+
+/// Reads a manifest, as CSV if its extension says so and JSON otherwise.
+fn read_manifest(path: &Path) -> Result<atlas::AtlasManifest, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path)?;
+    let is_csv = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    let manifest = if is_csv { atlas::AtlasManifest::from_csv(&text)? } else { serde_json::from_str(&text)? };
+    Ok(manifest)
+}
+
+/// Reads the failure manifest `--retry-failed` names, checking it can be
+/// retried in `mode`.
+fn read_failure_manifest(path: &Path, mode: &str) -> Result<FailureManifest, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read failure manifest '{}': {}", path.display(), e))?;
+    let manifest: FailureManifest = serde_json::from_str(&text).map_err(|e| format!("Failed to read failure manifest '{}': {}", path.display(), e))?;
+    manifest.check(mode)?;
+    Ok(manifest)
+}
+
+/// Writes a bulk run's failures to `path`, or removes the manifest there if
+/// nothing failed, so it never lists files that have since converted.
+fn save_failure_manifest(path: &Path, manifest: &FailureManifest) -> Result<(), Box<dyn std::error::Error>> {
+    if manifest.failures.is_empty() {
+        match fs::remove_file(path) {
+            Ok(()) => eprintln!("Every file converted, so '{}' is removed", path.display()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to remove failure manifest '{}': {}", path.display(), e).into()),
+        }
+        return Ok(());
+    }
+
+    fs::write(path, serde_json::to_string_pretty(manifest)?)
+        .map_err(|e| format!("Failed to write failure manifest '{}': {}", path.display(), e))?;
+    eprintln!("Failed files listed in '{}', to retry with --retry-failed", path.display());
+    Ok(())
+}
+
+/// Pastes `--image` over the first texture in the input file at `--region`,
+/// re-encoding only the tiles it touches, and writes the file back out with
+/// any other textures in it unchanged.
+///
+/// Without an output path, the input file is patched in place.
+fn patch_file(args: &ConvertArgs, options: &SharedOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let image_path = args.image.as_deref().ok_or("Patch mode needs an --image to paste")?;
+    let (x, y) = args.region.ok_or("Patch mode needs a --region to paste the image at")?;
+    if args.mirror {
+        return Err("--mirror can't be used in patch mode".into());
+    }
+
+    let patch = ImageReader::open(image_path)
+        .map_err(|e| format!("Failed to open image file '{}': {}", image_path.display(), e))?
+        .with_guessed_format()?
+        .decode()
+        .map_err(|e| format!("Failed to decode image: {}", e))?
+        .to_rgba8();
+
+    // Every texture is written back out, so they all have to be readable
+    let mut textures = read_textures(&args.input_path, options)?.into_iter()
+        .collect::<Result<Vec<NamedTexture>, _>>()
+        .map_err(|e| format!("Failed to process '{}': {}", args.input_path.display(), e))?;
+    let target = &mut textures[0];
+
+    let (width, height) = target.texture.dimensions();
+    target.texture = pica_texture::patch_texture(&target.texture, &patch, x, y, &options.encode_options)
+        .map_err(|e| format!("Failed to patch '{}': {}", args.input_path.display(), e))?;
+
+    let (left, top, right, bottom) = pica_texture::patch::affected_pixels(width, height, x, y, patch.width(), patch.height());
+    if (left, top, right, bottom) != (x, y, x + patch.width(), y + patch.height()) {
+        eprintln!("Warning: the image doesn't cover whole 8x8 tiles, so the pixels around it from {},{} to {},{} are re-encoded too", left, top, right, bottom);
+    }
+
+    let output_path = args.output_path.as_deref().unwrap_or(&args.input_path);
+    let registry = output_registry(options, &args.input_path, output_path);
+    let container = output_container(&registry, options, Some(output_path))?;
+    container.write_file(output_path, &textures)?;
+    eprintln!("Patched {}x{} pixels at {},{} in '{}'", patch.width(), patch.height(), x, y, output_path.display());
+
+    Ok(())
+}
+
+/// Parses `--output-format`: `keep`, or any name [`pica_texture::TextureFormat`] accepts.
+///
+/// The values offered to shell completion come from the format's
+/// [`ValueEnum`](clap::ValueEnum) implementation, which lists the same names
+/// its `FromStr` implementation accepts.
+#[derive(Clone)]
+struct OutputFormatParser;
+
+impl clap::builder::TypedValueParser for OutputFormatParser {
+    type Value = OutputFormat;
+
+    fn parse_ref(&self, cmd: &clap::Command, arg: Option<&clap::Arg>, value: &std::ffi::OsStr) -> Result<OutputFormat, clap::Error> {
+        let value = value.to_string_lossy();
+        if value.eq_ignore_ascii_case("keep") {
+            return Ok(OutputFormat::Keep);
+        }
+        value.parse().map(OutputFormat::Format).map_err(|e: pica_texture::TextureError| {
+            let arg = arg.map(|arg| arg.to_string()).unwrap_or_default();
+            clap::Error::raw(clap::error::ErrorKind::InvalidValue, format!("invalid value '{}' for '{}': {}\n", value, arg, e)).with_cmd(cmd)
+        })
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = clap::builder::PossibleValue> + '_>> {
+        let formats = pica_texture::TextureFormat::value_variants().iter().filter_map(clap::ValueEnum::to_possible_value);
+        let keep = clap::builder::PossibleValue::new("keep").help("The format of the texture the input was decoded from");
+        Some(Box::new(formats.chain([keep])))
+    }
+}
+
+/// Parses a `--region` offset like `64,32`.
+fn parse_region(value: &str) -> Result<(u32, u32), String> {
+    let invalid = || "expected X,Y pixel offsets, like 64,32".to_string();
+
+    let (x, y) = value.split_once(',').ok_or_else(invalid)?;
+    let offset = |offset: &str| offset.trim().parse::<u32>().map_err(|_| invalid());
+    Ok((offset(x)?, offset(y)?))
+}
+
+/// Prints a table of the estimated output size of the input image in every format.
+fn print_estimates(input_path: &Path, output_path: Option<&Path>, options: &SharedOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut width, mut height) = ImageReader::open(input_path)?
+        .with_guessed_format()?
+        .into_dimensions()?;
+
+    if options.resize {
+        (width, height) = pica_texture::DimensionLimits::default().resize_target(width, height);
+    }
+
+    let registry = ContainerRegistry::default();
+    let container = output_container(&registry, options, output_path)?;
+
+    println!("{} ({}x{}):", input_path.display(), width, height);
+    println!("  {:<10} {:>12} {:>12}", "Format", "Data", container.name());
+    for format in pica_texture::TextureFormat::ALL {
+        let data_len = pica_texture::estimate_encoded_size(&format, width, height, 1);
+        let file_len = container.estimate_size(&format, width, height, data_len);
+        println!("  {:<10} {:>12} {:>12}", format.name(), data_len, file_len);
+    }
+
+    Ok(())
+}
+
+/// Builds the containers to write `output_path` with: the same ones as
+/// [`ContainerRegistry::default`], set up from the command line.
+///
+/// With `--edit-data`, ctex files get an EditData block. Re-encoding over a
+/// file that has one keeps it, so the texture's history isn't lost;
+/// otherwise a new one names `input_path` as the source. The same goes for
+/// the Path attribute, unless `--ctex-path` or `--strip-paths` say otherwise,
+/// and the Version and Namespace, unless `--ctex-version` or `--ctex-namespace` do.
+/// Either way they're written in the form the replaced file used, as elements
+/// or as attributes.
+fn output_registry(options: &SharedOptions, input_path: &Path, output_path: &Path) -> ContainerRegistry {
+    let mut ctex_options = CtexOptions {
+        indent: options.indent,
+        encoding: options.encoding,
+        wrap: options.wrap,
+        strip_paths: options.strip_paths,
+        ..Default::default()
+    };
+    let existing = File::open(output_path).ok()
+        .and_then(|file| ctex::read_document_settings(BufReader::new(file)).ok())
+        .unwrap_or_default();
+    ctex_options.path = match &options.ctex_path {
+        Some(template) => Some(template.replace("{stem}", &input_path.file_stem().unwrap_or_default().to_string_lossy())),
+        None if options.strip_paths => None,
+        None => existing.path,
+    };
+    if let Some(version) = options.ctex_version.clone().or(existing.version) {
+        ctex_options.version = version;
+    }
+    if let Some(namespace) = options.ctex_namespace.clone().or(existing.namespace) {
+        ctex_options.namespace = namespace;
+    }
+    if let Some(header_style) = existing.header_style {
+        ctex_options.header_style = header_style;
+    }
+    if options.edit_data {
+        let source = fs::canonicalize(input_path).unwrap_or_else(|_| input_path.to_path_buf());
+        ctex_options.edit_data = Some(existing.edit_data.unwrap_or_else(|| EditData::Created(CreateInfo::new(source.to_string_lossy()))));
+    }
+
+    let mut registry = ContainerRegistry::default();
+    registry.replace(Box::new(Ctex::with_options(ctex_options)));
+    registry
+}
+
+/// Picks the container to encode into: the `--container` flag if given, then
+/// the output file's extension, then the registry's first container.
+fn output_container<'a>(registry: &'a ContainerRegistry, options: &SharedOptions, output_path: Option<&Path>) -> Result<&'a dyn TextureContainer, Box<dyn std::error::Error>> {
+    if let Some(name) = &options.container {
+        return registry.by_extension(name)
+            .ok_or_else(|| format!("Unknown container '{}'", name).into());
+    }
+
+    output_path.and_then(|path| registry.for_path(path))
+        .or_else(|| registry.containers().next())
+        .ok_or_else(|| "No texture containers available".into())
+}
+
+/// Builds the containers to read input files with: the same ones as
+/// [`ContainerRegistry::default`], set up from the command line.
+fn input_registry(options: &SharedOptions) -> ContainerRegistry {
+    let mut registry = ContainerRegistry::default();
+    registry.replace(Box::new(Ctex::with_options(CtexOptions { lenient: options.lenient, ..Default::default() })));
+    registry
+}
+
+/// Reads the first texture from a container file, detecting its container type.
+fn read_texture(path: &Path, options: &SharedOptions) -> Result<pica_texture::PicaTexture, Box<dyn std::error::Error>> {
+    read_textures(path, options)?.into_iter()
+        .next()
+        .ok_or_else(|| format!("No textures found in '{}'", path.display()))?
+        .map(|named| named.texture)
+}
+
+/// Reads every texture from a container file, detecting its container type,
+/// with a result for each so one bad texture doesn't lose the rest.
+fn read_textures(path: &Path, options: &SharedOptions) -> Result<Vec<TextureResult>, Box<dyn std::error::Error>> {
+    let registry = input_registry(options);
+    let container = registry.detect(path)?;
+
+    let textures = container.read_file_each(path, options.mmap)?;
+    if textures.is_empty() {
+        return Err(format!("No textures found in '{}'", path.display()).into());
+    }
+    Ok(textures)
+}
+
+/// Checks that a texture's dimensions are ones the PICA supports, unless
+/// `--allow-nonstandard` was given.
+///
+/// Decoding checks the dimensions a file declares before decoding it, so a
+/// corrupt header can't ask for gigabytes of decoded pixels.
+fn check_dimensions(width: u32, height: u32, options: &SharedOptions) -> Result<(), pica_texture::TextureError> {
+    if options.allow_nonstandard {
+        return Ok(());
+    }
+    pica_texture::validate_dimensions(width, height, &pica_texture::DimensionLimits::default())
+}
+
+/// Decodes every texture in a container file, returning how many of them
+/// were decoded and how many there were.
+///
+/// A file with one texture is written to `output_path`. With more, each is
+/// written beside it, named after the texture or, failing that, its index.
+/// A texture that fails is reported and the rest are still decoded.
+fn decode_texture(input_path: &Path, output_path: &Path, options: &SharedOptions) -> Result<TextureCount, StageError> {
+    let textures = read_textures(input_path, options).at_stage(Stage::Load)?;
+    if output_path == Path::new("-") && textures.len() > 1 {
+        return Err(StageError::new(Stage::Validate, format!("'{}' holds {} textures, so they can't all be written to stdout", input_path.display(), textures.len())));
+    }
+    let output_paths = texture_output_paths(output_path, &textures);
+
+    let mut count = TextureCount { decoded: 0, total: textures.len() };
+    for (index, (result, output_path)) in textures.into_iter().zip(&output_paths).enumerate() {
+        let decoded = result.at_stage(Stage::Load)
+            .and_then(|named| with_layout_override(named, options).at_stage(Stage::Validate))
+            .and_then(|named| decode_one(&named, output_path, options));
+        match decoded {
+            Ok(()) => count.decoded += 1,
+            Err(e) if count.total == 1 => return Err(e),
+            Err(e) => progress!("Texture {}: {}", index, report(&e.in_file(input_path, Some(output_path)), options)),
+        }
+    }
+
+    Ok(count)
+}
+
+/// Applies `--layout` to a texture about to be decoded, for data stored in
+/// rows that its container doesn't mark as linear, or the other way round.
+fn with_layout_override(named: NamedTexture, options: &SharedOptions) -> Result<NamedTexture, Box<dyn std::error::Error>> {
+    match options.layout {
+        Some(layout) => Ok(NamedTexture::new(named.name, named.texture.with_layout(layout)?)),
+        None => Ok(named),
+    }
+}
+
+/// Decodes a single texture and saves it to `output_path`, with its sidecar
+/// beside it if `--sidecar` asks for one.
+fn decode_one(named: &NamedTexture, output_path: &Path, options: &SharedOptions) -> Result<(), StageError> {
+    let encoded_texture = &named.texture;
+    let (width, height) = encoded_texture.dimensions();
+    if let Err(e) = check_dimensions(width, height, options) {
+        return Err(StageError::new(Stage::Validate, format!("{} (use --allow-nonstandard to decode it anyway)", e)));
+    }
+
+    // Padded textures are cropped back to the image they were padded from
+    let crop = encoded_texture.original_dimensions().filter(|_| !options.keep_padding);
+    let decode_options = pica_texture::DecodeOptions { crop: crop.or(options.decode_options.crop), ..options.decode_options.clone() };
+    if options.raw_output {
+        let (width, height) = decode_options.crop.unwrap_or((width, height));
+        let mut decoded = image::RgbaImage::from_raw(width, height, pica_texture::decode_to_rgba(encoded_texture, &decode_options).at_stage(Stage::Decode)?)
+            .ok_or("Decoded pixels don't match the texture's dimensions")
+            .at_stage(Stage::Decode)?;
+        if let Some(background) = &options.background {
+            decoded = pica_texture::composite_over(&decoded, background);
+        }
+
+        write_raw_rgba(&decoded, output_path).at_stage(Stage::Save)?;
+
+        if let Some(tiles) = options.tile_preview {
+            write_tile_preview(&decoded, output_path, tiles).at_stage(Stage::Save)?;
+        }
+    } else {
+        let mut dec_texture = pica_texture::decode_texture(encoded_texture, &decode_options).at_stage(Stage::Decode)?;
+        if let Some(background) = &options.background {
+            dec_texture = pica_texture::composite_over(&dec_texture.to_rgba8(), background).into();
+        }
+
+        dec_texture.save(output_path).at_stage(Stage::Save)?;
+        progress!("Decoded file written to '{}'", output_path.display());
+
+        if let Some(tiles) = options.tile_preview {
+            write_tile_preview(&dec_texture.to_rgba8(), output_path, tiles).at_stage(Stage::Save)?;
+        }
+    }
+
+    if options.sidecar {
+        let sidecar_path = TextureSidecar::path_for(output_path);
+        let sidecar = serde_json::to_string_pretty(&TextureSidecar::from_texture(named)).at_stage(Stage::Save)?;
+        fs::write(&sidecar_path, sidecar).at_stage(Stage::Save)?;
+        progress!("Sidecar written to '{}'", sidecar_path.display());
+    }
+
+    Ok(())
+}
+
+/// Decodes a raw dump of texture data, with no container around it, from
+/// the input file or from stdin for `-`.
+///
+/// Nothing in the dump says what it holds, so `--width`, `--height` and
+/// `--output-format` must, and its length must be exactly what they add up to.
+/// It's taken to be tiled unless `--layout linear` says it's in rows.
+fn decode_raw_file(args: &ConvertArgs, options: &SharedOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if !matches!(args.mode, Mode::Decode) {
+        return Err("--input-format raw takes decode mode".into());
+    }
+    let format = match &args.output_format {
+        Some(OutputFormat::Format(format)) => format,
+        Some(OutputFormat::Keep) => return Err("A raw dump has no texture to keep the format of; give its format with --output-format".into()),
+        None => return Err("--input-format raw needs the dump's format, given with --output-format".into()),
+    };
+    let (width, height) = match (args.width, args.height) {
+        (Some(width), Some(height)) => (width, height),
+        (None, None) => return Err("--input-format raw needs the dump's dimensions, given with --width and --height".into()),
+        (Some(_), None) => return Err("--input-format raw needs --height as well as --width".into()),
+        (None, Some(_)) => return Err("--input-format raw needs --width as well as --height".into()),
+    };
+    if let Err(e) = check_dimensions(width, height, options) {
+        return Err(format!("{} (use --allow-nonstandard to decode it anyway)", e).into());
+    }
+    let output_path = args.output_path.as_deref().ok_or("Output path is required for decoding.")?;
+
+    let from_stdin = args.input_path == Path::new("-");
+    let (input_name, data) = if from_stdin {
+        let mut data = Vec::new();
+        io::stdin().lock().read_to_end(&mut data)
+            .at_stage(Stage::Load)
+            .map_err(|e| file_failure(e.in_file(Path::new("-"), None), options))?;
+        (Path::new("stdin"), data)
+    } else {
+        let failed = |e: StageError| file_failure(e.in_file(&args.input_path, None), options);
+        if fs::metadata(&args.input_path).at_stage(Stage::Load).map_err(failed)?.is_dir() {
+            return Err("--input-format raw takes a single dump, or '-' to read one from stdin".into());
+        }
+        (args.input_path.as_path(), fs::read(&args.input_path).at_stage(Stage::Load).map_err(failed)?)
+    };
+
+    let expected = format.expected_data_len(width, height);
+    if data.len() != expected {
+        let source = if from_stdin { "stdin".to_string() } else { format!("'{}'", args.input_path.display()) };
+        return Err(format!("Read {} bytes from {}, but a {}x{} {} texture takes {} bytes", data.len(), source, width, height, format, expected).into());
+    }
+    let texture = pica_texture::PicaTexture::try_new(*format, width, height, data)
+        .and_then(|texture| texture.with_layout(options.layout.unwrap_or_default()))
+        .at_stage(Stage::Validate)
+        .map_err(|e| file_failure(e.in_file(input_name, None), options))?;
+
+    let output_path = batch::prepare_output_file(output_path, input_name, decoded_extension(options), args.mkdirs).map_err(|e| e.to_string())?;
+    let name = input_name.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    decode_one(&NamedTexture::new(name, texture), &output_path, options)
+        .map_err(|e| file_failure(e.in_file(input_name, Some(&output_path)), options))?;
+
+    eprintln!("Conversion complete");
+    Ok(())
+}
+
+/// Writes decoded pixels as headerless RGBA8 bytes for `--raw-output`,
+/// reporting their dimensions since nothing in the bytes records them.
+fn write_raw_rgba(decoded: &image::RgbaImage, output_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if output_path == Path::new("-") {
+        io::stdout().lock().write_all(decoded.as_raw())?;
+        progress!("Raw RGBA8 written to stdout: {}x{}", decoded.width(), decoded.height());
+    } else {
+        fs::write(output_path, decoded.as_raw())?;
+        progress!("Raw RGBA8 written to '{}': {}x{}", output_path.display(), decoded.width(), decoded.height());
+    }
+    Ok(())
+}
+
+/// The extension decoded files are written with when only a directory is given.
+fn decoded_extension(options: &SharedOptions) -> &'static str {
+    if options.raw_output { "rgba" } else { "png" }
+}
+
+/// Writes a decoded texture repeated as `--tile-preview` asks, beside
+/// `output_path`, at [`tile_preview_path`].
+fn write_tile_preview(decoded: &image::RgbaImage, output_path: &Path, (columns, rows): (u32, u32)) -> Result<(), Box<dyn std::error::Error>> {
+    let preview = pica_texture::tile_preview(decoded, columns, rows)?;
+
+    let preview_path = tile_preview_path(output_path, (columns, rows));
+    preview.save(&preview_path)?;
+    progress!("Tile preview written to '{}'", preview_path.display());
+
+    Ok(())
+}
+
+/// Names the tile preview of `output_path` as `{stem}_tile{N}x{M}.png` beside it.
+fn tile_preview_path(output_path: &Path, (columns, rows): (u32, u32)) -> PathBuf {
+    let stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
+    output_path.with_file_name(format!("{}_tile{}x{}.png", stem, columns, rows))
+}
+
+/// Parses a `--tile-preview` count like `3x2`.
+fn parse_tile_count(value: &str) -> Result<(u32, u32), String> {
+    let max = pica_texture::preview::MAX_TILE_REPEAT;
+    let invalid = || format!("expected NxM with each count from 1 to {}, like 2x2", max);
+
+    let (columns, rows) = value.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let count = |count: &str| count.trim().parse::<u32>().ok().filter(|count| (1..=max).contains(count)).ok_or_else(invalid);
+    Ok((count(columns)?, count(rows)?))
+}
+
+/// Picks where each texture read from a file is decoded to.
+///
+/// A lone texture goes to `output_path`; otherwise each goes beside it as
+/// `{stem}_{name}`, using the texture's index where its name is empty,
+/// unusable in a file name, or already taken, and suffixing the index in
+/// turn if that's taken too.
+fn texture_output_paths(output_path: &Path, textures: &[TextureResult]) -> Vec<PathBuf> {
+    if textures.len() == 1 {
+        return vec![output_path.to_path_buf()];
+    }
+
+    let stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output_path.extension().unwrap_or_default().to_string_lossy();
+
+    let mut used = std::collections::HashSet::new();
+    textures.iter().enumerate().map(|(index, result)| {
+        let name = result.as_ref().ok()
+            .map(|named| named.name.as_str())
+            .filter(|name| !name.is_empty() && !name.contains(['/', '\\', ':']) && !matches!(*name, "." | ".."))
+            .filter(|name| !used.contains(*name))
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                // Another texture may already be named after this index
+                (0..).map(|n| if n == 0 { index.to_string() } else { format!("{}_{}", index, n) })
+                    .find(|name| !used.contains(name.as_str()))
+                    .unwrap()
+            });
+        used.insert(name.clone());
+        output_path.with_file_name(format!("{}_{}.{}", stem, name, extension))
+    }).collect()
+}
+
+/// Reports how many textures were decoded, and how many files couldn't be read at all.
+fn print_decode_summary(count: TextureCount, failed_files: usize) {
+    eprintln!("Decoded {} of {} textures", count.decoded, count.total);
+    if failed_files > 0 {
+        eprintln!("Files that couldn't be read: {}", failed_files);
+    }
+}
+
+/// Reports how many files were encoded and passed `--verify`, with the files
+/// that failed verification apart from those that failed to encode.
+fn print_verify_summary(total: usize, failed_files: usize, failed_verification: usize) {
+    eprintln!("Verified {} of {} files", total - failed_files - failed_verification, total);
+    if failed_verification > 0 {
+        eprintln!("Files that failed verification: {}", failed_verification);
+    }
+    if failed_files > 0 {
+        eprintln!("Files that failed to encode: {}", failed_files);
+    }
+}
+
+/// How many of the textures in a run were decoded.
+#[derive(Clone, Copy)]
+struct TextureCount {
+    decoded: usize,
+    total: usize,
+}
+
+fn texture_info(input_path: &Path, options: &SharedOptions) -> Result<(), StageError> {
+    let texture = read_texture(input_path, options).at_stage(Stage::Load)?;
+    let info = texture.info();
+
+    if options.json {
+        println!("{}", serde_json::to_string(&info).at_stage(Stage::Save)?);
+    } else {
+        println!("{}:", input_path.display());
+        println!("  Format:         {}", info.format);
+        println!("  Dimensions:     {}x{}", info.width, info.height);
+        println!("  Mip levels:     {}", info.mip_count);
+        println!("  Bits per pixel: {}", info.bits_per_pixel);
+        println!("  Data length:    {} bytes", info.data_length);
+    }
+
+    Ok(())
+}
+
+/// What `--output-format` asks for.
+#[derive(Clone, Debug)]
+enum OutputFormat {
+    Format(pica_texture::TextureFormat),
+    /// Reuse the format of the texture the input was decoded from.
+    Keep,
+}
+
+/// How `--input-format` says input textures are stored.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum InputFormat {
+    Container,
+    /// Tiled texture data with no header, as dumped from the 3DS's memory.
+    Raw,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum ResizeMode {
+    Scale,
+    Pad,
+}
+
 #[derive(clap::ValueEnum, Clone, Debug)]
 enum Mode {
     Encode,
-    Decode
+    Decode,
+    Info,
+    Atlas,
+    Split,
+    Patch,
 }
\ No newline at end of file
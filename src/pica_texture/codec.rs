@@ -0,0 +1,379 @@
+use std::ops::Deref;
+use std::sync::LazyLock;
+
+use image::{DynamicImage, ImageBuffer, Rgba, RgbaImage};
+
+use crate::pica_texture::{TextureFormat, TextureLayout, TextureError, EncodeOptions, DecodeOptions, PicaTexture, PicaTextureRef};
+use crate::pica_texture::encode::{
+    encode_rgba8888, encode_rgb888, encode_rgba5551, encode_rgb565, encode_rgba4444, encode_la88,
+    encode_hl8, encode_l8, encode_a8, encode_la44, encode_l4, encode_a4, encode_linear, encode_with_codec, encode_texture_with,
+};
+use crate::pica_texture::decode::{
+    decode_rgba8888, decode_rgb888, decode_rgba5551, decode_rgb565, decode_rgba4444, decode_la88,
+    decode_hl8, decode_l8, decode_a8, decode_la44, decode_l4, decode_a4, decode_shadow, decode_linear, decode_with_codec, decode_texture_with,
+};
+use crate::pica_texture::decode::decode_etc1;
+#[cfg(any(feature = "etc1", feature = "etc1-rust"))]
+use crate::pica_texture::encode::encode_etc1;
+
+/// RGBA8 pixels borrowed from an image or buffer, as codecs read them.
+pub type RgbaView<'a> = ImageBuffer<Rgba<u8>, &'a [u8]>;
+
+/// Borrows the pixels of an RGBA8 image, owned or not.
+pub(crate) fn view<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>) -> RgbaView<'_> {
+    ImageBuffer::from_raw(img.width(), img.height(), &**img.as_raw()).expect("an image holds its own pixels")
+}
+
+/// Converts between RGBA8 pixels and one texture format's raw data.
+///
+/// Every built-in [`TextureFormat`] is backed by a [`BuiltinCodec`]. Formats
+/// this crate doesn't know about can implement this trait and be added to a
+/// [`CodecRegistry`] to reuse the rest of the encode/decode pipeline.
+pub trait TextureCodec: Send + Sync {
+    /// The name the codec is looked up by.
+    fn name(&self) -> &str;
+
+    /// The number of bytes of encoded data for a `width` x `height` texture.
+    fn expected_len(&self, width: u32, height: u32) -> usize;
+
+    /// Encodes an RGBA image into raw texture data.
+    ///
+    /// The image has already been mirrored if [`EncodeOptions::flip_horizontal`] is set.
+    /// Its pixels are borrowed from the caller, so encoding a buffer doesn't copy it.
+    ///
+    /// Codecs return an error for options they can't encode with, rather
+    /// than data of the wrong length.
+    fn encode(&self, img: &RgbaView<'_>, options: &EncodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>>;
+
+    /// Decodes raw texture data into `width * height * 4` bytes of RGBA data.
+    ///
+    /// Rows are returned top first, so decoding the output of
+    /// [`encode`](TextureCodec::encode) gives back an image the same way up.
+    ///
+    /// `data` is guaranteed to hold at least [`expected_len`](TextureCodec::expected_len) bytes.
+    /// The output is flipped and mirrored afterwards as the options ask, so
+    /// codecs shouldn't apply [`DecodeOptions::flip_vertical`] or
+    /// [`DecodeOptions::flip_horizontal`] themselves.
+    ///
+    /// Codecs for formats where some data is invalid, such as compressed
+    /// blocks, return an error for it rather than decoding it to garbage.
+    fn decode(&self, data: &[u8], width: u32, height: u32, options: &DecodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>>;
+}
+
+type EncodeFn = fn(&RgbaView<'_>, u32, u32, &EncodeOptions) -> Vec<u8>;
+type DecodeFn = fn(&[u8], u32, u32) -> Result<Vec<u8>, Box<dyn std::error::Error>>;
+
+/// The [`TextureCodec`] for one of the built-in [`TextureFormat`]s.
+#[derive(Clone)]
+pub struct BuiltinCodec {
+    format: TextureFormat,
+    encode: EncodeFn,
+    decode: DecodeFn,
+}
+
+impl BuiltinCodec {
+    /// Returns the codec for a built-in format.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::{BuiltinCodec, TextureCodec, TextureFormat};
+    /// let codec = BuiltinCodec::for_format(&TextureFormat::LA88);
+    /// assert_eq!(codec.name(), "LA88");
+    /// assert_eq!(codec.expected_len(16, 16), 16 * 16 * 2);
+    /// ```
+    ///
+    /// Every format has a codec, but the ones this build can't encode or
+    /// decode return an error rather than data:
+    ///
+    /// ```
+    /// # use image::RgbaImage;
+    /// # use pica_convert::pica_texture::{encode_with_codec, BuiltinCodec, TextureCodec, TextureFormat, EncodeOptions, DecodeOptions};
+    /// let img = RgbaImage::new(8, 8);
+    /// assert!(encode_with_codec(BuiltinCodec::for_format(&TextureFormat::Shadow), &img, &EncodeOptions::default()).is_err());
+    /// assert!(encode_with_codec(BuiltinCodec::for_format(&TextureFormat::Gas), &img, &EncodeOptions::default()).is_err());
+    /// assert!(BuiltinCodec::for_format(&TextureFormat::Gas).decode(&[0; 256], 8, 8, &DecodeOptions::default()).is_err());
+    /// ```
+    pub fn for_format(format: &TextureFormat) -> &'static BuiltinCodec {
+        BUILTIN_CODECS.iter().find(|codec| codec.format == *format).expect("every format has a codec")
+    }
+
+    /// Returns the format this codec handles.
+    pub fn format(&self) -> &TextureFormat {
+        &self.format
+    }
+}
+
+impl TextureCodec for BuiltinCodec {
+    fn name(&self) -> &str {
+        self.format.name()
+    }
+
+    fn expected_len(&self, width: u32, height: u32) -> usize {
+        self.format.expected_data_len(width, height)
+    }
+
+    fn encode(&self, img: &RgbaView<'_>, options: &EncodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if let Some(err) = self.format.encode_options_error(options) {
+            return Err(err.into());
+        }
+
+        let data = match options.layout {
+            TextureLayout::Tiled => (self.encode)(img, img.width(), img.height(), options),
+            TextureLayout::Linear => LinearCodec::new(&self.format).encode(img, options)?,
+        };
+
+        // Every built-in encoder writes whole tiles, exactly filling the texture
+        debug_assert_eq!(data.len(), self.expected_len(img.width(), img.height()), "{} encoder output at {}x{}", self.name(), img.width(), img.height());
+        Ok(data)
+    }
+
+    fn decode(&self, data: &[u8], width: u32, height: u32, _options: &DecodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        (self.decode)(data, width, height)
+    }
+}
+
+/// The codec for a built-in format's [`TextureLayout::Linear`] data, so it
+/// goes through the same checks and post-processing as tiled data.
+pub(crate) struct LinearCodec<'a> {
+    format: &'a TextureFormat,
+}
+
+impl<'a> LinearCodec<'a> {
+    pub(crate) fn new(format: &'a TextureFormat) -> Self {
+        Self { format }
+    }
+}
+
+impl TextureCodec for LinearCodec<'_> {
+    fn name(&self) -> &str {
+        self.format.name()
+    }
+
+    fn expected_len(&self, width: u32, height: u32) -> usize {
+        self.format.expected_data_len(width, height)
+    }
+
+    fn encode(&self, img: &RgbaView<'_>, _options: &EncodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        encode_linear(img, img.width(), img.height(), self.format).ok_or_else(|| TextureError::LinearUnsupported(*self.format).into())
+    }
+
+    fn decode(&self, data: &[u8], width: u32, height: u32, _options: &DecodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        decode_linear(data, width, height, self.format).ok_or_else(|| TextureError::LinearUnsupported(*self.format).into())
+    }
+}
+
+/// Built-in codecs, in the order of [`TextureFormat::ALL`].
+static BUILTIN_CODECS: [BuiltinCodec; 16] = [
+    BuiltinCodec { format: TextureFormat::RGBA8888, encode: |img, w, h, _| encode_rgba8888(img, w, h), decode: |data, w, h| Ok(decode_rgba8888(data, w, h)) },
+    BuiltinCodec { format: TextureFormat::RGB888,   encode: |img, w, h, _| encode_rgb888(img, w, h),   decode: |data, w, h| Ok(decode_rgb888(data, w, h)) },
+    BuiltinCodec { format: TextureFormat::RGBA5551, encode: |img, w, h, _| encode_rgba5551(img, w, h), decode: |data, w, h| Ok(decode_rgba5551(data, w, h)) },
+    BuiltinCodec { format: TextureFormat::RGB565,   encode: |img, w, h, _| encode_rgb565(img, w, h),   decode: |data, w, h| Ok(decode_rgb565(data, w, h)) },
+    BuiltinCodec { format: TextureFormat::RGBA4444, encode: |img, w, h, _| encode_rgba4444(img, w, h), decode: |data, w, h| Ok(decode_rgba4444(data, w, h)) },
+    BuiltinCodec { format: TextureFormat::LA88,     encode: |img, w, h, _| encode_la88(img, w, h),     decode: |data, w, h| Ok(decode_la88(data, w, h)) },
+    BuiltinCodec { format: TextureFormat::HL8,      encode: |img, w, h, _| encode_hl8(img, w, h),      decode: |data, w, h| Ok(decode_hl8(data, w, h)) },
+    BuiltinCodec { format: TextureFormat::L8,       encode: |img, w, h, _| encode_l8(img, w, h),       decode: |data, w, h| Ok(decode_l8(data, w, h)) },
+    BuiltinCodec { format: TextureFormat::A8,       encode: |img, w, h, _| encode_a8(img, w, h),       decode: |data, w, h| Ok(decode_a8(data, w, h)) },
+    BuiltinCodec { format: TextureFormat::LA44,     encode: |img, w, h, _| encode_la44(img, w, h),     decode: |data, w, h| Ok(decode_la44(data, w, h)) },
+    BuiltinCodec { format: TextureFormat::L4,       encode: |img, w, h, _| encode_l4(img, w, h),       decode: |data, w, h| Ok(decode_l4(data, w, h)) },
+    BuiltinCodec { format: TextureFormat::A4,       encode: |img, w, h, _| encode_a4(img, w, h),       decode: |data, w, h| Ok(decode_a4(data, w, h)) },
+    BuiltinCodec { format: TextureFormat::ETC1,     encode: etc1_encode::<false>, decode: etc1_decode::<false> },
+    BuiltinCodec { format: TextureFormat::ETC1A4,   encode: etc1_encode::<true>,  decode: etc1_decode::<true> },
+    BuiltinCodec { format: TextureFormat::Shadow,   encode: unsupported_encode, decode: |data, w, h| Ok(decode_shadow(data, w, h)) },
+    BuiltinCodec { format: TextureFormat::Gas,      encode: unsupported_encode, decode: |_, _, _| Err(TextureError::DecodeUnsupported(TextureFormat::Gas).into()) },
+];
+
+/// Placeholder for formats with no encoder. [`BuiltinCodec::encode`] rejects
+/// them with [`TextureError::EncodeUnsupported`] before reaching it.
+fn unsupported_encode(_: &RgbaView<'_>, _: u32, _: u32, _: &EncodeOptions) -> Vec<u8> {
+    unreachable!("formats without an encoder are rejected before encoding")
+}
+
+#[cfg(any(feature = "etc1", feature = "etc1-rust"))]
+fn etc1_encode<const ALPHA: bool>(img: &RgbaView<'_>, width: u32, height: u32, options: &EncodeOptions) -> Vec<u8> {
+    encode_etc1(img, width, height, ALPHA, options).expect("the backend is checked before encoding")
+}
+
+fn etc1_decode<const ALPHA: bool>(data: &[u8], width: u32, height: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Ok(decode_etc1(data, width, height, ALPHA)?)
+}
+
+/// Placeholder for builds without an ETC1 compressor, from either the `etc1`
+/// or `etc1-rust` feature. [`BuiltinCodec::encode`] rejects encoding ETC1
+/// with [`TextureError::Etc1BackendUnavailable`] before reaching it.
+#[cfg(not(any(feature = "etc1", feature = "etc1-rust")))]
+fn etc1_encode<const ALPHA: bool>(_: &RgbaView<'_>, _: u32, _: u32, _: &EncodeOptions) -> Vec<u8> {
+    unreachable!("ETC1 is rejected before encoding without a backend")
+}
+
+/// A table of [`TextureCodec`]s, looked up by name.
+///
+/// # Example
+///
+/// ```
+/// # use image::RgbaImage;
+/// # use pica_convert::pica_texture::{CodecRegistry, RgbaView, TextureCodec, TextureLayout, EncodeOptions, DecodeOptions};
+/// // A made-up format storing pixels linearly as BGRA
+/// struct LinearBgra;
+///
+/// impl TextureCodec for LinearBgra {
+///     fn name(&self) -> &str { "LinearBGRA" }
+///     fn expected_len(&self, width: u32, height: u32) -> usize { (width * height * 4) as usize }
+///     fn encode(&self, img: &RgbaView, _: &EncodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+///         Ok(img.pixels().flat_map(|p| [p[2], p[1], p[0], p[3]]).collect())
+///     }
+///     fn decode(&self, data: &[u8], _: u32, _: u32, _: &DecodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+///         Ok(data.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect())
+///     }
+/// }
+///
+/// let mut registry = CodecRegistry::default();
+/// registry.register(Box::new(LinearBgra));
+///
+/// let img = RgbaImage::from_fn(8, 8, |x, y| image::Rgba([x as u8, y as u8, 7, 255]));
+/// let data = registry.encode("linearbgra", &img, &EncodeOptions::default()).unwrap();
+/// assert_eq!(&data[..4], &[7, 0, 0, 255]);
+///
+/// let decoded = registry.decode("LinearBGRA", &data, 8, 8, &DecodeOptions::default()).unwrap();
+/// assert_eq!(&decoded, img.as_raw());
+///
+/// // Built-in formats are registered under their canonical names
+/// assert!(registry.get("LA44").is_some());
+/// assert!(registry.encode("Unknown", &img, &EncodeOptions::default()).is_err());
+///
+/// // Options a built-in format can't be encoded with are errors, not empty data
+/// let linear = EncodeOptions { layout: TextureLayout::Linear, ..Default::default() };
+/// assert!(registry.encode("ETC1", &img, &linear).is_err());
+/// assert!(registry.encode("RGB565", &img, &linear).is_ok());
+/// ```
+pub struct CodecRegistry {
+    codecs: Vec<Box<dyn TextureCodec>>,
+}
+
+impl CodecRegistry {
+    /// Creates a registry with no codecs.
+    pub fn empty() -> Self {
+        Self { codecs: Vec::new() }
+    }
+
+    /// Adds a codec to the registry. Earlier registrations take priority.
+    pub fn register(&mut self, codec: Box<dyn TextureCodec>) {
+        self.codecs.push(codec);
+    }
+
+    /// Adds the codec for every built-in [`TextureFormat`], after the codecs
+    /// already registered, so those replace the built-ins with their names.
+    pub fn register_builtins(&mut self) {
+        for codec in BUILTIN_CODECS.iter() {
+            self.register(Box::new(codec.clone()));
+        }
+    }
+
+    /// The registry of built-in codecs that [`encode_texture`](crate::pica_texture::encode_texture),
+    /// [`decode_texture`](crate::pica_texture::decode_texture) and the other
+    /// free encode and decode functions dispatch through.
+    pub fn builtin() -> &'static CodecRegistry {
+        static BUILTIN: LazyLock<CodecRegistry> = LazyLock::new(CodecRegistry::default);
+        &BUILTIN
+    }
+
+    /// Iterates over the registered codecs.
+    pub fn codecs(&self) -> impl Iterator<Item = &dyn TextureCodec> {
+        self.codecs.iter().map(|c| c.as_ref())
+    }
+
+    /// Finds the codec with the given name, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&dyn TextureCodec> {
+        self.codecs().find(|c| c.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Encodes an RGBA image with the named codec.
+    pub fn encode(&self, name: &str, img: &RgbaImage, options: &EncodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let codec = self.get(name).ok_or_else(|| format!("Unknown texture codec '{}'", name))?;
+        encode_with_codec(codec, img, options)
+    }
+
+    /// Decodes raw texture data into RGBA data with the named codec.
+    pub fn decode(&self, name: &str, data: &[u8], width: u32, height: u32, options: &DecodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let codec = self.get(name).ok_or_else(|| format!("Unknown texture codec '{}'", name))?;
+        decode_with_codec(codec, data, width, height, options)
+    }
+
+    /// Finds the codec registered under a built-in format's name.
+    pub(crate) fn codec_for(&self, format: &TextureFormat) -> Result<&dyn TextureCodec, Box<dyn std::error::Error>> {
+        Ok(self.get(format.name()).ok_or_else(|| format!("No texture codec registered for {}", format))?)
+    }
+
+    /// Encodes an image into a texture like [`encode_texture`](crate::pica_texture::encode_texture),
+    /// with the codec registered under `format`'s name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no codec is registered for `format`, or if the
+    /// codec's output isn't [`TextureFormat::expected_data_len`] bytes long,
+    /// as well as for the reasons `encode_texture` does.
+    ///
+    /// # Example
+    ///
+    /// A codec registered ahead of the built-ins replaces the built-in one
+    /// for the whole pipeline:
+    ///
+    /// ```
+    /// # use image::{DynamicImage, Rgba, RgbaImage};
+    /// # use pica_convert::pica_texture::{encode_texture, BuiltinCodec, CodecRegistry, RgbaView, TextureCodec, TextureFormat};
+    /// # use pica_convert::pica_texture::{EncodeOptions, DecodeOptions};
+    /// // Stores every RGB565 texture as black
+    /// struct BlankRgb565;
+    ///
+    /// impl TextureCodec for BlankRgb565 {
+    ///     fn name(&self) -> &str { "RGB565" }
+    ///     fn expected_len(&self, width: u32, height: u32) -> usize { TextureFormat::RGB565.expected_data_len(width, height) }
+    ///     fn encode(&self, img: &RgbaView, _: &EncodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    ///         Ok(vec![0; self.expected_len(img.width(), img.height())])
+    ///     }
+    ///     fn decode(&self, data: &[u8], width: u32, height: u32, options: &DecodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    ///         BuiltinCodec::for_format(&TextureFormat::RGB565).decode(data, width, height, options)
+    ///     }
+    /// }
+    ///
+    /// let mut registry = CodecRegistry::empty();
+    /// registry.register(Box::new(BlankRgb565));
+    /// registry.register_builtins();
+    ///
+    /// let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(16, 8, Rgba([200, 100, 50, 255])));
+    /// let options = EncodeOptions::default();
+    /// let texture = registry.encode_texture(&img, &TextureFormat::RGB565, &options).unwrap();
+    /// assert!(texture.data().iter().all(|&byte| byte == 0));
+    ///
+    /// // Other formats still use the built-in codecs
+    /// let la88 = registry.encode_texture(&img, &TextureFormat::LA88, &options).unwrap();
+    /// assert_eq!(la88, encode_texture(&img, &TextureFormat::LA88, &options).unwrap());
+    /// assert!(CodecRegistry::empty().encode_texture(&img, &TextureFormat::LA88, &options).is_err());
+    /// ```
+    pub fn encode_texture(&self, img: &DynamicImage, format: &TextureFormat, options: &EncodeOptions) -> Result<PicaTexture, Box<dyn std::error::Error>> {
+        encode_texture_with(self, img, format, options)
+    }
+
+    /// Decodes a texture like [`decode_texture`](crate::pica_texture::decode_texture),
+    /// with the codec registered under its format's name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no codec is registered for the texture's format,
+    /// as well as for the reasons `decode_texture` does.
+    pub fn decode_texture<'a>(&self, texture: impl Into<PicaTextureRef<'a>>, options: &DecodeOptions) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+        decode_texture_with(self, texture.into(), options)
+    }
+}
+
+impl Default for CodecRegistry {
+    /// Creates a registry containing the codec for every built-in [`TextureFormat`].
+    ///
+    /// Codecs for formats this build can't encode or decode are included,
+    /// and return an error for what they can't do.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register_builtins();
+        registry
+    }
+}
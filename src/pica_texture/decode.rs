@@ -1,18 +1,22 @@
 use image::{DynamicImage, ImageBuffer};
 
-use crate::pica_texture::etc1::{decompress_block};
-use crate::pica_texture::{TextureFormat, PicaTexture};
-use crate::pica_texture::util::{XT, YT, SWIZZLE_LUT, flip_vertical, swap64};
+use crate::pica_texture::etc1::{decompress_block, Etc1Error};
+use crate::pica_texture::{TextureFormat, TextureLayout, PicaTextureRef, DecodeOptions, TextureError};
+use crate::pica_texture::codec::{TextureCodec, CodecRegistry, LinearCodec};
+use crate::pica_texture::limits::ensure_nonzero;
+use crate::pica_texture::util::{SwizzleMap, crop, flip_vertical, flip_horizontal, tiled_index};
+use crate::pica_texture::util::{XT, YT, swap64};
 
 /// Decodes raw PICA texture data into a [`DynamicImage`].
 ///
-/// This function takes raw texture bytes along with image dimensions and a
-/// [`TextureFormat`] enum, decodes the pixel data into RGBA format, and flips
-/// the image vertically to match the expected orientation.
+/// This is the primary decode entry point. It decodes the texture's pixel data
+/// into RGBA format and wraps it in an RGBA8 [`ImageBuffer`]; see
+/// [`decode_raw`] for data that isn't held in a [`PicaTexture`].
 ///
 /// # Arguments
 ///
 /// * `texture` - The PicaTexture to decode.
+/// * `options` - The [`DecodeOptions`] to decode with.
 ///
 /// # Returns
 ///
@@ -23,50 +27,213 @@ use crate::pica_texture::util::{XT, YT, SWIZZLE_LUT, flip_vertical, swap64};
 /// # Errors
 ///
 /// Returns an error if:
-/// - The provided `img` data is not valid for the given dimensions and format.
+/// - The texture data is not valid for its dimensions and format.
 /// - Construction of the [`ImageBuffer`] fails (e.g., data length mismatch).
 ///
 /// # Examples
 ///
 /// ```
-/// use pica_convert::pica_texture::{decode_texture, TextureFormat};
+/// use pica_convert::pica_texture::{decode_texture, DecodeOptions, PicaTexture, TextureFormat};
 ///
 /// // Suppose `raw_bytes` contains valid RGBA8888 texture data.
-/// let width = 128;
-/// let height = 128;
-/// let format = TextureFormat::RGBA8888;
+/// let raw_bytes = vec![0u8; 128 * 128 * 4];
+/// let texture = PicaTexture::try_new(TextureFormat::RGBA8888, 128, 128, raw_bytes).unwrap();
 ///
-/// let decoded = decode_texture(&raw_bytes, width, height, &format).unwrap();
+/// let decoded = decode_texture(&texture, &DecodeOptions::default()).unwrap();
 /// assert_eq!(decoded.width(), 128);
 /// assert_eq!(decoded.height(), 128);
+///
+/// // Empty textures are rejected rather than decoded
+/// # use pica_convert::pica_texture::TextureError;
+/// for (width, height) in [(0, 0), (0, 16), (16, 0)] {
+///     let texture = PicaTexture::try_new(TextureFormat::L8, width, height, Vec::new()).unwrap();
+///     let err = decode_texture(&texture, &DecodeOptions::default()).unwrap_err();
+///     assert!(matches!(err.downcast_ref(), Some(TextureError::InvalidDimensions { .. })));
+/// }
+/// ```
+///
+/// Decoding undoes [`encode_texture`](crate::pica_texture::encode_texture) for
+/// every format, with the image the same way up:
+///
+/// ```
+/// # use image::{imageops, DynamicImage, Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{decode_texture, encode_texture, DecodeOptions, EncodeOptions, TextureFormat};
+/// // Opaque white over transparent black, split partway down a band of tiles
+/// let img = RgbaImage::from_fn(16, 24, |_, y| if y < 10 { Rgba([255; 4]) } else { Rgba([0; 4]) });
+/// let upside_down = imageops::flip_vertical(&img);
+///
+/// let distance = |a: &RgbaImage, b: &RgbaImage| -> u64 {
+///     a.as_raw().iter().zip(b.as_raw()).map(|(&a, &b)| a.abs_diff(b) as u64).sum()
+/// };
+///
+/// for format in TextureFormat::ALL.into_iter().filter(|format| format.is_available()) {
+///     let texture = encode_texture(&DynamicImage::ImageRgba8(img.clone()), &format, &EncodeOptions::default()).unwrap();
+///     let decoded = decode_texture(&texture, &DecodeOptions::default()).unwrap().to_rgba8();
+///
+///     assert!(distance(&decoded, &img) < distance(&decoded, &upside_down), "{} is upside down", format);
+/// }
+///
+/// // Lossless formats come back exactly
+/// let texture = encode_texture(&DynamicImage::ImageRgba8(img.clone()), &TextureFormat::RGBA8888, &EncodeOptions::default()).unwrap();
+/// assert_eq!(decode_texture(&texture, &DecodeOptions::default()).unwrap().to_rgba8(), img);
 /// ```
-pub fn decode_texture(texture: &PicaTexture) -> Result<DynamicImage, Box<dyn std::error::Error>> {
-    println!("Decoding texture...");
+///
+/// Textures stored padded beyond the image, as other tools write them, are
+/// cropped back to the image with [`DecodeOptions::crop`]:
+///
+/// ```
+/// # use image::{imageops, DynamicImage, Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{decode_texture, decode_to_rgba, decode_into, encode_texture, DecodeOptions, EncodeOptions, TextureFormat};
+/// let img = RgbaImage::from_fn(200, 120, |x, y| Rgba([x as u8, y as u8, (x ^ y) as u8, 255]));
+/// let mut padded = RgbaImage::from_pixel(256, 128, Rgba([255, 0, 255, 255]));
+/// imageops::replace(&mut padded, &img, 0, 0);
+/// let texture = encode_texture(&DynamicImage::ImageRgba8(padded), &TextureFormat::RGBA8888, &EncodeOptions::default()).unwrap();
+///
+/// let options = DecodeOptions { crop: Some((200, 120)), ..Default::default() };
+/// assert_eq!(decode_texture(&texture, &options).unwrap().to_rgba8(), img);
+///
+/// let mut buffer = vec![0; 200 * 120 * 4];
+/// decode_into(&texture, &mut buffer, &options).unwrap();
+/// assert_eq!(buffer, img.as_raw().as_slice());
+///
+/// // Flipping applies to the cropped image, not the padded texture
+/// let flipped = DecodeOptions { flip_vertical: true, flip_horizontal: true, ..options };
+/// assert_eq!(decode_texture(&texture, &flipped).unwrap().to_rgba8(), imageops::rotate180(&img));
+///
+/// // Crops must fit within the texture
+/// for crop in [(257, 128), (256, 129), (0, 64)] {
+///     assert!(decode_to_rgba(&texture, &DecodeOptions { crop: Some(crop), ..Default::default() }).is_err());
+/// }
+/// ```
+pub fn decode_texture<'a>(texture: impl Into<PicaTextureRef<'a>>, options: &DecodeOptions) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    decode_texture_with(CodecRegistry::builtin(), texture.into(), options)
+}
 
-    let data = texture.data();
+/// [`decode_texture`] with the codecs of `registry`, for [`CodecRegistry::decode_texture`].
+pub(crate) fn decode_texture_with(registry: &CodecRegistry, texture: PicaTextureRef<'_>, options: &DecodeOptions) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    let (width, height) = options.output_dimensions(texture.width(), texture.height());
+    into_image(decode_to_rgba_with(registry, texture, options)?, width, height)
+}
+
+/// Decodes raw PICA texture data into a `Vec<u8>` of row-major RGBA8 pixels.
+///
+/// This is the same pixel data [`decode_texture`] produces, without wrapping
+/// it in a [`DynamicImage`].
+///
+/// # Arguments
+///
+/// * `texture` - The PicaTexture to decode.
+/// * `options` - The [`DecodeOptions`] to decode with.
+///
+/// # Returns
+///
+/// A `Vec<u8>` of `width * height * 4` bytes on success, or of the
+/// [`DecodeOptions::crop`] dimensions if set.
+///
+/// # Examples
+///
+/// ```
+/// use image::RgbaImage;
+/// use pica_convert::pica_texture::{decode_texture, decode_to_rgba, encode_rgba_buffer};
+/// use pica_convert::pica_texture::{DecodeOptions, EncodeOptions, TextureFormat};
+///
+/// let pixels: Vec<u8> = (0..16 * 16 * 4).map(|i| (i * 13 % 256) as u8).collect();
+/// let texture = encode_rgba_buffer(&pixels, 16, 16, &TextureFormat::RGBA4444, &EncodeOptions::default()).unwrap();
+///
+/// let options = DecodeOptions::default();
+/// let rgba = decode_to_rgba(&texture, &options).unwrap();
+/// assert_eq!(rgba.len(), 16 * 16 * 4);
+///
+/// // Agrees byte for byte with the DynamicImage path
+/// let image = decode_texture(&texture, &options).unwrap();
+/// assert_eq!(image.to_rgba8().as_raw(), &rgba);
+/// ```
+pub fn decode_to_rgba<'a>(texture: impl Into<PicaTextureRef<'a>>, options: &DecodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    decode_to_rgba_with(CodecRegistry::builtin(), texture.into(), options)
+}
+
+/// [`decode_to_rgba`] with the codecs of `registry`.
+fn decode_to_rgba_with(registry: &CodecRegistry, texture: PicaTextureRef<'_>, options: &DecodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
     let (width, height) = texture.dimensions();
-    let format = texture.format();
+    match texture.layout() {
+        TextureLayout::Tiled => decode_pixels(registry, texture.data(), width, height, texture.format(), options),
+        TextureLayout::Linear => {
+            if let Some(err) = texture.format().decode_error() {
+                return Err(err.into());
+            }
+            decode_with_codec(&LinearCodec::new(texture.format()), texture.data(), width, height, options)
+        }
+    }
+}
 
-    let mut decoded_texture_data = match format {
-        TextureFormat::RGBA8888 => decode_rgba8888(data, width, height),
-        TextureFormat::RGB888   => decode_rgb888(data, width, height),
-        TextureFormat::RGBA5551 => decode_rgba5551(data, width, height),
-        TextureFormat::RGB565   => decode_rgb565(data, width, height),
-        TextureFormat::RGBA4444 => decode_rgba4444(data, width, height),
-        TextureFormat::LA88     => decode_la88(data, width, height),
-        TextureFormat::HL8      => decode_hl8(data, width, height),
-        TextureFormat::L8       => decode_l8(data, width, height),
-        TextureFormat::A8       => decode_a8(data, width, height),
-        TextureFormat::LA44     => decode_la44(data, width, height),
-        TextureFormat::L4       => decode_l4(data, width, height),
-        TextureFormat::A4       => decode_a4(data, width, height),
-        TextureFormat::ETC1     => decode_etc1(data, width, height, false),
-        TextureFormat::ETC1A4   => decode_etc1(data, width, height, true)
-    };
+/// Decodes raw PICA texture bytes that aren't wrapped in a [`PicaTexture`] into a [`DynamicImage`].
+///
+/// Useful for callers reading texture data straight out of a container or
+/// memory dump, where the dimensions and format are known separately.
+///
+/// # Arguments
+///
+/// * `data` - A byte slice containing the raw texture data.
+/// * `width` - The width of the image in pixels.
+/// * `height` - The height of the image in pixels.
+/// * `format` - The [`TextureFormat`] the data is encoded in.
+/// * `options` - The [`DecodeOptions`] to decode with.
+///
+/// # Returns
+///
+/// * `Ok(DynamicImage)` containing the decoded image if successful.
+/// * `Err(Box<dyn std::error::Error>)` if the data cannot be decoded.
+///
+/// # Examples
+///
+/// ```
+/// use pica_convert::pica_texture::{decode_raw, decode_texture, DecodeOptions, PicaTexture, TextureFormat};
+///
+/// let raw_bytes: Vec<u8> = (0..32 * 32 * 2).map(|i| (i % 251) as u8).collect();
+/// let options = DecodeOptions::default();
+///
+/// let decoded = decode_raw(&raw_bytes, 32, 32, &TextureFormat::LA88, &options).unwrap();
+/// assert_eq!((decoded.width(), decoded.height()), (32, 32));
+///
+/// // Truncated data is rejected rather than read out of bounds
+/// assert!(decode_raw(&raw_bytes[..100], 32, 32, &TextureFormat::LA88, &options).is_err());
+///
+/// // For every format, however little is missing
+/// # use pica_convert::pica_texture::TextureError;
+/// for format in TextureFormat::ALL.into_iter().filter(|format| format.is_available()) {
+///     for (width, height) in [(8, 8), (32, 16), (12, 5)] {
+///         let expected = format.expected_data_len(width, height);
+///         for actual in [0, 1, expected / 2, expected - 1] {
+///             let err = decode_raw(&vec![0; actual], width, height, &format, &options).unwrap_err();
+///             assert_eq!(err.downcast_ref(), Some(&TextureError::DataLengthMismatch { expected, actual }));
+///         }
+///
+///         // Trailing data, such as mipmaps, is ignored
+///         let data: Vec<u8> = (0..expected + 24).map(|i| i as u8).collect();
+///         let exact = decode_raw(&data[..expected], width, height, &format, &options).unwrap();
+///         assert_eq!(decode_raw(&data, width, height, &format, &options).unwrap(), exact);
+///     }
+/// }
+///
+/// // Absurd dimensions are caught by the length check before anything is allocated for them
+/// for (width, height) in [(65535, 65535), (1 << 20, 8), (u32::MAX, u32::MAX)] {
+///     let err = decode_raw(&raw_bytes[..16], width, height, &TextureFormat::RGBA8888, &options).unwrap_err();
+///     assert!(matches!(err.downcast_ref(), Some(TextureError::DataLengthMismatch { actual: 16, .. })));
+/// }
+///
+/// // Both entry points share the same decoders
+/// let texture = PicaTexture::try_new(TextureFormat::LA88, 32, 32, raw_bytes).unwrap();
+/// assert_eq!(decode_texture(&texture, &options).unwrap(), decoded);
+/// ```
+pub fn decode_raw(data: &[u8], width: u32, height: u32, format: &TextureFormat, options: &DecodeOptions) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    let decoded_texture_data = decode_pixels(CodecRegistry::builtin(), data, width, height, format, options)?;
 
-    // Flip decoded texture vertically
-    flip_vertical(&mut decoded_texture_data, width, height);
+    let (width, height) = options.output_dimensions(width, height);
+    into_image(decoded_texture_data, width, height)
+}
 
+/// Wraps decoded RGBA data in a [`DynamicImage`].
+fn into_image(decoded_texture_data: Vec<u8>, width: u32, height: u32) -> Result<DynamicImage, Box<dyn std::error::Error>> {
     let decoded_image = ImageBuffer::from_raw(width, height, decoded_texture_data)
         .map(DynamicImage::ImageRgba8)
         .ok_or("Failed to construct ImageBuffer from raw data")?;
@@ -74,47 +241,481 @@ pub fn decode_texture(texture: &PicaTexture) -> Result<DynamicImage, Box<dyn std
     Ok(decoded_image)
 }
 
-/// Decodes RGBA8888 PICA texture data into a `Vec<u8>` of RGBA texture data.
+/// Decodes raw PICA texture bytes into a `Vec<u8>` of RGBA texture data,
+/// through `registry`'s codec for `format`.
+///
+/// Shared by every public decode entry point.
+///
+fn decode_pixels(registry: &CodecRegistry, data: &[u8], width: u32, height: u32, format: &TextureFormat, options: &DecodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if let Some(err) = format.decode_error() {
+        return Err(err.into());
+    }
+
+    decode_with_codec(registry.codec_for(format)?, data, width, height, options)
+}
+
+/// Decodes raw texture bytes into a `Vec<u8>` of RGBA texture data using any [`TextureCodec`].
+///
+/// This checks the data length against the codec and applies the
+/// [`DecodeOptions`] post-processing, so external codecs behave the same as
+/// the built-in formats.
 ///
 /// # Arguments
 ///
-/// * `texture_data` - A byte slice containing the raw texture data.
+/// * `codec` - The [`TextureCodec`] the data is encoded with.
+/// * `data` - A byte slice containing the raw texture data.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
+/// * `options` - The [`DecodeOptions`] to decode with.
 ///
 /// # Returns
 ///
-/// A `Vec<u8>` containing the decoded RGBA data.
+/// A `Vec<u8>` of `width * height * 4` bytes on success, or of the
+/// [`DecodeOptions::crop`] dimensions if set.
+///
+/// # Examples
 ///
-fn decode_rgba8888(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
-    println!("Decoding as RGBA8888");
+/// ```
+/// use pica_convert::pica_texture::{decode_raw, decode_with_codec, BuiltinCodec, DecodeOptions, TextureFormat};
+///
+/// let raw_bytes: Vec<u8> = (0..16 * 16).map(|i| i as u8).collect();
+/// let codec = BuiltinCodec::for_format(&TextureFormat::A8);
+/// let options = DecodeOptions::default();
+///
+/// let rgba = decode_with_codec(codec, &raw_bytes, 16, 16, &options).unwrap();
+/// let image = decode_raw(&raw_bytes, 16, 16, &TextureFormat::A8, &options).unwrap();
+/// assert_eq!(image.to_rgba8().as_raw(), &rgba);
+///
+/// assert!(decode_with_codec(codec, &raw_bytes[..8], 16, 16, &options).is_err());
+/// ```
+///
+/// The built-in decoders match a straightforward per-pixel unswizzle of the
+/// whole padded tiles on random data:
+///
+/// ```
+/// # use pica_convert::pica_texture::{decode_with_codec, BuiltinCodec, DecodeOptions, TextureFormat};
+/// # use pica_convert::pica_texture::util::unswizzle;
+/// let mut seed = 0x2545_F491u32;
+/// let mut random = move || {
+///     seed ^= seed << 13;
+///     seed ^= seed >> 17;
+///     seed ^= seed << 5;
+///     seed
+/// };
+///
+/// for _ in 0..20 {
+///     let (width, height) = (random() % 70 + 1, random() % 70 + 1);
+///
+///     for (format, bpp) in [(TextureFormat::RGBA8888, 4), (TextureFormat::RGB888, 3)] {
+///         let data: Vec<u8> = (0..format.expected_data_len(width, height)).map(|_| random() as u8).collect();
+///         let fast = decode_with_codec(BuiltinCodec::for_format(&format), &data, width, height, &DecodeOptions::default()).unwrap();
+///
+///         let (padded_width, padded_height) = (width.next_multiple_of(8), height.next_multiple_of(8));
+///         let naive: Vec<u8> = unswizzle(&data, padded_width, padded_height, bpp as usize)
+///             .chunks_exact((padded_width * bpp) as usize)
+///             .take(height as usize)
+///             .flat_map(|row| row[..(width * bpp) as usize].iter().copied())
+///             .collect::<Vec<u8>>()
+///             .chunks_exact(bpp as usize)
+///             .flat_map(|p| match p {
+///                 [a, b, g, r] => [*r, *g, *b, *a],
+///                 [b, g, r] => [*r, *g, *b, 0xFF],
+///                 _ => unreachable!(),
+///             })
+///             .collect();
+///
+///         assert_eq!(fast, naive, "{} at {}x{}", format, width, height);
+///     }
+/// }
+/// ```
+pub fn decode_with_codec(codec: &dyn TextureCodec, data: &[u8], width: u32, height: u32, options: &DecodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    crate::progress!("Decoding texture...");
 
-    let bytes_per_pixel = 32 / 8;
-    let mut output: Vec<u8> = vec![0; (width * height * 4) as usize];
-    let mut src_idx: usize = 0;
+    ensure_nonzero(width, height)?;
 
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for px in SWIZZLE_LUT {
+    let expected = codec.expected_len(width, height);
+    if data.len() < expected {
+        return Err(TextureError::DataLengthMismatch { expected, actual: data.len() }.into());
+    }
 
-                let x = px & 7;
-                let y = (px - x) >> 3;
+    // The data is all there, but the decoded pixels must still fit in memory
+    let decoded_len = (width as usize).checked_mul(height as usize).and_then(|pixels| pixels.checked_mul(4))
+        .ok_or_else(|| TextureError::InvalidDimensions { width, height, reason: "are too large to decode".to_string() })?;
 
-                let out_idx = ((tx + x + (height - 1 - (ty + y)) * width) * 4) as usize;
+    let (padded_width, padded_height) = (width, height);
+    let mut decoded_texture_data = codec.decode(data, width, height, options)?;
+    if decoded_texture_data.len() != decoded_len {
+        return Err(format!("Codec '{}' decoded {} bytes, expected {}", codec.name(), decoded_texture_data.len(), decoded_len).into());
+    }
 
-                output[out_idx    ] = texture_data[src_idx + 3];
-                output[out_idx + 1] = texture_data[src_idx + 2];
-                output[out_idx + 2] = texture_data[src_idx + 1];
-                output[out_idx + 3] = texture_data[src_idx    ];
+    // Crop away any padding, so the flips mirror the image rather than the padded texture
+    let (width, height) = options.output_dimensions(padded_width, padded_height);
+    if (width, height) != (padded_width, padded_height) {
+        crop(&mut decoded_texture_data, padded_width, padded_height, width, height, 4)?;
+    }
 
-                src_idx += bytes_per_pixel;
-            }
+    // Flip decoded texture vertically
+    if options.flip_vertical {
+        flip_vertical(&mut decoded_texture_data, width, height, 4)?;
+    }
+
+    // Mirror decoded texture horizontally
+    if options.flip_horizontal {
+        flip_horizontal(&mut decoded_texture_data, width, height, 4)?;
+    }
+
+    Ok(decoded_texture_data)
+}
+
+/// Decodes raw PICA texture data into a caller-provided RGBA8 buffer.
+///
+/// # Arguments
+///
+/// * `texture` - The PicaTexture to decode.
+/// * `output` - The buffer to write to, which must be exactly `width * height * 4` bytes,
+///   using the [`DecodeOptions::crop`] dimensions if set.
+/// * `options` - The [`DecodeOptions`] to decode with.
+///
+/// # Errors
+///
+/// Returns an error if `output` has the wrong length or the texture cannot be decoded.
+///
+/// # Examples
+///
+/// ```
+/// use pica_convert::pica_texture::{decode_into, decode_to_rgba, DecodeOptions, PicaTexture, TextureFormat};
+///
+/// let texture = PicaTexture::try_new(TextureFormat::L8, 8, 8, (0..64).collect()).unwrap();
+/// let options = DecodeOptions::default();
+///
+/// let mut buffer = vec![0u8; 8 * 8 * 4];
+/// decode_into(&texture, &mut buffer, &options).unwrap();
+/// assert_eq!(buffer, decode_to_rgba(&texture, &options).unwrap());
+///
+/// let mut too_small = vec![0u8; 16];
+/// assert!(decode_into(&texture, &mut too_small, &options).is_err());
+/// ```
+pub fn decode_into<'a>(texture: impl Into<PicaTextureRef<'a>>, output: &mut [u8], options: &DecodeOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let texture = texture.into();
+    let (width, height) = options.output_dimensions(texture.width(), texture.height());
+    let expected_len = width as usize * height as usize * 4;
+    if output.len() != expected_len {
+        return Err(format!("Output buffer is {} bytes, expected {} for {}x{}", output.len(), expected_len, width, height).into());
+    }
+
+    let decoded_texture_data = decode_to_rgba(texture, options)?;
+    output.copy_from_slice(&decoded_texture_data);
+
+    Ok(())
+}
+
+/// Converts each pixel of tiled data to RGBA with `convert` and lays the results out in rows.
+///
+/// Shared by the uncompressed decoders with whole-byte pixels. Each 8-row band
+/// of tiles is scattered through its [`SwizzleMap`], straight into the output
+/// if it's made of whole tiles. Bands with partial tiles at the edges are
+/// decoded in full into a scratch band, and only the pixels inside the image
+/// are copied out.
+///
+fn decode_tiled<const N: usize>(texture_data: &[u8], width: u32, height: u32, convert: impl Fn(&[u8; N]) -> [u8; 4]) -> Vec<u8> {
+    let row_bytes = width as usize * 4;
+    let mut output: Vec<u8> = vec![0; row_bytes * height as usize];
+    if output.is_empty() {
+        return output;
+    }
+
+    let padded_width = width.next_multiple_of(8);
+    let padded_row_bytes = padded_width as usize * 4;
+    let map = SwizzleMap::get(padded_width, 8);
+    let mut band = Vec::new();
+
+    let scatter = |band_data: &[u8], out_band: &mut [u8]| {
+        for (src, &dst) in band_data.chunks_exact(N).zip(map.indices()) {
+            let idx = dst as usize * 4;
+            out_band[idx..idx + 4].copy_from_slice(&convert(src.try_into().unwrap()));
+        }
+    };
+
+    // The top band of tiles is stored first
+    let band_data_len = padded_width as usize * 8 * N;
+
+    for (band_data, out_band) in texture_data.chunks(band_data_len).zip(output.chunks_mut(row_bytes * 8)) {
+        if out_band.len() == padded_row_bytes * 8 {
+            scatter(band_data, out_band);
+            continue;
+        }
+
+        band.resize(padded_row_bytes * 8, 0);
+        scatter(band_data, &mut band);
+        for (out_row, band_row) in out_band.chunks_exact_mut(row_bytes).zip(band.chunks_exact(padded_row_bytes)) {
+            out_row.copy_from_slice(&band_row[..row_bytes]);
         }
     }
     output
 }
 
-/// Decodes RGB888 PICA texture data into a `Vec<u8>` of RGBA texture data.
+/// Converts each 4-bit pixel of tiled data to RGBA with `convert` and lays the
+/// results out in rows, the first pixel of each pair being the low nibble.
+///
+/// The 4-bit counterpart of [`decode_tiled`], scattering each band through
+/// the same [`SwizzleMap`].
+fn decode_tiled_4bpp(texture_data: &[u8], width: u32, height: u32, convert: impl Fn(u8) -> [u8; 4]) -> Vec<u8> {
+    let row_bytes = width as usize * 4;
+    let mut output: Vec<u8> = vec![0; row_bytes * height as usize];
+    if output.is_empty() {
+        return output;
+    }
+
+    let padded_width = width.next_multiple_of(8);
+    let padded_row_bytes = padded_width as usize * 4;
+    let map = SwizzleMap::get(padded_width, 8);
+    let mut band = Vec::new();
+
+    let scatter = |band_data: &[u8], out_band: &mut [u8]| {
+        for (i, &dst) in map.indices().iter().enumerate().take(band_data.len() * 2) {
+            let idx = dst as usize * 4;
+            out_band[idx..idx + 4].copy_from_slice(&convert((band_data[i >> 1] >> ((i & 1) << 2)) & 0xF));
+        }
+    };
+
+    // The top band of tiles is stored first
+    let band_data_len = padded_width as usize * 8 / 2;
+
+    for (band_data, out_band) in texture_data.chunks(band_data_len).zip(output.chunks_mut(row_bytes * 8)) {
+        if out_band.len() == padded_row_bytes * 8 {
+            scatter(band_data, out_band);
+            continue;
+        }
+
+        band.resize(padded_row_bytes * 8, 0);
+        scatter(band_data, &mut band);
+        for (out_row, band_row) in out_band.chunks_exact_mut(row_bytes).zip(band.chunks_exact(padded_row_bytes)) {
+            out_row.copy_from_slice(&band_row[..row_bytes]);
+        }
+    }
+    output
+}
+
+/// Converts each pixel of linear data to RGBA with `convert`.
+///
+/// Shared by the linear decoders with whole-byte pixels. Linear rows are
+/// padded to whole tiles like tiled data, so only the pixels inside the
+/// image are kept.
+///
+fn decode_rows<const N: usize>(texture_data: &[u8], width: u32, height: u32, convert: impl Fn(&[u8; N]) -> [u8; 4]) -> Vec<u8> {
+    let padded_row_len = width.next_multiple_of(8) as usize * N;
+    texture_data.chunks_exact(padded_row_len)
+        .take(height as usize)
+        .flat_map(|row| row[..width as usize * N].chunks_exact(N).flat_map(|p| convert(p.try_into().unwrap())))
+        .collect()
+}
+
+/// Converts each 4-bit pixel of linear data to RGBA with `convert`, the
+/// first pixel of each pair being the low nibble.
+fn decode_rows_4bpp(texture_data: &[u8], width: u32, height: u32, convert: impl Fn(u8) -> [u8; 4]) -> Vec<u8> {
+    let padded_row_len = width.next_multiple_of(8) as usize / 2;
+    texture_data.chunks_exact(padded_row_len)
+        .take(height as usize)
+        .flat_map(|row| (0..width as usize).flat_map(|x| convert((row[x >> 1] >> ((x & 1) << 2)) & 0xF)))
+        .collect()
+}
+
+/// Decodes [`TextureLayout::Linear`] data, stored in rows instead of tiles,
+/// into a `Vec<u8>` of RGBA texture data.
+///
+/// Each pixel is expanded exactly as the tiled decoders like
+/// [`decode_la88`] expand it; only the tile reordering is left out. The data
+/// must hold at least [`TextureFormat::expected_data_len`] bytes.
+///
+/// # Returns
+///
+/// The decoded data, or `None` for formats without a linear layout (see
+/// [`TextureFormat::has_linear_layout`]) or that can't be decoded.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{TextureFormat, TextureLayout};
+/// # use pica_convert::pica_texture::decode::{decode_la88, decode_l4, decode_linear};
+/// # use pica_convert::pica_texture::util::{swizzle, swizzle_4bpp};
+/// // 12x5 rows, padded out to a 16x8 tile's worth
+/// let rows: Vec<u8> = (0..16 * 8 * 2).map(|i| (i * 7) as u8).collect();
+/// assert_eq!(decode_linear(&rows, 12, 5, &TextureFormat::LA88).unwrap(), decode_la88(&swizzle(&rows, 16, 8, 2), 12, 5));
+///
+/// let rows = &rows[..16 * 8 / 2];
+/// assert_eq!(decode_linear(rows, 12, 5, &TextureFormat::L4).unwrap(), decode_l4(&swizzle_4bpp(rows, 16, 8), 12, 5));
+///
+/// assert!(decode_linear(rows, 8, 8, &TextureFormat::ETC1).is_none());
+/// ```
+pub fn decode_linear(texture_data: &[u8], width: u32, height: u32, format: &TextureFormat) -> Option<Vec<u8>> {
+    if format.layout_error(TextureLayout::Linear).is_some() || format.decode_error().is_some() {
+        return None;
+    }
+    crate::progress!("Decoding as linear {}", format);
+
+    Some(match format {
+        TextureFormat::RGBA8888 => decode_rows(texture_data, width, height, expand_rgba8888),
+        TextureFormat::RGB888 => decode_rows(texture_data, width, height, expand_rgb888),
+        TextureFormat::RGBA5551 => decode_rows(texture_data, width, height, expand_rgba5551),
+        TextureFormat::RGB565 => decode_rows(texture_data, width, height, expand_rgb565),
+        TextureFormat::RGBA4444 => decode_rows(texture_data, width, height, expand_rgba4444),
+        TextureFormat::LA88 => decode_rows(texture_data, width, height, expand_la88),
+        TextureFormat::HL8 => decode_rows(texture_data, width, height, expand_hl8),
+        TextureFormat::L8 => decode_rows(texture_data, width, height, expand_l8),
+        TextureFormat::A8 => decode_rows(texture_data, width, height, expand_a8),
+        TextureFormat::LA44 => decode_rows(texture_data, width, height, expand_la44),
+        TextureFormat::L4 => decode_rows_4bpp(texture_data, width, height, expand_l4),
+        TextureFormat::A4 => decode_rows_4bpp(texture_data, width, height, expand_a4),
+        TextureFormat::Shadow => decode_rows(texture_data, width, height, expand_shadow),
+        TextureFormat::ETC1 | TextureFormat::ETC1A4 | TextureFormat::Gas => return None,
+    })
+}
+
+/// Decodes a single stored texel of an uncompressed format to RGBA.
+///
+/// `texel` holds the texel's `bits_per_pixel / 8` bytes as stored, or for the
+/// 4-bit formats a byte with the texel in its low nibble. These are the same
+/// conversions the whole-texture decoders apply to every texel.
+///
+/// # Returns
+///
+/// The RGBA pixel, or `None` if `texel` is the wrong length or the format
+/// has no single texels to decode: ETC1 and ETC1A4 (see
+/// [`decode_etc1_texel`]) and gas.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{decode::decode_texel, decode_raw, DecodeOptions, TextureFormat};
+/// assert_eq!(decode_texel(&[0x1F, 0xF8], &TextureFormat::RGB565), Some([0xFF, 0x00, 0xFF, 0xFF]));
+/// assert_eq!(decode_texel(&[0x36], &TextureFormat::L4), Some([0x66, 0x66, 0x66, 0xFF]));
+/// assert_eq!(decode_texel(&[0x12, 0x34, 0x56, 0x78], &TextureFormat::A8), None);
+/// assert_eq!(decode_texel(&[0; 8], &TextureFormat::ETC1), None);
+///
+/// // Each texel decodes as it does in a whole texture
+/// for format in TextureFormat::ALL.into_iter().filter(|format| format.can_decode() && !format.is_compressed()) {
+///     let bytes = (format.bits_per_pixel() as usize).div_ceil(8);
+///     for value in [0x00u8, 0x5A, 0xA5, 0xFF] {
+///         let texel = vec![value; bytes];
+///         let tile = vec![value; format.expected_data_len(8, 8)];
+///         let decoded = decode_raw(&tile, 8, 8, &format, &DecodeOptions::default()).unwrap().to_rgba8();
+///         assert_eq!(decode_texel(&texel, &format), Some(decoded.get_pixel(0, 0).0), "{} {:#04X}", format, value);
+///     }
+/// }
+/// ```
+pub fn decode_texel(texel: &[u8], format: &TextureFormat) -> Option<[u8; 4]> {
+    let nibble = || <[u8; 1]>::try_from(texel).ok().map(|[byte]| byte & 0xF);
+
+    Some(match format {
+        TextureFormat::RGBA8888 => expand_rgba8888(texel.try_into().ok()?),
+        TextureFormat::RGB888 => expand_rgb888(texel.try_into().ok()?),
+        TextureFormat::RGBA5551 => expand_rgba5551(texel.try_into().ok()?),
+        TextureFormat::RGB565 => expand_rgb565(texel.try_into().ok()?),
+        TextureFormat::RGBA4444 => expand_rgba4444(texel.try_into().ok()?),
+        TextureFormat::LA88 => expand_la88(texel.try_into().ok()?),
+        TextureFormat::HL8 => expand_hl8(texel.try_into().ok()?),
+        TextureFormat::L8 => expand_l8(texel.try_into().ok()?),
+        TextureFormat::A8 => expand_a8(texel.try_into().ok()?),
+        TextureFormat::LA44 => expand_la44(texel.try_into().ok()?),
+        TextureFormat::L4 => expand_l4(nibble()?),
+        TextureFormat::A4 => expand_a4(nibble()?),
+        TextureFormat::Shadow => expand_shadow(texel.try_into().ok()?),
+        TextureFormat::ETC1 | TextureFormat::ETC1A4 | TextureFormat::Gas => return None,
+    })
+}
+
+/// Decodes the pixel at `x`, `y` of a single stored ETC1 or ETC1A4 block.
+///
+/// # Arguments
+///
+/// * `block` - The block as stored: 8 bytes of byte swapped color data, after 8 bytes of alpha for ETC1A4.
+/// * `x` - The pixel's column within the block, 0 to 3.
+/// * `y` - The pixel's row within the block, 0 to 3.
+/// * `has_alpha` - Whether the block is ETC1A4, with its alpha first.
+///
+/// # Errors
+///
+/// Returns [`Etc1Error::InvalidBlock`] if the block's colors are out of range.
+///
+/// # Panics
+///
+/// Panics if `block` is shorter than 8 bytes, or 16 with alpha, or `x` or `y` is past 3.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{decode::{decode_etc1, decode_etc1_texel}, synthetic::synthetic_texture, TextureFormat};
+/// for (format, has_alpha) in [(TextureFormat::ETC1, false), (TextureFormat::ETC1A4, true)] {
+///     let texture = synthetic_texture(&format, 8, 8, 9);
+///     let decoded = decode_etc1(texture.data(), 8, 8, has_alpha).unwrap();
+///
+///     // The first block of a tile holds its top left 4x4 pixels
+///     let block = &texture.data()[..if has_alpha { 16 } else { 8 }];
+///     for (x, y) in [(0, 0), (3, 0), (1, 2), (3, 3)] {
+///         let offset = (y * 8 + x) * 4;
+///         assert_eq!(decode_etc1_texel(block, x as u32, y as u32, has_alpha).unwrap(), decoded[offset..offset + 4], "{} ({}, {})", format, x, y);
+///     }
+/// }
+/// ```
+pub fn decode_etc1_texel(block: &[u8], x: u32, y: u32, has_alpha: bool) -> Result<[u8; 4], Etc1Error> {
+    assert!(x < 4 && y < 4, "pixel ({}, {}) is outside a 4x4 block", x, y);
+    let (alpha, color) = if has_alpha { (Some(&block[..8]), &block[8..16]) } else { (None, &block[..8]) };
+
+    let decoded = decompress_block(&swap64(color.try_into().unwrap()), false)?;
+    let offset = (y * 4 + x) as usize * 4;
+    let mut pixel: [u8; 4] = decoded[offset..offset + 4].try_into().unwrap();
+
+    if let Some(alpha) = alpha {
+        let alpha_block = u64::from_le_bytes(alpha.try_into().unwrap());
+        let a = ((alpha_block >> ((x * 4 + y) << 2)) & 0xF) as u8;
+        pixel[3] = (a << 4) | a;
+    }
+    Ok(pixel)
+}
+
+/// Decodes the pixel at `x`, `y` of a texture's base level, reading only
+/// the texel or block that holds it. See [`PicaTexture::decode_pixel`].
+pub(crate) fn decode_pixel(texture: PicaTextureRef<'_>, x: u32, y: u32) -> Result<[u8; 4], TextureError> {
+    let format = texture.format();
+    let (width, height) = texture.dimensions();
+    if x >= width || y >= height {
+        return Err(TextureError::PixelOutOfBounds { x, y, width, height });
+    }
+    if let Some(err) = format.decode_error() {
+        return Err(err);
+    }
+    let data = texture.data();
+    let expected = format.expected_data_len(width, height);
+    if data.len() < expected {
+        return Err(TextureError::DataLengthMismatch { expected, actual: data.len() });
+    }
+
+    if format.is_compressed() {
+        // A tile's four blocks are in the same Z-order as its pixels, so each
+        // block's index is its top left pixel's tiled index over its 16 pixels
+        let (block_x, block_y) = (x & !3, y & !3);
+        let block_len = format.bits_per_pixel() as usize * 16 / 8;
+        let offset = tiled_index(block_x, block_y, width) / 16 * block_len;
+        return decode_etc1_texel(&data[offset..offset + block_len], x & 3, y & 3, *format == TextureFormat::ETC1A4)
+            .map_err(|source| TextureError::InvalidBlock { x: block_x, y: block_y, source });
+    }
+
+    let index = match texture.layout() {
+        TextureLayout::Tiled => tiled_index(x, y, width),
+        TextureLayout::Linear => y as usize * width.next_multiple_of(8) as usize + x as usize,
+    };
+    let pixel = match format.bits_per_pixel() {
+        // The first pixel of each pair is the low nibble
+        4 => decode_texel(&[data[index / 2] >> ((index & 1) << 2)], format),
+        bits => {
+            let bytes = bits as usize / 8;
+            decode_texel(&data[index * bytes..(index + 1) * bytes], format)
+        }
+    };
+    Ok(pixel.expect("every decodable format without blocks has single texels"))
+}
+
+/// Decodes RGBA8888 PICA texture data into a `Vec<u8>` of RGBA texture data.
 ///
 /// # Arguments
 ///
@@ -126,32 +727,37 @@ fn decode_rgba8888(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
 ///
 /// A `Vec<u8>` containing the decoded RGBA data.
 ///
-fn decode_rgb888(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
-    println!("Decoding as RGB888");
-
-    let bytes_per_pixel = 24 / 8;
-    let mut output: Vec<u8> = vec![0; (width * height * 4) as usize];
-    let mut src_idx: usize = 0;
+pub(crate) fn decode_rgba8888(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    crate::progress!("Decoding as RGBA8888");
 
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for px in SWIZZLE_LUT {
+    decode_tiled(texture_data, width, height, expand_rgba8888)
+}
 
-                let x = px & 7;
-                let y = (px - x) >> 3;
+fn expand_rgba8888(p: &[u8; 4]) -> [u8; 4] {
+    // ABGR to RGBA is a byte swap of the whole pixel
+    u32::from_le_bytes(*p).swap_bytes().to_le_bytes()
+}
 
-                let out_idx = ((tx + x + (height - 1 - (ty + y)) * width) * 4) as usize;
+/// Decodes RGB888 PICA texture data into a `Vec<u8>` of RGBA texture data.
+///
+/// # Arguments
+///
+/// * `texture_data` - A byte slice containing the raw texture data.
+/// * `width` - The width of the image in pixels.
+/// * `height` - The height of the image in pixels.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the decoded RGBA data.
+///
+pub(crate) fn decode_rgb888(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    crate::progress!("Decoding as RGB888");
 
-                output[out_idx    ] = texture_data[src_idx + 2];
-                output[out_idx + 1] = texture_data[src_idx + 1];
-                output[out_idx + 2] = texture_data[src_idx    ];
-                output[out_idx + 3] = 0xFF;
+    decode_tiled(texture_data, width, height, expand_rgb888)
+}
 
-                src_idx += bytes_per_pixel;
-            }
-        }
-    }
-    output
+fn expand_rgb888(p: &[u8; 3]) -> [u8; 4] {
+    [p[2], p[1], p[0], 0xFF]
 }
 
 /// Decodes RGBA5551 PICA texture data into a `Vec<u8>` of RGBA texture data.
@@ -166,38 +772,21 @@ fn decode_rgb888(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
 ///
 /// A `Vec<u8>` containing the decoded RGBA data.
 ///
-fn decode_rgba5551(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
-    println!("Decoding as RGBA5551");
-
-    let bytes_per_pixel = 16 / 8;
-    let mut output: Vec<u8> = vec![0; (width * height * 4) as usize];
-    let mut src_idx: usize = 0;
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for px in SWIZZLE_LUT {
-
-                let x = px & 7;
-                let y = (px - x) >> 3;
+pub(crate) fn decode_rgba5551(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    crate::progress!("Decoding as RGBA5551");
 
-                let out_idx = ((tx + x + (height - 1 - (ty + y)) * width) * 4) as usize;
-                let value = (texture_data[src_idx] as u16) | ((texture_data[src_idx + 1] as u16) << 8);
+    decode_tiled(texture_data, width, height, expand_rgba5551)
+}
 
-                let r = (((value >>  1) & 0x1F) << 3) as u8;
-                let g = (((value >>  6) & 0x1F) << 3) as u8;
-                let b = (((value >> 11) & 0x1F) << 3) as u8;
-                let a = (value & 1) as u8;
+fn expand_rgba5551(p: &[u8; 2]) -> [u8; 4] {
+    let value = u16::from_le_bytes(*p);
 
-                output[out_idx    ] = b | (b >> 5);
-                output[out_idx + 1] = g | (g >> 5);
-                output[out_idx + 2] = r | (r >> 5);
-                output[out_idx + 3] = a * 0xFF;
+    let r = (((value >> 11) & 0x1F) << 3) as u8;
+    let g = (((value >>  6) & 0x1F) << 3) as u8;
+    let b = (((value >>  1) & 0x1F) << 3) as u8;
+    let a = (value & 1) as u8;
 
-                src_idx += bytes_per_pixel;
-            }
-        }
-    }
-    output
+    [r | (r >> 5), g | (g >> 5), b | (b >> 5), a * 0xFF]
 }
 
 /// Decodes RGB565 PICA texture data into a `Vec<u8>` of RGBA texture data.
@@ -212,37 +801,20 @@ fn decode_rgba5551(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
 ///
 /// A `Vec<u8>` containing the decoded RGBA data.
 ///
-fn decode_rgb565(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
-    println!("Decoding as RGB565");
-
-    let bytes_per_pixel = 16 / 8;
-    let mut output: Vec<u8> = vec![0; (width * height * 4) as usize];
-    let mut src_idx: usize = 0;
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for px in SWIZZLE_LUT {
+pub(crate) fn decode_rgb565(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    crate::progress!("Decoding as RGB565");
 
-                let x = px & 7;
-                let y = (px - x) >> 3;
-
-                let out_idx = ((tx + x + (height - 1 - (ty + y)) * width) * 4) as usize;
-                let value = (texture_data[src_idx] as u16) | ((texture_data[src_idx + 1] as u16) << 8);
+    decode_tiled(texture_data, width, height, expand_rgb565)
+}
 
-                let r = ((value & 0x1F) << 3) as u8;
-                let g = (((value >>  5) & 0x3F) << 2) as u8;
-                let b = (((value >> 11) & 0x1F) << 3) as u8;
+fn expand_rgb565(p: &[u8; 2]) -> [u8; 4] {
+    let value = u16::from_le_bytes(*p);
 
-                output[out_idx    ] = b | (b >> 5);
-                output[out_idx + 1] = g | (g >> 6);
-                output[out_idx + 2] = r | (r >> 5);
-                output[out_idx + 3] = 0xFF;
+    let r = (((value >> 11) & 0x1F) << 3) as u8;
+    let g = (((value >>  5) & 0x3F) << 2) as u8;
+    let b = ((value & 0x1F) << 3) as u8;
 
-                src_idx += bytes_per_pixel;
-            }
-        }
-    }
-    output
+    [r | (r >> 5), g | (g >> 6), b | (b >> 5), 0xFF]
 }
 
 /// Decodes RGBA4444 PICA texture data into a `Vec<u8>` of RGBA texture data.
@@ -257,42 +829,28 @@ fn decode_rgb565(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
 ///
 /// A `Vec<u8>` containing the decoded RGBA data.
 ///
-fn decode_rgba4444(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
-    println!("Decoding as RGBA4444");
-
-    let bytes_per_pixel = 16 / 8;
-    let mut output: Vec<u8> = vec![0; (width * height * 4) as usize];
-    let mut src_idx: usize = 0;
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for px in SWIZZLE_LUT {
-
-                let x = px & 7;
-                let y = (px - x) >> 3;
+pub(crate) fn decode_rgba4444(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    crate::progress!("Decoding as RGBA4444");
 
-                let out_idx = ((tx + x + (height - 1 - (ty + y)) * width) * 4) as usize;
-                let value = (texture_data[src_idx] as u16) | ((texture_data[src_idx + 1] as u16) << 8);
+    decode_tiled(texture_data, width, height, expand_rgba4444)
+}
 
-                let r = ((value >>  4) & 0xF) as u8;
-                let g = ((value >>  8) & 0xF) as u8;
-                let b = ((value >> 12) & 0xF) as u8;
-                let a = (value & 0xF) as u8;
+fn expand_rgba4444(p: &[u8; 2]) -> [u8; 4] {
+    let value = u16::from_le_bytes(*p);
 
-                output[out_idx    ] = b | (b << 4);
-                output[out_idx + 1] = g | (g << 4);
-                output[out_idx + 2] = r | (r << 4);
-                output[out_idx + 3] = a | (a << 4);
+    let r = ((value >> 12) & 0xF) as u8;
+    let g = ((value >>  8) & 0xF) as u8;
+    let b = ((value >>  4) & 0xF) as u8;
+    let a = (value & 0xF) as u8;
 
-                src_idx += bytes_per_pixel;
-            }
-        }
-    }
-    output
+    [r | (r << 4), g | (g << 4), b | (b << 4), a | (a << 4)]
 }
 
 /// Decodes LA88 PICA texture data into a `Vec<u8>` of RGBA texture data.
 ///
+/// Alpha is the first byte of each texel and luminance the second, as
+/// [`encode_la88`](crate::pica_texture::encode::encode_la88) writes them.
+///
 /// # Arguments
 ///
 /// * `texture_data` - A byte slice containing the raw texture data.
@@ -304,35 +862,20 @@ fn decode_rgba4444(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
 /// A `Vec<u8>` containing the decoded RGBA data.
 ///
 pub fn decode_la88(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
-    println!("Decoding as LA88");
-
-    let bytes_per_pixel = 16 / 8;
-    let mut output: Vec<u8> = vec![0; (width * height * 4) as usize];
-    let mut src_idx: usize = 0;
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for px in SWIZZLE_LUT {
-
-                let x = px & 7;
-                let y = (px - x) >> 3;
+    crate::progress!("Decoding as LA88");
 
-                let out_idx = ((tx + x + (height - 1 - (ty + y)) * width) * 4) as usize;
-
-                output[out_idx    ] = texture_data[src_idx + 1];
-                output[out_idx + 1] = texture_data[src_idx + 1];
-                output[out_idx + 2] = texture_data[src_idx + 1];
-                output[out_idx + 3] = texture_data[src_idx    ];
+    decode_tiled(texture_data, width, height, expand_la88)
+}
 
-                src_idx += bytes_per_pixel;
-            }
-        }
-    }
-    output
+fn expand_la88(p: &[u8; 2]) -> [u8; 4] {
+    [p[1], p[1], p[1], p[0]]
 }
 
 /// Decodes HL8 PICA texture data into a `Vec<u8>` of RGBA texture data.
 ///
+/// Green is the first byte of each texel and red the second, as
+/// [`encode_hl8`](crate::pica_texture::encode::encode_hl8) writes them.
+///
 /// # Arguments
 ///
 /// * `texture_data` - A byte slice containing the raw texture data.
@@ -344,31 +887,13 @@ pub fn decode_la88(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
 /// A `Vec<u8>` containing the decoded RGBA data.
 ///
 pub fn decode_hl8(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
-    println!("Decoding as HL8");
-
-    let bytes_per_pixel = 16 / 8;
-    let mut output: Vec<u8> = vec![0; (width * height * 4) as usize];
-    let mut src_idx: usize = 0;
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for px in SWIZZLE_LUT {
+    crate::progress!("Decoding as HL8");
 
-                let x = px & 7;
-                let y = (px - x) >> 3;
-
-                let out_idx = ((tx + x + (height - 1 - (ty + y)) * width) * 4) as usize;
-
-                output[out_idx    ] = texture_data[src_idx + 1];
-                output[out_idx + 1] = texture_data[src_idx    ];
-                output[out_idx + 2] = 0x00;
-                output[out_idx + 3] = 0xFF;
+    decode_tiled(texture_data, width, height, expand_hl8)
+}
 
-                src_idx += bytes_per_pixel;
-            }
-        }
-    }
-    output
+fn expand_hl8(p: &[u8; 2]) -> [u8; 4] {
+    [p[1], p[0], 0x00, 0xFF]
 }
 
 /// Decodes L8 PICA texture data into a `Vec<u8>` of RGBA texture data.
@@ -384,31 +909,46 @@ pub fn decode_hl8(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
 /// A `Vec<u8>` containing the decoded RGBA data.
 ///
 pub fn decode_l8(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
-    println!("Decoding as L8");
-
-    let bytes_per_pixel = 1;
-    let mut output: Vec<u8> = vec![0; (width * height * 4) as usize];
-    let mut src_idx: usize = 0;
+    crate::progress!("Decoding as L8");
 
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for px in SWIZZLE_LUT {
+    decode_tiled(texture_data, width, height, expand_l8)
+}
 
-                let x = px & 7;
-                let y = (px - x) >> 3;
+fn expand_l8(p: &[u8; 1]) -> [u8; 4] {
+    [p[0], p[0], p[0], 0xFF]
+}
 
-                let out_idx = ((tx + x + (height - 1 - (ty + y)) * width) * 4) as usize;
+/// Decodes shadow map PICA texture data into a `Vec<u8>` of RGBA texture data.
+///
+/// Each texel holds a 24-bit depth above an 8-bit shadow intensity. The top
+/// 8 bits of the depth become gray levels, and the intensity becomes alpha.
+///
+/// # Arguments
+///
+/// * `texture_data` - A byte slice containing the raw texture data.
+/// * `width` - The width of the image in pixels.
+/// * `height` - The height of the image in pixels.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the decoded RGBA data.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::decode::decode_shadow;
+/// let mut data = vec![0; 8 * 8 * 4];
+/// data[..4].copy_from_slice(&0x80_12_34_C0u32.to_le_bytes());
+/// assert_eq!(&decode_shadow(&data, 8, 8)[..4], &[0x80, 0x80, 0x80, 0xC0]);
+/// ```
+pub fn decode_shadow(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    crate::progress!("Decoding as Shadow");
 
-                output[out_idx    ] = texture_data[src_idx];
-                output[out_idx + 1] = texture_data[src_idx];
-                output[out_idx + 2] = texture_data[src_idx];
-                output[out_idx + 3] = 0xFF;
+    decode_tiled(texture_data, width, height, expand_shadow)
+}
 
-                src_idx += bytes_per_pixel;
-            }
-        }
-    }
-    output
+fn expand_shadow(p: &[u8; 4]) -> [u8; 4] {
+    [p[3], p[3], p[3], p[0]]
 }
 
 /// Decodes A8 PICA texture data into a `Vec<u8>` of RGBA texture data.
@@ -424,31 +964,13 @@ pub fn decode_l8(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
 /// A `Vec<u8>` containing the decoded RGBA data.
 ///
 pub fn decode_a8(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
-    println!("Decoding as A8");
-
-    let bytes_per_pixel = 1;
-    let mut output: Vec<u8> = vec![0; (width * height * 4) as usize];
-    let mut src_idx: usize = 0;
+    crate::progress!("Decoding as A8");
 
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for px in SWIZZLE_LUT {
-
-                let x = px & 7;
-                let y = (px - x) >> 3;
-
-                let out_idx = ((tx + x + (height - 1 - (ty + y)) * width) * 4) as usize;
-
-                output[out_idx    ] = 0xFF;
-                output[out_idx + 1] = 0xFF;
-                output[out_idx + 2] = 0xFF;
-                output[out_idx + 3] = texture_data[src_idx];
+    decode_tiled(texture_data, width, height, expand_a8)
+}
 
-                src_idx += bytes_per_pixel;
-            }
-        }
-    }
-    output
+fn expand_a8(p: &[u8; 1]) -> [u8; 4] {
+    [0xFF, 0xFF, 0xFF, p[0]]
 }
 
 /// Decodes LA44 PICA texture data into a `Vec<u8>` of RGBA texture data.
@@ -464,31 +986,16 @@ pub fn decode_a8(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
 /// A `Vec<u8>` containing the decoded RGBA data.
 ///
 pub fn decode_la44(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
-    println!("Decoding as LA44");
-
-    let bytes_per_pixel = 1;
-    let mut output: Vec<u8> = vec![0; (width * height * 4) as usize];
-    let mut src_idx: usize = 0;
+    crate::progress!("Decoding as LA44");
 
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for px in SWIZZLE_LUT {
-
-                let x = px & 7;
-                let y = (px - x) >> 3;
-
-                let out_idx = ((tx + x + (height - 1 - (ty + y)) * width) * 4) as usize;
+    decode_tiled(texture_data, width, height, expand_la44)
+}
 
-                output[out_idx    ] = (texture_data[src_idx] >> 4) | (texture_data[src_idx] & 0xF0);
-                output[out_idx + 1] = (texture_data[src_idx] >> 4) | (texture_data[src_idx] & 0xF0);
-                output[out_idx + 2] = (texture_data[src_idx] >> 4) | (texture_data[src_idx] & 0xF0);
-                output[out_idx + 3] = (texture_data[src_idx] << 4) | (texture_data[src_idx] & 0x0F);
+fn expand_la44(p: &[u8; 1]) -> [u8; 4] {
+    let l = (p[0] >> 4) | (p[0] & 0xF0);
+    let a = (p[0] << 4) | (p[0] & 0x0F);
 
-                src_idx += bytes_per_pixel;
-            }
-        }
-    }
-    output
+    [l, l, l, a]
 }
 
 /// Decodes L4 PICA texture data into a `Vec<u8>` of RGBA texture data.
@@ -503,34 +1010,33 @@ pub fn decode_la44(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
 ///
 /// A `Vec<u8>` containing the decoded RGBA data.
 ///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::decode::{decode_a4, decode_l4};
+/// # use pica_convert::pica_texture::util::unswizzle_4bpp;
+/// // Partial tiles at the edges are stored in full but left out
+/// for (width, height) in [(8u32, 8u32), (12, 5), (24, 16), (3, 19)] {
+///     let data: Vec<u8> = (0..width.next_multiple_of(8) * height.next_multiple_of(8) / 2).map(|i| (i * 37) as u8).collect();
+///     let rows = unswizzle_4bpp(&data, width, height);
+///     let nibble = |i: usize| rows[i >> 1] >> ((i & 1) << 2) & 0xF;
+///
+///     let l4 = decode_l4(&data, width, height);
+///     let a4 = decode_a4(&data, width, height);
+///     for i in 0..(width * height) as usize {
+///         assert_eq!(l4[i * 4..i * 4 + 4], [nibble(i) * 17, nibble(i) * 17, nibble(i) * 17, 0xFF]);
+///         assert_eq!(a4[i * 4..i * 4 + 4], [0xFF, 0xFF, 0xFF, nibble(i) * 17]);
+///     }
+/// }
+/// ```
 pub fn decode_l4(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
-    println!("Decoding as L4");
-
-    let bytes_per_pixel = 1;
-    let mut output: Vec<u8> = vec![0; (width * height * 4) as usize];
-    let mut src_idx: usize = 0;
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for px in SWIZZLE_LUT {
-
-                let x = px & 7;
-                let y = (px - x) >> 3;
+    crate::progress!("Decoding as L4");
 
-                let out_idx = ((tx + x + (height - 1 - (ty + y)) * width) * 4) as usize;
-
-                let l = (texture_data[src_idx >> 1] >> ((src_idx & 1) << 2)) & 0xF;
-
-                output[out_idx    ] = l << 4 | l;
-                output[out_idx + 1] = l << 4 | l;
-                output[out_idx + 2] = l << 4 | l;
-                output[out_idx + 3] = 0xFF;
+    decode_tiled_4bpp(texture_data, width, height, expand_l4)
+}
 
-                src_idx += bytes_per_pixel;
-            }
-        }
-    }
-    output
+fn expand_l4(l: u8) -> [u8; 4] {
+    [l << 4 | l, l << 4 | l, l << 4 | l, 0xFF]
 }
 
 /// Decodes A4 PICA texture data into a `Vec<u8>` of RGBA texture data.
@@ -546,33 +1052,13 @@ pub fn decode_l4(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
 /// A `Vec<u8>` containing the decoded RGBA data.
 ///
 pub fn decode_a4(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
-    println!("Decoding as a4");
-
-    let bytes_per_pixel = 1;
-    let mut output: Vec<u8> = vec![0; (width * height * 4) as usize];
-    let mut src_idx: usize = 0;
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for px in SWIZZLE_LUT {
+    crate::progress!("Decoding as A4");
 
-                let x = px & 7;
-                let y = (px - x) >> 3;
-
-                let out_idx = ((tx + x + (height - 1 - (ty + y)) * width) * 4) as usize;
-
-                let a = (texture_data[src_idx >> 1] >> ((src_idx & 1) << 2)) & 0xF;
-
-                output[out_idx    ] = 0xFF;
-                output[out_idx + 1] = 0xFF;
-                output[out_idx + 2] = 0xFF;
-                output[out_idx + 3] = a << 4 | a;
+    decode_tiled_4bpp(texture_data, width, height, expand_a4)
+}
 
-                src_idx += bytes_per_pixel;
-            }
-        }
-    }
-    output
+fn expand_a4(a: u8) -> [u8; 4] {
+    [0xFF, 0xFF, 0xFF, a << 4 | a]
 }
 
 /// Decodes ETC1 PICA texture data into a `Vec<u8>` of RGBA texture data.
@@ -588,8 +1074,30 @@ pub fn decode_a4(texture_data: &[u8], width: u32, height: u32) -> Vec<u8> {
 ///
 /// A `Vec<u8>` containing the decoded RGBA data.
 ///
-pub fn decode_etc1(texture_data: &[u8], width: u32, height: u32, has_alpha: bool) -> Vec<u8> {
-    if has_alpha { println!("Decoding as etc1a4") } else  { println!("Decoding as etc1"); };
+/// # Errors
+///
+/// Returns [`TextureError::InvalidBlock`] with the position of the first
+/// block whose colors are out of range.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{decode::decode_etc1, TextureError};
+/// // Blocks are stored byte swapped, so the red base and delta are in the last byte
+/// let mut data = vec![0u8; 4 * 8];
+/// data[3 * 8 + 4] = 0b10;
+/// data[3 * 8 + 7] = 31 << 3 | 3;
+///
+/// match decode_etc1(&data, 8, 8, false) {
+///     Err(TextureError::InvalidBlock { x, y, .. }) => assert_eq!((x, y), (4, 4)),
+///     _ => panic!("expected an invalid block"),
+/// }
+///
+/// data[3 * 8 + 7] = 0;
+/// assert_eq!(decode_etc1(&data, 8, 8, false).unwrap().len(), 8 * 8 * 4);
+/// ```
+pub fn decode_etc1(texture_data: &[u8], width: u32, height: u32, has_alpha: bool) -> Result<Vec<u8>, TextureError> {
+    if has_alpha { crate::progress!("Decoding as etc1a4") } else  { crate::progress!("Decoding as etc1"); };
 
     let mut output = vec![0u8; (width as usize) * (height as usize) * 4];
     let mut src_offs = 0;
@@ -607,7 +1115,8 @@ pub fn decode_etc1(texture_data: &[u8], width: u32, height: u32, has_alpha: bool
                 color_block = swap64(color_block);
                 src_offs += 8;
 
-                let decoded = decompress_block(&color_block, true);
+                let decoded = decompress_block(&color_block, true)
+                    .map_err(|source| TextureError::InvalidBlock { x: tx + XT[t], y: ty + YT[t], source })?;
 
                 for i in 0..16 {
                     let px = XT[t] + (i % 4);
@@ -619,7 +1128,7 @@ pub fn decode_etc1(texture_data: &[u8], width: u32, height: u32, has_alpha: bool
                         continue;
                     }
 
-                    let out_offs = (((height - 1 - dst_y) * width + dst_x) * 4) as usize;
+                    let out_offs = ((dst_y * width + dst_x) * 4) as usize;
 
                     let r = decoded[i as usize * 4    ];
                     let g = decoded[i as usize * 4 + 1];
@@ -640,5 +1149,5 @@ pub fn decode_etc1(texture_data: &[u8], width: u32, height: u32, has_alpha: bool
             }
         }
     }
-    output
+    Ok(output)
 }
@@ -1,19 +1,30 @@
-use image::{DynamicImage, GenericImageView, RgbaImage};
-
-use crate::pica_texture::types::{TextureFormat, PicaTexture};
-use crate::pica_texture::etc1::{self, compress_block, Etc1PackParams};
-use crate::pica_texture::util::{XT, YT, SWIZZLE_LUT, swap64};
+use std::borrow::Cow;
+use std::ops::Deref;
+
+use image::{DynamicImage, ImageBuffer, Pixel, Rgba, RgbaImage};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::pica_texture::types::{TextureFormat, TextureLayout, PicaTexture, max_mip_levels};
+use crate::pica_texture::options::EncodeOptions;
+use crate::pica_texture::error::TextureError;
+use crate::pica_texture::codec::{TextureCodec, CodecRegistry, RgbaView, view};
+use crate::pica_texture::limits::ensure_nonzero;
+use crate::pica_texture::util::{swizzle, swizzle_4bpp};
+#[cfg(any(feature = "etc1", feature = "etc1-rust"))]
+use crate::pica_texture::util::{XT, YT, swap64};
 
 /// Encodes a [`DynamicImage`] into raw PICA texture data for a given [`TextureFormat`].
 ///
 /// This function converts the input image to `RGBA8` internally to ensure
-/// consistent pixel access, then dispatches to the appropriate encoder
-/// depending on the requested texture format.
+/// consistent pixel access; images that are already `RGBA8` are read in
+/// place rather than copied.
 ///
 /// # Arguments
 ///
 /// * `img` - The source image to encode.
 /// * `format` - The target [`TextureFormat`] specifying how the image should be encoded.
+/// * `options` - The [`EncodeOptions`] to encode with.
 ///
 /// # Returns
 ///
@@ -28,48 +39,408 @@ use crate::pica_texture::util::{XT, YT, SWIZZLE_LUT, swap64};
 ///
 /// ```
 /// # use image::DynamicImage;
-/// # use pica_convert::pica_texture::{encode::encode_texture, TextureFormat};
-/// // Create a blank 4x4 RGBA image
+/// # use pica_convert::pica_texture::{encode::encode_texture, EncodeOptions, TextureFormat};
+/// // Create a blank 32x32 RGBA image
 /// let img = DynamicImage::new_rgba8(32, 32);
 ///
 /// // Encode the image into RGBA8888 format
-/// let encoded = encode_texture(&img, &TextureFormat::RGBA8888).unwrap();
+/// let encoded = encode_texture(&img, &TextureFormat::RGBA8888, &EncodeOptions::default()).unwrap();
 ///
 /// // Each pixel is 4 bytes in RGBA8888
-/// assert_eq!(encoded.len(), 32 * 32 * 4);
+/// assert_eq!(encoded.data().len(), 32 * 32 * 4);
+///
+/// // Empty images are rejected
+/// # use pica_convert::pica_texture::TextureError;
+/// for (width, height) in [(0, 0), (0, 32), (32, 0)] {
+///     let err = encode_texture(&DynamicImage::new_rgba8(width, height), &TextureFormat::RGBA8888, &EncodeOptions::default()).err().unwrap();
+///     assert!(matches!(err.downcast_ref(), Some(TextureError::InvalidDimensions { .. })));
+/// }
+///
+/// // Every format produces exactly the data its texture size calls for
+/// for format in TextureFormat::ALL.into_iter().filter(|format| format.is_available()) {
+///     for (width, height) in [(8, 8), (32, 32), (64, 16), (16, 128)] {
+///         let img = DynamicImage::new_rgba8(width, height);
+///         let encoded = encode_texture(&img, &format, &EncodeOptions::default()).unwrap();
+///         assert_eq!(encoded.data().len(), format.expected_data_len(width, height), "{} at {}x{}", format, width, height);
+///     }
+/// }
+/// ```
+///
+/// Textures that don't fill their edge tiles are padded to whole tiles, and
+/// decode back to their own size:
+///
+/// ```
+/// # use image::{DynamicImage, Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{decode_texture, encode_texture, DecodeOptions, EncodeOptions, TextureFormat};
+/// for (width, height) in [(1, 1), (2, 2), (4, 4), (8, 4), (12, 3)] {
+///     // Opaque grays that every format below stores exactly
+///     let img = RgbaImage::from_fn(width, height, |x, y| {
+///         let l = ((x * 3 + y * 5) % 16) as u8 * 0x11;
+///         Rgba([l, l, l, 255])
+///     });
+///
+///     for format in TextureFormat::ALL.into_iter().filter(|format| format.is_available()) {
+///         let texture = encode_texture(&DynamicImage::ImageRgba8(img.clone()), &format, &EncodeOptions::default()).unwrap();
+///         assert_eq!(texture.data().len(), format.expected_data_len(width, height));
+///
+///         let decoded = decode_texture(&texture, &DecodeOptions::default()).unwrap().to_rgba8();
+///         assert_eq!(decoded.dimensions(), (width, height));
+///
+///         let lossless = [TextureFormat::RGBA8888, TextureFormat::RGB888, TextureFormat::RGBA4444, TextureFormat::LA88,
+///             TextureFormat::L8, TextureFormat::LA44, TextureFormat::L4];
+///         if lossless.contains(&format) {
+///             assert_eq!(decoded, img, "{} at {}x{}", format, width, height);
+///         }
+///     }
+/// }
+/// ```
+pub fn encode_texture(img: &DynamicImage, format: &TextureFormat, options: &EncodeOptions) -> Result<PicaTexture, Box<dyn std::error::Error>> {
+    encode_texture_with(CodecRegistry::builtin(), img, format, options)
+}
+
+/// [`encode_texture`] with the codecs of `registry`, for [`CodecRegistry::encode_texture`].
+pub(crate) fn encode_texture_with(registry: &CodecRegistry, img: &DynamicImage, format: &TextureFormat, options: &EncodeOptions) -> Result<PicaTexture, Box<dyn std::error::Error>> {
+    encode_rgba_image(registry, &view(&*as_rgba8(img)), format, options)
+}
+
+/// Encodes a buffer of raw RGBA8 pixels into PICA texture data for a given [`TextureFormat`].
+///
+/// The pixels are borrowed as-is, so callers that already hold RGBA data in
+/// memory don't need to build a [`DynamicImage`] first.
+///
+/// # Arguments
+///
+/// * `pixels` - Row-major RGBA8 pixel data, 4 bytes per pixel.
+/// * `width` - The width of the image in pixels.
+/// * `height` - The height of the image in pixels.
+/// * `format` - The target [`TextureFormat`] specifying how the image should be encoded.
+/// * `options` - The [`EncodeOptions`] to encode with.
+///
+/// # Returns
+///
+/// A PicaTexture containing the encoded texture data on success,
+/// or an error if encoding fails.
+///
+/// # Errors
+///
+/// Returns an error if the length of `pixels` is not `width * height * 4`.
+///
+/// # Example
+///
+/// ```
+/// # use image::{DynamicImage, RgbaImage};
+/// # use pica_convert::pica_texture::{encode_rgba_buffer, encode_texture, EncodeOptions, TextureFormat};
+/// let pixels: Vec<u8> = (0..16 * 16 * 4).map(|i| (i * 7 % 256) as u8).collect();
+/// let options = EncodeOptions::default();
+///
+/// let from_buffer = encode_rgba_buffer(&pixels, 16, 16, &TextureFormat::RGB565, &options).unwrap();
+///
+/// // Matches the DynamicImage entry point byte for byte
+/// let img = DynamicImage::ImageRgba8(RgbaImage::from_raw(16, 16, pixels.clone()).unwrap());
+/// let from_image = encode_texture(&img, &TextureFormat::RGB565, &options).unwrap();
+/// assert_eq!(from_buffer, from_image);
+///
+/// // Buffers of the wrong length are rejected
+/// assert!(encode_rgba_buffer(&pixels[4..], 16, 16, &TextureFormat::RGB565, &options).is_err());
+/// ```
+pub fn encode_rgba_buffer(pixels: &[u8], width: u32, height: u32, format: &TextureFormat, options: &EncodeOptions) -> Result<PicaTexture, Box<dyn std::error::Error>> {
+    let expected_len = width as usize * height as usize * 4;
+    if pixels.len() != expected_len {
+        return Err(format!("RGBA buffer is {} bytes, expected {} for {}x{}", pixels.len(), expected_len, width, height).into());
+    }
+
+    let img = RgbaView::from_raw(width, height, pixels)
+        .ok_or("Failed to construct ImageBuffer from raw data")?;
+
+    encode_rgba_image(CodecRegistry::builtin(), &img, format, options)
+}
+
+/// Encodes a [`DynamicImage`] along with `mip_levels - 1` smaller copies of
+/// it, each half the size of the last, into one texture holding the chain.
+///
+/// Each level is scaled down from the full image with a triangle filter and
+/// padded to whole tiles, in the layout
+/// [`PicaTexture::try_with_mip_levels`] expects.
+///
+/// # Errors
+///
+/// Returns [`TextureError::InvalidMipLevels`] if there are no levels or more
+/// than [`max_mip_levels`](crate::pica_texture::max_mip_levels) allows, or an
+/// error if the format can't be encoded.
+///
+/// # Example
+///
+/// ```
+/// # use image::{DynamicImage, Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{encode_mip_chain, encode_texture, estimate_encoded_size, EncodeOptions, TextureFormat};
+/// let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(32, 16, |x, y| Rgba([x as u8 * 8, y as u8 * 16, 0, 255])));
+/// let options = EncodeOptions::default();
+///
+/// let texture = encode_mip_chain(&img, &TextureFormat::RGB565, 4, &options).unwrap();
+/// assert_eq!(texture.mip_levels(), 4);
+/// assert_eq!(texture.data().len() as u64, estimate_encoded_size(&TextureFormat::RGB565, 32, 16, 4));
+///
+/// // The base level is the image itself
+/// let base = encode_texture(&img, &TextureFormat::RGB565, &options).unwrap();
+/// assert_eq!(&texture.data()[..base.data().len()], base.data());
+///
+/// // A single level is a plain texture
+/// assert_eq!(encode_mip_chain(&img, &TextureFormat::RGB565, 1, &options).unwrap(), base);
+/// assert!(encode_mip_chain(&img, &TextureFormat::RGB565, 7, &options).is_err());
 /// ```
-pub fn encode_texture(img: &DynamicImage, format: &TextureFormat) -> Result<PicaTexture, Box<dyn std::error::Error>> {
+pub fn encode_mip_chain(img: &DynamicImage, format: &TextureFormat, mip_levels: u32, options: &EncodeOptions) -> Result<PicaTexture, Box<dyn std::error::Error>> {
+    let img = as_rgba8(img);
     let (width, height) = img.dimensions();
-    // Ensure image is rgba8 before doing any encoding
-    let img = img.to_rgba8();
-
-    let output_texture = match format {
-        TextureFormat::RGBA8888 => encode_rgba8888(&img, width, height),
-        TextureFormat::RGB888   => encode_rgb888(&img, width, height),
-        TextureFormat::RGBA5551 => encode_rgba5551(&img, width, height),
-        TextureFormat::RGB565   => encode_rgb565(&img, width, height),
-        TextureFormat::RGBA4444 => encode_rgba4444(&img, width, height),
-        TextureFormat::LA88     => encode_la88(&img, width, height),
-        TextureFormat::HL8      => encode_hl8(&img, width, height),
-        TextureFormat::L8       => encode_l8(&img, width, height),
-        TextureFormat::A8       => encode_a8(&img, width, height),
-        TextureFormat::LA44     => encode_la44(&img, width, height),
-        TextureFormat::L4       => encode_l4(&img, width, height),
-        TextureFormat::A4       => encode_a4(&img, width, height),
-        TextureFormat::ETC1     => encode_etc1(&img, width, height, false),
-        TextureFormat::ETC1A4   => encode_etc1(&img, width, height, true),
-    };
 
-    let tex = PicaTexture::new(format.clone(), width, height, output_texture);
+    let max = max_mip_levels(width, height);
+    if mip_levels == 0 || mip_levels > max {
+        return Err(TextureError::InvalidMipLevels { levels: mip_levels, max }.into());
+    }
+
+    let registry = CodecRegistry::builtin();
+    let mut data = encode_rgba_image(registry, &view(&*img), format, options)?.into_data();
+    for level in 1..mip_levels {
+        let (level_width, level_height) = ((width >> level).max(1), (height >> level).max(1));
+        let scaled = image::imageops::resize(&*img, level_width, level_height, image::imageops::FilterType::Triangle);
+        data.extend(encode_rgba_image(registry, &view(&scaled), format, options)?.into_data());
+    }
+
+    Ok(PicaTexture::try_with_mip_levels(*format, width, height, mip_levels, data)?.with_layout(options.layout)?)
+}
+
+/// Returns `img` as RGBA8, borrowing it if it already is and converting it otherwise.
+///
+/// Images decoded from textures are always RGBA8, so transcoding one never
+/// copies its pixels just to read them.
+///
+fn as_rgba8(img: &DynamicImage) -> Cow<'_, RgbaImage> {
+    img.as_rgba8().map_or_else(|| Cow::Owned(img.to_rgba8()), Cow::Borrowed)
+}
+
+/// Encodes an RGBA image through `registry`'s codec for `format`.
+///
+/// Shared by every public encode entry point.
+///
+fn encode_rgba_image(registry: &CodecRegistry, img: &RgbaView<'_>, format: &TextureFormat, options: &EncodeOptions) -> Result<PicaTexture, Box<dyn std::error::Error>> {
+    if let Some(err) = format.encode_options_error(options) {
+        return Err(err.into());
+    }
+    ensure_nonzero(img.width(), img.height())?;
+
+    crate::progress!("Encoding as {}", format);
+
+    let output_texture = encode_with_codec(registry.codec_for(format)?, img, options)?;
+    let expected = format.expected_data_len(img.width(), img.height());
+    if output_texture.len() != expected {
+        return Err(TextureError::DataLengthMismatch { expected, actual: output_texture.len() }.into());
+    }
+
+    Ok(PicaTexture::new_unchecked(*format, img.width(), img.height(), output_texture).with_layout(options.layout)?)
+}
+
+/// Encodes an RGBA image into raw texture data using any [`TextureCodec`].
+///
+/// This applies the [`EncodeOptions`] pre-processing before handing the
+/// image to the codec, so external codecs behave the same as the built-in formats.
+///
+/// # Arguments
+///
+/// * `codec` - The [`TextureCodec`] to encode with.
+/// * `img` - The source image to encode.
+/// * `options` - The [`EncodeOptions`] to encode with.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the encoded texture data, or the codec's error if
+/// it can't encode the image with `options`.
+///
+/// # Example
+///
+/// ```
+/// # use image::{imageops, Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{encode_with_codec, BuiltinCodec, EncodeOptions, TextureCodec, TextureFormat, TextureLayout};
+/// let img = RgbaImage::from_fn(8, 8, |x, y| Rgba([x as u8 * 30, y as u8 * 30, 0, 255]));
+/// let codec = BuiltinCodec::for_format(&TextureFormat::RGBA8888);
+///
+/// // Mirroring during encode is the same as encoding a mirrored image
+/// let options = EncodeOptions { flip_horizontal: true, ..Default::default() };
+/// let mirrored = encode_with_codec(codec, &img, &options).unwrap();
+/// assert_eq!(mirrored, encode_with_codec(codec, &imageops::flip_horizontal(&img), &EncodeOptions::default()).unwrap());
+///
+/// // Formats without a linear layout are rejected rather than encoded to nothing
+/// let linear = EncodeOptions { layout: TextureLayout::Linear, ..Default::default() };
+/// assert!(encode_with_codec(BuiltinCodec::for_format(&TextureFormat::ETC1), &img, &linear).is_err());
+/// ```
+pub fn encode_with_codec<C: Deref<Target = [u8]>>(codec: &dyn TextureCodec, img: &ImageBuffer<Rgba<u8>, C>, options: &EncodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if options.flip_horizontal {
+        let mirrored = image::imageops::flip_horizontal(img);
+        return codec.encode(&view(&mirrored), options);
+    }
+
+    codec.encode(&view(img), options)
+}
+
+/// Returns one band of `rows` rows of RGBA pixels, padded out to whole 8x8 tiles.
+///
+/// The hardware stores partial tiles at the right and bottom edges in full,
+/// so the padding repeats the nearest edge pixel. Bands that are already
+/// whole tiles are borrowed as they are.
+///
+fn padded_band(pixels: &[u8], width: u32, rows: u32) -> Cow<'_, [u8]> {
+    let padded_width = width.next_multiple_of(8) as usize;
+    if (padded_width == width as usize && rows == 8) || pixels.is_empty() {
+        return Cow::Borrowed(pixels);
+    }
+
+    let row_bytes = width as usize * 4;
+    let mut band = Vec::with_capacity(padded_width * 8 * 4);
+    for row in pixels.chunks_exact(row_bytes) {
+        band.extend_from_slice(row);
+        let edge = &row[row_bytes - 4..];
+        for _ in width as usize..padded_width {
+            band.extend_from_slice(edge);
+        }
+    }
+
+    let padded_row_bytes = padded_width * 4;
+    while band.len() < padded_row_bytes * 8 {
+        band.extend_from_within(band.len() - padded_row_bytes..);
+    }
+    Cow::Owned(band)
+}
+
+/// Packs each pixel of an RGBA image with `convert` and lays the results out as `layout` says.
+///
+/// Shared by the uncompressed encoders with whole-byte pixels. Each band is
+/// packed in rows, then tiled unless it's wanted linear.
+///
+fn encode_packed<C: Deref<Target = [u8]>, const N: usize>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32, layout: TextureLayout, convert: impl Fn(&Rgba<u8>) -> [u8; N] + Sync) -> Vec<u8> {
+    let raw_pixels: &[u8] = img.as_raw();
+    let row_bytes = width as usize * 4;
+    let padded_width = width.next_multiple_of(8);
+
+    encode_bands(height, |band_y| {
+        let band_height = (height - band_y).min(8);
+        let band_start = band_y as usize * row_bytes;
+        let band_pixels = padded_band(&raw_pixels[band_start..band_start + band_height as usize * row_bytes], width, band_height);
+
+        let packed: Vec<u8> = band_pixels.chunks_exact(4).flat_map(|pixel| convert(Rgba::from_slice(pixel))).collect();
+        match layout {
+            TextureLayout::Tiled => swizzle(&packed, padded_width, 8, N),
+            TextureLayout::Linear => packed,
+        }
+    })
+}
+
+/// Encodes each 8-row band of tiles with `encode_band` and joins the results in order.
+///
+/// Tiles are stored band by band, so bands encode independently. With the
+/// `parallel` feature they're spread across rayon's global thread pool, which
+/// is shared with any outer parallelism such as bulk conversion.
+///
+fn encode_bands(height: u32, encode_band: impl Fn(u32) -> Vec<u8> + Sync) -> Vec<u8> {
+    let band_count = height.div_ceil(8);
+
+    #[cfg(feature = "parallel")]
+    let bands: Vec<Vec<u8>> = (0..band_count).into_par_iter().map(|band| encode_band(band * 8)).collect();
+    #[cfg(not(feature = "parallel"))]
+    let bands: Vec<Vec<u8>> = (0..band_count).map(|band| encode_band(band * 8)).collect();
+
+    bands.concat()
+}
 
-    Ok(tex)
+/// Packs each pixel of an RGBA image to a 4-bit value with `convert` and lays the results out as `layout` says.
+///
+/// Shared by the 4-bit encoders. The first pixel of each pair goes in the low nibble.
+///
+fn encode_packed_4bpp<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32, layout: TextureLayout, convert: impl Fn(&Rgba<u8>) -> u8 + Sync) -> Vec<u8> {
+    let raw_pixels: &[u8] = img.as_raw();
+    let row_bytes = width as usize * 4;
+    let padded_width = width.next_multiple_of(8);
+
+    // Padded rows are a multiple of 8 pixels, so pairs never straddle two rows
+    encode_bands(height, |band_y| {
+        let band_height = (height - band_y).min(8);
+        let band_start = band_y as usize * row_bytes;
+        let band_pixels = padded_band(&raw_pixels[band_start..band_start + band_height as usize * row_bytes], width, band_height);
+
+        let packed: Vec<u8> = band_pixels.chunks_exact(8).map(|pair| {
+            let first = convert(Rgba::from_slice(&pair[..4])) & 0xF;
+            let second = convert(Rgba::from_slice(&pair[4..])) & 0xF;
+            first | (second << 4)
+        }).collect();
+        match layout {
+            TextureLayout::Tiled => swizzle_4bpp(&packed, padded_width, 8),
+            TextureLayout::Linear => packed,
+        }
+    })
+}
+
+/// Encodes an RGBA image as `format` with its pixels in rows instead of
+/// tiles, for textures stored in [`TextureLayout::Linear`].
+///
+/// Pixels are packed exactly as the tiled encoders like [`encode_rgb565`]
+/// pack them, over the image padded to whole tiles; only the tiling is left
+/// out.
+///
+/// # Returns
+///
+/// The encoded data, or `None` for formats without a linear layout (see
+/// [`TextureFormat::has_linear_layout`]) or that can't be encoded.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{encode_texture, EncodeOptions, TextureFormat};
+/// # use pica_convert::pica_texture::encode::encode_linear;
+/// # use pica_convert::pica_texture::synthetic::synthetic_image;
+/// # use pica_convert::pica_texture::util::{swizzle, swizzle_4bpp};
+/// let img = synthetic_image(24, 16, 5);
+///
+/// // Tiling the linear data gives what the tiled encoders write
+/// for format in TextureFormat::ALL.into_iter().filter(|format| format.is_available() && format.has_linear_layout()) {
+///     let linear = encode_linear(&img, 24, 16, &format).unwrap();
+///     let tiled = encode_texture(&img.clone().into(), &format, &EncodeOptions::default()).unwrap();
+///     assert_eq!(linear.len(), tiled.data().len());
+///
+///     let retiled = match format.bits_per_pixel() {
+///         4 => swizzle_4bpp(&linear, 24, 16),
+///         bits => swizzle(&linear, 24, 16, bits as usize / 8),
+///     };
+///     assert_eq!(retiled, tiled.data(), "{}", format);
+/// }
+///
+/// // Rows come out in order, two 4-bit pixels to a byte
+/// let pixel = |x: u32, y: u32| *img.get_pixel(x, y);
+/// assert_eq!(&encode_linear(&img, 24, 16, &TextureFormat::A8).unwrap()[24..27], &[pixel(0, 1)[3], pixel(1, 1)[3], pixel(2, 1)[3]]);
+/// assert_eq!(encode_linear(&img, 24, 16, &TextureFormat::A4).unwrap()[0], (pixel(0, 0)[3] >> 4) | (pixel(1, 0)[3] >> 4 << 4));
+///
+/// assert!(encode_linear(&img, 24, 16, &TextureFormat::ETC1).is_none());
+/// ```
+pub fn encode_linear<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32, format: &TextureFormat) -> Option<Vec<u8>> {
+    let layout = TextureLayout::Linear;
+    Some(match format {
+        TextureFormat::RGBA8888 => encode_packed(img, width, height, layout, pack_rgba8888),
+        TextureFormat::RGB888 => encode_packed(img, width, height, layout, pack_rgb888),
+        TextureFormat::RGBA5551 => encode_packed(img, width, height, layout, pack_rgba5551),
+        TextureFormat::RGB565 => encode_packed(img, width, height, layout, pack_rgb565),
+        TextureFormat::RGBA4444 => encode_packed(img, width, height, layout, pack_rgba4444),
+        TextureFormat::LA88 => encode_packed(img, width, height, layout, pack_la88),
+        TextureFormat::HL8 => encode_packed(img, width, height, layout, pack_hl8),
+        TextureFormat::L8 => encode_packed(img, width, height, layout, pack_l8),
+        TextureFormat::A8 => encode_packed(img, width, height, layout, pack_a8),
+        TextureFormat::LA44 => encode_packed(img, width, height, layout, pack_la44),
+        TextureFormat::L4 => encode_packed_4bpp(img, width, height, layout, pack_l4),
+        TextureFormat::A4 => encode_packed_4bpp(img, width, height, layout, pack_a4),
+        TextureFormat::ETC1 | TextureFormat::ETC1A4 | TextureFormat::Shadow | TextureFormat::Gas => return None,
+    })
 }
 
 /// Encodes an RGBA image as RGBA8888 PICA texture data.
 ///
 /// # Arguments
 ///
-/// * `img` - A reference to the input image (`RgbaImage`) to encode.
+/// * `img` - A reference to the input RGBA image to encode.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
 ///
@@ -86,38 +457,19 @@ pub fn encode_texture(img: &DynamicImage, format: &TextureFormat) -> Result<Pica
 /// let encoded = encode_rgba8888(&img, 128, 128);
 /// assert_eq!(encoded.len(), 128 * 128 * 4);
 /// ```
-pub fn encode_rgba8888(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
-    println!("Encoding as RGBA8888");
-
-    let mut output: Vec<u8> = Vec::with_capacity(width as usize * height as usize * 4);
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for &px in SWIZZLE_LUT.iter() {
-
-                let x = px & 7;
-                let y = (px >> 3) & 7;
-
-                let img_x = tx + x;
-                let img_y = ty + y;
-
-                if img_x >= width || img_y >= height {
-                    continue;
-                }
+pub fn encode_rgba8888<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32) -> Vec<u8> {
+    encode_packed(img, width, height, TextureLayout::Tiled, pack_rgba8888)
+}
 
-                let pixel = img.get_pixel(img_x, img_y);
-                output.extend([pixel[3], pixel[2], pixel[1], pixel[0]]);
-            }
-        }
-    }
-    output
+fn pack_rgba8888(pixel: &Rgba<u8>) -> [u8; 4] {
+    [pixel[3], pixel[2], pixel[1], pixel[0]]
 }
 
 /// Encodes an RGBA image as RGB888 PICA texture data.
 ///
 /// # Arguments
 ///
-/// * `img` - A reference to the input image (`RgbaImage`) to encode.
+/// * `img` - A reference to the input RGBA image to encode.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
 ///
@@ -134,38 +486,19 @@ pub fn encode_rgba8888(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
 /// let encoded = encode_rgb888(&img, 128, 128);
 /// assert_eq!(encoded.len(), 128 * 128 * 3);
 /// ```
-pub fn encode_rgb888(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
-    println!("Encoding as RGB888");
-
-    let mut output: Vec<u8> = Vec::with_capacity(width as usize * height as usize * 3);
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for &px in SWIZZLE_LUT.iter() {
-
-                let x = px & 7;
-                let y = (px >> 3) & 7;
-
-                let img_x = tx + x;
-                let img_y = ty + y;
-
-                if img_x >= width || img_y >= height {
-                    continue;
-                }
+pub fn encode_rgb888<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32) -> Vec<u8> {
+    encode_packed(img, width, height, TextureLayout::Tiled, pack_rgb888)
+}
 
-                let pixel = img.get_pixel(img_x, img_y);
-                output.extend([pixel[2], pixel[1], pixel[0]]);
-            }
-        }
-    }
-    output
+fn pack_rgb888(pixel: &Rgba<u8>) -> [u8; 3] {
+    [pixel[2], pixel[1], pixel[0]]
 }
 
 /// Encodes an RGBA image as RGBA5551 PICA texture data.
 ///
 /// # Arguments
 ///
-/// * `img` - A reference to the input image (`RgbaImage`) to encode.
+/// * `img` - A reference to the input RGBA image to encode.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
 ///
@@ -173,54 +506,54 @@ pub fn encode_rgb888(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
 ///
 /// A `Vec<u8>` containing the encoded RGBA5551 data.
 ///
+/// Each pixel is a little-endian `u16` with red in bits 15-11, green in
+/// bits 10-6, blue in bits 5-1 and alpha in bit 0.
+///
 /// # Example
 ///
 /// ```rust
-/// # use image::RgbaImage;
+/// # use image::{Rgba, RgbaImage};
 /// # use pica_convert::pica_texture::encode::encode_rgba5551;
+/// # use pica_convert::pica_texture::{decode_raw, DecodeOptions, TextureFormat};
 /// let img = RgbaImage::new(128, 128);
-/// let encoded = encode_rgb5551(&img, 128, 128);
+/// let encoded = encode_rgba5551(&img, 128, 128);
 /// assert_eq!(encoded.len(), 128 * 128 * 2);
+///
+/// // The bit layout, one solid 8x8 tile per color
+/// for (color, bytes) in [
+///     ([255, 0, 0, 255], [0x01, 0xF8]),
+///     ([0, 255, 0, 255], [0xC1, 0x07]),
+///     ([0, 0, 255, 255], [0x3F, 0x00]),
+///     ([255, 255, 255, 0], [0xFE, 0xFF]),
+/// ] {
+///     let img = RgbaImage::from_pixel(8, 8, Rgba(color));
+///     let encoded = encode_rgba5551(&img, 8, 8);
+///     assert_eq!(encoded, bytes.repeat(64));
+///
+///     // Every channel survives the round trip
+///     let decoded = decode_raw(&encoded, 8, 8, &TextureFormat::RGBA5551, &DecodeOptions::default()).unwrap();
+///     assert_eq!(decoded.to_rgba8(), img);
+/// }
 /// ```
-pub fn encode_rgba5551(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
-    println!("Encoding as RGBA5551");
-
-    let mut output: Vec<u8> = Vec::with_capacity(width as usize * height as usize * 2);
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for &px in SWIZZLE_LUT.iter() {
-
-                let x = px & 7;
-                let y = (px >> 3) & 7;
-
-                let img_x = tx + x;
-                let img_y = ty + y;
-
-                if img_x >= width || img_y >= height {
-                    continue;
-                }
-
-                let pixel = img.get_pixel(img_x, img_y);
+pub fn encode_rgba5551<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32) -> Vec<u8> {
+    encode_packed(img, width, height, TextureLayout::Tiled, pack_rgba5551)
+}
 
-                let r = (pixel[0] >> 3) as u16;
-                let g = (pixel[1] >> 3) as u16;
-                let b = (pixel[2] >> 3) as u16;
-                let a = if pixel[3] > 127 { 1 } else { 0 } as u16;
-                let value = (r << 11) | (g << 6) | (b << 1) | a;
+fn pack_rgba5551(pixel: &Rgba<u8>) -> [u8; 2] {
+    let r = (pixel[0] >> 3) as u16;
+    let g = (pixel[1] >> 3) as u16;
+    let b = (pixel[2] >> 3) as u16;
+    let a = if pixel[3] > 127 { 1 } else { 0 } as u16;
+    let value = (r << 11) | (g << 6) | (b << 1) | a;
 
-                output.extend([(value & 0xFF) as u8, (value >> 8) as u8]);
-            }
-        }
-    }
-    output
+    value.to_le_bytes()
 }
 
 /// Encodes an RGBA image as RGB565 PICA texture data.
 ///
 /// # Arguments
 ///
-/// * `img` - A reference to the input image (`RgbaImage`) to encode.
+/// * `img` - A reference to the input RGBA image to encode.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
 ///
@@ -228,53 +561,53 @@ pub fn encode_rgba5551(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
 ///
 /// A `Vec<u8>` containing the encoded RGB565 data.
 ///
+/// Each pixel is a little-endian `u16` with red in bits 15-11, green in
+/// bits 10-5 and blue in bits 4-0.
+///
 /// # Example
 ///
 /// ```rust
-/// # use image::RgbaImage;
+/// # use image::{Rgba, RgbaImage};
 /// # use pica_convert::pica_texture::encode::encode_rgb565;
+/// # use pica_convert::pica_texture::{decode_raw, DecodeOptions, TextureFormat};
 /// let img = RgbaImage::new(128, 128);
 /// let encoded = encode_rgb565(&img, 128, 128);
 /// assert_eq!(encoded.len(), 128 * 128 * 2);
+///
+/// // The bit layout, one solid 8x8 tile per color
+/// for (color, bytes) in [
+///     ([255, 0, 0, 255], [0x00, 0xF8]),
+///     ([0, 255, 0, 255], [0xE0, 0x07]),
+///     ([0, 0, 255, 255], [0x1F, 0x00]),
+/// ] {
+///     let img = RgbaImage::from_pixel(8, 8, Rgba(color));
+///     assert_eq!(encode_rgb565(&img, 8, 8), bytes.repeat(64));
+/// }
+///
+/// // Distinct values in every channel come back where they started
+/// let color = Rgba([0xF8, 0x84, 0x10, 0xFF]);
+/// let img = RgbaImage::from_pixel(8, 8, color);
+/// let decoded = decode_raw(&encode_rgb565(&img, 8, 8), 8, 8, &TextureFormat::RGB565, &DecodeOptions::default()).unwrap();
+/// assert_eq!(decoded.to_rgba8().get_pixel(3, 5), &Rgba([0xFF, 0x86, 0x10, 0xFF]));
 /// ```
-pub fn encode_rgb565(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
-    println!("Encoding as RGB565");
-
-    let mut output: Vec<u8> = Vec::with_capacity(width as usize * height as usize * 2);
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for &px in SWIZZLE_LUT.iter() {
-
-                let x = px & 7;
-                let y = (px >> 3) & 7;
-
-                let img_x = tx + x;
-                let img_y = ty + y;
-
-                if img_x >= width || img_y >= height {
-                    continue;
-                }
-
-                let pixel = img.get_pixel(img_x, img_y);
+pub fn encode_rgb565<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32) -> Vec<u8> {
+    encode_packed(img, width, height, TextureLayout::Tiled, pack_rgb565)
+}
 
-                let r = (pixel[0] >> 3) as u16;
-                let g = (pixel[1] >> 2) as u16;
-                let b = (pixel[2] >> 3) as u16;
-                let value = (r << 11) | (g << 5) | b;
+fn pack_rgb565(pixel: &Rgba<u8>) -> [u8; 2] {
+    let r = (pixel[0] >> 3) as u16;
+    let g = (pixel[1] >> 2) as u16;
+    let b = (pixel[2] >> 3) as u16;
+    let value = (r << 11) | (g << 5) | b;
 
-                output.extend([(value & 0xFF) as u8, (value >> 8) as u8]);
-            }
-        }
-    }
-    output
+    value.to_le_bytes()
 }
 
 /// Encodes an RGBA image as RGBA4444 PICA texture data.
 ///
 /// # Arguments
 ///
-/// * `img` - A reference to the input image (`RgbaImage`) to encode.
+/// * `img` - A reference to the input RGBA image to encode.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
 ///
@@ -282,54 +615,51 @@ pub fn encode_rgb565(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
 ///
 /// A `Vec<u8>` containing the encoded RGBA4444 data.
 ///
+/// Each pixel is a little-endian `u16` with red in bits 15-12, green in
+/// bits 11-8, blue in bits 7-4 and alpha in bits 3-0.
+///
 /// # Example
 ///
 /// ```rust
-/// # use image::RgbaImage;
+/// # use image::{Rgba, RgbaImage};
 /// # use pica_convert::pica_texture::encode::encode_rgba4444;
+/// # use pica_convert::pica_texture::{decode_raw, DecodeOptions, TextureFormat};
 /// let img = RgbaImage::new(128, 128);
 /// let encoded = encode_rgba4444(&img, 128, 128);
 /// assert_eq!(encoded.len(), 128 * 128 * 2);
+///
+/// // The nibble order, with a different value in every channel
+/// let img = RgbaImage::from_pixel(8, 8, Rgba([0x10, 0x20, 0x30, 0x40]));
+/// let encoded = encode_rgba4444(&img, 8, 8);
+/// assert_eq!(encoded, [0x34, 0x12].repeat(64));
+///
+/// let decoded = decode_raw(&encoded, 8, 8, &TextureFormat::RGBA4444, &DecodeOptions::default()).unwrap();
+/// assert_eq!(decoded.to_rgba8(), RgbaImage::from_pixel(8, 8, Rgba([0x11, 0x22, 0x33, 0x44])));
 /// ```
-pub fn encode_rgba4444(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
-    println!("Encoding as RGBA4444");
-
-    let mut output: Vec<u8> = Vec::with_capacity(width as usize * height as usize * 2);
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for &px in SWIZZLE_LUT.iter() {
-
-                let x = px & 7;
-                let y = (px >> 3) & 7;
-
-                let img_x = tx + x;
-                let img_y = ty + y;
-
-                if img_x >= width || img_y >= height {
-                    continue;
-                }
-
-                let pixel = img.get_pixel(img_x, img_y);
+pub fn encode_rgba4444<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32) -> Vec<u8> {
+    encode_packed(img, width, height, TextureLayout::Tiled, pack_rgba4444)
+}
 
-                let r = (pixel[0] >> 4) as u16;
-                let g = (pixel[1] >> 4) as u16;
-                let b = (pixel[2] >> 4) as u16;
-                let a = (pixel[3] >> 4) as u16;
-                let value = (r << 12) | (g << 8) | (b << 4) | a;
+fn pack_rgba4444(pixel: &Rgba<u8>) -> [u8; 2] {
+    let r = (pixel[0] >> 4) as u16;
+    let g = (pixel[1] >> 4) as u16;
+    let b = (pixel[2] >> 4) as u16;
+    let a = (pixel[3] >> 4) as u16;
+    let value = (r << 12) | (g << 8) | (b << 4) | a;
 
-                output.extend([(value & 0xFF) as u8, (value >> 8) as u8]);
-            }
-        }
-    }
-    output
+    value.to_le_bytes()
 }
 
 /// Encodes an RGBA image as LA88 PICA texture data.
 ///
+/// Each texel is a little-endian 16-bit value with luminance in the high
+/// byte, so alpha is stored first. This is the order Citra reads `IA8`
+/// texels in; `tests/texel_order.rs` checks this and the other uncompressed
+/// formats against its texel decoding.
+///
 /// # Arguments
 ///
-/// * `img` - A reference to the input image (`RgbaImage`) to encode.
+/// * `img` - A reference to the input RGBA image to encode.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
 ///
@@ -345,47 +675,39 @@ pub fn encode_rgba4444(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
 /// let img = RgbaImage::new(128, 128);
 /// let encoded = encode_la88(&img, 128, 128);
 /// assert_eq!(encoded.len(), 128 * 128 * 2);
+///
+/// // Luminance is the average of red, green and blue
+/// # use image::Rgba;
+/// # use pica_convert::pica_texture::decode::decode_la88;
+/// let img = RgbaImage::from_pixel(8, 8, Rgba([90, 120, 150, 200]));
+/// let encoded = encode_la88(&img, 8, 8);
+/// assert_eq!(encoded, [200, 120].repeat(64));
+/// assert_eq!(decode_la88(&encoded, 8, 8), [120, 120, 120, 200].repeat(64));
 /// ```
-pub fn encode_la88(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
-    println!("Encoding as LA88");
-
-    let mut output: Vec<u8> = Vec::with_capacity(width as usize * height as usize * 2);
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for &px in SWIZZLE_LUT.iter() {
-
-                let x = px & 7;
-                let y = (px >> 3) & 7;
-
-                let img_x = tx + x;
-                let img_y = ty + y;
-
-                if img_x >= width || img_y >= height {
-                    continue;
-                }
-
-                let pixel = img.get_pixel(img_x, img_y);
+pub fn encode_la88<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32) -> Vec<u8> {
+    encode_packed(img, width, height, TextureLayout::Tiled, pack_la88)
+}
 
-                let r = pixel[0] as u32;
-                let g = pixel[1] as u32;
-                let b = pixel[2] as u32;
-                let a = pixel[3];
+fn pack_la88(pixel: &Rgba<u8>) -> [u8; 2] {
+    let r = pixel[0] as u32;
+    let g = pixel[1] as u32;
+    let b = pixel[2] as u32;
+    let a = pixel[3];
 
-                let l = ((r + g + b) / 3) as u8;
+    let l = ((r + g + b) / 3) as u8;
 
-                output.extend([a, l]);
-            }
-        }
-    }
-    output
+    [a, l]
 }
 
 /// Encodes an RGBA image as HL8 PICA texture data.
 ///
+/// Each texel is a little-endian 16-bit value with red in the high byte and
+/// green in the low byte, so green is stored first, as Citra reads `RG8`
+/// texels.
+///
 /// # Arguments
 ///
-/// * `img` - A reference to the input image (`RgbaImage`) to encode.
+/// * `img` - A reference to the input RGBA image to encode.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
 ///
@@ -401,42 +723,30 @@ pub fn encode_la88(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
 /// let img = RgbaImage::new(128, 128);
 /// let encoded = encode_hl8(&img, 128, 128);
 /// assert_eq!(encoded.len(), 128 * 128 * 2);
+///
+/// # use image::Rgba;
+/// # use pica_convert::pica_texture::decode::decode_hl8;
+/// let img = RgbaImage::from_pixel(8, 8, Rgba([0x12, 0x34, 0x56, 0x78]));
+/// let encoded = encode_hl8(&img, 8, 8);
+/// assert_eq!(encoded, [0x34, 0x12].repeat(64));
+/// assert_eq!(decode_hl8(&encoded, 8, 8), [0x12, 0x34, 0x00, 0xFF].repeat(64));
 /// ```
-pub fn encode_hl8(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
-    println!("Encoding as HL8");
-
-    let mut output: Vec<u8> = Vec::with_capacity(width as usize * height as usize * 2);
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for &px in SWIZZLE_LUT.iter() {
-
-                let x = px & 7;
-                let y = (px >> 3) & 7;
-
-                let img_x = tx + x;
-                let img_y = ty + y;
-
-                if img_x >= width || img_y >= height {
-                    continue;
-                }
+pub fn encode_hl8<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32) -> Vec<u8> {
+    encode_packed(img, width, height, TextureLayout::Tiled, pack_hl8)
+}
 
-                let pixel = img.get_pixel(img_x, img_y);
+fn pack_hl8(pixel: &Rgba<u8>) -> [u8; 2] {
+    let l = pixel[0];
+    let h = pixel[1];
 
-                let l = pixel[0];
-                let h = pixel[1];
-                output.extend([h, l]);
-            }
-        }
-    }
-    output
+    [h, l]
 }
 
 /// Encodes an RGBA image as L8 PICA texture data.
 ///
 /// # Arguments
 ///
-/// * `img` - A reference to the input image (`RgbaImage`) to encode.
+/// * `img` - A reference to the input RGBA image to encode.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
 ///
@@ -452,46 +762,35 @@ pub fn encode_hl8(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
 /// let img = RgbaImage::new(128, 128);
 /// let encoded = encode_l8(&img, 128, 128);
 /// assert_eq!(encoded.len(), 128 * 128);
+///
+/// # use image::Rgba;
+/// # use pica_convert::pica_texture::decode::decode_l8;
+/// let img = RgbaImage::from_pixel(8, 8, Rgba([30, 60, 90, 10]));
+/// let encoded = encode_l8(&img, 8, 8);
+/// assert_eq!(encoded, [60; 64]);
+/// assert_eq!(decode_l8(&encoded, 8, 8), [60, 60, 60, 255].repeat(64));
+///
+/// // Texels within each 8x8 tile are in Morton order
+/// let img = RgbaImage::from_fn(8, 8, |x, y| { let l = (y * 8 + x) as u8; Rgba([l, l, l, 255]) });
+/// assert_eq!(encode_l8(&img, 8, 8)[..16], [0, 1, 8, 9, 2, 3, 10, 11, 16, 17, 24, 25, 18, 19, 26, 27]);
 /// ```
-pub fn encode_l8(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
-    println!("Encoding as L8");
-
-    let mut output: Vec<u8> = Vec::with_capacity(width as usize * height as usize);
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for &px in SWIZZLE_LUT.iter() {
-
-                let x = px & 7;
-                let y = (px >> 3) & 7;
-
-                let img_x = tx + x;
-                let img_y = ty + y;
-
-                if img_x >= width || img_y >= height {
-                    continue;
-                }
-
-                let pixel = img.get_pixel(img_x, img_y);
-
-                let r = pixel[0] as u32;
-                let g = pixel[1] as u32;
-                let b = pixel[2] as u32;
+pub fn encode_l8<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32) -> Vec<u8> {
+    encode_packed(img, width, height, TextureLayout::Tiled, pack_l8)
+}
 
-                let l = ((r + g + b) / 3) as u8;
+fn pack_l8(pixel: &Rgba<u8>) -> [u8; 1] {
+    let r = pixel[0] as u32;
+    let g = pixel[1] as u32;
+    let b = pixel[2] as u32;
 
-                output.extend([l]);
-            }
-        }
-    }
-    output
+    [((r + g + b) / 3) as u8]
 }
 
 /// Encodes an RGBA image as A8 PICA texture data.
 ///
 /// # Arguments
 ///
-/// * `img` - A reference to the input image (`RgbaImage`) to encode.
+/// * `img` - A reference to the input RGBA image to encode.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
 ///
@@ -507,41 +806,27 @@ pub fn encode_l8(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
 /// let img = RgbaImage::new(128, 128);
 /// let encoded = encode_a8(&img, 128, 128);
 /// assert_eq!(encoded.len(), 128 * 128);
+///
+/// # use image::Rgba;
+/// # use pica_convert::pica_texture::decode::decode_a8;
+/// let img = RgbaImage::from_pixel(8, 8, Rgba([30, 60, 90, 10]));
+/// let encoded = encode_a8(&img, 8, 8);
+/// assert_eq!(encoded, [10; 64]);
+/// assert_eq!(decode_a8(&encoded, 8, 8), [255, 255, 255, 10].repeat(64));
 /// ```
-pub fn encode_a8(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
-    println!("Encoding as A8");
-
-    let mut output: Vec<u8> = Vec::with_capacity(width as usize * height as usize);
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for &px in SWIZZLE_LUT.iter() {
-
-                let x = px & 7;
-                let y = (px >> 3) & 7;
-
-                let img_x = tx + x;
-                let img_y = ty + y;
-
-                if img_x >= width || img_y >= height {
-                    continue;
-                }
-
-                let pixel = img.get_pixel(img_x, img_y);
+pub fn encode_a8<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32) -> Vec<u8> {
+    encode_packed(img, width, height, TextureLayout::Tiled, pack_a8)
+}
 
-                let a = pixel[3];
-                output.extend([a]);
-            }
-        }
-    }
-    output
+fn pack_a8(pixel: &Rgba<u8>) -> [u8; 1] {
+    [pixel[3]]
 }
 
 /// Encodes an RGBA image as LA44 PICA texture data.
 ///
 /// # Arguments
 ///
-/// * `img` - A reference to the input image (`RgbaImage`) to encode.
+/// * `img` - A reference to the input RGBA image to encode.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
 ///
@@ -558,46 +843,26 @@ pub fn encode_a8(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
 /// let encoded = encode_la44(&img, 128, 128);
 /// assert_eq!(encoded.len(), 128 * 128);
 /// ```
-pub fn encode_la44(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
-    println!("Encoding as LA44");
-
-    let mut output: Vec<u8> = Vec::with_capacity(width as usize * height as usize);
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for &px in SWIZZLE_LUT.iter() {
-
-                let x = px & 7;
-                let y = (px >> 3) & 7;
-
-                let img_x = tx + x;
-                let img_y = ty + y;
-
-                if img_x >= width || img_y >= height {
-                    continue;
-                }
-
-                let pixel = img.get_pixel(img_x, img_y);
+pub fn encode_la44<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32) -> Vec<u8> {
+    encode_packed(img, width, height, TextureLayout::Tiled, pack_la44)
+}
 
-                let r = pixel[0] as u32;
-                let g = pixel[1] as u32;
-                let b = pixel[2] as u32;
+fn pack_la44(pixel: &Rgba<u8>) -> [u8; 1] {
+    let r = pixel[0] as u32;
+    let g = pixel[1] as u32;
+    let b = pixel[2] as u32;
 
-                let l = (((r + g + b) / 3) >> 4) as u8;
-                let a = pixel.0[3] >> 4;
+    let l = (((r + g + b) / 3) >> 4) as u8;
+    let a = pixel[3] >> 4;
 
-                output.extend([(l << 4) | a]);
-            }
-        }
-    }
-    output
+    [(l << 4) | a]
 }
 
 /// Encodes an RGBA image as L4 PICA texture data.
 ///
 /// # Arguments
 ///
-/// * `img` - A reference to the input image (`RgbaImage`) to encode.
+/// * `img` - A reference to the input RGBA image to encode.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
 ///
@@ -612,55 +877,35 @@ pub fn encode_la44(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
 /// # use pica_convert::pica_texture::encode::encode_l4;
 /// let img = RgbaImage::new(128, 128);
 /// let encoded = encode_l4(&img, 128, 128);
-/// assert_eq!(encoded.len(), 128 * 128);
+/// assert_eq!(encoded.len(), 128 * 128 / 2);
+///
+/// // Each pixel's luminance lands in its tiled nibble
+/// # use image::Rgba;
+/// # use pica_convert::pica_texture::util::unswizzle_4bpp;
+/// let img = RgbaImage::from_fn(24, 16, |x, y| Rgba([x as u8 * 10, y as u8 * 15, (x * y) as u8, 255]));
+/// let linear = unswizzle_4bpp(&encode_l4(&img, 24, 16), 24, 16);
+/// for (i, pixel) in img.pixels().enumerate() {
+///     let l = ((pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 3) as u8 >> 4;
+///     assert_eq!((linear[i / 2] >> (i % 2 * 4)) & 0xF, l);
+/// }
 /// ```
-pub fn encode_l4(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
-    println!("Encoding as L4");
-
-    let mut output: Vec<u8> = vec![0; width as usize * height as usize];
-
-    let mut dst_index = 0;
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for &px in SWIZZLE_LUT.iter() {
-
-                let x = px & 7;
-                let y = (px >> 3) & 7;
-
-                let img_x = tx + x;
-                let img_y = ty + y;
-
-                if img_x >= width || img_y >= height {
-                    continue;
-                }
-
-                let pixel = img.get_pixel(img_x, img_y);
-
-                let r = pixel[0] as u32;
-                let g = pixel[1] as u32;
-                let b = pixel[2] as u32;
-
-                let l = (((r + g + b) / 3) >> 4) as u8;
-
-                let byte_index = dst_index >> 1;
-                let shift = (dst_index & 1) << 2;
+pub fn encode_l4<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32) -> Vec<u8> {
+    encode_packed_4bpp(img, width, height, TextureLayout::Tiled, pack_l4)
+}
 
-                output[byte_index] &= !(0xF << shift);
-                output[byte_index] |= (l & 0xF) << shift;
+fn pack_l4(pixel: &Rgba<u8>) -> u8 {
+    let r = pixel[0] as u32;
+    let g = pixel[1] as u32;
+    let b = pixel[2] as u32;
 
-                dst_index += 1;
-            }
-        }
-    }
-    output
+    (((r + g + b) / 3) >> 4) as u8
 }
 
 /// Encodes an RGBA image as A4 PICA texture data.
 ///
 /// # Arguments
 ///
-/// * `img` - A reference to the input image (`RgbaImage`) to encode.
+/// * `img` - A reference to the input RGBA image to encode.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
 ///
@@ -675,130 +920,205 @@ pub fn encode_l4(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
 /// # use pica_convert::pica_texture::encode::encode_a4;
 /// let img = RgbaImage::new(128, 128);
 /// let encoded = encode_a4(&img, 128, 128);
-/// assert_eq!(encoded.len(), 128 * 128);
+/// assert_eq!(encoded.len(), 128 * 128 / 2);
 /// ```
-pub fn encode_a4(img: &RgbaImage, width: u32, height: u32) -> Vec<u8> {
-    println!("Encoding as A4");
-
-    let mut output: Vec<u8> = vec![0; width as usize * height as usize];
-
-    let mut dst_index = 0;
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for px in SWIZZLE_LUT {
-
-                let x = px & 7;
-                let y = (px >> 3) & 7;
-
-                let img_x = tx + x;
-                let img_y = ty + y;
-
-                if img_x >= width || img_y >= height {
-                    continue;
-                }
-
-                let pixel = img.get_pixel(img_x, img_y);
-
-                let a = pixel[3] >> 4;
-
-                let byte_index = dst_index >> 1;
-                let shift = (dst_index & 1) << 2;
-
-                output[byte_index] &= !(0xF << shift);
-                output[byte_index] |= (a & 0xF) << shift;
+pub fn encode_a4<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32) -> Vec<u8> {
+    encode_packed_4bpp(img, width, height, TextureLayout::Tiled, pack_a4)
+}
 
-                dst_index += 1;
-            }
-        }
-    }
-    output
+fn pack_a4(pixel: &Rgba<u8>) -> u8 {
+    pixel[3] >> 4
 }
 
 
 /// Encodes an RGBA image as ETC1 PICA texture data.
 ///
+/// Any size is accepted, down to the hardware minimum of 4x4 and below. Like
+/// every format, the data is padded to whole 8x8 tiles, so a 4x4 texture
+/// still stores all four of its tile's blocks; pixels past the right and
+/// bottom edges repeat the nearest edge pixel rather than black, so the
+/// padding doesn't bleed dark colors into the edge blocks.
+///
 /// # Arguments
 ///
-/// * `img` - A reference to the input image (`RgbaImage`) to encode.
+/// * `img` - A reference to the input RGBA image to encode.
 /// * `width` - The width of the image in pixels.
 /// * `height` - The height of the image in pixels.
 /// * `has_alpha` - Determines whether to encode as ETC1 or ETC1A4.
+/// * `options` - The [`EncodeOptions`] supplying the ETC1 backend, quality and dithering settings.
 ///
 /// # Returns
 ///
 /// A `Vec<u8>` containing the encoded ETC1 data.
 ///
+/// # Errors
+///
+/// Returns [`TextureError::Etc1BackendUnavailable`] if this build doesn't
+/// have [`EncodeOptions::etc1_backend`].
+///
 /// # Example
 ///
 /// ```rust
 /// # use image::RgbaImage;
-/// # use pica_convert::pica_texture::encode::encode_etc1;
+/// # use pica_convert::pica_texture::{encode::encode_etc1, EncodeOptions};
 /// let img = RgbaImage::new(128, 128);
-/// let encoded = encode_etc1(&img, 128, 128, false);
-/// assert_eq!(encoded.len(), 128 * 128);
+/// let encoded = encode_etc1(&img, 128, 128, false, &EncodeOptions::default()).unwrap();
+/// assert_eq!(encoded.len(), 128 * 128 / 2);
+///
+/// // A backend this build doesn't have is an error, not empty data
+/// # use pica_convert::pica_texture::etc1::Backend;
+/// for etc1_backend in Backend::ALL.into_iter().filter(|backend| !backend.is_available()) {
+///     assert!(encode_etc1(&img, 128, 128, false, &EncodeOptions { etc1_backend, ..Default::default() }).is_err());
+/// }
+///
+/// // ETC1 stores 8 bytes per 4x4 block and ETC1A4 16, for every block of
+/// // every whole 8x8 tile, including tiles the image only partly covers
+/// # use pica_convert::pica_texture::{decode_raw, DecodeOptions, TextureFormat};
+/// for (width, height) in [(8, 8), (16, 8), (64, 32), (4, 4), (4, 8), (8, 4), (12, 12), (1, 1), (12, 20), (72, 40), (9, 17)] {
+///     let img = RgbaImage::new(width, height);
+///     let tiles = (width.div_ceil(8) * height.div_ceil(8)) as usize;
+///
+///     for (has_alpha, format, tile_bytes) in [(false, TextureFormat::ETC1, 32), (true, TextureFormat::ETC1A4, 64)] {
+///         let encoded = encode_etc1(&img, width, height, has_alpha, &EncodeOptions::default()).unwrap();
+///         assert_eq!(encoded.len(), tiles * tile_bytes, "{} at {}x{}", format, width, height);
+///         assert_eq!(encoded.len(), format.expected_data_len(width, height));
+///
+///         let decoded = decode_raw(&encoded, width, height, &format, &DecodeOptions::default()).unwrap();
+///         assert_eq!((decoded.width(), decoded.height()), (width, height));
+///     }
+/// }
 /// ```
-pub fn encode_etc1(img: &RgbaImage, width: u32, height: u32, has_alpha: bool) -> Vec<u8> {
-    let blocks_x = width.div_ceil(4);
-    let blocks_y = height.div_ceil(4);
-    let num_blocks = blocks_x * blocks_y;
-
-    let bytes_per_block = if has_alpha { 16 } else { 8 };
-    let mut output = Vec::with_capacity((num_blocks * bytes_per_block) as usize);
-
-    let raw_pixels = img.as_raw();
-
-    for ty in (0..height).step_by(8) {
-        for tx in (0..width).step_by(8) {
-            for t in 0..4 {
-                let mut block_rgba = [0; 64];
-                let mut alpha_block: u64 = 0;
-
-                for i in 0..16 {
-                    let px = XT[t] + (i % 4);
-                    let py = YT[t] + (i / 4);
-                    let dst_x = tx + px;
-                    let dst_y = ty + py;
+///
+/// Padding repeats the edge pixels, so small textures round trip without
+/// dark fringes and a solid 4x4 texture stores four identical blocks:
+///
+/// ```rust
+/// # use image::{Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{encode::encode_etc1, decode_raw, DecodeOptions, EncodeOptions, TextureFormat};
+/// for (width, height) in [(4, 4), (4, 8), (8, 4), (12, 12)] {
+///     let img = RgbaImage::from_fn(width, height, |x, y| Rgba([200 + (x * 4) as u8, 220, 200 + (y * 4) as u8, 255]));
+///     let encoded = encode_etc1(&img, width, height, false, &EncodeOptions::default()).unwrap();
+///     let decoded = decode_raw(&encoded, width, height, &TextureFormat::ETC1, &DecodeOptions::default()).unwrap().to_rgba8();
+///
+///     for (x, y, pixel) in decoded.enumerate_pixels() {
+///         let source = img.get_pixel(x, y);
+///         assert!(pixel.0.iter().zip(source.0).all(|(&a, b)| a.abs_diff(b) <= 12), "{}x{} at ({}, {}): {:?}", width, height, x, y, pixel);
+///     }
+/// }
+///
+/// let solid = RgbaImage::from_pixel(4, 4, Rgba([10, 120, 250, 255]));
+/// let encoded = encode_etc1(&solid, 4, 4, false, &EncodeOptions::default()).unwrap();
+/// assert!(encoded.chunks(8).all(|block| block == &encoded[..8]));
+/// ```
+///
+/// Tiles are compressed in parallel with the `parallel` feature, with the
+/// same output as compressing them one at a time:
+///
+/// ```rust
+/// # use image::{Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{encode::encode_etc1, etc1, EncodeOptions};
+/// # #[cfg(feature = "parallel")] {
+/// let img = RgbaImage::from_fn(72, 40, |x, y| Rgba([(x * 7) as u8, (y * 11) as u8, (x ^ y) as u8, (x * y) as u8]));
+/// let options = EncodeOptions { etc1_quality: etc1::quality::LOW, ..Default::default() };
+///
+/// let serial = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+/// let expected = serial.install(|| encode_etc1(&img, 72, 40, true, &options)).unwrap();
+///
+/// let parallel = rayon::ThreadPoolBuilder::new().num_threads(8).build().unwrap();
+/// for _ in 0..4 {
+///     assert_eq!(parallel.install(|| encode_etc1(&img, 72, 40, true, &options)).unwrap(), expected);
+/// }
+/// # }
+/// ```
+///
+/// The files in `tests/fixtures/etc1` are regression snapshots of this
+/// function's output, written by rg_etc1 on x86-64 Linux, so a platform or
+/// build profile that compresses differently fails here:
+///
+/// ```rust
+/// # use pica_convert::pica_texture::{encode::encode_etc1, etc1, synthetic::synthetic_image, EncodeOptions};
+/// # #[cfg(feature = "etc1")] {
+/// let img = synthetic_image(16, 16, 1);
+/// let fixture = |name: &str| std::fs::read(format!("{}/tests/fixtures/etc1/synthetic_16x16_{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap();
+///
+/// for (name, etc1_quality) in [("low", etc1::quality::LOW), ("medium", etc1::quality::MEDIUM), ("high", etc1::quality::HIGH)] {
+///     let options = EncodeOptions { etc1_quality, ..Default::default() };
+///     assert!(encode_etc1(&img, 16, 16, false, &options).unwrap() == fixture(&format!("{}.etc1", name)), "{} etc1", name);
+///     assert!(encode_etc1(&img, 16, 16, true, &options).unwrap() == fixture(&format!("{}.etc1a4", name)), "{} etc1a4", name);
+/// }
+///
+/// let dithered = EncodeOptions { etc1_quality: etc1::quality::MEDIUM, etc1_dithering: true, ..Default::default() };
+/// assert!(encode_etc1(&img, 16, 16, false, &dithered).unwrap() == fixture("medium_dithered.etc1"));
+/// # }
+/// ```
+#[cfg(any(feature = "etc1", feature = "etc1-rust"))]
+pub fn encode_etc1<C: Deref<Target = [u8]>>(img: &ImageBuffer<Rgba<u8>, C>, width: u32, height: u32, has_alpha: bool, options: &EncodeOptions) -> Result<Vec<u8>, TextureError> {
+    if !options.etc1_backend.is_available() {
+        return Err(TextureError::Etc1BackendUnavailable(options.etc1_backend));
+    }
+    let format = if has_alpha { TextureFormat::ETC1A4 } else { TextureFormat::ETC1 };
+    let tile_bytes = format.expected_data_len(8, 8);
+    let tiles_x = width.div_ceil(8);
+
+    let raw_pixels: &[u8] = img.as_raw();
+
+    // Every tile is stored in full, so each one's place in the output is known
+    // up front and tiles can be compressed in any order
+    let encode_tile = |(tile_index, tile): (usize, &mut [u8])| {
+        let tx = (tile_index as u32 % tiles_x) * 8;
+        let ty = (tile_index as u32 / tiles_x) * 8;
+        let mut dst = 0;
+
+        for t in 0..4 {
+            let mut block_rgba = [0; 64];
+            let mut alpha_block: u64 = 0;
+
+            for i in 0..16 {
+                let px = XT[t] + (i % 4);
+                let py = YT[t] + (i / 4);
+                let dst_x = tx + px;
+                let dst_y = ty + py;
                 
-                    let (r, g, b, a) = if dst_x < width && dst_y < height {
-                        let idx = ((dst_y * width + dst_x) * 4) as usize;
-                        (
-                            raw_pixels[idx    ],
-                            raw_pixels[idx + 1],
-                            raw_pixels[idx + 2],
-                            raw_pixels[idx + 3],
-                        )
-                    } else {
-                        (0, 0, 0, 255)
-                    };
-
-                    let offset = (i * 4) as usize;
-                    block_rgba[offset    ] = r;
-                    block_rgba[offset + 1] = g;
-                    block_rgba[offset + 2] = b;
-                    block_rgba[offset + 3] = a;
-
-                    if has_alpha {
-                        let alpha_shift = ((px & 3) * 4 + (py & 3)) << 2;
-                        alpha_block |= (((a >> 4) & 0xF) as u64) << alpha_shift;
-                    }
-                }
-                let pack_params = Etc1PackParams {
-                    quality: etc1::quality::HIGH,
-                    dithering: 0
-                };
-
-                let compressed_color = compress_block(&block_rgba, Some(pack_params));
+                // Pixels past the edges repeat the nearest edge pixel
+                let idx = ((dst_y.min(height - 1) * width + dst_x.min(width - 1)) * 4) as usize;
+                let (r, g, b, a) = (
+                    raw_pixels[idx    ],
+                    raw_pixels[idx + 1],
+                    raw_pixels[idx + 2],
+                    raw_pixels[idx + 3],
+                );
+
+                let offset = (i * 4) as usize;
+                block_rgba[offset    ] = r;
+                block_rgba[offset + 1] = g;
+                block_rgba[offset + 2] = b;
+                block_rgba[offset + 3] = a;
 
                 if has_alpha {
-                    output.extend_from_slice(&alpha_block.to_le_bytes());
+                    let alpha_shift = ((px & 3) * 4 + (py & 3)) << 2;
+                    alpha_block |= (((a >> 4) & 0xF) as u64) << alpha_shift;
                 }
+            }
+            let compressed_color = options.etc1_backend
+                .compress_block(&block_rgba, options.etc1_quality, options.etc1_dithering)
+                .expect("the backend was checked above");
 
-                let c_block = swap64(compressed_color);
-                output.extend_from_slice(&c_block);
-
+            if has_alpha {
+                tile[dst..dst + 8].copy_from_slice(&alpha_block.to_le_bytes());
+                dst += 8;
             }
+
+            tile[dst..dst + 8].copy_from_slice(&swap64(compressed_color));
+            dst += 8;
         }
-    }
-    output
-}
\ No newline at end of file
+    };
+
+    let mut output = vec![0; format.expected_data_len(width, height)];
+
+    #[cfg(feature = "parallel")]
+    output.par_chunks_mut(tile_bytes).enumerate().for_each(encode_tile);
+    #[cfg(not(feature = "parallel"))]
+    output.chunks_mut(tile_bytes).enumerate().for_each(encode_tile);
+
+    Ok(output)
+}
@@ -0,0 +1,106 @@
+use std::fmt;
+
+use crate::pica_texture::TextureFormat;
+use crate::pica_texture::etc1::{Backend, Etc1Error};
+
+/// Errors produced while building, encoding or decoding PICA textures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextureError {
+    /// The texture data doesn't match the size required by its format and dimensions.
+    DataLengthMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// A texture format name didn't match any known format.
+    UnknownFormat(String),
+    /// A PICA hardware format ID didn't match any known format.
+    UnknownHardwareId(u8),
+    /// The format's codec wasn't compiled into this build (see [`TextureFormat::is_available`]).
+    FormatUnavailable(TextureFormat),
+    /// The requested ETC1 compressor wasn't compiled into this build (see [`Backend::is_available`]).
+    Etc1BackendUnavailable(Backend),
+    /// The format can be read and written, but there's no encoder for it.
+    EncodeUnsupported(TextureFormat),
+    /// The format can be read and written, but there's no decoder for it.
+    DecodeUnsupported(TextureFormat),
+    /// The format's data only comes in the tiled layout (see [`TextureFormat::has_linear_layout`]).
+    LinearUnsupported(TextureFormat),
+    /// The texture's dimensions break a [`DimensionLimits`](crate::pica_texture::DimensionLimits).
+    InvalidDimensions {
+        width: u32,
+        height: u32,
+        reason: String,
+    },
+    /// A texture's mip level count is zero or more than its dimensions allow
+    /// (see [`max_mip_levels`](crate::pica_texture::max_mip_levels)).
+    InvalidMipLevels {
+        levels: u32,
+        max: u32,
+    },
+    /// A pixel was asked for outside the texture's dimensions.
+    PixelOutOfBounds {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// A compressed block couldn't be decoded. `x` and `y` are the pixel
+    /// coordinates of its top left corner.
+    InvalidBlock {
+        x: u32,
+        y: u32,
+        source: Etc1Error,
+    },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::DataLengthMismatch { expected, actual } => {
+                write!(f, "Texture data is {} bytes, expected {}", actual, expected)
+            }
+            TextureError::UnknownFormat(name) => {
+                write!(f, "Unknown TextureFormat: {}", name)
+            }
+            TextureError::UnknownHardwareId(id) => {
+                write!(f, "Unknown hardware texture format ID: {:#04X}", id)
+            }
+            TextureError::FormatUnavailable(format) => {
+                write!(f, "{} support isn't available in this build", format)
+            }
+            TextureError::Etc1BackendUnavailable(backend) => {
+                write!(f, "The {} ETC1 backend isn't available in this build", backend.name())
+            }
+            TextureError::EncodeUnsupported(format) => {
+                write!(f, "{} textures can't be encoded", format)
+            }
+            TextureError::DecodeUnsupported(format) => {
+                write!(f, "{} textures can't be decoded", format)
+            }
+            TextureError::LinearUnsupported(format) => {
+                write!(f, "{} textures can only be stored tiled", format)
+            }
+            TextureError::InvalidDimensions { width, height, reason } => {
+                write!(f, "Texture dimensions {}x{} {}", width, height, reason)
+            }
+            TextureError::InvalidMipLevels { levels, max } => {
+                write!(f, "Texture can't have {} mip levels, expected 1 to {}", levels, max)
+            }
+            TextureError::PixelOutOfBounds { x, y, width, height } => {
+                write!(f, "Pixel ({}, {}) is outside the {}x{} texture", x, y, width, height)
+            }
+            TextureError::InvalidBlock { x, y, source } => {
+                write!(f, "Block at ({}, {}) can't be decoded: {}", x, y, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TextureError::InvalidBlock { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
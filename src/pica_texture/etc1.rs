@@ -1,14 +1,26 @@
-use std::mem::MaybeUninit;
+//! Bindings to the rg_etc1 block compressor, a plain Rust compressor, and an
+//! ETC1 block decoder.
+//!
+//! rg_etc1 is C++ and is only built with the `etc1` feature, so
+//! [`compress_block`] is unavailable without it. [`pack_block`] is built
+//! with the `etc1-rust` feature, and [`Backend`] picks between the two.
+//! [`decompress_block`] is plain Rust and, like [`quality`], is always available.
+
+use std::fmt;
+#[cfg(feature = "etc1")]
 use std::sync::Once;
 
+#[cfg(feature = "etc1")]
 static INIT: Once = Once::new();
 
+#[cfg(feature = "etc1")]
 #[repr(C)]
 pub struct Etc1PackParams {
     pub quality: i32,
     pub dithering: i32,
 }
 
+#[cfg(feature = "etc1")]
 #[link(name = "rg_etc1_wrapper")]
 unsafe extern "C" {
     fn etc1_pack_init();
@@ -16,16 +28,28 @@ unsafe extern "C" {
     fn etc1_decompress_block(etc1_block: *const u8, out_rgba: *mut u32, preserve_alpha: i32) -> i32;
 }
 
+/// The intensity modifiers of the 8 tables each subblock picks one from, in
+/// rg_etc1's selector order.
+const INTENSITY_TABLES: [[i32; 4]; 8] = [
+    [-8, -2, 2, 8], [-17, -5, 5, 17], [-29, -9, 9, 29], [-42, -13, 13, 42],
+    [-60, -18, 18, 60], [-80, -24, 24, 80], [-106, -33, 33, 106], [-183, -47, 47, 183],
+];
+
+/// Maps a pixel's 2-bit index, MSB above LSB, to its column of [`INTENSITY_TABLES`].
+const SELECTOR_INDEX: [usize; 4] = [2, 3, 1, 0];
+
 /// Initializes the ETC1 compressor.
 /// 
 /// Must be called before compressing any blocks.
 /// 
+#[cfg(feature = "etc1")]
 fn init() {
     unsafe { etc1_pack_init() }
 }
 
 /// Ensures that the ETC1 compressor has been initialized.
 /// 
+#[cfg(feature = "etc1")]
 fn ensure_initialized() {
     INIT.call_once(|| {
         init();
@@ -34,6 +58,15 @@ fn ensure_initialized() {
 
 /// Compresses a single 4x4 block of RGBA pixels into an 8-byte ETC1 block.
 ///
+/// This is safe to call from several threads at once. rg_etc1's only global
+/// state is its two lookup tables, which are only written by the one-time
+/// initialization that [`Once`] orders before every compression. Packing
+/// reads them through `const` pointers and keeps everything else on the stack.
+///
+/// rg_etc1 reads each pixel as 4 bytes in R, G, B, A order in memory, so the
+/// pixels are repacked into `u32`s with their native byte order preserved.
+/// `rgba` itself needn't be aligned.
+///
 /// # Arguments
 ///
 /// * `rgba` - A block of 16 8-bit RGBA pixels to be compressed.
@@ -41,13 +74,85 @@ fn ensure_initialized() {
 /// 
 /// # Returns
 /// A `[u8; 8]` containing the compressed block.
-/// 
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::etc1::{compress_block, quality, Etc1PackParams};
+/// let pixels: Vec<u8> = (0..64).map(|i| (i * 37 % 256) as u8).collect();
+/// let params = || Some(Etc1PackParams { quality: quality::MEDIUM, dithering: 0 });
+///
+/// // A block at an odd address compresses just like an aligned one
+/// let mut buffer = vec![0u8; 65];
+/// buffer[1..].copy_from_slice(&pixels);
+/// let misaligned: &[u8; 64] = buffer[1..].try_into().unwrap();
+/// assert_eq!(compress_block(misaligned, params()), compress_block(&pixels.try_into().unwrap(), params()));
+///
+/// // Channels keep their meaning: a pure red block decodes as red
+/// # use pica_convert::pica_texture::etc1::decompress_block;
+/// let red = [255, 0, 0, 255].repeat(16);
+/// let block = compress_block(&red.try_into().unwrap(), params());
+/// let [r, g, b, a] = decompress_block(&block, false).unwrap()[..4] else { unreachable!() };
+/// assert!(r > 240 && g < 16 && b < 16 && a == 255, "{:?}", (r, g, b, a));
+/// ```
+///
+/// Compressing on many threads at once gives the same blocks as compressing
+/// on one:
+///
+/// ```
+/// # use pica_convert::pica_texture::etc1::{compress_block, quality, Etc1PackParams};
+/// # use pica_convert::pica_texture::synthetic::synthetic_image;
+/// let image = synthetic_image(32, 32, 3);
+/// let blocks: Vec<[u8; 64]> = (0..64)
+///     .map(|b| std::array::from_fn(|i| image.get_pixel(b % 8 * 4 + i as u32 / 4 % 4, b / 8 * 4 + i as u32 / 16)[i % 4]))
+///     .collect();
+/// let compress_all = || -> Vec<[u8; 8]> {
+///     [quality::LOW, quality::MEDIUM].into_iter().flat_map(|quality| {
+///         blocks.iter().map(move |block| compress_block(block, Some(Etc1PackParams { quality, dithering: 1 })))
+///     }).collect()
+/// };
+///
+/// let serial = compress_all();
+/// std::thread::scope(|scope| {
+///     let threads: Vec<_> = (0..8).map(|_| scope.spawn(|| (0..2).map(|_| compress_all()).collect::<Vec<_>>())).collect();
+///     for thread in threads {
+///         assert!(thread.join().unwrap().iter().all(|run| *run == serial));
+///     }
+/// });
+/// ```
+///
+/// These bytes are a regression snapshot of rg_etc1's own output, not
+/// blocks from an independent encoder, so any change in it fails here:
+///
+/// ```
+/// # use pica_convert::pica_texture::etc1::{compress_block, quality, Etc1PackParams};
+/// let solid = [96, 160, 32, 255].repeat(16);
+/// let split: Vec<u8> = (0..16).flat_map(|i| if i % 4 < 2 { [255, 0, 0, 255] } else { [0, 0, 255, 255] }).collect();
+/// let ramp: Vec<u8> = (0..16).flat_map(|i| { let v = i * 17; [v, v, v, 255] }).collect();
+///
+/// let golden: [(&[u8], [[u8; 8]; 3]); 3] = [
+///     (&solid, [[0x98, 0xD8, 0x58, 0x92, 0xFF, 0xFF, 0xFF, 0xFF]; 3]),
+///     (&split, [[0xF0, 0x00, 0x0F, 0x00, 0xFF, 0xFF, 0x00, 0x00]; 3]),
+///     (&ramp, [
+///         [0x4C, 0x4C, 0x4C, 0x91, 0x55, 0x55, 0xA0, 0x55],
+///         [0x3C, 0x3C, 0x3C, 0x91, 0x45, 0x55, 0xA2, 0x45],
+///         [0x3C, 0x3C, 0x3C, 0x91, 0x55, 0x5D, 0xA2, 0x45],
+///     ]),
+/// ];
+/// for (pixels, expected) in golden {
+///     for (quality, expected) in [quality::LOW, quality::MEDIUM, quality::HIGH].into_iter().zip(expected) {
+///         let params = Etc1PackParams { quality, dithering: 0 };
+///         assert_eq!(compress_block(pixels.try_into().unwrap(), Some(params)), expected, "quality {}", quality);
+///     }
+/// }
+/// ```
+#[cfg(feature = "etc1")]
 pub fn compress_block(rgba: &[u8; 64], params: Option<Etc1PackParams>) -> [u8; 8] {
     ensure_initialized();
 
     let mut out_block = [0u8; 8];
 
-    let rgba32: &[u32; 16] = unsafe { &*(rgba.as_ptr() as *const [u32; 16]) };
+    let rgba32: [u32; 16] = std::array::from_fn(|i| u32::from_ne_bytes([rgba[i * 4], rgba[i * 4 + 1], rgba[i * 4 + 2], rgba[i * 4 + 3]]));
 
     let param_ptr = match params {
         Some(ref p) => p as *const Etc1PackParams,
@@ -61,35 +166,384 @@ pub fn compress_block(rgba: &[u8; 64], params: Option<Etc1PackParams>) -> [u8; 8
     out_block
 }
 
+/// Maps a column of [`INTENSITY_TABLES`] to the 2-bit index that selects it,
+/// the inverse of [`SELECTOR_INDEX`].
+#[cfg(feature = "etc1-rust")]
+const COLUMN_INDEX: [u64; 4] = [3, 2, 0, 1];
+
+/// The best table for one half of a block: its squared error, table and
+/// the column of that table each of its pixels picks.
+#[cfg(feature = "etc1-rust")]
+struct HalfFit {
+    error: u32,
+    table: u64,
+    columns: [usize; 8],
+}
+
+/// Finds the table, and each pixel's column in it, that best fits `pixels`
+/// around the expanded 8-bit `base` color.
+#[cfg(feature = "etc1-rust")]
+fn fit_half(base: [i32; 3], pixels: &[[i32; 3]; 8]) -> HalfFit {
+    let mut best = HalfFit { error: u32::MAX, table: 0, columns: [0; 8] };
+    for (table, modifiers) in INTENSITY_TABLES.iter().enumerate() {
+        let mut fit = HalfFit { error: 0, table: table as u64, columns: [0; 8] };
+        for (pixel, column) in pixels.iter().zip(fit.columns.iter_mut()) {
+            let (pixel_error, pixel_column) = modifiers.iter().enumerate()
+                .map(|(i, modifier)| {
+                    let error = (0..3).map(|c| ((base[c] + modifier).clamp(0, 255) - pixel[c]).pow(2) as u32).sum::<u32>();
+                    (error, i)
+                })
+                .min()
+                .unwrap();
+            fit.error += pixel_error;
+            *column = pixel_column;
+        }
+        if fit.error < best.error {
+            best = fit;
+        }
+    }
+    best
+}
+
+/// Compresses a single 4x4 block of RGBA pixels into an 8-byte ETC1 block,
+/// in plain Rust, as [`Backend::Rust`].
+///
+/// Each way of splitting the block is tried in both individual and
+/// differential mode. A half's base color starts as its pixels' average,
+/// and `quality` widens the search to base colors up to 0
+/// ([`quality::LOW`]), 1 or 2 ([`quality::HIGH`]) steps brighter or darker.
+/// The block with the least squared error wins. Alpha is ignored, and there
+/// is no dithering.
+///
+/// The block is in the same byte order as [`compress_block`]'s, and
+/// everything is integer math, so output is the same on every platform.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::etc1::{decompress_block, pack_block, quality};
+/// let pixels: Vec<u8> = (0..16u8).flat_map(|i| [100 + i % 4 * 6, 200 - i / 4 * 5, 40, 255]).collect();
+/// for quality in [quality::LOW, quality::MEDIUM, quality::HIGH] {
+///     let block = pack_block(pixels[..].try_into().unwrap(), quality);
+///     let decoded = decompress_block(&block, false).unwrap();
+///     assert!(decoded.iter().zip(&pixels).all(|(a, b)| a.abs_diff(*b) <= 12), "{:?}", decoded);
+/// }
+///
+/// // A solid block comes back within the few steps its 4 or 5-bit color allows
+/// let solid: Vec<u8> = [200, 16, 96, 255].repeat(16);
+/// let decoded = decompress_block(&pack_block(solid[..].try_into().unwrap(), quality::HIGH), false).unwrap();
+/// assert!(decoded.iter().zip(&solid).all(|(a, b)| a.abs_diff(*b) <= 4), "{:?}", decoded);
+/// ```
+#[cfg(feature = "etc1-rust")]
+pub fn pack_block(rgba: &[u8; 64], quality: i32) -> [u8; 8] {
+    let radius = quality.clamp(quality::LOW, quality::HIGH);
+    let pixel = |i: usize| [rgba[i * 4] as i32, rgba[i * 4 + 1] as i32, rgba[i * 4 + 2] as i32];
+
+    let mut best = (u32::MAX, 0u64);
+    for flip in [false, true] {
+        // The pixels of each half, in block order
+        let halves: [[[i32; 3]; 8]; 2] = std::array::from_fn(|half| {
+            let mut pixels = (0..16).filter(|i| (if flip { i / 8 } else { i % 4 / 2 }) == half).map(pixel);
+            std::array::from_fn(|_| pixels.next().unwrap())
+        });
+        let averages = halves.map(|pixels| {
+            let sum = pixels.iter().fold([0; 3], |sum, pixel| std::array::from_fn(|c| sum[c] + pixel[c]));
+            sum.map(|c| (c + 4) / 8)
+        });
+
+        for differential in [false, true] {
+            let (max, expand): (i32, fn(i32) -> i32) = if differential {
+                (31, |c| (c << 3) | (c >> 2))
+            } else {
+                (15, |c| (c << 4) | c)
+            };
+
+            // Each half's best base color within the search radius
+            let mut fits: [(HalfFit, [i32; 3]); 2] = std::array::from_fn(|half| {
+                let quantized = averages[half].map(|c| (c * max + 127) / 255);
+                (-radius..=radius)
+                    .map(|step| {
+                        let color = quantized.map(|c| (c + step).clamp(0, max));
+                        (fit_half(color.map(expand), &halves[half]), color)
+                    })
+                    .min_by_key(|(fit, _)| fit.error)
+                    .unwrap()
+            });
+
+            if differential {
+                // The second color is stored as a 3-bit delta from the first
+                let first = fits[0].1;
+                let second = fits[1].1;
+                let clamped: [i32; 3] = std::array::from_fn(|c| first[c] + (second[c] - first[c]).clamp(-4, 3));
+                if clamped != second {
+                    fits[1] = (fit_half(clamped.map(expand), &halves[1]), clamped);
+                }
+            }
+
+            let error = fits[0].0.error + fits[1].0.error;
+            if error >= best.0 {
+                continue;
+            }
+
+            let [(first_fit, first), (second_fit, second)] = &fits;
+            let mut bits = (flip as u64) << 32 | (differential as u64) << 33 | first_fit.table << 37 | second_fit.table << 34;
+            for (c, offset) in [56, 48, 40].into_iter().enumerate() {
+                bits |= if differential {
+                    (first[c] as u64) << (offset + 3) | ((second[c] - first[c]) as u64 & 7) << offset
+                } else {
+                    (first[c] as u64) << (offset + 4) | (second[c] as u64) << offset
+                };
+            }
+
+            let mut next = [0; 2];
+            for i in 0..16 {
+                let (x, y) = (i % 4, i / 4);
+                let half = if flip { y / 2 } else { x / 2 };
+                let index = COLUMN_INDEX[fits[half].0.columns[next[half]]];
+                next[half] += 1;
+
+                let bit = x * 4 + y;
+                bits |= (index >> 1) << (bit + 16) | (index & 1) << bit;
+            }
+            best = (error, bits);
+        }
+    }
+    best.1.to_be_bytes()
+}
+
+/// Errors from decompressing ETC1 blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Etc1Error {
+    /// A differential mode block whose second base color, the first plus
+    /// its delta, falls outside the 5-bit range. Valid encoders never write one.
+    InvalidBlock([u8; 8]),
+}
+
+impl fmt::Display for Etc1Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Etc1Error::InvalidBlock(block) => {
+                write!(f, "Invalid ETC1 block {:02X?}: its differential color is out of range", block)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Etc1Error {}
+
 /// Decompresses a single 8-byte block of ETC1 data into a 4x4 block of RGBA pixels.
 ///
+/// The block is read as a big-endian 64-bit value, as ETC1 defines it. Each
+/// half of the block, split left/right or top/bottom by its flip bit, has a
+/// base color, 4-bit per channel in individual mode or 5-bit with a 3-bit
+/// delta for the second half in differential mode. Each pixel adds an
+/// intensity modifier from its half's table to the base color, clamped to
+/// 0-255. Pixels are returned in rows, each as R, G, B, A bytes.
+///
 /// # Arguments
 ///
 /// * `block` - The block of ETC1 data to decompress.
-/// * `preserve_alpha` - Determines whether to keep existing alpha values. If false, alpha is set to 255.
+/// * `preserve_alpha` - Whether to leave the alpha bytes alone, in which case they're 0. If false, alpha is set to 255.
 /// 
 /// # Returns
 /// A `[u8; 64]` containing the decompressed RGBA data.
-/// 
-pub fn decompress_block(block: &[u8; 8], preserve_alpha: bool) -> [u8; 64] {
+///
+/// # Errors
+///
+/// Returns [`Etc1Error::InvalidBlock`] for a differential block whose
+/// second base color is out of range, rather than the clamped colors rg_etc1
+/// unpacks it to.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::etc1::{decompress_block, Etc1Error};
+/// // Individual mode: both halves gray (8 << 4 | 8 = 136), table 0, every pixel's index 0 (+2)
+/// let gray = [0x88, 0x88, 0x88, 0x00, 0, 0, 0, 0];
+/// let pixels = decompress_block(&gray, false).unwrap();
+/// assert!(pixels.chunks(4).all(|pixel| pixel == [138, 138, 138, 255]), "{:?}", pixels);
+///
+/// // Alpha isn't stored in the block
+/// let pixels = decompress_block(&gray, true).unwrap();
+/// assert!(pixels.chunks(4).all(|pixel| pixel[3] == 0));
+///
+/// // Flipped, so the bottom half uses the second color: black, with index 3 (-8) clamping at 0
+/// let halves = [0xF0, 0xF0, 0xF0, 0b001, 0xFF, 0xFF, 0xFF, 0xFF];
+/// let pixels = decompress_block(&halves, false).unwrap();
+/// assert_eq!(&pixels[..4], &[247, 247, 247, 255]);
+/// assert_eq!(&pixels[60..], &[0, 0, 0, 255]);
+///
+/// // Differential mode, with a red base of 31 and a delta of +3
+/// let invalid = [31 << 3 | 3, 0, 0, 0b10, 0, 0, 0, 0];
+/// assert_eq!(decompress_block(&invalid, false), Err(Etc1Error::InvalidBlock(invalid)));
+/// ```
+pub fn decompress_block(block: &[u8; 8], preserve_alpha: bool) -> Result<[u8; 64], Etc1Error> {
+    let bits = u64::from_be_bytes(*block);
+    let field = |offset: u32, width: u32| ((bits >> offset) & ((1 << width) - 1)) as i32;
+
+    let flip = field(32, 1) != 0;
+    let differential = field(33, 1) != 0;
+    let tables = [field(37, 3) as usize, field(34, 3) as usize];
+
+    let base_colors = if differential {
+        let first = [field(59, 5), field(51, 5), field(43, 5)];
+        // The deltas are 3-bit two's complement
+        let delta = [field(56, 3), field(48, 3), field(40, 3)].map(|d| if d >= 4 { d - 8 } else { d });
+        let second: [i32; 3] = std::array::from_fn(|i| first[i] + delta[i]);
+        if second.iter().any(|c| !(0..32).contains(c)) {
+            return Err(Etc1Error::InvalidBlock(*block));
+        }
+
+        let expand5 = |c: i32| (c << 3) | (c >> 2);
+        [first.map(expand5), second.map(expand5)]
+    } else {
+        let expand4 = |c: i32| (c << 4) | c;
+        [[field(60, 4), field(52, 4), field(44, 4)].map(expand4), [field(56, 4), field(48, 4), field(40, 4)].map(expand4)]
+    };
+
+    let mut out_bytes = [0u8; 64];
+    for (i, pixel) in out_bytes.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        let half = if flip { y / 2 } else { x / 2 };
+
+        // Indices are stored a column at a time, LSBs in the low 16 bits and MSBs above them
+        let bit = x * 4 + y;
+        let index = (((bits >> (bit + 16)) & 1) << 1 | ((bits >> bit) & 1)) as usize;
+        let modifier = INTENSITY_TABLES[tables[half]][SELECTOR_INDEX[index]];
+
+        for (channel, base) in pixel.iter_mut().zip(base_colors[half]) {
+            *channel = (base + modifier).clamp(0, 255) as u8;
+        }
+        pixel[3] = if preserve_alpha { 0 } else { 255 };
+    }
+    Ok(out_bytes)
+}
+
+/// Decompresses a block with rg_etc1's own decoder, which
+/// [`decompress_block`] stands in for, so tests can check the two agree.
+/// Not part of the public API.
+#[doc(hidden)]
+#[cfg(feature = "etc1")]
+pub fn reference_decompress_block(block: &[u8; 8], preserve_alpha: bool) -> Result<[u8; 64], Etc1Error> {
     ensure_initialized();
 
-    let mut out: [MaybeUninit<u32>; 16] = unsafe { MaybeUninit::uninit().assume_init() };
+    let mut out = [0u32; 16];
 
-    unsafe {
-        etc1_decompress_block(
-            block.as_ptr(),
-            out.as_mut_ptr() as *mut u32,
-            preserve_alpha as i32,
-        );
+    let valid = unsafe {
+        etc1_decompress_block(block.as_ptr(), out.as_mut_ptr(), preserve_alpha as i32)
+    };
+    if valid == 0 {
+        return Err(Etc1Error::InvalidBlock(*block));
     }
 
-    let out_bytes: [u8; 16 * 4] = unsafe { std::mem::transmute(out) };
-    out_bytes
+    // rg_etc1 writes each pixel as 4 bytes in R, G, B, A order in memory
+    let mut out_bytes = [0u8; 64];
+    for (bytes, pixel) in out_bytes.chunks_exact_mut(4).zip(out) {
+        bytes.copy_from_slice(&pixel.to_ne_bytes());
+    }
+    Ok(out_bytes)
 }
 
 pub mod quality {
     pub const LOW: i32 = 0;
     pub const MEDIUM: i32 = 1;
     pub const HIGH: i32 = 2;
-}
\ No newline at end of file
+}
+
+/// The ETC1 compressors a build can encode with. Each is behind its own
+/// cargo feature, and both are built by default.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::etc1::Backend;
+/// assert_eq!(Backend::RgEtc1.is_available(), cfg!(feature = "etc1"));
+/// assert_eq!(Backend::Rust.is_available(), cfg!(feature = "etc1-rust"));
+/// assert_eq!(Backend::RgEtc1.name(), "rg_etc1");
+///
+/// // rg_etc1 is preferred when it's built
+/// if cfg!(feature = "etc1") {
+///     assert_eq!(Backend::default(), Backend::RgEtc1);
+/// }
+/// ```
+///
+/// Both compress to blocks that decode close to the source:
+///
+/// ```
+/// # use image::{Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{decode_raw, encode_texture, etc1::Backend};
+/// # use pica_convert::pica_texture::{DecodeOptions, EncodeOptions, TextureFormat};
+/// let img = RgbaImage::from_fn(32, 32, |x, y| Rgba([(x * 8) as u8, 255 - (y * 6) as u8, ((x + y) * 3) as u8, 255]));
+/// for etc1_backend in Backend::ALL.into_iter().filter(Backend::is_available) {
+///     let options = EncodeOptions { etc1_backend, ..Default::default() };
+///     let texture = encode_texture(&img.clone().into(), &TextureFormat::ETC1, &options).unwrap();
+///     let decoded = decode_raw(texture.data(), 32, 32, &TextureFormat::ETC1, &DecodeOptions::default()).unwrap().to_rgba8();
+///
+///     for (x, y, pixel) in decoded.enumerate_pixels() {
+///         let source = img.get_pixel(x, y);
+///         assert!(pixel.0.iter().zip(source.0).all(|(&a, b)| a.abs_diff(b) <= 16), "{} at ({}, {}): {:?}", etc1_backend.name(), x, y, pixel);
+///     }
+/// }
+///
+/// // Asking for a backend the build doesn't have fails cleanly
+/// # use pica_convert::pica_texture::TextureError;
+/// for etc1_backend in Backend::ALL.into_iter().filter(|backend| !backend.is_available()) {
+///     let options = EncodeOptions { etc1_backend, ..Default::default() };
+///     let err = encode_texture(&img.clone().into(), &TextureFormat::ETC1, &options).err().unwrap();
+///     assert_eq!(err.downcast_ref(), Some(&TextureError::Etc1BackendUnavailable(etc1_backend)));
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Backend {
+    /// rg_etc1's C++ compressor ([`compress_block`]), built with the `etc1` feature.
+    #[cfg_attr(feature = "cli", value(name = "rg_etc1"))]
+    RgEtc1,
+    /// The plain Rust compressor ([`pack_block`]), built with the `etc1-rust`
+    /// feature. It searches fewer base colors than rg_etc1, and builds
+    /// without a C++ toolchain.
+    Rust,
+}
+
+impl Backend {
+    /// Every backend, whether or not this build has it.
+    pub const ALL: [Backend; 2] = [Backend::RgEtc1, Backend::Rust];
+
+    /// Returns the backend's name, as `--etc1-backend` takes it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::RgEtc1 => "rg_etc1",
+            Backend::Rust => "rust",
+        }
+    }
+
+    /// Returns whether this build has the backend.
+    pub fn is_available(&self) -> bool {
+        match self {
+            Backend::RgEtc1 => cfg!(feature = "etc1"),
+            Backend::Rust => cfg!(feature = "etc1-rust"),
+        }
+    }
+
+    /// Compresses a block with the backend, or returns `None` if this build
+    /// doesn't have it. `dithering` only applies to rg_etc1.
+    pub fn compress_block(&self, rgba: &[u8; 64], quality: i32, dithering: bool) -> Option<[u8; 8]> {
+        match self {
+            #[cfg(feature = "etc1")]
+            Backend::RgEtc1 => Some(compress_block(rgba, Some(Etc1PackParams { quality, dithering: dithering as i32 }))),
+            #[cfg(feature = "etc1-rust")]
+            Backend::Rust => Some(pack_block(rgba, quality)),
+            #[allow(unreachable_patterns)]
+            _ => {
+                let _ = (rgba, quality, dithering);
+                None
+            }
+        }
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        if cfg!(feature = "etc1") { Backend::RgEtc1 } else { Backend::Rust }
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+use crate::pica_texture::{PicaTexture, TextureFormat};
+
+/// Summary metadata describing a [`PicaTexture`].
+///
+/// Unlike [`TextureFormat`]'s own serde implementation, which uses the ctex
+/// spelling, the format is serialized here by its canonical name so the
+/// output reads naturally in JSON.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{PicaTexture, TextureFormat, TextureInfo};
+/// let texture = PicaTexture::try_new(TextureFormat::ETC1A4, 64, 32, vec![0; 2048]).unwrap();
+/// let info = texture.info();
+///
+/// let json = serde_json::to_string(&info).unwrap();
+/// assert_eq!(json, r#"{"format":"ETC1A4","width":64,"height":32,"mip_count":1,"data_length":2048,"bits_per_pixel":8}"#);
+///
+/// let parsed: TextureInfo = serde_json::from_str(&json).unwrap();
+/// assert_eq!(parsed, info);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextureInfo {
+    #[serde(with = "format_name")]
+    pub format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub mip_count: u32,
+    pub data_length: usize,
+    pub bits_per_pixel: u32,
+}
+
+impl PicaTexture {
+    /// Returns the [`TextureInfo`] describing this texture.
+    pub fn info(&self) -> TextureInfo {
+        TextureInfo {
+            format: *self.format(),
+            width: self.width(),
+            height: self.height(),
+            mip_count: self.mip_levels(),
+            data_length: self.data().len(),
+            bits_per_pixel: self.format().bits_per_pixel(),
+        }
+    }
+}
+
+/// Serde helpers that represent a [`TextureFormat`] by its canonical name.
+pub(crate) mod format_name {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::pica_texture::TextureFormat;
+
+    pub fn serialize<S: Serializer>(format: &TextureFormat, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(format.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<TextureFormat, D::Error> {
+        let s: String = Deserialize::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
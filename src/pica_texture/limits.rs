@@ -0,0 +1,125 @@
+use crate::pica_texture::TextureError;
+
+/// The largest width or height the PICA can sample a texture at.
+pub const MAX_DIMENSION: u32 = 1024;
+
+/// Limits on the dimensions of a texture.
+///
+/// # Example
+///
+/// ```
+/// use pica_convert::pica_texture::DimensionLimits;
+///
+/// let limits = DimensionLimits::default();
+/// assert_eq!((limits.max_width, limits.max_height), (1024, 1024));
+/// assert!(limits.power_of_two);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DimensionLimits {
+    /// The largest allowed width in pixels.
+    pub max_width: u32,
+    /// The largest allowed height in pixels.
+    pub max_height: u32,
+    /// Whether both dimensions must be powers of two.
+    pub power_of_two: bool,
+}
+
+impl Default for DimensionLimits {
+    fn default() -> Self {
+        Self {
+            max_width: MAX_DIMENSION,
+            max_height: MAX_DIMENSION,
+            power_of_two: true,
+        }
+    }
+}
+
+impl DimensionLimits {
+    /// Returns the dimensions a `width` x `height` image should be resized to
+    /// so that it fits these limits.
+    ///
+    /// With `power_of_two` set, each side is rounded up to the next power of
+    /// two; sides over the maximum are then scaled down to it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::DimensionLimits;
+    /// let limits = DimensionLimits::default();
+    /// assert_eq!(limits.resize_target(300, 64), (512, 64));
+    /// assert_eq!(limits.resize_target(1500, 900), (1024, 1024));
+    /// assert_eq!(limits.resize_target(1024, 1030), (1024, 1024));
+    /// ```
+    pub fn resize_target(&self, width: u32, height: u32) -> (u32, u32) {
+        let fit = |side: u32, max: u32| {
+            let side = if self.power_of_two { side.next_power_of_two() } else { side };
+            side.min(max)
+        };
+
+        (fit(width, self.max_width), fit(height, self.max_height))
+    }
+}
+
+/// Checks that a `width` x `height` texture fits within `limits`.
+///
+/// # Errors
+///
+/// Returns [`TextureError::InvalidDimensions`] describing the first limit the
+/// dimensions break.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{validate_dimensions, DimensionLimits, TextureError};
+/// let limits = DimensionLimits::default();
+///
+/// assert!(validate_dimensions(1024, 1024, &limits).is_ok());
+/// assert!(validate_dimensions(8, 1024, &limits).is_ok());
+/// assert!(validate_dimensions(2048, 1024, &limits).is_err());
+/// assert!(validate_dimensions(1024, 1025, &limits).is_err());
+/// assert!(matches!(validate_dimensions(96, 64, &limits), Err(TextureError::InvalidDimensions { width: 96, height: 64, .. })));
+///
+/// // Validation applies to the dimensions after resizing, not before
+/// for (width, height) in [(1500, 900), (1030, 1030)] {
+///     assert!(validate_dimensions(width, height, &limits).is_err());
+///
+///     let (width, height) = limits.resize_target(width, height);
+///     assert!(validate_dimensions(width, height, &limits).is_ok());
+/// }
+///
+/// // Empty textures never fit
+/// for (width, height) in [(0, 0), (0, 64), (64, 0)] {
+///     let err = validate_dimensions(width, height, &limits).unwrap_err();
+///     assert_eq!(err.to_string(), format!("Texture dimensions {}x{} must be nonzero", width, height));
+/// }
+///
+/// // Without the power of two requirement only the maximum applies
+/// let relaxed = DimensionLimits { power_of_two: false, ..Default::default() };
+/// assert!(validate_dimensions(96, 64, &relaxed).is_ok());
+/// assert!(validate_dimensions(1030, 64, &relaxed).is_err());
+/// ```
+pub fn validate_dimensions(width: u32, height: u32, limits: &DimensionLimits) -> Result<(), TextureError> {
+    ensure_nonzero(width, height)?;
+
+    let invalid = |reason: String| Err(TextureError::InvalidDimensions { width, height, reason });
+
+    if width > limits.max_width || height > limits.max_height {
+        return invalid(format!("must not exceed {}x{}", limits.max_width, limits.max_height));
+    }
+    if limits.power_of_two && !(width.is_power_of_two() && height.is_power_of_two()) {
+        return invalid("must be powers of two".to_string());
+    }
+
+    Ok(())
+}
+
+/// Checks that a `width` x `height` texture has at least one pixel.
+///
+/// Every encode and decode entry point checks this, whatever the limits.
+///
+pub(crate) fn ensure_nonzero(width: u32, height: u32) -> Result<(), TextureError> {
+    if width == 0 || height == 0 {
+        return Err(TextureError::InvalidDimensions { width, height, reason: "must be nonzero".to_string() });
+    }
+    Ok(())
+}
@@ -3,9 +3,29 @@ pub mod decode;
 pub mod types;
 pub mod util;
 pub mod etc1;
+pub mod options;
+pub mod error;
+pub mod info;
+pub mod codec;
+pub mod verify;
+pub mod preview;
+pub mod stream;
+pub mod synthetic;
+pub mod limits;
+pub mod patch;
 
 pub use types::TextureFormat;
-pub use types::PicaTexture;
+pub use types::{PicaTexture, PicaTextureRef, TextureLayout};
+pub use types::{estimate_encoded_size, max_mip_levels};
+pub use options::{EncodeOptions, DecodeOptions};
+pub use error::TextureError;
+pub use info::TextureInfo;
+pub use codec::{TextureCodec, BuiltinCodec, CodecRegistry, RgbaView};
+pub use verify::{RoundtripReport, verify_roundtrip, try_verify_roundtrip, verify_texture};
+pub use preview::{tile_preview, composite_over, Background};
+pub use stream::TileEncoder;
+pub use limits::{DimensionLimits, validate_dimensions};
+pub use patch::patch_texture;
 
-pub use encode::encode_texture;
-pub use decode::decode_texture;
+pub use encode::{encode_texture, encode_rgba_buffer, encode_mip_chain, encode_with_codec};
+pub use decode::{decode_texture, decode_raw, decode_to_rgba, decode_into, decode_with_codec};
@@ -0,0 +1,79 @@
+use crate::pica_texture::etc1;
+use crate::pica_texture::TextureLayout;
+
+/// Options controlling how an image is encoded into PICA texture data.
+///
+/// # Example
+///
+/// ```
+/// use pica_convert::pica_texture::{EncodeOptions, etc1};
+///
+/// let options = EncodeOptions {
+///     etc1_quality: etc1::quality::LOW,
+///     ..Default::default()
+/// };
+/// assert!(!options.etc1_dithering);
+/// ```
+#[derive(Clone, Debug)]
+pub struct EncodeOptions {
+    /// Quality level used when compressing ETC1/ETC1A4 blocks (see [`etc1::quality`]).
+    pub etc1_quality: i32,
+    /// Whether the ETC1 compressor should dither block colors. Only rg_etc1 dithers.
+    pub etc1_dithering: bool,
+    /// The compressor ETC1/ETC1A4 blocks are encoded with.
+    pub etc1_backend: etc1::Backend,
+    /// Whether to mirror the image horizontally before encoding.
+    pub flip_horizontal: bool,
+    /// The order to store the encoded pixels in.
+    pub layout: TextureLayout,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            etc1_quality: etc1::quality::HIGH,
+            etc1_dithering: false,
+            etc1_backend: etc1::Backend::default(),
+            flip_horizontal: false,
+            layout: TextureLayout::Tiled,
+        }
+    }
+}
+
+/// Options controlling how PICA texture data is decoded.
+///
+/// # Example
+///
+/// ```
+/// use pica_convert::pica_texture::DecodeOptions;
+///
+/// let options = DecodeOptions::default();
+/// assert!(!options.flip_vertical);
+/// assert!(!options.flip_horizontal);
+/// assert_eq!(options.output_dimensions(256, 128), (256, 128));
+///
+/// let cropped = DecodeOptions { crop: Some((200, 120)), ..Default::default() };
+/// assert_eq!(cropped.output_dimensions(256, 128), (200, 120));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DecodeOptions {
+    /// Whether to flip the decoded pixels vertically as the final decode step.
+    ///
+    /// Decoded images are the same way up as the images they were encoded
+    /// from, so this is only needed for data that was stored upside down.
+    pub flip_vertical: bool,
+    /// Whether to mirror the decoded pixels horizontally as the final decode step.
+    pub flip_horizontal: bool,
+    /// The true width and height of the image, for textures stored padded
+    /// to larger dimensions. The decoded pixels are cropped to these,
+    /// keeping the top left corner, before any flipping.
+    pub crop: Option<(u32, u32)>,
+}
+
+impl DecodeOptions {
+    /// Returns the dimensions of the pixels decoded from a `width` x `height`
+    /// texture with these options.
+    pub fn output_dimensions(&self, width: u32, height: u32) -> (u32, u32) {
+        self.crop.unwrap_or((width, height))
+    }
+}
@@ -0,0 +1,168 @@
+use image::RgbaImage;
+
+use crate::pica_texture::{PicaTexture, TextureLayout, EncodeOptions, DecodeOptions, TextureError};
+use crate::pica_texture::codec::BuiltinCodec;
+use crate::pica_texture::decode::decode_raw;
+use crate::pica_texture::encode::encode_with_codec;
+
+/// The width and height of the tiles a patch is re-encoded in, in pixels.
+pub const TILE_SIZE: u32 = 8;
+
+/// Returns the pixels a `width` x `height` patch at `x`, `y` re-encodes, as
+/// `(left, top, right, bottom)`: the patch rounded out to whole tiles and
+/// clipped to the `texture_width` x `texture_height` texture.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::patch::affected_pixels;
+/// assert_eq!(affected_pixels(64, 64, 16, 8, 16, 8), (16, 8, 32, 16));
+/// assert_eq!(affected_pixels(64, 64, 12, 20, 16, 8), (8, 16, 32, 32));
+///
+/// // Partial tiles at the edge stop at the texture's edge
+/// assert_eq!(affected_pixels(60, 20, 50, 10, 10, 10), (48, 8, 60, 20));
+/// ```
+pub fn affected_pixels(texture_width: u32, texture_height: u32, x: u32, y: u32, width: u32, height: u32) -> (u32, u32, u32, u32) {
+    let left = x / TILE_SIZE * TILE_SIZE;
+    let top = y / TILE_SIZE * TILE_SIZE;
+    let right = (x + width).next_multiple_of(TILE_SIZE).min(texture_width);
+    let bottom = (y + height).next_multiple_of(TILE_SIZE).min(texture_height);
+    (left, top, right, bottom)
+}
+
+/// Replaces the pixels of `texture` under `patch`, with its top left corner
+/// at `x`, `y`, re-encoding only the 8x8 tiles the patch touches.
+///
+/// Every other tile's data is copied over untouched. Where the patch covers
+/// whole tiles, the old pixels aren't decoded at all; otherwise the rows of
+/// tiles it touches are decoded so the pixels around the patch are kept.
+/// Those pixels go through another encode, so for lossy formats a patch
+/// that isn't aligned to tiles slightly changes the pixels around it.
+///
+/// The patch is encoded with `options`, apart from
+/// [`EncodeOptions::flip_horizontal`] and [`EncodeOptions::layout`], which
+/// are ignored: the patch lands where `x` and `y` say, in the texture's tiles.
+///
+/// # Errors
+///
+/// Returns [`TextureError::InvalidDimensions`] if the patch is empty or
+/// doesn't fit in the texture, an error if the texture has mip levels, which
+/// would be left holding the old pixels, or is linear, or if its format can't be encoded with `options`,
+/// or decoded when the patch doesn't cover whole tiles.
+///
+/// # Example
+///
+/// ```
+/// # use image::{DynamicImage, Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{encode_texture, DecodeOptions, EncodeOptions, TextureFormat};
+/// # use pica_convert::pica_texture::patch::patch_texture;
+/// let img = RgbaImage::from_fn(64, 40, |x, y| Rgba([x as u8 * 4, y as u8 * 6, (x ^ y) as u8, 255 - x as u8]));
+/// let label = RgbaImage::from_fn(16, 8, |x, y| Rgba([255, x as u8 * 16, y as u8 * 32, 200]));
+/// let mut patched_img = img.clone();
+/// image::imageops::replace(&mut patched_img, &label, 16, 8);
+///
+/// let options = EncodeOptions::default();
+/// for format in TextureFormat::ALL.into_iter().filter(|f| f.is_available() && f.can_decode()) {
+///     let original = encode_texture(&DynamicImage::ImageRgba8(img.clone()), &format, &options).unwrap();
+///     let patched = patch_texture(&original, &label, 16, 8, &options).unwrap();
+///
+///     // A tile-aligned patch gives the same data as encoding the whole patched image
+///     let expected = encode_texture(&DynamicImage::ImageRgba8(patched_img.clone()), &format, &options).unwrap();
+///     assert_eq!(patched.data(), expected.data(), "{}", format);
+///
+///     // Only the two tiles under the patch change; every other tile is byte-identical
+///     let tile_len = format.expected_data_len(8, 8);
+///     for (index, (old, new)) in original.data().chunks(tile_len).zip(patched.data().chunks(tile_len)).enumerate() {
+///         assert_eq!(old == new, ![10, 11].contains(&index), "{} tile {}", format, index);
+///     }
+/// }
+/// ```
+///
+/// A patch that isn't aligned to tiles keeps the pixels around it:
+///
+/// ```
+/// # use image::{DynamicImage, Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{encode_texture, DecodeOptions, EncodeOptions, TextureFormat};
+/// # use pica_convert::pica_texture::patch::patch_texture;
+/// let img = RgbaImage::from_fn(60, 20, |x, y| Rgba([x as u8 * 4, y as u8 * 12, 7, 255]));
+/// let label = RgbaImage::from_pixel(13, 9, Rgba([1, 2, 3, 4]));
+/// let mut patched_img = img.clone();
+/// image::imageops::replace(&mut patched_img, &label, 45, 6);
+///
+/// let options = EncodeOptions::default();
+/// let original = encode_texture(&DynamicImage::ImageRgba8(img), &TextureFormat::RGBA8888, &options).unwrap();
+/// let patched = patch_texture(&original, &label, 45, 6, &options).unwrap();
+/// assert_eq!(patched.to_image(&DecodeOptions::default()).unwrap().to_rgba8(), patched_img);
+///
+/// // Tiles left of x = 40 aren't touched
+/// let tile_len = TextureFormat::RGBA8888.expected_data_len(8, 8);
+/// for row in 0..3 {
+///     let row_start = row * 8 * tile_len;
+///     assert_eq!(patched.data()[row_start..row_start + 5 * tile_len], original.data()[row_start..row_start + 5 * tile_len]);
+/// }
+///
+/// assert!(patch_texture(&original, &label, 50, 6, &options).is_err());
+/// assert!(patch_texture(&original, &RgbaImage::new(0, 0), 0, 0, &options).is_err());
+/// ```
+pub fn patch_texture(texture: &PicaTexture, patch: &RgbaImage, x: u32, y: u32, options: &EncodeOptions) -> Result<PicaTexture, Box<dyn std::error::Error>> {
+    let format = texture.format();
+    let options = EncodeOptions { flip_horizontal: false, layout: TextureLayout::Tiled, ..options.clone() };
+    if let Some(err) = format.encode_options_error(&options) {
+        return Err(err.into());
+    }
+    if texture.mip_levels() > 1 {
+        return Err(format!("Texture has {} mip levels, which patching would leave holding the old pixels", texture.mip_levels()).into());
+    }
+    if texture.layout() == TextureLayout::Linear {
+        return Err("Only tiled textures can be patched, as they're re-encoded a tile at a time".into());
+    }
+
+    let (texture_width, texture_height) = texture.dimensions();
+    let (width, height) = patch.dimensions();
+    let fits = x.checked_add(width).is_some_and(|right| right <= texture_width)
+        && y.checked_add(height).is_some_and(|bottom| bottom <= texture_height);
+    if width == 0 || height == 0 || !fits {
+        return Err(TextureError::InvalidDimensions {
+            width,
+            height,
+            reason: format!("at {},{} don't fit in the {}x{} texture", x, y, texture_width, texture_height),
+        }.into());
+    }
+
+    let (left, top, right, bottom) = affected_pixels(texture_width, texture_height, x, y, width, height);
+    let tile_len = format.expected_data_len(TILE_SIZE, TILE_SIZE);
+    let tiles_x = texture_width.div_ceil(TILE_SIZE) as usize;
+
+    // Only decode the rows of tiles under the patch, and only if some of their pixels are kept
+    let covers_tiles = (x, y, x + width, y + height) == (left, top, right, bottom);
+    let canvas = if covers_tiles {
+        patch.clone()
+    } else {
+        let band_start = (top / TILE_SIZE) as usize * tiles_x * tile_len;
+        let band_end = bottom.div_ceil(TILE_SIZE) as usize * tiles_x * tile_len;
+        let band = decode_raw(&texture.data()[band_start..band_end], texture_width, bottom - top, format, &DecodeOptions::default())?.into_rgba8();
+
+        let mut canvas = image::imageops::crop_imm(&band, left, 0, right - left, bottom - top).to_image();
+        image::imageops::replace(&mut canvas, patch, (x - left) as i64, (y - top) as i64);
+        canvas
+    };
+
+    let encoded = encode_with_codec(BuiltinCodec::for_format(format), &canvas, &options)?;
+
+    // Each encoded tile goes back where it came from in the texture's rows of tiles
+    let mut data = texture.data().to_vec();
+    let patch_tiles_x = (right - left).div_ceil(TILE_SIZE) as usize;
+    for (index, tile) in encoded.chunks_exact(tile_len).enumerate() {
+        let tile_x = (left / TILE_SIZE) as usize + index % patch_tiles_x;
+        let tile_y = (top / TILE_SIZE) as usize + index / patch_tiles_x;
+        let offset = (tile_y * tiles_x + tile_x) * tile_len;
+        data[offset..offset + tile_len].copy_from_slice(tile);
+    }
+
+    let patched = PicaTexture::try_new(*format, texture_width, texture_height, data)?;
+    match texture.original_dimensions() {
+        Some((width, height)) => Ok(patched.with_original_dimensions(width, height)?),
+        None => Ok(patched),
+    }
+}
+
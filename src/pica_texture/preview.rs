@@ -0,0 +1,159 @@
+//! Previews of decoded textures: repeated to show how they tile, or blended
+//! over a background to show their transparency.
+
+use std::str::FromStr;
+
+use image::{Rgba, RgbaImage};
+
+use crate::pica_texture::TextureError;
+
+/// The most times [`tile_preview`] repeats an image in each direction.
+pub const MAX_TILE_REPEAT: u32 = 8;
+
+/// The widest or tallest image [`tile_preview`] builds.
+pub const MAX_TILE_PREVIEW_DIMENSION: u32 = 8192;
+
+/// Repeats a decoded image `columns` times across and `rows` times down, to
+/// show the seams where a tiling texture wraps around.
+///
+/// # Errors
+///
+/// Returns [`TextureError::InvalidDimensions`] if either count is zero or
+/// above [`MAX_TILE_REPEAT`], or the preview would be wider or taller than
+/// [`MAX_TILE_PREVIEW_DIMENSION`].
+///
+/// # Example
+///
+/// ```
+/// # use image::{Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::tile_preview;
+/// let img = RgbaImage::from_fn(16, 8, |x, y| Rgba([x as u8, y as u8, 0, 255]));
+/// let preview = tile_preview(&img, 3, 2).unwrap();
+/// assert_eq!(preview.dimensions(), (48, 16));
+///
+/// // Every copy's corners are the source's corners
+/// for (col, row) in [(0, 0), (2, 0), (1, 1), (2, 1)] {
+///     for (x, y) in [(0, 0), (15, 0), (0, 7), (15, 7)] {
+///         assert_eq!(preview.get_pixel(col * 16 + x, row * 8 + y), img.get_pixel(x, y));
+///     }
+/// }
+/// assert_eq!(tile_preview(&img, 1, 1).unwrap(), img);
+///
+/// assert!(tile_preview(&img, 0, 2).is_err());
+/// assert!(tile_preview(&img, 2, 9).is_err());
+/// assert!(tile_preview(&RgbaImage::new(1024, 1024), 8, 8).is_ok());
+/// assert!(tile_preview(&RgbaImage::new(2048, 4), 5, 1).is_err());
+/// ```
+pub fn tile_preview(img: &RgbaImage, columns: u32, rows: u32) -> Result<RgbaImage, TextureError> {
+    let (width, height) = img.dimensions();
+    let invalid = |reason: String| TextureError::InvalidDimensions { width, height, reason };
+
+    if !(1..=MAX_TILE_REPEAT).contains(&columns) || !(1..=MAX_TILE_REPEAT).contains(&rows) {
+        return Err(invalid(format!("can't be tiled {}x{}, expected 1 to {} each way", columns, rows, MAX_TILE_REPEAT)));
+    }
+    let (preview_width, preview_height) = (width as u64 * columns as u64, height as u64 * rows as u64);
+    if preview_width > MAX_TILE_PREVIEW_DIMENSION as u64 || preview_height > MAX_TILE_PREVIEW_DIMENSION as u64 {
+        return Err(invalid(format!("tiled {}x{} would be {}x{}, larger than {} either way", columns, rows, preview_width, preview_height, MAX_TILE_PREVIEW_DIMENSION)));
+    }
+
+    Ok(RgbaImage::from_fn(preview_width as u32, preview_height as u32, |x, y| *img.get_pixel(x % width, y % height)))
+}
+
+/// The checkerboard cell size [`Background`] parses a bare `checker` as, in pixels.
+pub const DEFAULT_CHECKER_SIZE: u32 = 8;
+
+/// What [`composite_over`] puts behind a decoded image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Background {
+    /// A single RGB color.
+    Solid([u8; 3]),
+    /// Alternating light and dark gray squares, `cell_size` pixels across,
+    /// starting with light in the top left corner.
+    Checker { cell_size: u32 },
+}
+
+impl Background {
+    /// The checkerboard's light and dark colors.
+    pub const CHECKER_COLORS: [[u8; 3]; 2] = [[255, 255, 255], [204, 204, 204]];
+
+    fn color_at(&self, x: u32, y: u32) -> [u8; 3] {
+        match *self {
+            Background::Solid(color) => color,
+            Background::Checker { cell_size } => {
+                let cell_size = cell_size.max(1);
+                Self::CHECKER_COLORS[((x / cell_size + y / cell_size) % 2) as usize]
+            }
+        }
+    }
+}
+
+impl FromStr for Background {
+    type Err = String;
+
+    /// Parses `checker` or `checker:SIZE`, or a color as `RRGGBB` hex digits
+    /// with an optional leading `#`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, size) = s.split_once(':').unwrap_or((s, ""));
+        if name.eq_ignore_ascii_case("checker") {
+            if size.is_empty() {
+                return Ok(Background::Checker { cell_size: DEFAULT_CHECKER_SIZE });
+            }
+            return match size.parse::<u32>() {
+                Ok(cell_size) if cell_size > 0 => Ok(Background::Checker { cell_size }),
+                _ => Err(format!("Invalid checker size '{}', expected a whole number of pixels above 0", size)),
+            };
+        }
+
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let channel = |index: usize| hex.get(index..index + 2).and_then(|digits| u8::from_str_radix(digits, 16).ok());
+        match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) if hex.is_ascii() => Ok(Background::Solid([r, g, b])),
+            _ => Err(format!("Unknown background '{}', expected RRGGBB, checker or checker:SIZE", s)),
+        }
+    }
+}
+
+/// Blends a decoded image over a background, giving a fully opaque image.
+///
+/// Blending is done on the sRGB values as they're stored, without converting
+/// them to linear light first. That's what most image viewers do, so the
+/// result matches how the texture looks when they show it.
+///
+/// # Example
+///
+/// ```
+/// # use image::{Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::preview::{composite_over, Background};
+/// // Half transparent red over white is pink
+/// let img = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 128]));
+/// let white: Background = "FFFFFF".parse().unwrap();
+/// assert_eq!(composite_over(&img, &white).get_pixel(2, 2).0, [255, 127, 127, 255]);
+///
+/// // Opaque pixels are kept as they are
+/// let opaque = RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255]));
+/// assert_eq!(composite_over(&opaque, &"#00ff00".parse().unwrap()), opaque);
+///
+/// // Transparent pixels show the checkerboard
+/// let clear = RgbaImage::new(8, 8);
+/// let checked = composite_over(&clear, &Background::Checker { cell_size: 4 });
+/// let [light, dark] = Background::CHECKER_COLORS.map(|[r, g, b]| [r, g, b, 255]);
+/// assert_eq!(checked.get_pixel(0, 0).0, light);
+/// assert_eq!(checked.get_pixel(3, 3).0, light);
+/// assert_eq!(checked.get_pixel(4, 0).0, dark);
+/// assert_eq!(checked.get_pixel(0, 7).0, dark);
+/// assert_eq!(checked.get_pixel(7, 7).0, light);
+///
+/// assert_eq!("checker".parse(), Ok(Background::Checker { cell_size: 8 }));
+/// assert_eq!("checker:16".parse(), Ok(Background::Checker { cell_size: 16 }));
+/// assert!("checker:0".parse::<Background>().is_err());
+/// assert!("FFF".parse::<Background>().is_err());
+/// assert!("GGGGGG".parse::<Background>().is_err());
+/// ```
+pub fn composite_over(img: &RgbaImage, background: &Background) -> RgbaImage {
+    RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b, a] = img.get_pixel(x, y).0;
+        let behind = background.color_at(x, y);
+        let blend = |front: u8, back: u8| ((front as u32 * a as u32 + back as u32 * (255 - a as u32) + 127) / 255) as u8;
+        Rgba([blend(r, behind[0]), blend(g, behind[1]), blend(b, behind[2]), 255])
+    })
+}
@@ -0,0 +1,140 @@
+use image::RgbaImage;
+
+use crate::pica_texture::{TextureFormat, EncodeOptions};
+use crate::pica_texture::codec::BuiltinCodec;
+use crate::pica_texture::encode::encode_with_codec;
+
+/// The number of pixel rows in each band of tiles.
+const BAND_HEIGHT: u32 = 8;
+
+/// Encodes a texture incrementally, one 8-pixel band of tiles at a time.
+///
+/// The PICA lays tiles out left to right and then top to bottom, so each band
+/// of 8 rows encodes independently of the rest of the image. Feeding rows in
+/// with [`push_rows`](TileEncoder::push_rows) only keeps a single band of
+/// pixels resident, and the output is identical to
+/// [`encode_texture`](crate::pica_texture::encode_texture)'s.
+///
+/// # Example
+///
+/// ```
+/// # use image::{DynamicImage, Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{encode_texture, EncodeOptions, TextureFormat, TileEncoder};
+/// let options = EncodeOptions::default();
+///
+/// // Including sizes that end in partial tiles
+/// for (width, height) in [(32, 24), (30, 21)] {
+///     let img = RgbaImage::from_fn(width, height, |x, y| Rgba([x as u8 * 8, y as u8 * 10, (x ^ y) as u8, 255 - x as u8]));
+///
+///     for format in TextureFormat::ALL.into_iter().filter(|f| f.is_available()) {
+///         let one_shot = encode_texture(&DynamicImage::ImageRgba8(img.clone()), &format, &options).unwrap();
+///
+///         // Feed the image in uneven chunks of rows
+///         let mut encoder = TileEncoder::new(format, width, height, options.clone());
+///         let mut streamed = Vec::new();
+///         for rows in img.as_raw().chunks(width as usize * 4 * 5) {
+///             streamed.extend(encoder.push_rows(rows).unwrap());
+///         }
+///         streamed.extend(encoder.finish().unwrap());
+///
+///         assert_eq!(streamed, one_shot.data(), "{} at {}x{}", format, width, height);
+///     }
+/// }
+/// ```
+pub struct TileEncoder {
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    options: EncodeOptions,
+    band: Vec<u8>,
+    rows_received: u32,
+}
+
+impl TileEncoder {
+    /// Creates an encoder for a `width` x `height` texture.
+    pub fn new(format: TextureFormat, width: u32, height: u32, options: EncodeOptions) -> Self {
+        Self {
+            format,
+            width,
+            height,
+            options,
+            band: Vec::with_capacity(width as usize * BAND_HEIGHT as usize * 4),
+            rows_received: 0,
+        }
+    }
+
+    /// Returns the number of rows pushed so far.
+    pub fn rows_received(&self) -> u32 {
+        self.rows_received
+    }
+
+    /// Feeds whole rows of RGBA8 pixels into the encoder, top row first.
+    ///
+    /// # Returns
+    ///
+    /// The encoded bytes for every band completed by these rows, which may be empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `rows` isn't a whole number of rows, holds more
+    /// rows than the texture has left, or the format isn't available in this
+    /// build or in [`EncodeOptions::layout`].
+    pub fn push_rows(&mut self, rows: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if let Some(err) = self.format.encode_options_error(&self.options) {
+            return Err(err.into());
+        }
+
+        let row_bytes = self.width as usize * 4;
+        if row_bytes == 0 || !rows.len().is_multiple_of(row_bytes) {
+            return Err(format!("Row data is {} bytes, expected a multiple of {}", rows.len(), row_bytes).into());
+        }
+
+        let row_count = (rows.len() / row_bytes) as u32;
+        if row_count > self.height - self.rows_received {
+            return Err(format!("Received {} rows, but only {} of {} remain", row_count, self.height - self.rows_received, self.height).into());
+        }
+
+        let mut output = Vec::new();
+        for row in rows.chunks_exact(row_bytes) {
+            self.band.extend_from_slice(row);
+            self.rows_received += 1;
+
+            if self.band.len() == row_bytes * BAND_HEIGHT as usize || self.rows_received == self.height {
+                output.extend(self.encode_band()?);
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Finishes encoding, checking that every row was pushed.
+    ///
+    /// # Returns
+    ///
+    /// Any encoded bytes not yet returned by [`push_rows`](TileEncoder::push_rows).
+    pub fn finish(mut self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if self.rows_received != self.height {
+            return Err(format!("Received {} rows, expected {}", self.rows_received, self.height).into());
+        }
+
+        if self.band.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.encode_band()
+    }
+
+    /// Encodes and clears the buffered band.
+    fn encode_band(&mut self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let band_height = (self.band.len() / (self.width as usize * 4)) as u32;
+        let band = RgbaImage::from_raw(self.width, band_height, std::mem::take(&mut self.band))
+            .ok_or("Failed to construct ImageBuffer from raw data")?;
+
+        let encoded = encode_with_codec(BuiltinCodec::for_format(&self.format), &band, &self.options)?;
+
+        // Reuse the band's allocation for the next rows
+        self.band = band.into_raw();
+        self.band.clear();
+
+        Ok(encoded)
+    }
+}
@@ -0,0 +1,91 @@
+//! Deterministic synthetic images and texture data.
+//!
+//! Benchmarks and tests need inputs that are the same on every run and every
+//! machine, without checking large image files into the repository. Everything
+//! here is derived from the dimensions and a seed alone.
+
+use image::{Rgba, RgbaImage};
+
+use crate::pica_texture::{PicaTexture, TextureFormat};
+
+/// Mixes a pixel position and seed into 32 well-distributed bits.
+fn hash(x: u32, y: u32, seed: u32) -> u32 {
+    let mut h = x.wrapping_mul(0x9E37_79B1) ^ y.wrapping_mul(0x85EB_CA77) ^ seed.wrapping_mul(0xC2B2_AE3D);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7FEB_352D);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846C_A68B);
+    h ^ (h >> 16)
+}
+
+/// Generates a `width` x `height` image that is identical for the same seed.
+///
+/// The image mixes smooth gradients, per-pixel noise, hard 8x8 edges and
+/// partial transparency, so every encoder (including ETC1's block search) has
+/// representative work to do.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::synthetic::synthetic_image;
+/// let img = synthetic_image(64, 32, 1);
+/// assert_eq!(img.dimensions(), (64, 32));
+///
+/// // The same seed always gives the same image, different seeds don't
+/// assert_eq!(img, synthetic_image(64, 32, 1));
+/// assert_ne!(img, synthetic_image(64, 32, 2));
+///
+/// // The top half is opaque, the bottom half fades out
+/// assert!(img.rows().take(16).flatten().all(|pixel| pixel[3] == 255));
+/// assert!(img.rows().skip(16).flatten().any(|pixel| pixel[3] < 255));
+/// ```
+pub fn synthetic_image(width: u32, height: u32, seed: u32) -> RgbaImage {
+    let ramp = |value: u32, extent: u32| (value * 255 / extent.saturating_sub(1).max(1)) as u8;
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let noise = hash(x, y, seed).to_le_bytes();
+        let checker = if (x / 8 + y / 8) % 2 == 0 { 0 } else { 96 };
+
+        let r = ramp(x, width).wrapping_add(noise[0] & 0x0F);
+        let g = ramp(y, height).wrapping_add(checker);
+        let b = noise[1];
+        let a = if y < height / 2 { 255 } else { 255 - ramp(x, width) / 2 - (noise[2] & 0x1F) };
+
+        Rgba([r, g, b, a])
+    })
+}
+
+/// Generates raw `format` texture data for a `width` x `height` texture.
+///
+/// The data is noise, sized to exactly what [`TextureFormat::expected_data_len`]
+/// asks for. ETC1 blocks are kept in individual color mode, since noise in
+/// differential mode can push their colors out of range.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::synthetic::synthetic_texture;
+/// # use pica_convert::pica_texture::{decode_texture, DecodeOptions, TextureFormat};
+/// for format in TextureFormat::ALL.into_iter().filter(|format| format.is_available()) {
+///     let texture = synthetic_texture(&format, 32, 16, 7);
+///     assert_eq!(texture.data().len(), format.expected_data_len(32, 16));
+///     assert_eq!(texture.data(), synthetic_texture(&format, 32, 16, 7).data());
+///
+///     assert!(decode_texture(&texture, &DecodeOptions::default()).is_ok());
+/// }
+/// ```
+pub fn synthetic_texture(format: &TextureFormat, width: u32, height: u32, seed: u32) -> PicaTexture {
+    let len = format.expected_data_len(width, height);
+    let mut data: Vec<u8> = (0..len as u32).map(|i| hash(i, 0, seed) as u8).collect();
+
+    // Color blocks are stored byte swapped, so the diff bit is in byte 4
+    if format.is_compressed() {
+        let (block_width, block_height) = format.block_dims();
+        let block_size = (format.bits_per_pixel() * block_width * block_height / 8) as usize;
+        for block in data.chunks_exact_mut(block_size) {
+            block[block_size - 4] &= !0b10;
+        }
+    }
+
+    PicaTexture::new_unchecked(*format, width, height, data)
+}
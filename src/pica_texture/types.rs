@@ -1,7 +1,45 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
-use clap::ValueEnum;
+#[cfg(feature = "cli")]
+use clap::{builder::PossibleValue, ValueEnum};
+use image::DynamicImage;
+
+use crate::pica_texture::{decode, decode_texture, encode_texture, DecodeOptions, EncodeOptions, TextureError};
+use crate::pica_texture::util::{swizzle, swizzle_4bpp};
 
-#[derive(ValueEnum, Clone, Debug)]
+/// A PICA texture format.
+///
+/// Formats are plain values that copy and hash, so they work as map keys.
+/// They sort in the order of [`TextureFormat::ALL`]: by hardware ID, then
+/// the shadow and gas formats last, although they share ID 0 with RGBA8888.
+///
+/// # Example
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use pica_convert::pica_texture::TextureFormat;
+/// let mut counts: HashMap<TextureFormat, usize> = HashMap::new();
+/// for format in [TextureFormat::ETC1, TextureFormat::L8, TextureFormat::ETC1, TextureFormat::ETC1A4] {
+///     *counts.entry(format).or_default() += 1;
+/// }
+/// assert_eq!(counts[&TextureFormat::ETC1], 2);
+/// assert_eq!(counts.get(&TextureFormat::RGB565), None);
+///
+/// // Every format is its own key
+/// let by_format: HashMap<TextureFormat, &str> = TextureFormat::ALL.into_iter().map(|format| (format, format.name())).collect();
+/// assert_eq!(by_format.len(), TextureFormat::ALL.len());
+///
+/// let mut sorted = [TextureFormat::ETC1A4, TextureFormat::RGBA8888, TextureFormat::L4];
+/// sorted.sort();
+/// assert_eq!(sorted, [TextureFormat::RGBA8888, TextureFormat::L4, TextureFormat::ETC1A4]);
+/// assert!(TextureFormat::ALL.windows(2).all(|pair| pair[0] < pair[1]));
+/// assert!(TextureFormat::Shadow > TextureFormat::ETC1A4);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum TextureFormat {
     RGBA8888,
     RGB888,
@@ -16,7 +54,209 @@ pub enum TextureFormat {
     L4,
     A4,
     ETC1,
-    ETC1A4
+    ETC1A4,
+    Shadow,
+    Gas
+}
+
+impl TextureFormat {
+    /// Every texture format, in hardware order, followed by the shadow and
+    /// gas formats that share RGBA8888's texel layout.
+    pub const ALL: [TextureFormat; 16] = [
+        TextureFormat::RGBA8888,
+        TextureFormat::RGB888,
+        TextureFormat::RGBA5551,
+        TextureFormat::RGB565,
+        TextureFormat::RGBA4444,
+        TextureFormat::LA88,
+        TextureFormat::HL8,
+        TextureFormat::L8,
+        TextureFormat::A8,
+        TextureFormat::LA44,
+        TextureFormat::L4,
+        TextureFormat::A4,
+        TextureFormat::ETC1,
+        TextureFormat::ETC1A4,
+        TextureFormat::Shadow,
+        TextureFormat::Gas,
+    ];
+
+    /// The name table for this format: its canonical name, the spelling ctex
+    /// files use, and any other accepted aliases.
+    ///
+    /// Every way of naming a format (parsing, display, serde and the CLI) is
+    /// built on this table. The aliases cover the spellings used by
+    /// NintendoWare, Citra (`IA8`, `RG8`, `I4`, ...) and other converters;
+    /// separators and case don't matter, so `Rgb5_a1` already matches `RGB5A1`.
+    fn names(&self) -> (&'static str, &'static str, &'static [&'static str]) {
+        match self {
+            TextureFormat::RGBA8888 => ("RGBA8888", "Rgba8888", &["RGBA8"]),
+            TextureFormat::RGB888   => ("RGB888",   "Rgb888",   &["RGB8"]),
+            TextureFormat::RGBA5551 => ("RGBA5551", "Rgba5551", &["RGB5A1"]),
+            TextureFormat::RGB565   => ("RGB565",   "Rgb565",   &[]),
+            TextureFormat::RGBA4444 => ("RGBA4444", "Rgba4444", &["RGBA4"]),
+            TextureFormat::LA88     => ("LA88",     "La88",     &["LA8", "IA8"]),
+            TextureFormat::HL8      => ("HL8",      "Hl8",      &["HILO8", "RG8"]),
+            TextureFormat::L8       => ("L8",       "L8",       &["I8"]),
+            TextureFormat::A8       => ("A8",       "A8",       &[]),
+            TextureFormat::LA44     => ("LA44",     "La44",     &["LA4", "IA4"]),
+            TextureFormat::L4       => ("L4",       "L4",       &["I4"]),
+            TextureFormat::A4       => ("A4",       "A4",       &[]),
+            TextureFormat::ETC1     => ("ETC1",     "Etc1",     &["RGB_ETC1", "ETC1_RGB8"]),
+            TextureFormat::ETC1A4   => ("ETC1A4",   "Etc1_a4",  &["RGBA_ETC1", "RGB_ETC1_A4"]),
+            TextureFormat::Shadow   => ("Shadow",   "Shadow",   &["SHADOW_DMP"]),
+            TextureFormat::Gas      => ("Gas",      "Gas",      &["GAS_DMP"]),
+        }
+    }
+
+    /// Returns the canonical name of this format.
+    pub fn name(&self) -> &'static str {
+        self.names().0
+    }
+
+    /// Returns the name of this format as spelled in ctex files.
+    pub fn ctex_name(&self) -> &'static str {
+        self.names().1
+    }
+
+    /// Returns the other names this format is parsed from, besides
+    /// [`name`](Self::name) and [`ctex_name`](Self::ctex_name).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::TextureFormat;
+    /// assert_eq!(TextureFormat::HL8.aliases(), &["HILO8", "RG8"]);
+    ///
+    /// // No spelling is shared between two formats
+    /// let mut spellings: Vec<String> = TextureFormat::ALL.iter()
+    ///     .flat_map(|format| [format.name(), format.ctex_name()].into_iter().chain(format.aliases().iter().copied())
+    ///         .map(|name| name.replace(['_', '-'], "").to_ascii_uppercase())
+    ///         .collect::<std::collections::BTreeSet<_>>())
+    ///     .collect();
+    /// let count = spellings.len();
+    /// spellings.sort();
+    /// spellings.dedup();
+    /// assert_eq!(spellings.len(), count);
+    /// ```
+    pub fn aliases(&self) -> &'static [&'static str] {
+        self.names().2
+    }
+}
+
+/// Normalizes a format name for comparison, ignoring case and `_`/`-` separators.
+fn normalize_format_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Parses a texture format from any of its accepted names.
+///
+/// Matching ignores case and underscores, so the canonical names, the ctex
+/// spellings and the hardware-style aliases are all accepted.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{TextureError, TextureFormat};
+/// let accepted = [
+///     ("RGBA8888", TextureFormat::RGBA8888), ("Rgba8888", TextureFormat::RGBA8888), ("rgba8", TextureFormat::RGBA8888),
+///     ("RGB888", TextureFormat::RGB888), ("rgb8", TextureFormat::RGB888),
+///     ("Rgba5551", TextureFormat::RGBA5551), ("Rgba_5551", TextureFormat::RGBA5551), ("RGB5A1", TextureFormat::RGBA5551), ("Rgb5_a1", TextureFormat::RGBA5551),
+///     ("rgb565", TextureFormat::RGB565),
+///     ("Rgba4444", TextureFormat::RGBA4444), ("RGBA4", TextureFormat::RGBA4444),
+///     ("La88", TextureFormat::LA88), ("La_88", TextureFormat::LA88), ("LA8", TextureFormat::LA88), ("IA8", TextureFormat::LA88),
+///     ("Hl8", TextureFormat::HL8), ("HILO8", TextureFormat::HL8), ("Hilo8", TextureFormat::HL8), ("RG8", TextureFormat::HL8),
+///     ("L8", TextureFormat::L8), ("I8", TextureFormat::L8), ("a8", TextureFormat::A8),
+///     ("La44", TextureFormat::LA44), ("LA4", TextureFormat::LA44), ("IA4", TextureFormat::LA44),
+///     ("l4", TextureFormat::L4), ("I4", TextureFormat::L4), ("A4", TextureFormat::A4),
+///     ("Etc1", TextureFormat::ETC1), ("Rgb_etc1", TextureFormat::ETC1), ("ETC1_RGB8", TextureFormat::ETC1),
+///     ("Etc1_a4", TextureFormat::ETC1A4), ("ETC1A4", TextureFormat::ETC1A4), ("Rgba_etc1", TextureFormat::ETC1A4), ("Rgb_etc1_a4", TextureFormat::ETC1A4),
+///     ("Shadow", TextureFormat::Shadow), ("SHADOW", TextureFormat::Shadow), ("Shadow_DMP", TextureFormat::Shadow),
+///     ("Gas", TextureFormat::Gas), ("gas", TextureFormat::Gas), ("GAS_DMP", TextureFormat::Gas),
+/// ];
+///
+/// for (name, format) in accepted {
+///     assert_eq!(name.parse::<TextureFormat>().unwrap().name(), format.name(), "{}", name);
+/// }
+///
+/// // Every name in the table is accepted, however it's cased
+/// for format in TextureFormat::ALL {
+///     for name in [format.name(), format.ctex_name()].into_iter().chain(format.aliases().iter().copied()) {
+///         assert_eq!(name.parse::<TextureFormat>().unwrap().name(), format.name(), "{}", name);
+///         assert_eq!(name.to_lowercase().parse::<TextureFormat>().unwrap().name(), format.name(), "{}", name);
+///     }
+/// }
+///
+/// // But serialization always writes the canonical names
+/// assert_eq!("Rgb_etc1".parse::<TextureFormat>().unwrap().to_string(), "ETC1");
+/// assert_eq!("IA8".parse::<TextureFormat>().unwrap().ctex_name(), "La88");
+///
+/// assert_eq!("Rgba9999".parse::<TextureFormat>().err(), Some(TextureError::UnknownFormat("Rgba9999".to_string())));
+/// ```
+impl FromStr for TextureFormat {
+    type Err = TextureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = normalize_format_name(s);
+
+        TextureFormat::ALL.into_iter()
+            .find(|format| {
+                let (name, ctex_name, aliases) = format.names();
+                std::iter::once(&name).chain(&[ctex_name]).chain(aliases)
+                    .any(|alias| normalize_format_name(alias) == normalized)
+            })
+            .ok_or_else(|| TextureError::UnknownFormat(s.to_string()))
+    }
+}
+
+/// Displays the canonical name of the format.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::TextureFormat;
+/// assert_eq!(TextureFormat::ETC1A4.to_string(), "ETC1A4");
+///
+/// for format in TextureFormat::ALL {
+///     assert_eq!(format.to_string().parse::<TextureFormat>().unwrap().name(), format.name());
+/// }
+/// ```
+impl fmt::Display for TextureFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Converts a PICA hardware ID into a texture format.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{TextureError, TextureFormat};
+/// assert_eq!(TextureFormat::try_from(12u8).unwrap().name(), "ETC1");
+/// assert_eq!(TextureFormat::try_from(42u8).err(), Some(TextureError::UnknownHardwareId(42)));
+/// ```
+impl TryFrom<u8> for TextureFormat {
+    type Error = TextureError;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        TextureFormat::from_hw_id(id).ok_or(TextureError::UnknownHardwareId(id))
+    }
+}
+
+#[cfg(feature = "cli")]
+impl ValueEnum for TextureFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        &TextureFormat::ALL
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        let (name, ctex_name, aliases) = self.names();
+        Some(PossibleValue::new(name).alias(ctex_name).aliases(aliases.iter().copied()))
+    }
 }
 
 impl<'de> Deserialize<'de> for TextureFormat {
@@ -24,65 +264,605 @@ impl<'de> Deserialize<'de> for TextureFormat {
     where D: serde::Deserializer<'de>,
     {
         let s: String = Deserialize::deserialize(deserializer)?;
-        let normalized = s.replace('_', "").to_uppercase();
-
-        match normalized.as_str() {
-            "RGBA8888" => Ok(TextureFormat::RGBA8888),
-            "RGB888" => Ok(TextureFormat::RGB888),
-            "RGBA5551" => Ok(TextureFormat::RGBA5551),
-            "RGB565" => Ok(TextureFormat::RGB565),
-            "RGBA4444" => Ok(TextureFormat::RGBA4444),
-            "LA88" => Ok(TextureFormat::LA88),
-            "HL8" => Ok(TextureFormat::HL8),
-            "L8" => Ok(TextureFormat::L8),
-            "A8" => Ok(TextureFormat::A8),
-            "LA44" => Ok(TextureFormat::LA44),
-            "L4" => Ok(TextureFormat::L4),
-            "A4" => Ok(TextureFormat::A4),
-            "ETC1" => Ok(TextureFormat::ETC1),
-            "ETC1A4" => Ok(TextureFormat::ETC1A4),
-            other => Err(serde::de::Error::custom(format!("Unknown TextureFormat: {}", other))),
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl TextureFormat {
+    /// Returns the PICA hardware ID of this format, as used by binary containers
+    /// such as BCLIM, BFLIM and CTPK.
+    ///
+    /// Shadow and gas textures have no ID of their own: the texture unit
+    /// reads their 32-bit texels as RGBA8888 and the texture type says how to
+    /// use them, so they share its ID. The ID alone can't tell the three apart,
+    /// and [`from_hw_id`](Self::from_hw_id) always gives RGBA8888 for it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::TextureFormat;
+    /// assert_eq!(TextureFormat::RGBA8888.hw_id(), 0);
+    /// assert_eq!(TextureFormat::ETC1A4.hw_id(), 13);
+    /// assert_eq!(TextureFormat::Shadow.hw_id(), 0);
+    /// ```
+    pub fn hw_id(&self) -> u8 {
+        match self {
+            TextureFormat::RGBA8888 => 0,
+            TextureFormat::RGB888   => 1,
+            TextureFormat::RGBA5551 => 2,
+            TextureFormat::RGB565   => 3,
+            TextureFormat::RGBA4444 => 4,
+            TextureFormat::LA88     => 5,
+            TextureFormat::HL8      => 6,
+            TextureFormat::L8       => 7,
+            TextureFormat::A8       => 8,
+            TextureFormat::LA44     => 9,
+            TextureFormat::L4       => 10,
+            TextureFormat::A4       => 11,
+            TextureFormat::ETC1     => 12,
+            TextureFormat::ETC1A4   => 13,
+            TextureFormat::Shadow   |
+            TextureFormat::Gas      => 0,
         }
     }
+
+    /// Returns the format with the given PICA hardware ID, or `None` if the ID is unknown.
+    ///
+    /// ID 0 is always RGBA8888, since the shadow and gas formats share it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::TextureFormat;
+    /// for format in TextureFormat::ALL {
+    ///     let id = format.hw_id();
+    ///     assert_eq!(TextureFormat::from_hw_id(id).unwrap().hw_id(), id);
+    /// }
+    ///
+    /// assert!(TextureFormat::from_hw_id(14).is_none());
+    /// assert!(TextureFormat::from_hw_id(0xFF).is_none());
+    /// ```
+    pub fn from_hw_id(id: u8) -> Option<Self> {
+        TextureFormat::ALL.into_iter().find(|format| format.hw_id() == id)
+    }
+
+    /// The capability table for this format: bits per pixel, whether it
+    /// stores alpha, whether it decodes gray, and its block dimensions.
+    ///
+    /// Every question about what a format can hold is answered from this
+    /// table, so a new format only needs a row here.
+    fn properties(&self) -> (u32, bool, bool, (u32, u32)) {
+        match self {
+            TextureFormat::RGBA8888 => (32, true,  false, (1, 1)),
+            TextureFormat::RGB888   => (24, false, false, (1, 1)),
+            TextureFormat::RGBA5551 => (16, true,  false, (1, 1)),
+            TextureFormat::RGB565   => (16, false, false, (1, 1)),
+            TextureFormat::RGBA4444 => (16, true,  false, (1, 1)),
+            TextureFormat::LA88     => (16, true,  true,  (1, 1)),
+            TextureFormat::HL8      => (16, false, false, (1, 1)),
+            TextureFormat::L8       => ( 8, false, true,  (1, 1)),
+            TextureFormat::A8       => ( 8, true,  true,  (1, 1)),
+            TextureFormat::LA44     => ( 8, true,  true,  (1, 1)),
+            TextureFormat::L4       => ( 4, false, true,  (1, 1)),
+            TextureFormat::A4       => ( 4, true,  true,  (1, 1)),
+            TextureFormat::ETC1     => ( 4, false, false, (4, 4)),
+            TextureFormat::ETC1A4   => ( 8, true,  false, (4, 4)),
+            TextureFormat::Shadow   => (32, true,  true,  (1, 1)),
+            TextureFormat::Gas      => (32, true,  false, (1, 1)),
+        }
+    }
+
+    /// Returns the number of bits used to store a single pixel in this format.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::TextureFormat;
+    /// assert_eq!(TextureFormat::RGB888.bits_per_pixel(), 24);
+    /// assert_eq!(TextureFormat::ETC1.bits_per_pixel(), 4);
+    ///
+    /// // Every format packs whole pixels into bytes, or pixel pairs into 4-bit nibbles
+    /// for format in TextureFormat::ALL {
+    ///     assert!([4, 8, 16, 24, 32].contains(&format.bits_per_pixel()), "{}", format);
+    /// }
+    /// ```
+    pub fn bits_per_pixel(&self) -> u32 {
+        self.properties().0
+    }
+
+    /// Returns whether the format stores an alpha channel. Formats without
+    /// one decode fully opaque.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::{decode_raw, synthetic::synthetic_texture, DecodeOptions, TextureFormat};
+    /// assert!(TextureFormat::ETC1A4.has_alpha());
+    /// assert!(!TextureFormat::RGB565.has_alpha());
+    ///
+    /// // Formats with alpha keep some of it; formats without it always decode opaque
+    /// for format in TextureFormat::ALL.into_iter().filter(TextureFormat::can_decode) {
+    ///     let texture = synthetic_texture(&format, 16, 16, 3);
+    ///     let decoded = decode_raw(texture.data(), 16, 16, &format, &DecodeOptions::default()).unwrap().to_rgba8();
+    ///     assert_eq!(decoded.pixels().any(|pixel| pixel[3] != 255), format.has_alpha(), "{}", format);
+    /// }
+    /// ```
+    pub fn has_alpha(&self) -> bool {
+        self.properties().1
+    }
+
+    /// Returns whether the format stores blocks of pixels compressed
+    /// together rather than each pixel on its own: ETC1 and ETC1A4.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::TextureFormat;
+    /// let compressed: Vec<_> = TextureFormat::ALL.into_iter().filter(TextureFormat::is_compressed).collect();
+    /// assert_eq!(compressed, [TextureFormat::ETC1, TextureFormat::ETC1A4]);
+    ///
+    /// // Each block is a whole number of bytes
+    /// for format in compressed {
+    ///     let (width, height) = format.block_dims();
+    ///     assert_eq!(format.bits_per_pixel() * width * height % 8, 0);
+    /// }
+    /// ```
+    pub fn is_compressed(&self) -> bool {
+        self.block_dims() != (1, 1)
+    }
+
+    /// Returns whether the format decodes to gray: luminance or alpha only,
+    /// with no color, so every decoded pixel has equal red, green and blue.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::{decode_raw, synthetic::synthetic_texture, DecodeOptions, TextureFormat};
+    /// assert!(TextureFormat::LA44.is_grayscale());
+    /// assert!(!TextureFormat::HL8.is_grayscale());
+    ///
+    /// for format in TextureFormat::ALL.into_iter().filter(TextureFormat::can_decode) {
+    ///     let texture = synthetic_texture(&format, 16, 16, 5);
+    ///     let decoded = decode_raw(texture.data(), 16, 16, &format, &DecodeOptions::default()).unwrap().to_rgba8();
+    ///     let gray = decoded.pixels().all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2]);
+    ///     assert_eq!(gray, format.is_grayscale(), "{}", format);
+    /// }
+    /// ```
+    pub fn is_grayscale(&self) -> bool {
+        self.properties().2
+    }
+
+    /// Returns the `(width, height)` of the smallest unit of data this format
+    /// stores independently: 4x4 blocks for ETC1/ETC1A4, single pixels otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::TextureFormat;
+    /// assert_eq!(TextureFormat::ETC1A4.block_dims(), (4, 4));
+    /// assert_eq!(TextureFormat::LA88.block_dims(), (1, 1));
+    ///
+    /// // Blocks always fit a whole number of times in an 8x8 tile
+    /// for format in TextureFormat::ALL {
+    ///     let (width, height) = format.block_dims();
+    ///     assert!(8 % width == 0 && 8 % height == 0, "{}", format);
+    /// }
+    /// ```
+    pub fn block_dims(&self) -> (u32, u32) {
+        self.properties().3
+    }
+
+    /// Returns whether this build can encode and decode the format.
+    ///
+    /// Encoding ETC1 and ETC1A4 needs an ETC1 compressor, built with the
+    /// `etc1` or `etc1-rust` feature (see [`etc1::Backend`](crate::pica_texture::etc1::Backend));
+    /// they decode without one. Shadow textures can only be decoded, and gas
+    /// textures can't be converted at all. Every other format is always
+    /// available.
+    ///
+    /// Callers that only need to read a format should check
+    /// [`can_decode`](Self::can_decode) instead, which is true for shadow
+    /// textures.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::TextureFormat;
+    /// assert!(TextureFormat::RGBA8888.is_available());
+    /// assert_eq!(TextureFormat::ETC1.is_available(), cfg!(any(feature = "etc1", feature = "etc1-rust")));
+    /// assert!(!TextureFormat::Shadow.is_available());
+    /// ```
+    pub fn is_available(&self) -> bool {
+        self.encode_error().is_none() && self.decode_error().is_none()
+    }
+
+    /// Returns whether this build can decode the format.
+    ///
+    /// Shadow textures are rendered by the GPU rather than authored, so they
+    /// decode but can't be encoded. The layout of gas textures isn't
+    /// documented, so they can be read and written as raw data but not decoded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::TextureFormat;
+    /// assert!(TextureFormat::Shadow.can_decode());
+    /// assert!(!TextureFormat::Gas.can_decode());
+    /// assert!(TextureFormat::ETC1.can_decode());
+    /// ```
+    ///
+    /// Files using either format still read, and fail cleanly where they can't be converted:
+    ///
+    /// ```
+    /// # use image::DynamicImage;
+    /// # use pica_convert::pica_texture::{decode_texture, encode_texture, DecodeOptions, EncodeOptions, TextureError, TextureFormat};
+    /// # use pica_convert::serialization::ctex;
+    /// for (name, format) in [("Shadow", TextureFormat::Shadow), ("Gas", TextureFormat::Gas)] {
+    ///     let xml = format!(
+    ///         r#"<ImageTextureCtr Format="{}" Width="8" Height="8" Encoding="Hex"><Images><PixelBasedImageCtr>{}</PixelBasedImageCtr></Images></ImageTextureCtr>"#,
+    ///         name, "C0341280".repeat(64),
+    ///     );
+    ///     let texture = ctex::deserialize_from_slice(xml.as_bytes()).unwrap();
+    ///     assert_eq!(texture.format(), &format);
+    ///     assert_eq!(texture.data().len(), 256);
+    ///
+    ///     let decoded = decode_texture(&texture, &DecodeOptions::default());
+    ///     match format {
+    ///         TextureFormat::Shadow => assert_eq!(&decoded.unwrap().to_rgba8().as_raw()[..4], &[0x80, 0x80, 0x80, 0xC0]),
+    ///         _ => assert_eq!(decoded.err().unwrap().downcast_ref(), Some(&TextureError::DecodeUnsupported(format))),
+    ///     }
+    ///
+    ///     let err = encode_texture(&DynamicImage::new_rgba8(8, 8), &format, &EncodeOptions::default()).err().unwrap();
+    ///     assert_eq!(err.downcast_ref(), Some(&TextureError::EncodeUnsupported(format)));
+    /// }
+    /// ```
+    pub fn can_decode(&self) -> bool {
+        self.decode_error().is_none()
+    }
+
+    /// Returns whether the format's data can be stored in
+    /// [`TextureLayout::Linear`]: every format but ETC1 and ETC1A4, whose
+    /// compressed blocks only come tiled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::TextureFormat;
+    /// assert!(TextureFormat::L4.has_linear_layout());
+    /// assert!(!TextureFormat::ETC1A4.has_linear_layout());
+    /// ```
+    pub fn has_linear_layout(&self) -> bool {
+        !self.is_compressed()
+    }
+
+    /// The error storing this format in `layout` fails with, if it can't be.
+    pub(crate) fn layout_error(&self, layout: TextureLayout) -> Option<TextureError> {
+        match layout {
+            TextureLayout::Linear if !self.has_linear_layout() => Some(TextureError::LinearUnsupported(*self)),
+            _ => None,
+        }
+    }
+
+    /// The error encoding this format fails with, if this build can't encode it.
+    pub(crate) fn encode_error(&self) -> Option<TextureError> {
+        match self {
+            TextureFormat::Shadow | TextureFormat::Gas => Some(TextureError::EncodeUnsupported(*self)),
+            TextureFormat::ETC1 | TextureFormat::ETC1A4 if !cfg!(any(feature = "etc1", feature = "etc1-rust")) => Some(TextureError::FormatUnavailable(*self)),
+            _ => None,
+        }
+    }
+
+    /// The error encoding this format with `options` fails with: the ETC1
+    /// backend's, the format's own [`encode_error`](Self::encode_error), or its layout's.
+    ///
+    /// The backend comes first, so a build without the backend asked for
+    /// names it rather than reporting ETC1 as unavailable.
+    pub(crate) fn encode_options_error(&self, options: &EncodeOptions) -> Option<TextureError> {
+        let backend_error = match self {
+            TextureFormat::ETC1 | TextureFormat::ETC1A4 if !options.etc1_backend.is_available() => {
+                Some(TextureError::Etc1BackendUnavailable(options.etc1_backend))
+            }
+            _ => None,
+        };
+        backend_error.or_else(|| self.encode_error()).or_else(|| self.layout_error(options.layout))
+    }
+
+    /// The error decoding this format fails with, if this build can't decode it.
+    pub(crate) fn decode_error(&self) -> Option<TextureError> {
+        match self {
+            TextureFormat::Gas => Some(TextureError::DecodeUnsupported(*self)),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of bytes of texture data required for an image of the given size.
+    ///
+    /// Texture data is stored in 8x8 tiles, so dimensions are rounded up to the
+    /// next multiple of 8. 4-bit formats pack two pixels per byte, and ETC1/ETC1A4
+    /// store each 4x4 block in 8/16 bytes.
+    ///
+    /// Dimensions too large to address saturate to `usize::MAX` rather than
+    /// overflowing, so no real data ever matches them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::TextureFormat;
+    /// let sizes = [(8, 8), (64, 32), (12, 20), (1, 1)];
+    /// let expected = [
+    ///     (TextureFormat::RGBA8888, [256, 8192, 1536, 256]),
+    ///     (TextureFormat::RGB888,   [192, 6144, 1152, 192]),
+    ///     (TextureFormat::RGBA5551, [128, 4096,  768, 128]),
+    ///     (TextureFormat::RGB565,   [128, 4096,  768, 128]),
+    ///     (TextureFormat::RGBA4444, [128, 4096,  768, 128]),
+    ///     (TextureFormat::LA88,     [128, 4096,  768, 128]),
+    ///     (TextureFormat::HL8,      [128, 4096,  768, 128]),
+    ///     (TextureFormat::L8,       [ 64, 2048,  384,  64]),
+    ///     (TextureFormat::A8,       [ 64, 2048,  384,  64]),
+    ///     (TextureFormat::LA44,     [ 64, 2048,  384,  64]),
+    ///     (TextureFormat::L4,       [ 32, 1024,  192,  32]),
+    ///     (TextureFormat::A4,       [ 32, 1024,  192,  32]),
+    ///     (TextureFormat::ETC1,     [ 32, 1024,  192,  32]),
+    ///     (TextureFormat::ETC1A4,   [ 64, 2048,  384,  64]),
+    ///     (TextureFormat::Shadow,   [256, 8192, 1536, 256]),
+    ///     (TextureFormat::Gas,      [256, 8192, 1536, 256]),
+    /// ];
+    ///
+    /// for (format, lens) in expected {
+    ///     for ((width, height), len) in sizes.into_iter().zip(lens) {
+    ///         assert_eq!(format.expected_data_len(width, height), len, "{:?} at {}x{}", format, width, height);
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(TextureFormat::RGBA8888.expected_data_len(u32::MAX, u32::MAX), usize::MAX);
+    /// ```
+    pub fn expected_data_len(&self, width: u32, height: u32) -> usize {
+        let width = (width as u64).next_multiple_of(8);
+        let height = (height as u64).next_multiple_of(8);
+        width.checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(self.bits_per_pixel() as u64))
+            .and_then(|bits| usize::try_from(bits / 8).ok())
+            .unwrap_or(usize::MAX)
+    }
+}
+
+/// Estimates the size of encoded texture data without encoding anything.
+///
+/// Each mip level halves the previous level's dimensions (down to 1 pixel)
+/// and is padded to whole tiles like the base level; see
+/// [`TextureFormat::expected_data_len`].
+///
+/// # Arguments
+///
+/// * `format` - The [`TextureFormat`] the texture would be encoded in.
+/// * `width` - The width of the base level in pixels.
+/// * `height` - The height of the base level in pixels.
+/// * `mip_levels` - The number of mip levels, including the base level.
+///
+/// # Returns
+///
+/// The total number of bytes of texture data across every mip level.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{estimate_encoded_size, TextureFormat};
+/// assert_eq!(estimate_encoded_size(&TextureFormat::RGBA8888, 8, 8, 1), 256);
+/// assert_eq!(estimate_encoded_size(&TextureFormat::ETC1, 8, 8, 1), 32);
+/// assert_eq!(estimate_encoded_size(&TextureFormat::RGBA8888, 1024, 1024, 1), 4 * 1024 * 1024);
+/// assert_eq!(estimate_encoded_size(&TextureFormat::ETC1A4, 1024, 1024, 1), 1024 * 1024);
+/// assert_eq!(estimate_encoded_size(&TextureFormat::RGB565, 256, 64, 1), 256 * 64 * 2);
+///
+/// // Mip levels below a single tile are still padded to 8x8
+/// assert_eq!(estimate_encoded_size(&TextureFormat::L8, 32, 16, 4), 512 + 128 + 64 + 64);
+/// assert_eq!(estimate_encoded_size(&TextureFormat::RGBA8888, 1024, 1024, 11), 5_593_088);
+///
+/// assert_eq!(estimate_encoded_size(&TextureFormat::A4, 64, 64, 0), 0);
+/// ```
+pub fn estimate_encoded_size(format: &TextureFormat, width: u32, height: u32, mip_levels: u32) -> u64 {
+    (0..mip_levels.min(32))
+        .map(|level| format.expected_data_len((width >> level).max(1), (height >> level).max(1)) as u64)
+        .sum()
+}
+
+/// The most mip levels a texture of the given dimensions can have, counting
+/// the base level and halving down to a single pixel.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::max_mip_levels;
+/// assert_eq!(max_mip_levels(1, 1), 1);
+/// assert_eq!(max_mip_levels(8, 8), 4);
+/// assert_eq!(max_mip_levels(1024, 16), 11);
+/// assert_eq!(max_mip_levels(24, 8), 5);
+/// ```
+pub fn max_mip_levels(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
 }
 
 impl Serialize for TextureFormat {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where S: serde::Serializer,
     {
-        let s = match self {
-            TextureFormat::RGBA8888 => "Rgba8888",
-            TextureFormat::RGB888   => "Rgb888",
-            TextureFormat::RGBA5551 => "Rgba5551",
-            TextureFormat::RGB565   => "Rgb565",
-            TextureFormat::RGBA4444 => "Rgba4444",
-            TextureFormat::LA88     => "La88",
-            TextureFormat::HL8      => "Hl8",
-            TextureFormat::L8       => "L8",
-            TextureFormat::A8       => "A8",
-            TextureFormat::LA44     => "La44",
-            TextureFormat::L4       => "L4",
-            TextureFormat::A4       => "A4",
-            TextureFormat::ETC1     => "Etc1",
-            TextureFormat::ETC1A4   => "Etc1_a4",
-        };
-        serializer.serialize_str(s)
+        serializer.serialize_str(self.ctex_name())
+    }
+}
+
+/// The order a texture's pixels are stored in.
+///
+/// Either way the data covers the texture padded to whole 8x8 tiles, so both
+/// layouts of a texture are the same length.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{decode_texture, encode_texture, DecodeOptions, EncodeOptions, TextureFormat, TextureLayout};
+/// # use pica_convert::pica_texture::synthetic::synthetic_image;
+/// let img = synthetic_image(20, 12, 4).into();
+/// let linear_options = EncodeOptions { layout: TextureLayout::Linear, ..Default::default() };
+///
+/// // Linear textures decode to the same image as tiled ones
+/// for format in TextureFormat::ALL.into_iter().filter(|format| format.is_available() && format.has_linear_layout()) {
+///     let tiled = encode_texture(&img, &format, &EncodeOptions::default()).unwrap();
+///     let linear = encode_texture(&img, &format, &linear_options).unwrap();
+///     assert_eq!(linear.layout(), TextureLayout::Linear);
+///     assert_eq!(decode_texture(&linear, &DecodeOptions::default()).unwrap(), decode_texture(&tiled, &DecodeOptions::default()).unwrap(), "{}", format);
+/// }
+///
+/// assert!(encode_texture(&img, &TextureFormat::ETC1, &linear_options).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum TextureLayout {
+    /// 8x8 tiles, as the PICA samples them (see [`swizzle`](crate::pica_texture::util::swizzle)).
+    #[default]
+    Tiled,
+    /// Rows of pixels, top to bottom, for code that tiles textures itself
+    /// when uploading them.
+    Linear,
+}
+
+impl TextureLayout {
+    /// Returns the layout's name, as ctex files record it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextureLayout::Tiled => "Tiled",
+            TextureLayout::Linear => "Linear",
+        }
     }
 }
 
+/// Raw PICA texture data, with the format and dimensions needed to decode it.
+///
+/// The data is laid out in 8x8 tiles, left to right and then top to bottom:
+/// the first tiles hold the top rows of the image. Encoding and decoding both
+/// use this orientation, so images come back the same way up. Textures can
+/// also hold their pixels in rows instead (see [`TextureLayout`]).
+///
+/// Textures compare equal when their format, dimensions, mip levels, original
+/// dimensions, layout and data all match. Their `Debug` output sums up the
+/// data as its length and a hash, rather than printing every byte.
+///
+/// A texture whose data lives somewhere else, such as a memory-mapped file,
+/// can be decoded or written out without copying it into a `PicaTexture`
+/// first; see [`PicaTextureRef`].
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{PicaTexture, TextureFormat};
+/// let texture = PicaTexture::try_new(TextureFormat::L8, 8, 8, (0..64).collect()).unwrap();
+/// assert_eq!(texture.clone(), texture);
+/// assert_ne!(texture.clone().with_original_dimensions(6, 8).unwrap(), texture);
+/// assert_ne!(PicaTexture::try_new(TextureFormat::A8, 8, 8, (0..64).collect()).unwrap(), texture);
+/// assert_ne!(PicaTexture::try_new(TextureFormat::L8, 8, 8, vec![0; 64]).unwrap(), texture);
+///
+/// let debug = format!("{:?}", texture);
+/// assert!(debug.starts_with("PicaTexture { format: L8, width: 8, height: 8, mip_levels: 1,"), "{}", debug);
+/// assert!(debug.contains("data: 64 bytes, hash "), "{}", debug);
+/// assert!(debug.len() < 200, "{}", debug);
+/// ```
+#[derive(Clone, PartialEq, Eq)]
 pub struct PicaTexture {
     format: TextureFormat,
     width: u32,
     height: u32,
+    mip_levels: u32,
+    original_dimensions: Option<(u32, u32)>,
+    layout: TextureLayout,
     data: Vec<u8>
 }
 
+impl fmt::Debug for PicaTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.view().debug_as("PicaTexture", f)
+    }
+}
+
 impl PicaTexture {
+    /// Creates a new PicaTexture, checking that `data` is the size required
+    /// by `format` at the given dimensions.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TextureError::DataLengthMismatch`] if the data length is wrong.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::{PicaTexture, TextureError, TextureFormat};
+    /// assert!(PicaTexture::try_new(TextureFormat::ETC1A4, 16, 16, vec![0; 256]).is_ok());
+    ///
+    /// let err = PicaTexture::try_new(TextureFormat::L4, 16, 16, vec![0; 256]).err();
+    /// assert_eq!(err, Some(TextureError::DataLengthMismatch { expected: 128, actual: 256 }));
+    /// ```
+    pub fn try_new(format: TextureFormat, width: u32, height: u32, data: Vec<u8>) -> Result<Self, TextureError> {
+        check_data_len(format.expected_data_len(width, height), &data)?;
+        Ok(Self::new_unchecked(format, width, height, data))
+    }
+
+    /// Creates a new PicaTexture holding a chain of `mip_levels` levels, the
+    /// base level first and each following level at half the size of the last.
+    ///
+    /// Every level is padded to whole tiles, so `data` must be the size given
+    /// by [`estimate_encoded_size`]. Textures are only ever decoded from their
+    /// base level; the rest of the chain is carried along so it can be written
+    /// back out.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TextureError::InvalidMipLevels`] if there are no levels or
+    /// more than [`max_mip_levels`] allows, or
+    /// [`TextureError::DataLengthMismatch`] if the data length is wrong.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::{PicaTexture, TextureError, TextureFormat};
+    /// // 16x16, 8x8, and 4x4 padded to 8x8
+    /// let texture = PicaTexture::try_with_mip_levels(TextureFormat::L8, 16, 16, 3, vec![0; 256 + 64 + 64]).unwrap();
+    /// assert_eq!(texture.mip_levels(), 3);
+    ///
+    /// // A single level is the same as try_new
+    /// assert_eq!(PicaTexture::try_with_mip_levels(TextureFormat::L8, 16, 16, 1, vec![0; 256]).unwrap().mip_levels(), 1);
+    /// assert_eq!(PicaTexture::try_new(TextureFormat::L8, 16, 16, vec![0; 256]).unwrap().mip_levels(), 1);
+    ///
+    /// let err = PicaTexture::try_with_mip_levels(TextureFormat::L8, 16, 16, 3, vec![0; 256 + 64]).err();
+    /// assert_eq!(err, Some(TextureError::DataLengthMismatch { expected: 384, actual: 320 }));
+    ///
+    /// let err = PicaTexture::try_with_mip_levels(TextureFormat::L8, 16, 16, 6, vec![0; 512]).err();
+    /// assert_eq!(err, Some(TextureError::InvalidMipLevels { levels: 6, max: 5 }));
+    /// assert!(PicaTexture::try_with_mip_levels(TextureFormat::L8, 16, 16, 0, Vec::new()).is_err());
+    /// ```
+    pub fn try_with_mip_levels(format: TextureFormat, width: u32, height: u32, mip_levels: u32, data: Vec<u8>) -> Result<Self, TextureError> {
+        check_data_len(mip_chain_len(&format, width, height, mip_levels)?, &data)?;
+        Ok(Self { mip_levels, ..Self::new_unchecked(format, width, height, data) })
+    }
+
+    /// Creates a new PicaTexture without validating the length of `data`.
+    ///
+    /// Kept for callers written before [`PicaTexture::try_new`] existed; it
+    /// behaves like [`PicaTexture::new_unchecked`].
+    ///
+    #[deprecated(note = "use `PicaTexture::try_new`, or `PicaTexture::new_unchecked` for data known to be well formed")]
     pub fn new(format: TextureFormat, width: u32, height: u32, data: Vec<u8>) -> Self {
+        Self::new_unchecked(format, width, height, data)
+    }
+
+    /// Creates a new PicaTexture without validating the length of `data`.
+    ///
+    /// Prefer [`PicaTexture::try_new`] unless the data is known to be well formed.
+    ///
+    pub fn new_unchecked(format: TextureFormat, width: u32, height: u32, data: Vec<u8>) -> Self {
         Self {
             format,
             width,
             height,
+            mip_levels: 1,
+            original_dimensions: None,
+            layout: TextureLayout::Tiled,
             data
         }
     }
@@ -107,4 +887,549 @@ impl PicaTexture {
         &self.format
     }
 
+    /// The number of mip levels in the texture's data, including the base level.
+    pub fn mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    /// The dimensions of the image the texture was padded from, if it was.
+    ///
+    /// Decoding with these as the [`DecodeOptions::crop`] gives back the
+    /// image without its padding.
+    pub fn original_dimensions(&self) -> Option<(u32, u32)> {
+        self.original_dimensions
+    }
+
+    /// Records that the texture holds a `width` x `height` image padded out
+    /// to the texture's dimensions, with the image in the top left corner.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TextureError::InvalidDimensions`] if either dimension is zero
+    /// or larger than the texture's.
+    ///
+    /// # Example
+    ///
+    /// A 200x120 image padded to 256x128 decodes back to 200x120:
+    ///
+    /// ```
+    /// # use image::{DynamicImage, Rgba, RgbaImage};
+    /// # use pica_convert::pica_texture::{DecodeOptions, EncodeOptions, PicaTexture, TextureFormat};
+    /// # use pica_convert::serialization::ctex;
+    /// let image = RgbaImage::from_fn(200, 120, |x, y| Rgba([x as u8, y as u8, 0x80, 0xFF]));
+    /// let mut padded = RgbaImage::new(256, 128);
+    /// image::imageops::replace(&mut padded, &image, 0, 0);
+    ///
+    /// let texture = PicaTexture::from_image(&DynamicImage::ImageRgba8(padded), TextureFormat::RGBA8888, &EncodeOptions::default())
+    ///     .unwrap()
+    ///     .with_original_dimensions(200, 120)
+    ///     .unwrap();
+    ///
+    /// // The original size survives a trip through a ctex file
+    /// let bytes = ctex::serialize_to_vec(&texture).unwrap();
+    /// let read_back = ctex::deserialize_from_slice(&bytes).unwrap();
+    /// assert_eq!(read_back.original_dimensions(), Some((200, 120)));
+    ///
+    /// let options = DecodeOptions { crop: read_back.original_dimensions(), ..Default::default() };
+    /// assert_eq!(read_back.to_image(&options).unwrap().to_rgba8(), image);
+    ///
+    /// assert!(read_back.with_original_dimensions(300, 120).is_err());
+    /// ```
+    pub fn with_original_dimensions(self, width: u32, height: u32) -> Result<Self, TextureError> {
+        check_original_dimensions(width, height, self.dimensions())?;
+        Ok(Self { original_dimensions: Some((width, height)), ..self })
+    }
+
+    /// The order the texture's pixels are stored in.
+    pub fn layout(&self) -> TextureLayout {
+        self.layout
+    }
+
+    /// Records that the texture's data is stored in `layout`, without
+    /// reordering it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TextureError::LinearUnsupported`] for a linear ETC1 or ETC1A4 texture.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::{PicaTexture, TextureFormat, TextureLayout};
+    /// let texture = PicaTexture::try_new(TextureFormat::L8, 8, 8, (0..64).collect()).unwrap();
+    /// assert_eq!(texture.layout(), TextureLayout::Tiled);
+    ///
+    /// let linear = texture.with_layout(TextureLayout::Linear).unwrap();
+    /// assert_eq!(linear.layout(), TextureLayout::Linear);
+    ///
+    /// let etc1 = PicaTexture::try_new(TextureFormat::ETC1, 8, 8, vec![0; 32]).unwrap();
+    /// assert!(etc1.with_layout(TextureLayout::Linear).is_err());
+    /// ```
+    pub fn with_layout(self, layout: TextureLayout) -> Result<Self, TextureError> {
+        if let Some(err) = self.format.layout_error(layout) {
+            return Err(err);
+        }
+        Ok(Self { layout, ..self })
+    }
+
+    /// Returns the texture's base level in the tiled layout the decoders
+    /// read, reordering it if the texture is linear.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::{PicaTexture, TextureFormat, TextureLayout};
+    /// # use pica_convert::pica_texture::util::unswizzle;
+    /// let tiled: Vec<u8> = (0..128).collect();
+    /// let texture = PicaTexture::try_new(TextureFormat::A8, 16, 8, tiled.clone()).unwrap();
+    /// assert_eq!(texture.tiled_base_level(), &tiled[..]);
+    ///
+    /// let linear = PicaTexture::try_new(TextureFormat::A8, 16, 8, unswizzle(&tiled, 16, 8, 1)).unwrap()
+    ///     .with_layout(TextureLayout::Linear)
+    ///     .unwrap();
+    /// assert_eq!(linear.tiled_base_level(), &tiled[..]);
+    /// ```
+    pub fn tiled_base_level(&self) -> Cow<'_, [u8]> {
+        self.view().tiled_base_level()
+    }
+
+    /// Decodes the single pixel at `x`, `y` of the base level to RGBA,
+    /// without decoding the rest of the texture.
+    ///
+    /// Only the texel, or for ETC1 and ETC1A4 the 4x4 block, holding the
+    /// pixel is read, so spot-checking a huge texture is cheap. The pixel is
+    /// the one [`decode_texture`] gives with default [`DecodeOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TextureError::PixelOutOfBounds`] if the pixel is outside
+    /// the texture, [`TextureError::DecodeUnsupported`] for gas textures,
+    /// [`TextureError::DataLengthMismatch`] if the data is too short, and
+    /// [`TextureError::InvalidBlock`] if the pixel's block can't be decoded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::{decode_texture, synthetic::synthetic_texture, DecodeOptions, TextureError, TextureFormat, TextureLayout};
+    /// for format in TextureFormat::ALL.into_iter().filter(TextureFormat::can_decode) {
+    ///     for (width, height) in [(8, 8), (20, 12), (64, 16)] {
+    ///         let texture = synthetic_texture(&format, width, height, 4);
+    ///         let decoded = decode_texture(&texture, &DecodeOptions::default()).unwrap().to_rgba8();
+    ///         for (x, y, pixel) in decoded.enumerate_pixels() {
+    ///             assert_eq!(texture.decode_pixel(x, y).unwrap(), pixel.0, "{} ({}, {}) of {}x{}", format, x, y, width, height);
+    ///         }
+    ///
+    ///         if format.has_linear_layout() {
+    ///             let linear = texture.clone().with_layout(TextureLayout::Linear).unwrap();
+    ///             let decoded = decode_texture(&linear, &DecodeOptions::default()).unwrap().to_rgba8();
+    ///             assert!(decoded.enumerate_pixels().all(|(x, y, pixel)| linear.decode_pixel(x, y).unwrap() == pixel.0), "linear {}", format);
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let texture = synthetic_texture(&TextureFormat::L8, 8, 8, 0);
+    /// assert_eq!(texture.decode_pixel(8, 0), Err(TextureError::PixelOutOfBounds { x: 8, y: 0, width: 8, height: 8 }));
+    /// ```
+    pub fn decode_pixel(&self, x: u32, y: u32) -> Result<[u8; 4], TextureError> {
+        self.view().decode_pixel(x, y)
+    }
+
+    /// Lazily decodes every pixel of the base level with
+    /// [`decode_pixel`](Self::decode_pixel), in rows from the top left,
+    /// yielding each one's coordinates and RGBA value.
+    ///
+    /// Nothing is decoded until it's asked for, so stopping early, or
+    /// sampling with [`Iterator::step_by`], skips the rest. Walking every
+    /// pixel of an ETC1 texture this way decodes each block 16 times;
+    /// [`decode_texture`] is faster for whole textures.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::{PicaTexture, TextureFormat};
+    /// let texture = PicaTexture::try_new(TextureFormat::L8, 8, 8, (0..64).collect()).unwrap();
+    /// let mut pixels = texture.decode_pixels();
+    /// assert_eq!(pixels.next().unwrap().unwrap(), (0, 0, [0, 0, 0, 255]));
+    /// assert_eq!(pixels.next().unwrap().unwrap(), (1, 0, [1, 1, 1, 255]));
+    /// assert_eq!(pixels.next().unwrap().unwrap(), (2, 0, [4, 4, 4, 255]));
+    ///
+    /// // A histogram of gray levels, without decoding to an image
+    /// let mut histogram = [0u32; 256];
+    /// for pixel in texture.decode_pixels() {
+    ///     let (_, _, [l, ..]) = pixel.unwrap();
+    ///     histogram[l as usize] += 1;
+    /// }
+    /// assert!(histogram.iter().take(64).all(|&count| count == 1));
+    /// assert_eq!(texture.decode_pixels().count(), 64);
+    /// ```
+    pub fn decode_pixels(&self) -> impl Iterator<Item = Result<(u32, u32, [u8; 4]), TextureError>> + '_ {
+        self.view().decode_pixels()
+    }
+
+    /// Borrows the texture as a [`PicaTextureRef`], without copying its data.
+    ///
+    /// Every function taking a `PicaTextureRef` also takes a `&PicaTexture`,
+    /// so this is only needed to hold on to the view itself.
+    ///
+    pub fn view(&self) -> PicaTextureRef<'_> {
+        PicaTextureRef {
+            format: self.format,
+            width: self.width,
+            height: self.height,
+            mip_levels: self.mip_levels,
+            original_dimensions: self.original_dimensions,
+            layout: self.layout,
+            data: &self.data,
+        }
+    }
+
+    /// Consumes the texture, returning its raw data without copying it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::{PicaTexture, TextureFormat};
+    /// let data = vec![0xAB; 1024 * 1024 * 4];
+    /// let ptr = data.as_ptr();
+    ///
+    /// let texture = PicaTexture::try_new(TextureFormat::RGBA8888, 1024, 1024, data).unwrap();
+    /// let data = texture.into_data();
+    ///
+    /// // The same allocation comes back out
+    /// assert_eq!(data.as_ptr(), ptr);
+    /// assert_eq!(data.len(), 1024 * 1024 * 4);
+    /// ```
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Consumes the texture, returning its format, width, height and raw data.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::pica_texture::{PicaTexture, TextureFormat};
+    /// let texture = PicaTexture::try_new(TextureFormat::L8, 512, 256, vec![0; 512 * 256]).unwrap();
+    /// let (format, width, height, data) = texture.into_parts();
+    ///
+    /// assert!(matches!(format, TextureFormat::L8));
+    /// assert_eq!((width, height), (512, 256));
+    /// assert_eq!(data.len(), 512 * 256);
+    /// ```
+    pub fn into_parts(self) -> (TextureFormat, u32, u32, Vec<u8>) {
+        (self.format, self.width, self.height, self.data)
+    }
+
+    /// Encodes a [`DynamicImage`] into a new PicaTexture.
+    ///
+    /// Shorthand for [`encode_texture`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use image::{DynamicImage, Rgba, RgbaImage};
+    /// # use pica_convert::pica_texture::{DecodeOptions, EncodeOptions, PicaTexture, TextureFormat};
+    /// let source = DynamicImage::ImageRgba8(RgbaImage::from_fn(16, 16, |x, y| Rgba([x as u8 * 16, y as u8 * 16, 128, 255])));
+    ///
+    /// // RGBA8888 is lossless, so the round trip is exact
+    /// let texture = PicaTexture::from_image(&source, TextureFormat::RGBA8888, &EncodeOptions::default()).unwrap();
+    /// assert_eq!(texture.to_image(&DecodeOptions::default()).unwrap(), source);
+    ///
+    /// // RGB565 quantizes each channel, so only expect values within a step
+    /// let texture = PicaTexture::from_image(&source, TextureFormat::RGB565, &EncodeOptions::default()).unwrap();
+    /// let decoded = texture.to_image(&DecodeOptions::default()).unwrap().to_rgba8();
+    /// for (a, b) in decoded.as_raw().iter().zip(source.to_rgba8().as_raw()) {
+    ///     assert!(a.abs_diff(*b) <= 8);
+    /// }
+    /// ```
+    pub fn from_image(img: &DynamicImage, format: TextureFormat, options: &EncodeOptions) -> Result<Self, Box<dyn std::error::Error>> {
+        encode_texture(img, &format, options)
+    }
+
+    /// Decodes this texture into a [`DynamicImage`].
+    ///
+    /// Shorthand for [`decode_texture`].
+    ///
+    pub fn to_image(&self, options: &DecodeOptions) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+        decode_texture(self, options)
+    }
+}
+
+/// A [`PicaTexture`] that borrows its data instead of owning it.
+///
+/// Decoding and writing a texture only ever read its data, so both take a
+/// `PicaTextureRef`, or anything that converts into one, such as a
+/// `&PicaTexture`. Building one over a slice lets a texture be decoded or
+/// written out straight from a memory-mapped file, or from a buffer another
+/// library owns, without first copying its data into a `Vec`.
+///
+/// Views are checked the same way as the textures they mirror, and compare
+/// and print the same way too.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{decode_texture, DecodeOptions, PicaTexture, PicaTextureRef, TextureFormat};
+/// # use pica_convert::serialization::ctex;
+/// let dump: Vec<u8> = (0..=255).cycle().take(4096).collect();
+///
+/// // The second 32x32 L8 texture in a dump of two, decoded in place
+/// let view = PicaTextureRef::try_new(TextureFormat::L8, 32, 32, &dump[1024..2048]).unwrap();
+/// assert_eq!(view.data().as_ptr(), dump[1024..].as_ptr());
+///
+/// let owned = view.to_texture();
+/// let options = DecodeOptions::default();
+/// assert_eq!(decode_texture(view, &options).unwrap(), decode_texture(&owned, &options).unwrap());
+/// assert_eq!(ctex::serialize_to_vec(view).unwrap(), ctex::serialize_to_vec(&owned).unwrap());
+/// assert_eq!(owned.view(), view);
+///
+/// assert!(PicaTextureRef::try_new(TextureFormat::L8, 32, 32, &dump[..1000]).is_err());
+/// assert!(format!("{:?}", view).starts_with("PicaTextureRef { format: L8, width: 32, height: 32,"));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PicaTextureRef<'a> {
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    mip_levels: u32,
+    original_dimensions: Option<(u32, u32)>,
+    layout: TextureLayout,
+    data: &'a [u8]
+}
+
+impl fmt::Debug for PicaTextureRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.debug_as("PicaTextureRef", f)
+    }
+}
+
+impl<'a> PicaTextureRef<'a> {
+    /// Creates a view of a single-level texture over `data`, checking that
+    /// it's the size required by `format` at the given dimensions.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TextureError::DataLengthMismatch`] if the data length is wrong.
+    ///
+    pub fn try_new(format: TextureFormat, width: u32, height: u32, data: &'a [u8]) -> Result<Self, TextureError> {
+        check_data_len(format.expected_data_len(width, height), data)?;
+        Ok(Self::new_unchecked(format, width, height, data))
+    }
+
+    /// Creates a view of a texture holding a chain of `mip_levels` levels,
+    /// as [`PicaTexture::try_with_mip_levels`] does.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TextureError::InvalidMipLevels`] if there are no levels or
+    /// more than [`max_mip_levels`] allows, or
+    /// [`TextureError::DataLengthMismatch`] if the data length is wrong.
+    ///
+    pub fn try_with_mip_levels(format: TextureFormat, width: u32, height: u32, mip_levels: u32, data: &'a [u8]) -> Result<Self, TextureError> {
+        check_data_len(mip_chain_len(&format, width, height, mip_levels)?, data)?;
+        Ok(Self { mip_levels, ..Self::new_unchecked(format, width, height, data) })
+    }
+
+    /// Creates a view without validating the length of `data`.
+    ///
+    /// Prefer [`PicaTextureRef::try_new`] unless the data is known to be well formed.
+    ///
+    pub fn new_unchecked(format: TextureFormat, width: u32, height: u32, data: &'a [u8]) -> Self {
+        Self {
+            format,
+            width,
+            height,
+            mip_levels: 1,
+            original_dimensions: None,
+            layout: TextureLayout::Tiled,
+            data
+        }
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn format(&self) -> &TextureFormat {
+        &self.format
+    }
+
+    /// The number of mip levels in the texture's data, including the base level.
+    pub fn mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    /// The dimensions of the image the texture was padded from, if it was.
+    pub fn original_dimensions(&self) -> Option<(u32, u32)> {
+        self.original_dimensions
+    }
+
+    /// Records that the texture holds a `width` x `height` image padded out
+    /// to the texture's dimensions, as [`PicaTexture::with_original_dimensions`] does.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TextureError::InvalidDimensions`] if either dimension is zero
+    /// or larger than the texture's.
+    ///
+    pub fn with_original_dimensions(self, width: u32, height: u32) -> Result<Self, TextureError> {
+        check_original_dimensions(width, height, self.dimensions())?;
+        Ok(Self { original_dimensions: Some((width, height)), ..self })
+    }
+
+    /// The order the texture's pixels are stored in.
+    pub fn layout(&self) -> TextureLayout {
+        self.layout
+    }
+
+    /// Records that the texture's data is stored in `layout`, without
+    /// reordering it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TextureError::LinearUnsupported`] for a linear ETC1 or ETC1A4 texture.
+    ///
+    pub fn with_layout(self, layout: TextureLayout) -> Result<Self, TextureError> {
+        if let Some(err) = self.format.layout_error(layout) {
+            return Err(err);
+        }
+        Ok(Self { layout, ..self })
+    }
+
+    /// Returns the texture's base level in the tiled layout the decoders
+    /// read, borrowing it unless the texture is linear and has to be reordered.
+    pub fn tiled_base_level(&self) -> Cow<'a, [u8]> {
+        let base_level = &self.data[..self.format.expected_data_len(self.width, self.height).min(self.data.len())];
+        if self.layout == TextureLayout::Tiled {
+            return Cow::Borrowed(base_level);
+        }
+
+        let (width, height) = (self.width.next_multiple_of(8), self.height.next_multiple_of(8));
+        Cow::Owned(match self.format.bits_per_pixel() {
+            4 => swizzle_4bpp(base_level, width, height),
+            bits => swizzle(base_level, width, height, bits as usize / 8),
+        })
+    }
+
+    /// Decodes the single pixel at `x`, `y` of the base level to RGBA; see
+    /// [`PicaTexture::decode_pixel`].
+    ///
+    /// # Errors
+    ///
+    /// As for [`PicaTexture::decode_pixel`].
+    ///
+    pub fn decode_pixel(&self, x: u32, y: u32) -> Result<[u8; 4], TextureError> {
+        decode::decode_pixel(*self, x, y)
+    }
+
+    /// Lazily decodes every pixel of the base level; see [`PicaTexture::decode_pixels`].
+    pub fn decode_pixels(self) -> impl Iterator<Item = Result<(u32, u32, [u8; 4]), TextureError>> + 'a {
+        (0..self.height).flat_map(move |y| {
+            (0..self.width).map(move |x| self.decode_pixel(x, y).map(|pixel| (x, y, pixel)))
+        })
+    }
+
+    /// Decodes the texture into a [`DynamicImage`].
+    ///
+    /// Shorthand for [`decode_texture`].
+    ///
+    pub fn to_image(&self, options: &DecodeOptions) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+        decode_texture(*self, options)
+    }
+
+    /// Copies the viewed data into a new [`PicaTexture`] with the same header.
+    pub fn to_texture(&self) -> PicaTexture {
+        PicaTexture {
+            format: self.format,
+            width: self.width,
+            height: self.height,
+            mip_levels: self.mip_levels,
+            original_dimensions: self.original_dimensions,
+            layout: self.layout,
+            data: self.data.to_vec(),
+        }
+    }
+
+    /// Writes the `Debug` output shared with [`PicaTexture`] under `name`.
+    fn debug_as(&self, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Textures run to megabytes, so the data is only identified, not shown
+        let mut hasher = std::hash::DefaultHasher::new();
+        self.data.hash(&mut hasher);
+
+        f.debug_struct(name)
+            .field("format", &self.format)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("mip_levels", &self.mip_levels)
+            .field("original_dimensions", &self.original_dimensions)
+            .field("layout", &self.layout)
+            .field("data", &format_args!("{} bytes, hash {:016x}", self.data.len(), hasher.finish()))
+            .finish()
+    }
+}
+
+impl<'a> From<&'a PicaTexture> for PicaTextureRef<'a> {
+    fn from(texture: &'a PicaTexture) -> Self {
+        texture.view()
+    }
+}
+
+/// Checks that texture data is `expected` bytes long.
+fn check_data_len(expected: usize, data: &[u8]) -> Result<(), TextureError> {
+    if data.len() != expected {
+        return Err(TextureError::DataLengthMismatch { expected, actual: data.len() });
+    }
+    Ok(())
+}
+
+/// Returns the length of a chain of `mip_levels` levels, if that many fit.
+fn mip_chain_len(format: &TextureFormat, width: u32, height: u32, mip_levels: u32) -> Result<usize, TextureError> {
+    let max = max_mip_levels(width, height);
+    if mip_levels == 0 || mip_levels > max {
+        return Err(TextureError::InvalidMipLevels { levels: mip_levels, max });
+    }
+    Ok(usize::try_from(estimate_encoded_size(format, width, height, mip_levels)).unwrap_or(usize::MAX))
+}
+
+/// Checks that a `width` x `height` image fits in a texture of `dimensions`.
+fn check_original_dimensions(width: u32, height: u32, dimensions: (u32, u32)) -> Result<(), TextureError> {
+    if width == 0 || height == 0 || width > dimensions.0 || height > dimensions.1 {
+        return Err(TextureError::InvalidDimensions {
+            width,
+            height,
+            reason: format!("don't fit in the {}x{} texture", dimensions.0, dimensions.1),
+        });
+    }
+    Ok(())
+}
+
+/// Decodes a [`PicaTexture`] with the default [`DecodeOptions`].
+///
+/// # Example
+///
+/// ```
+/// # use image::DynamicImage;
+/// # use pica_convert::pica_texture::{PicaTexture, TextureFormat};
+/// let texture = PicaTexture::try_new(TextureFormat::A8, 8, 8, vec![0x80; 64]).unwrap();
+/// let image = DynamicImage::try_from(&texture).unwrap();
+/// assert_eq!(image.width(), 8);
+/// ```
+impl TryFrom<&PicaTexture> for DynamicImage {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(texture: &PicaTexture) -> Result<Self, Self::Error> {
+        texture.to_image(&DecodeOptions::default())
+    }
 }
\ No newline at end of file
@@ -1,16 +1,49 @@
+use std::sync::{Arc, Mutex};
 
-/// Vertically Flips a buffer of raw texture data.
+use crate::pica_texture::TextureError;
+
+/// Vertically flips a buffer of raw texture data in place.
 ///
 /// # Arguments
 /// 
-/// * `data` - A byte slice containing the raw texture data.
+/// * `data` - A byte slice containing the raw, row-major texture data.
 /// * `width` - The width of the texture in pixels.
 /// * `height` - The height of the texture in pixels.
-/// 
-pub fn flip_vertical(data: &mut [u8], width: u32, height: u32) {
-    let row_bytes =(width * 4) as usize;
+/// * `bytes_per_pixel` - The size of each pixel in bytes.
+///
+/// # Errors
+///
+/// Returns [`TextureError::DataLengthMismatch`] if `data` is not exactly
+/// `width * height * bytes_per_pixel` bytes long.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::util::flip_vertical;
+/// for bytes_per_pixel in 1..=4 {
+///     for height in [1, 2, 3, 5] {
+///         let width = 3;
+///         let row_bytes = width as usize * bytes_per_pixel;
+///         let mut data: Vec<u8> = (0..row_bytes * height as usize).map(|i| i as u8).collect();
+///         let original = data.clone();
+///
+///         flip_vertical(&mut data, width, height, bytes_per_pixel).unwrap();
+///
+///         let flipped_rows: Vec<&[u8]> = original.chunks(row_bytes).rev().collect();
+///         assert_eq!(data, flipped_rows.concat());
+///     }
+/// }
+///
+/// // Buffers that don't match the dimensions are rejected
+/// let mut data = vec![0u8; 10];
+/// assert!(flip_vertical(&mut data, 2, 2, 3).is_err());
+/// ```
+pub fn flip_vertical(data: &mut [u8], width: u32, height: u32, bytes_per_pixel: usize) -> Result<(), TextureError> {
+    let row_bytes = width as usize * bytes_per_pixel;
     let img_size = row_bytes * height as usize;
-    assert_eq!(data.len(), img_size);
+    if data.len() != img_size {
+        return Err(TextureError::DataLengthMismatch { expected: img_size, actual: data.len() });
+    }
 
     for y in 0..(height as usize / 2) {
         let top_start = y * row_bytes;
@@ -19,8 +52,586 @@ pub fn flip_vertical(data: &mut [u8], width: u32, height: u32) {
         let (top, bot) = data.split_at_mut(bot_start);
         top[top_start..top_start + row_bytes].swap_with_slice(&mut bot[..row_bytes]);
     }
+
+    Ok(())
+}
+
+/// Horizontally flips a buffer of raw texture data in place.
+///
+/// Pixels are reversed within each row, keeping the bytes of each pixel in order.
+///
+/// # Arguments
+/// 
+/// * `data` - A byte slice containing the raw, row-major texture data.
+/// * `width` - The width of the texture in pixels.
+/// * `height` - The height of the texture in pixels.
+/// * `bytes_per_pixel` - The size of each pixel in bytes.
+///
+/// # Errors
+///
+/// Returns [`TextureError::DataLengthMismatch`] if `data` is not exactly
+/// `width * height * bytes_per_pixel` bytes long.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::util::{flip_horizontal, flip_vertical};
+/// for bytes_per_pixel in 1..=4 {
+///     // An asymmetric 3x2 pattern, with a distinct value in every byte
+///     let mut data: Vec<u8> = (0..3 * 2 * bytes_per_pixel as u8).collect();
+///     let pixels: Vec<Vec<u8>> = data.chunks(bytes_per_pixel).map(<[u8]>::to_vec).collect();
+///
+///     flip_horizontal(&mut data, 3, 2, bytes_per_pixel).unwrap();
+///     let mirrored = [2, 1, 0, 5, 4, 3].map(|i| pixels[i].clone()).concat();
+///     assert_eq!(data, mirrored);
+///
+///     // Mirroring and flipping together is a 180 degree rotation
+///     flip_vertical(&mut data, 3, 2, bytes_per_pixel).unwrap();
+///     let rotated = [5, 4, 3, 2, 1, 0].map(|i| pixels[i].clone()).concat();
+///     assert_eq!(data, rotated);
+/// }
+///
+/// let mut data = vec![0u8; 10];
+/// assert!(flip_horizontal(&mut data, 2, 2, 3).is_err());
+/// ```
+pub fn flip_horizontal(data: &mut [u8], width: u32, height: u32, bytes_per_pixel: usize) -> Result<(), TextureError> {
+    let row_bytes = width as usize * bytes_per_pixel;
+    let img_size = row_bytes * height as usize;
+    if data.len() != img_size || bytes_per_pixel == 0 {
+        return Err(TextureError::DataLengthMismatch { expected: img_size, actual: data.len() });
+    }
+
+    for row in data.chunks_exact_mut(row_bytes.max(1)) {
+        // Reversing the whole row reverses each pixel's bytes too, so put those back
+        row.reverse();
+        for pixel in row.chunks_exact_mut(bytes_per_pixel) {
+            pixel.reverse();
+        }
+    }
+
+    Ok(())
+}
+
+/// Crops a buffer of raw texture data in place to its top left
+/// `crop_width` x `crop_height` pixels.
+///
+/// # Arguments
+///
+/// * `data` - The raw, row-major texture data, which is truncated to the cropped size.
+/// * `width` - The width of the texture in pixels.
+/// * `height` - The height of the texture in pixels.
+/// * `crop_width` - The width to crop to in pixels.
+/// * `crop_height` - The height to crop to in pixels.
+/// * `bytes_per_pixel` - The size of each pixel in bytes.
+///
+/// # Errors
+///
+/// Returns [`TextureError::DataLengthMismatch`] if `data` is not exactly
+/// `width * height * bytes_per_pixel` bytes long, or
+/// [`TextureError::InvalidDimensions`] if the crop is empty or larger than
+/// the texture.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::util::crop;
+/// let mut data: Vec<u8> = (0..12).collect();
+/// crop(&mut data, 4, 3, 2, 2, 1).unwrap();
+/// assert_eq!(data, [0, 1, 4, 5]);
+///
+/// let mut data = vec![0u8; 12];
+/// assert!(crop(&mut data, 4, 3, 5, 2, 1).is_err());
+/// assert!(crop(&mut data, 4, 3, 0, 2, 1).is_err());
+/// assert!(crop(&mut data, 3, 3, 2, 2, 1).is_err());
+/// ```
+pub fn crop(data: &mut Vec<u8>, width: u32, height: u32, crop_width: u32, crop_height: u32, bytes_per_pixel: usize) -> Result<(), TextureError> {
+    let row_bytes = width as usize * bytes_per_pixel;
+    let img_size = row_bytes * height as usize;
+    if data.len() != img_size {
+        return Err(TextureError::DataLengthMismatch { expected: img_size, actual: data.len() });
+    }
+    if crop_width == 0 || crop_height == 0 || crop_width > width || crop_height > height {
+        return Err(TextureError::InvalidDimensions {
+            width: crop_width,
+            height: crop_height,
+            reason: format!("can't be cropped from a {}x{} texture", width, height),
+        });
+    }
+
+    let crop_row_bytes = crop_width as usize * bytes_per_pixel;
+    for y in 1..crop_height as usize {
+        data.copy_within(y * row_bytes..y * row_bytes + crop_row_bytes, y * crop_row_bytes);
+    }
+    data.truncate(crop_row_bytes * crop_height as usize);
+
+    Ok(())
+}
+
+/// Reorders row-major pixel data into the PICA's tiled layout.
+///
+/// The PICA stores textures as a sequence of 8x8 pixel tiles, left to right
+/// and then top to bottom. Within a tile, pixels follow a Morton (Z-order)
+/// curve: the bits of the pixel's x and y offsets in the tile are interleaved
+/// as `y2 x2 y1 x1 y0 x0` to give its position in the tile. Partial tiles at
+/// the right and bottom edges are padded to whole tiles, as the hardware
+/// expects, by repeating the nearest edge pixel, like the texture encoders.
+///
+/// On x86 and x86-64, whole tiles of 1, 2 and 4-byte pixels are reordered
+/// with SSE2 when the CPU supports it. The output is identical to
+/// [`swizzle_scalar`].
+///
+/// # Arguments
+///
+/// * `data` - Row-major pixel data, `bytes_per_pixel` bytes per pixel.
+/// * `width` - The width of the texture in pixels.
+/// * `height` - The height of the texture in pixels.
+/// * `bytes_per_pixel` - The size of each pixel in bytes.
+///
+/// # Returns
+///
+/// A `Vec<u8>` of tiled pixel data, `bytes_per_pixel` bytes for each pixel
+/// of the texture padded to whole 8x8 tiles.
+///
+/// # Panics
+///
+/// Panics if `data` is shorter than `width * height * bytes_per_pixel` bytes.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::util::{swizzle, swizzle_scalar, unswizzle};
+/// // One 8x8 tile of 1-byte pixels numbered by their row-major index
+/// let linear: Vec<u8> = (0..64).collect();
+/// let tiled = swizzle(&linear, 8, 8, 1);
+/// assert_eq!(&tiled[..8], &[0, 1, 8, 9, 2, 3, 10, 11]);
+///
+/// // Round trips for every pixel size and several texture sizes
+/// for bytes_per_pixel in 1..=4 {
+///     for (width, height) in [(8, 8), (16, 8), (8, 32), (64, 64), (12, 20)] {
+///         let len = width as usize * height as usize * bytes_per_pixel;
+///         let data: Vec<u8> = (0..len).map(|i| (i * 31 % 251) as u8).collect();
+///
+///         let tiled = swizzle(&data, width, height, bytes_per_pixel);
+///         assert_eq!(unswizzle(&tiled, width, height, bytes_per_pixel), data);
+///     }
+/// }
+///
+/// // A 12x20 texture is stored as 16x24, repeating its last column and row
+/// let data: Vec<u8> = (0..12 * 20).map(|i| i as u8).collect();
+/// let tiled = swizzle(&data, 12, 20, 1);
+/// assert_eq!(tiled.len(), 16 * 24);
+/// let padded: Vec<u8> = (0..16 * 24).map(|i: u32| data[((i / 16).min(19) * 12 + (i % 16).min(11)) as usize]).collect();
+/// assert_eq!(tiled, swizzle(&padded, 16, 24, 1));
+///
+/// // The SIMD path matches the scalar one on random images, including partial tiles
+/// let mut seed = 0x2545F491u32;
+/// for bytes_per_pixel in 1..=4 {
+///     for (width, height) in [(64, 64), (72, 40), (203, 141), (5, 3)] {
+///         let data: Vec<u8> = (0..width as usize * height as usize * bytes_per_pixel)
+///             .map(|_| { seed ^= seed << 13; seed ^= seed >> 17; seed ^= seed << 5; seed as u8 })
+///             .collect();
+///         assert_eq!(swizzle(&data, width, height, bytes_per_pixel), swizzle_scalar(&data, width, height, bytes_per_pixel));
+///     }
+/// }
+/// ```
+pub fn swizzle(data: &[u8], width: u32, height: u32, bytes_per_pixel: usize) -> Vec<u8> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if matches!(bytes_per_pixel, 1 | 2 | 4) && is_x86_feature_detected!("sse2") {
+        return swizzle_sse2(data, width, height, bytes_per_pixel);
+    }
+
+    swizzle_scalar(data, width, height, bytes_per_pixel)
+}
+
+/// Reorders row-major pixel data into the PICA's tiled layout, one pixel at a time.
+///
+/// This is the portable implementation behind [`swizzle`], kept public so the
+/// SIMD path can be checked and benchmarked against it.
+///
+/// # Panics
+///
+/// Panics if `data` is shorter than `width * height * bytes_per_pixel` bytes.
+///
+pub fn swizzle_scalar(data: &[u8], width: u32, height: u32, bytes_per_pixel: usize) -> Vec<u8> {
+    let len = width as usize * height as usize * bytes_per_pixel;
+    assert!(data.len() >= len, "pixel data is {} bytes, expected at least {}", data.len(), len);
+
+    let mut output = Vec::with_capacity(padded_len(width, height) * bytes_per_pixel);
+    for (x, y) in tiled_coords(width, height) {
+        let src = (y.min(height - 1) as usize * width as usize + x.min(width - 1) as usize) * bytes_per_pixel;
+        output.extend_from_slice(&data[src..src + bytes_per_pixel]);
+    }
+    output
+}
+
+/// Implements [`swizzle`] with SSE2 for tiles inside the image, falling back
+/// to per-pixel copies to pad the partial tiles at the edges.
+///
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn swizzle_sse2(data: &[u8], width: u32, height: u32, bytes_per_pixel: usize) -> Vec<u8> {
+    let len = width as usize * height as usize * bytes_per_pixel;
+    assert!(data.len() >= len, "pixel data is {} bytes, expected at least {}", data.len(), len);
+
+    let stride = width as usize * bytes_per_pixel;
+    let tile_bytes = 64 * bytes_per_pixel;
+
+    let mut output = vec![0; padded_len(width, height) * bytes_per_pixel];
+    let mut out = 0;
+    for ty in (0..height).step_by(8) {
+        for tx in (0..width).step_by(8) {
+            if tx + 8 <= width && ty + 8 <= height {
+                let src = ty as usize * stride + tx as usize * bytes_per_pixel;
+                // SAFETY: the caller checked that SSE2 is available
+                unsafe { swizzle_tile_sse2(&data[src..], stride, &mut output[out..out + tile_bytes], bytes_per_pixel) };
+                out += tile_bytes;
+                continue;
+            }
+
+            for &px in SWIZZLE_LUT.iter() {
+                let (x, y) = ((tx + (px & 7)).min(width - 1), (ty + (px >> 3)).min(height - 1));
+                let src = (y as usize * width as usize + x as usize) * bytes_per_pixel;
+                output[out..out + bytes_per_pixel].copy_from_slice(&data[src..src + bytes_per_pixel]);
+                out += bytes_per_pixel;
+            }
+        }
+    }
+    output
+}
+
+/// Reorders the 8x8 tile starting at `data[0]` into `tile`.
+///
+/// Each 4x2 block of pixels is stored as `row0[0..2] row1[0..2] row0[2..4] row1[2..4]`,
+/// so interleaving pixel pairs from two rows lays out whole blocks at once.
+/// Rows `y` and `y + 1` land at tile offset `(y & 2) * 4 + (y & 4) * 8`, with
+/// the right half of the tile 16 pixels further on.
+///
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+fn swizzle_tile_sse2(data: &[u8], stride: usize, tile: &mut [u8], bytes_per_pixel: usize) {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    let row_bytes = 8 * bytes_per_pixel;
+    let half = 16 * bytes_per_pixel;
+
+    for y in (0..8).step_by(2) {
+        let row0 = &data[y * stride..y * stride + row_bytes];
+        let row1 = &data[(y + 1) * stride..(y + 1) * stride + row_bytes];
+        let base = ((y & 2) * 4 + (y & 4) * 8) * bytes_per_pixel;
+        let dst = &mut tile[base..base + half + row_bytes];
+
+        // SAFETY: every load and store stays within the row and tile slices above
+        unsafe {
+            let src0 = row0.as_ptr() as *const __m128i;
+            let src1 = row1.as_ptr() as *const __m128i;
+            let out = dst.as_mut_ptr() as *mut __m128i;
+
+            match bytes_per_pixel {
+                4 => {
+                    let (a0, b0) = (_mm_loadu_si128(src0), _mm_loadu_si128(src0.add(1)));
+                    let (a1, b1) = (_mm_loadu_si128(src1), _mm_loadu_si128(src1.add(1)));
+                    _mm_storeu_si128(out, _mm_unpacklo_epi64(a0, a1));
+                    _mm_storeu_si128(out.add(1), _mm_unpackhi_epi64(a0, a1));
+                    _mm_storeu_si128(out.add(4), _mm_unpacklo_epi64(b0, b1));
+                    _mm_storeu_si128(out.add(5), _mm_unpackhi_epi64(b0, b1));
+                }
+                2 => {
+                    let (a0, a1) = (_mm_loadu_si128(src0), _mm_loadu_si128(src1));
+                    _mm_storeu_si128(out, _mm_unpacklo_epi32(a0, a1));
+                    _mm_storeu_si128(out.add(2), _mm_unpackhi_epi32(a0, a1));
+                }
+                1 => {
+                    let interleaved = _mm_unpacklo_epi16(_mm_loadl_epi64(src0), _mm_loadl_epi64(src1));
+                    _mm_storel_epi64(out, interleaved);
+                    _mm_storel_epi64(out.add(1), _mm_srli_si128(interleaved, 8));
+                }
+                _ => unreachable!("unsupported pixel size {}", bytes_per_pixel),
+            }
+        }
+    }
+}
+
+/// Reorders tiled PICA pixel data into row-major order.
+///
+/// This is the inverse of [`swizzle`], which describes the tile layout.
+///
+/// # Arguments
+///
+/// * `data` - Tiled pixel data, `bytes_per_pixel` bytes per pixel, with partial
+///   tiles padded to whole ones as [`swizzle`] writes them.
+/// * `width` - The width of the texture in pixels.
+/// * `height` - The height of the texture in pixels.
+/// * `bytes_per_pixel` - The size of each pixel in bytes.
+///
+/// # Returns
+///
+/// A `Vec<u8>` of `width * height * bytes_per_pixel` bytes of row-major pixel
+/// data. The padding is skipped.
+///
+/// # Panics
+///
+/// Panics if `data` is shorter than the padded tiles.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::util::unswizzle;
+/// let tiled: Vec<u8> = (0..64).collect();
+/// let linear = unswizzle(&tiled, 8, 8, 1);
+///
+/// // The second row of the tile starts with the third pixel stored
+/// assert_eq!(&linear[8..12], &[2, 3, 6, 7]);
+/// ```
+pub fn unswizzle(data: &[u8], width: u32, height: u32, bytes_per_pixel: usize) -> Vec<u8> {
+    let tiled_len = padded_len(width, height) * bytes_per_pixel;
+    assert!(data.len() >= tiled_len, "pixel data is {} bytes, expected at least {}", data.len(), tiled_len);
+
+    let mut output = vec![0; width as usize * height as usize * bytes_per_pixel];
+    for (i, (x, y)) in tiled_coords(width, height).enumerate() {
+        if x >= width || y >= height {
+            continue;
+        }
+        let src = i * bytes_per_pixel;
+        let dst = (y as usize * width as usize + x as usize) * bytes_per_pixel;
+        output[dst..dst + bytes_per_pixel].copy_from_slice(&data[src..src + bytes_per_pixel]);
+    }
+    output
+}
+
+/// Reorders row-major 4-bit pixel data into the PICA's tiled layout.
+///
+/// Works like [`swizzle`], with two pixels packed into each byte and the
+/// first pixel of each pair in the low nibble.
+///
+/// # Arguments
+///
+/// * `data` - Row-major packed 4-bit pixel data.
+/// * `width` - The width of the texture in pixels.
+/// * `height` - The height of the texture in pixels.
+///
+/// # Returns
+///
+/// A `Vec<u8>` of tiled pixel data, half a byte for each pixel of the
+/// texture padded to whole 8x8 tiles.
+///
+/// # Panics
+///
+/// Panics if `data` holds fewer than `width * height` pixels.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::util::{swizzle_4bpp, unswizzle_4bpp};
+/// // Pixels (0, 0), (1, 0), (0, 1) and (1, 1) come first in a tile
+/// let mut linear = vec![0u8; 32];
+/// linear[0] = 0x21;
+/// linear[4] = 0x43;
+/// assert_eq!(swizzle_4bpp(&linear, 8, 8)[..2], [0x21, 0x43]);
+///
+/// for (width, height) in [(8, 8), (16, 8), (8, 32), (64, 64)] {
+///     let len = (width * height / 2) as usize;
+///     let data: Vec<u8> = (0..len).map(|i| (i * 37 % 256) as u8).collect();
+///     assert_eq!(unswizzle_4bpp(&swizzle_4bpp(&data, width, height), width, height), data);
+/// }
+/// ```
+pub fn swizzle_4bpp(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let pixels = width as usize * height as usize;
+    assert!(data.len() * 2 >= pixels, "pixel data is {} bytes, expected at least {}", data.len(), pixels.div_ceil(2));
+
+    let mut output = vec![0; padded_len(width, height) / 2];
+    for (i, (x, y)) in tiled_coords(width, height).enumerate() {
+        let src = y.min(height - 1) as usize * width as usize + x.min(width - 1) as usize;
+        set_nibble(&mut output, i, get_nibble(data, src));
+    }
+    output
+}
+
+/// Reorders tiled 4-bit PICA pixel data into row-major order.
+///
+/// This is the inverse of [`swizzle_4bpp`].
+///
+/// # Arguments
+///
+/// * `data` - Tiled packed 4-bit pixel data, with partial tiles padded to
+///   whole ones as [`swizzle_4bpp`] writes them.
+/// * `width` - The width of the texture in pixels.
+/// * `height` - The height of the texture in pixels.
+///
+/// # Returns
+///
+/// A `Vec<u8>` of `(width * height).div_ceil(2)` bytes of row-major pixel
+/// data. The padding is skipped.
+///
+/// # Panics
+///
+/// Panics if `data` is shorter than the padded tiles.
+///
+pub fn unswizzle_4bpp(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let tiled_len = padded_len(width, height) / 2;
+    assert!(data.len() >= tiled_len, "pixel data is {} bytes, expected at least {}", data.len(), tiled_len);
+
+    let mut output = vec![0; (width as usize * height as usize).div_ceil(2)];
+    for (i, (x, y)) in tiled_coords(width, height).enumerate() {
+        if x >= width || y >= height {
+            continue;
+        }
+        let dst = y as usize * width as usize + x as usize;
+        set_nibble(&mut output, dst, get_nibble(data, i));
+    }
+    output
+}
+
+/// The tiled order of a texture's pixels, for reordering with a plain gather loop.
+///
+/// Entry `i` is the row-major index of the pixel stored at position `i` of the
+/// tiled data, following the layout described in [`swizzle`]. Padding in
+/// partial tiles maps to the edge pixel it repeats. The mapping only
+/// depends on the texture's size, so [`SwizzleMap::get`] caches recently used
+/// maps to share between images and threads.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::util::{swizzle_scalar, SwizzleMap};
+/// for (width, height) in [(8, 8), (64, 8), (203, 5), (16, 24)] {
+///     let map = SwizzleMap::get(width, height);
+///     assert_eq!(map.dimensions(), (width, height));
+///
+///     // Gathering pixel indices through the map matches the direct computation
+///     let linear: Vec<u32> = (0..width * height).collect();
+///     let bytes: Vec<u8> = linear.iter().flat_map(|i| i.to_le_bytes()).collect();
+///     let expected: Vec<u32> = swizzle_scalar(&bytes, width, height, 4)
+///         .chunks_exact(4)
+///         .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+///         .collect();
+///     assert_eq!(map.indices(), &expected[..]);
+/// }
+///
+/// // Repeated sizes reuse the cached map
+/// assert!(std::sync::Arc::ptr_eq(&SwizzleMap::get(64, 8), &SwizzleMap::get(64, 8)));
+/// ```
+pub struct SwizzleMap {
+    width: u32,
+    height: u32,
+    indices: Vec<u32>,
+}
+
+/// The number of sizes [`SwizzleMap::get`] keeps maps for.
+const SWIZZLE_MAP_CACHE_SIZE: usize = 16;
+
+/// Recently used swizzle maps, most recent first.
+static SWIZZLE_MAPS: Mutex<Vec<Arc<SwizzleMap>>> = Mutex::new(Vec::new());
+
+impl SwizzleMap {
+    /// Computes the map for a `width` x `height` texture.
+    pub fn new(width: u32, height: u32) -> Self {
+        let indices = tiled_coords(width, height)
+            .map(|(x, y)| y.min(height - 1) * width + x.min(width - 1))
+            .collect();
+
+        Self { width, height, indices }
+    }
+
+    /// Returns the map for a `width` x `height` texture, computing it only if
+    /// it isn't among the most recently used sizes.
+    pub fn get(width: u32, height: u32) -> Arc<SwizzleMap> {
+        if let Some(map) = Self::cached(width, height) {
+            return map;
+        }
+
+        // Compute outside the lock so other threads aren't held up
+        let map = Arc::new(Self::new(width, height));
+
+        let mut maps = SWIZZLE_MAPS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(existing) = maps.iter().find(|m| m.dimensions() == (width, height)) {
+            return existing.clone();
+        }
+        maps.insert(0, map.clone());
+        maps.truncate(SWIZZLE_MAP_CACHE_SIZE);
+        map
+    }
+
+    /// Looks up a cached map, moving it to the front of the cache.
+    fn cached(width: u32, height: u32) -> Option<Arc<SwizzleMap>> {
+        let mut maps = SWIZZLE_MAPS.lock().unwrap_or_else(|e| e.into_inner());
+        let pos = maps.iter().position(|m| m.dimensions() == (width, height))?;
+        let map = maps.remove(pos);
+        maps.insert(0, map.clone());
+        Some(map)
+    }
+
+    /// Returns the `(width, height)` of the texture this map is for.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Returns the row-major pixel index for each position in the tiled data.
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+}
+
+/// Iterates over the image coordinates of each pixel in tiled order,
+/// including the padding past the right and bottom edges.
+fn tiled_coords(width: u32, height: u32) -> impl Iterator<Item = (u32, u32)> {
+    (0..height).step_by(8).flat_map(move |ty| {
+        (0..width).step_by(8).flat_map(move |tx| {
+            SWIZZLE_LUT.iter().map(move |&px| (tx + (px & 7), ty + (px >> 3)))
+        })
+    })
+}
+
+/// The number of pixels in a texture padded to whole 8x8 tiles.
+fn padded_len(width: u32, height: u32) -> usize {
+    width.next_multiple_of(8) as usize * height.next_multiple_of(8) as usize
+}
+
+/// Returns the position of pixel `x`, `y` in the tiled data of a texture
+/// `width` pixels wide, counted in pixels: the inverse of [`swizzle`]'s order.
+///
+/// Rows of tiles span the texture's width rounded up to whole tiles, and
+/// within a tile pixels follow a Z-order curve, so the position is the tile's
+/// start plus `x` and `y`'s low three bits interleaved.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::util::{swizzle, tiled_index};
+/// assert_eq!(tiled_index(0, 0, 8), 0);
+/// assert_eq!(tiled_index(1, 1, 8), 3);
+/// assert_eq!(tiled_index(0, 8, 16), 128);
+///
+/// // Matches where swizzle puts each pixel, partial tiles included
+/// let (width, height) = (24, 16);
+/// let rows: Vec<u8> = (0..width * height).map(|i| i as u8).collect();
+/// let tiled = swizzle(&rows, width, height, 1);
+/// for y in 0..height {
+///     for x in 0..width {
+///         assert_eq!(tiled[tiled_index(x, y, width)], rows[(y * width + x) as usize], "({}, {})", x, y);
+///     }
+/// }
+/// assert_eq!(tiled_index(3, 2, 20), tiled_index(3, 2, 24));
+/// ```
+pub fn tiled_index(x: u32, y: u32, width: u32) -> usize {
+    let tiles_x = width.div_ceil(8) as usize;
+    let tile = (y / 8) as usize * tiles_x + (x / 8) as usize;
+    let (x, y) = (x & 7, y & 7);
+    let within = (x & 1) | (y & 1) << 1 | (x & 2) << 1 | (y & 2) << 2 | (x & 4) << 2 | (y & 4) << 3;
+    tile * 64 + within as usize
+}
+
+fn get_nibble(data: &[u8], index: usize) -> u8 {
+    (data[index >> 1] >> ((index & 1) << 2)) & 0xF
+}
+
+fn set_nibble(data: &mut [u8], index: usize, value: u8) {
+    let shift = (index & 1) << 2;
+    data[index >> 1] = (data[index >> 1] & !(0xF << shift)) | ((value & 0xF) << shift);
 }
 
+/// Offsets of the four 4x4 ETC1 blocks within an 8x8 tile.
 pub(crate) const XT: [u32; 4] = [0, 4, 0, 4];
 pub(crate) const YT: [u32; 4] = [0, 0, 4, 4];
 
@@ -0,0 +1,336 @@
+use std::fmt;
+
+use image::RgbaImage;
+
+use crate::pica_texture::{PicaTexture, TextureFormat, EncodeOptions, DecodeOptions, TextureError};
+use crate::pica_texture::{encode_rgba_buffer, decode_to_rgba};
+
+/// How closely a texture decodes back to the image it was encoded from.
+///
+/// Errors are measured per channel, in 8-bit units.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoundtripReport {
+    /// The largest difference between any original and decoded channel value.
+    pub max_error: u8,
+    /// The mean squared error across every channel of every pixel.
+    pub mse: f64,
+    /// Whether the decoded pixels match the original exactly.
+    pub exact: bool,
+}
+
+/// Encodes an image, decodes it again in memory and compares the result to the original.
+///
+/// # Arguments
+///
+/// * `img` - The image to encode.
+/// * `format` - The [`TextureFormat`] to encode as.
+/// * `options` - The [`EncodeOptions`] to encode with.
+///
+/// # Returns
+///
+/// A [`RoundtripReport`] describing the encoding error.
+///
+/// # Panics
+///
+/// Panics if `img` can't be encoded as `format` with `options`, such as an
+/// empty image or a format this build can't encode. Use
+/// [`try_verify_roundtrip`] to get those as errors instead.
+///
+/// # Example
+///
+/// ```
+/// # use image::{Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{verify_roundtrip, EncodeOptions, TextureFormat};
+/// let img = RgbaImage::from_fn(16, 16, |x, y| Rgba([x as u8 * 16 + 3, y as u8 * 16 + 5, 77, 200]));
+/// let options = EncodeOptions::default();
+///
+/// let report = verify_roundtrip(&img, &TextureFormat::RGBA8888, &options);
+/// assert!(report.exact);
+/// assert_eq!(report.max_error, 0);
+/// assert_eq!(report.mse, 0.0);
+///
+/// let report = verify_roundtrip(&img, &TextureFormat::RGB565, &options);
+/// assert!(!report.exact);
+/// assert!(report.max_error > 0 && report.mse > 0.0);
+///
+/// // Mirroring on encode is undone before comparing
+/// let mirrored = EncodeOptions { flip_horizontal: true, ..Default::default() };
+/// assert!(verify_roundtrip(&img, &TextureFormat::RGBA8888, &mirrored).exact);
+/// ```
+pub fn verify_roundtrip(img: &RgbaImage, format: &TextureFormat, options: &EncodeOptions) -> RoundtripReport {
+    try_verify_roundtrip(img, format, options).unwrap_or_else(|e| panic!("Round trip as {} failed: {}", format, e))
+}
+
+/// Like [`verify_roundtrip`], but returns an error if encoding or decoding
+/// fails rather than panicking.
+///
+/// # Example
+///
+/// ```
+/// # use image::RgbaImage;
+/// # use pica_convert::pica_texture::{try_verify_roundtrip, EncodeOptions, TextureFormat};
+/// let options = EncodeOptions::default();
+/// assert!(try_verify_roundtrip(&RgbaImage::new(8, 8), &TextureFormat::RGBA8888, &options).unwrap().exact);
+/// assert!(try_verify_roundtrip(&RgbaImage::new(8, 8), &TextureFormat::Shadow, &options).is_err());
+/// assert!(try_verify_roundtrip(&RgbaImage::new(0, 8), &TextureFormat::L8, &options).is_err());
+/// ```
+pub fn try_verify_roundtrip(img: &RgbaImage, format: &TextureFormat, options: &EncodeOptions) -> Result<RoundtripReport, Box<dyn std::error::Error>> {
+    let texture = encode_rgba_buffer(img.as_raw(), img.width(), img.height(), format, options)?;
+    verify_texture(img, &texture, options)
+}
+
+/// Decodes an already encoded texture in memory and compares it to the image it was encoded from.
+///
+/// # Arguments
+///
+/// * `img` - The image `texture` was encoded from.
+/// * `texture` - The encoded texture.
+/// * `options` - The [`EncodeOptions`] `texture` was encoded with.
+///
+/// # Returns
+///
+/// A [`RoundtripReport`] describing the encoding error, or an error if
+/// decoding fails or the texture's dimensions don't match the image.
+///
+pub fn verify_texture(img: &RgbaImage, texture: &PicaTexture, options: &EncodeOptions) -> Result<RoundtripReport, Box<dyn std::error::Error>> {
+    if img.dimensions() != texture.dimensions() {
+        return Err(format!("Texture is {}x{}, expected {}x{}", texture.width(), texture.height(), img.width(), img.height()).into());
+    }
+
+    let decode_options = DecodeOptions {
+        flip_horizontal: options.flip_horizontal,
+        ..Default::default()
+    };
+    let decoded = decode_to_rgba(texture, &decode_options)?;
+
+    let mut max_error = 0;
+    let mut squared_error = 0.0;
+    for (&original, &roundtrip) in img.as_raw().iter().zip(&decoded) {
+        let error = original.abs_diff(roundtrip);
+        max_error = max_error.max(error);
+        squared_error += (error as f64) * (error as f64);
+    }
+
+    let mse = if decoded.is_empty() { 0.0 } else { squared_error / decoded.len() as f64 };
+
+    Ok(RoundtripReport {
+        max_error,
+        mse,
+        exact: max_error == 0,
+    })
+}
+
+/// The mean squared error an ETC1 compressed channel may have against its
+/// source before [`verify_tolerance`] fails it.
+///
+/// ETC1 can't follow per-pixel noise, so the worst pixel says little. Both
+/// compressors stay below about 2100 on the noisy channels of
+/// [`synthetic_image`](crate::pica_texture::synthetic::synthetic_image) at
+/// every quality level, and this leaves a quarter again on top of that.
+/// Blocks swapped with their neighbours or transposed within a tile land
+/// above it, and so does uniform random noise, which ETC1 can't represent.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{encode_rgba_buffer, etc1, EncodeOptions, PicaTexture, TextureFormat};
+/// # use pica_convert::pica_texture::synthetic::synthetic_image;
+/// # use pica_convert::pica_texture::verify::verify_tolerance;
+/// let img = synthetic_image(64, 64, 3);
+/// for etc1_backend in etc1::Backend::ALL.into_iter().filter(|backend| backend.is_available()) {
+///     for etc1_quality in [etc1::quality::LOW, etc1::quality::MEDIUM, etc1::quality::HIGH] {
+///         let options = EncodeOptions { etc1_backend, etc1_quality, ..Default::default() };
+///         let texture = encode_rgba_buffer(img.as_raw(), 64, 64, &TextureFormat::ETC1, &options).unwrap();
+///         assert!(verify_tolerance(&img, &texture, &options).unwrap().iter().all(|check| check.passed()));
+///
+///         // Blocks in the wrong place fail
+///         let moved = |reorder: fn(&mut [[u8; 8]])| {
+///             let mut blocks: Vec<[u8; 8]> = texture.data().chunks_exact(8).map(|block| block.try_into().unwrap()).collect();
+///             blocks.chunks_exact_mut(4).for_each(reorder);
+///             PicaTexture::try_new(TextureFormat::ETC1, 64, 64, blocks.concat()).unwrap()
+///         };
+///         for texture in [moved(|tile| tile.swap(0, 1)), moved(|tile| tile.swap(1, 2))] {
+///             assert!(verify_tolerance(&img, &texture, &options).unwrap().iter().any(|check| !check.passed()));
+///         }
+///     }
+/// }
+/// ```
+pub const ETC1_MAX_MSE: f64 = 2560.0;
+
+/// A channel a texture format stores, as [`verify_tolerance`] compares it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    /// The mean of red, green and blue, as the luminance formats store it.
+    Luminance,
+}
+
+impl Channel {
+    fn value(self, pixel: &[u8]) -> u8 {
+        match self {
+            Channel::Red => pixel[0],
+            Channel::Green => pixel[1],
+            Channel::Blue => pixel[2],
+            Channel::Alpha => pixel[3],
+            Channel::Luminance => ((pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 3) as u8,
+        }
+    }
+}
+
+/// How far a decoded channel may stray from its source.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tolerance {
+    /// No pixel may be off by more than this, which is what quantizing the
+    /// channel to fewer bits costs. Zero for channels stored in full.
+    MaxError(u8),
+    /// The mean squared error across the image may be at most this, for
+    /// compressed channels.
+    MaxMse(f64),
+}
+
+impl Tolerance {
+    /// The tolerance of a channel stored in `bits` bits.
+    fn quantized(bits: u32) -> Self {
+        Tolerance::MaxError((255u32 >> bits) as u8)
+    }
+}
+
+/// Returns the channels `format` stores, each with how far it may be from
+/// the source image, or `None` for formats that can't be encoded.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::TextureFormat;
+/// # use pica_convert::pica_texture::verify::{format_tolerances, Channel, Tolerance};
+/// assert!(format_tolerances(&TextureFormat::RGBA8888).unwrap().iter().all(|(_, tolerance)| *tolerance == Tolerance::MaxError(0)));
+/// assert_eq!(format_tolerances(&TextureFormat::RGB565).unwrap()[1], (Channel::Green, Tolerance::MaxError(3)));
+/// assert_eq!(format_tolerances(&TextureFormat::LA44).unwrap(), [(Channel::Luminance, Tolerance::MaxError(15)), (Channel::Alpha, Tolerance::MaxError(15))]);
+/// assert!(format_tolerances(&TextureFormat::Gas).is_none());
+/// ```
+pub fn format_tolerances(format: &TextureFormat) -> Option<Vec<(Channel, Tolerance)>> {
+    use Channel::*;
+
+    let quantized = |channels: &[(Channel, u32)]| channels.iter().map(|&(channel, bits)| (channel, Tolerance::quantized(bits))).collect();
+    let etc1 = [Red, Green, Blue].map(|channel| (channel, Tolerance::MaxMse(ETC1_MAX_MSE)));
+
+    Some(match format {
+        TextureFormat::RGBA8888 => quantized(&[(Red, 8), (Green, 8), (Blue, 8), (Alpha, 8)]),
+        TextureFormat::RGB888 => quantized(&[(Red, 8), (Green, 8), (Blue, 8)]),
+        TextureFormat::RGBA5551 => quantized(&[(Red, 5), (Green, 5), (Blue, 5), (Alpha, 1)]),
+        TextureFormat::RGB565 => quantized(&[(Red, 5), (Green, 6), (Blue, 5)]),
+        TextureFormat::RGBA4444 => quantized(&[(Red, 4), (Green, 4), (Blue, 4), (Alpha, 4)]),
+        TextureFormat::LA88 => quantized(&[(Luminance, 8), (Alpha, 8)]),
+        TextureFormat::HL8 => quantized(&[(Red, 8), (Green, 8)]),
+        TextureFormat::L8 => quantized(&[(Luminance, 8)]),
+        TextureFormat::A8 => quantized(&[(Alpha, 8)]),
+        TextureFormat::LA44 => quantized(&[(Luminance, 4), (Alpha, 4)]),
+        TextureFormat::L4 => quantized(&[(Luminance, 4)]),
+        TextureFormat::A4 => quantized(&[(Alpha, 4)]),
+        TextureFormat::ETC1 => etc1.to_vec(),
+        TextureFormat::ETC1A4 => etc1.into_iter().chain([(Alpha, Tolerance::quantized(4))]).collect(),
+        TextureFormat::Shadow | TextureFormat::Gas => return None,
+    })
+}
+
+/// How one channel of a decoded texture compared to its source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelCheck {
+    pub channel: Channel,
+    pub tolerance: Tolerance,
+    /// The largest difference between any source and decoded value.
+    pub max_error: u8,
+    /// The mean squared error across every pixel.
+    pub mse: f64,
+}
+
+impl ChannelCheck {
+    /// Returns whether the channel is within its tolerance.
+    pub fn passed(&self) -> bool {
+        match self.tolerance {
+            Tolerance::MaxError(max) => self.max_error <= max,
+            Tolerance::MaxMse(max) => self.mse <= max,
+        }
+    }
+}
+
+impl fmt::Display for ChannelCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let channel = format!("{:?}", self.channel).to_lowercase();
+        match self.tolerance {
+            Tolerance::MaxError(max) => write!(f, "{} is off by up to {} (allowed {})", channel, self.max_error, max),
+            Tolerance::MaxMse(max) => write!(f, "{} has an MSE of {:.1} (allowed {:.1})", channel, self.mse, max),
+        }
+    }
+}
+
+/// Decodes a texture and checks each channel its format stores against the
+/// image it was encoded from, within [`format_tolerances`].
+///
+/// Unlike [`verify_texture`], which reports the error of a texture straight
+/// out of the encoder, this is meant for textures read back from where they
+/// were written, so anything lost or mangled on the way fails it.
+///
+/// # Returns
+///
+/// A [`ChannelCheck`] for every stored channel, or an error if decoding
+/// fails, the dimensions don't match, or the format can't be encoded.
+///
+/// # Example
+///
+/// ```
+/// # use image::{Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{encode_rgba_buffer, EncodeOptions, PicaTexture, TextureFormat};
+/// # use pica_convert::pica_texture::synthetic::synthetic_image;
+/// # use pica_convert::pica_texture::verify::verify_tolerance;
+/// let img = synthetic_image(64, 64, 3);
+/// let options = EncodeOptions::default();
+///
+/// // Every format's own output is within its tolerance
+/// for format in TextureFormat::ALL.into_iter().filter(|f| f.is_available()) {
+///     let texture = encode_rgba_buffer(img.as_raw(), 64, 64, &format, &options).unwrap();
+///     let checks = verify_tolerance(&img, &texture, &options).unwrap();
+///     assert!(checks.iter().all(|check| check.passed()), "{}: {:?}", format, checks);
+///
+///     // Another image's data isn't
+///     let inverted = RgbaImage::from_fn(64, 64, |x, y| Rgba(img.get_pixel(x, y).0.map(|value| 255 - value)));
+///     let other = encode_rgba_buffer(inverted.as_raw(), 64, 64, &format, &options).unwrap();
+///     let checks = verify_tolerance(&img, &other, &options).unwrap();
+///     assert!(checks.iter().any(|check| !check.passed()), "{}", format);
+/// }
+///
+/// // One step of quantization is allowed, two aren't
+/// let gray = RgbaImage::from_pixel(8, 8, Rgba([0x37, 0x37, 0x37, 0xFF]));
+/// let texture = encode_rgba_buffer(gray.as_raw(), 8, 8, &TextureFormat::L4, &options).unwrap();
+/// assert!(verify_tolerance(&gray, &texture, &options).unwrap()[0].passed());
+/// let darker = PicaTexture::try_new(TextureFormat::L4, 8, 8, vec![0x22; 32]).unwrap();
+/// assert!(!verify_tolerance(&gray, &darker, &options).unwrap()[0].passed());
+/// ```
+pub fn verify_tolerance(img: &RgbaImage, texture: &PicaTexture, options: &EncodeOptions) -> Result<Vec<ChannelCheck>, Box<dyn std::error::Error>> {
+    if img.dimensions() != texture.dimensions() {
+        return Err(format!("Texture is {}x{}, expected {}x{}", texture.width(), texture.height(), img.width(), img.height()).into());
+    }
+    let tolerances = format_tolerances(texture.format())
+        .ok_or_else(|| TextureError::EncodeUnsupported(*texture.format()))?;
+
+    let decode_options = DecodeOptions {
+        flip_horizontal: options.flip_horizontal,
+        ..Default::default()
+    };
+    let decoded = decode_to_rgba(texture, &decode_options)?;
+    let pixels = (decoded.len() / 4).max(1) as f64;
+
+    Ok(tolerances.into_iter().map(|(channel, tolerance)| {
+        let mut max_error = 0;
+        let mut squared_error = 0.0;
+        for (original, roundtrip) in img.as_raw().chunks_exact(4).zip(decoded.chunks_exact(4)) {
+            let error = channel.value(original).abs_diff(channel.value(roundtrip));
+            max_error = max_error.max(error);
+            squared_error += (error as f64) * (error as f64);
+        }
+        ChannelCheck { channel, tolerance, max_error, mse: squared_error / pixels }
+    }).collect())
+}
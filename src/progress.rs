@@ -0,0 +1,165 @@
+//! Progress messages from converting files, which go to stderr unless the
+//! thread is collecting them for the file it's converting, so bulk runs can
+//! print each file's messages together.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+thread_local! {
+    /// The messages being collected on this thread, innermost capture last.
+    static CAPTURES: RefCell<Vec<Vec<String>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Prints a progress message to stderr like `eprintln!`, or adds it to the
+/// innermost [`capture`](crate::progress::capture) running on this thread.
+#[macro_export]
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        $crate::progress::message(format_args!($($arg)*))
+    };
+}
+
+/// Prints a progress message, or collects it; see [`progress!`].
+pub fn message(args: fmt::Arguments<'_>) {
+    let uncaptured = CAPTURES.with_borrow_mut(|captures| match captures.last_mut() {
+        Some(lines) => {
+            lines.push(args.to_string());
+            false
+        }
+        None => true,
+    });
+    if uncaptured {
+        eprintln!("{}", args);
+    }
+}
+
+/// Pops this thread's innermost capture when it ends, even by panicking.
+struct CaptureGuard;
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        CAPTURES.with_borrow_mut(|captures| captures.pop());
+    }
+}
+
+/// Runs `f`, collecting the progress messages it logs on this thread instead
+/// of printing them, and returns them with its result.
+///
+/// Captures nest: if rayon runs another file's work on this thread while `f`
+/// waits, that work's own capture collects its messages, so they stay apart
+/// from `f`'s. Messages logged on other threads aren't collected.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::progress::capture;
+/// let (answer, lines) = capture(|| {
+///     pica_convert::progress!("Encoding as {}", "ETC1");
+///     let (_, inner) = capture(|| pica_convert::progress!("Decoding as L8"));
+///     assert_eq!(inner, ["Decoding as L8"]);
+///     42
+/// });
+/// assert_eq!(answer, 42);
+/// assert_eq!(lines, ["Encoding as ETC1"]);
+/// ```
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    CAPTURES.with_borrow_mut(|captures| captures.push(Vec::new()));
+    let guard = CaptureGuard;
+    let result = f();
+
+    let lines = CAPTURES.with_borrow_mut(|captures| captures.last_mut().map(std::mem::take)).unwrap_or_default();
+    drop(guard);
+    (result, lines)
+}
+
+/// Writes the messages of each file in a bulk run as one block when the file
+/// finishes, so files converted at the same time don't interleave.
+///
+/// Files are numbered by their place in the run. Ordered, each file's block
+/// waits until every earlier file's has been written, so a run's output is
+/// the same whichever order its files finish in.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::progress::OutputQueue;
+/// let queue = OutputQueue::new(Vec::new(), true);
+/// queue.finish(1, vec!["b: encoded".to_string()]).unwrap();
+/// queue.finish(2, vec![]).unwrap();
+/// queue.finish(0, vec!["a: resized".to_string(), "a: encoded".to_string()]).unwrap();
+/// assert_eq!(String::from_utf8(queue.into_inner().unwrap()).unwrap(), "a: resized\na: encoded\nb: encoded\n");
+///
+/// // Unordered, blocks are written as files finish
+/// let queue = OutputQueue::new(Vec::new(), false);
+/// queue.finish(1, vec!["b: encoded".to_string()]).unwrap();
+/// queue.finish(0, vec!["a: encoded".to_string()]).unwrap();
+/// assert_eq!(String::from_utf8(queue.into_inner().unwrap()).unwrap(), "b: encoded\na: encoded\n");
+/// ```
+pub struct OutputQueue<W> {
+    ordered: bool,
+    state: Mutex<QueueState<W>>,
+}
+
+struct QueueState<W> {
+    writer: W,
+    /// The next file to write, when ordered.
+    next: usize,
+    /// Finished files waiting on earlier ones, when ordered.
+    pending: BTreeMap<usize, Vec<String>>,
+}
+
+impl<W: Write> OutputQueue<W> {
+    pub fn new(writer: W, ordered: bool) -> Self {
+        OutputQueue { ordered, state: Mutex::new(QueueState { writer, next: 0, pending: BTreeMap::new() }) }
+    }
+
+    /// Records that the file numbered `index` has finished with `lines`,
+    /// writing them, and any later files' they were holding up.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer fails.
+    pub fn finish(&self, index: usize, lines: Vec<String>) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !self.ordered {
+            return write_block(&mut state.writer, &lines);
+        }
+
+        state.pending.insert(index, lines);
+        loop {
+            let next = state.next;
+            let Some(lines) = state.pending.remove(&next) else {
+                return Ok(());
+            };
+            write_block(&mut state.writer, &lines)?;
+            state.next += 1;
+        }
+    }
+
+    /// Writes the files still waiting on ones that never finished, in order,
+    /// and returns the writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer fails.
+    pub fn into_inner(self) -> io::Result<W> {
+        let mut state = self.state.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for lines in std::mem::take(&mut state.pending).into_values() {
+            write_block(&mut state.writer, &lines)?;
+        }
+        Ok(state.writer)
+    }
+}
+
+/// Writes `lines` with a single write, so other writers can't split them.
+fn write_block(writer: &mut impl Write, lines: &[String]) -> io::Result<()> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let mut block = lines.join("\n");
+    block.push('\n');
+    writer.write_all(block.as_bytes())
+}
@@ -1 +1,295 @@
 pub mod ctex;
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::pica_texture::{PicaTexture, TextureFormat, TextureInfo};
+
+/// The smallest file [`TextureContainer::read_file_mapped`] memory-maps;
+/// anything smaller is cheaper to read through a buffer.
+pub const MMAP_THRESHOLD: u64 = 256 * 1024;
+
+/// A texture along with the name its container stores it under.
+pub struct NamedTexture {
+    pub name: String,
+    pub texture: PicaTexture,
+}
+
+impl NamedTexture {
+    pub fn new(name: impl Into<String>, texture: PicaTexture) -> Self {
+        Self {
+            name: name.into(),
+            texture,
+        }
+    }
+}
+
+/// The result of reading one texture out of a container that holds several.
+pub type TextureResult = Result<NamedTexture, Box<dyn std::error::Error>>;
+
+/// A file format that stores one or more [`PicaTexture`]s.
+///
+/// Each serialization module implements this so callers can read and write
+/// textures without knowing which container they're dealing with; see
+/// [`ContainerRegistry`] for looking one up.
+pub trait TextureContainer: Send + Sync {
+    /// A short, human-readable name for the container.
+    fn name(&self) -> &'static str;
+
+    /// The file extensions (without the leading dot) this container uses,
+    /// with the preferred one first.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Returns whether `header`, the first bytes of a file, look like this container.
+    fn sniff(&self, header: &[u8]) -> bool;
+
+    /// Estimates the size of a container file holding a single texture with
+    /// `data_len` bytes of texture data.
+    ///
+    /// The default assumes the data is stored as-is with no overhead;
+    /// containers that add headers or re-encode the data should override this.
+    fn estimate_size(&self, _format: &TextureFormat, _width: u32, _height: u32, data_len: u64) -> u64 {
+        data_len
+    }
+
+    /// Reads every texture stored in the container.
+    fn read(&self, reader: &mut dyn Read) -> Result<Vec<NamedTexture>, Box<dyn std::error::Error>>;
+
+    /// Reads the [`TextureInfo`] of every texture stored in the container,
+    /// without needing to keep their data.
+    ///
+    /// The default reads each texture in full; containers that can describe
+    /// their textures from a header should override this.
+    fn read_info(&self, reader: &mut dyn Read) -> Result<Vec<TextureInfo>, Box<dyn std::error::Error>> {
+        Ok(self.read(reader)?.iter().map(|named| named.texture.info()).collect())
+    }
+
+    /// Reads every texture stored in a container that's already in memory.
+    ///
+    /// The default goes through [`read`](TextureContainer::read); containers
+    /// that can parse a slice without copying it should override this.
+    fn read_slice(&self, bytes: &[u8]) -> Result<Vec<NamedTexture>, Box<dyn std::error::Error>> {
+        self.read(&mut &*bytes)
+    }
+
+    /// Writes the given textures as a single container.
+    fn write(&self, writer: &mut dyn Write, textures: &[NamedTexture]) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Reads every texture stored in the container file at `path`.
+    fn read_file(&self, path: &Path) -> Result<Vec<NamedTexture>, Box<dyn std::error::Error>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        self.read(&mut reader)
+    }
+
+    /// Reads every texture stored in the container file at `path`, parsing
+    /// it with [`read_slice`](TextureContainer::read_slice) straight from a
+    /// memory map if it's at least [`MMAP_THRESHOLD`] bytes long.
+    ///
+    /// Without the `mmap` feature, for smaller files, or where the file can't
+    /// be mapped, this reads the file through a buffer like
+    /// [`read_file`](TextureContainer::read_file).
+    fn read_file_mapped(&self, path: &Path) -> Result<Vec<NamedTexture>, Box<dyn std::error::Error>> {
+        read_mapped(path, |bytes| self.read_slice(bytes), |mut reader| self.read(&mut reader))
+    }
+
+    /// Reads every texture stored in the container file at `path`, like
+    /// [`read_file`](TextureContainer::read_file) or, with `mmap`,
+    /// [`read_file_mapped`](TextureContainer::read_file_mapped), but with a
+    /// result for each texture so one bad texture doesn't lose the rest.
+    ///
+    /// Errors that leave the whole file unreadable still fail the call. The
+    /// default reads the textures together, so any error fails all of them;
+    /// containers that can carry on past a bad texture should override this.
+    fn read_file_each(&self, path: &Path, mmap: bool) -> Result<Vec<TextureResult>, Box<dyn std::error::Error>> {
+        let textures = if mmap { self.read_file_mapped(path)? } else { self.read_file(path)? };
+        Ok(textures.into_iter().map(Ok).collect())
+    }
+
+    /// Writes the given textures to a new container file at `path`.
+    ///
+    /// If writing fails partway, the incomplete file is removed rather than
+    /// left looking like a valid output. Containers that record their own
+    /// location can override this, using [`write_new_file`] to do the same.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::{Read, Write};
+    /// # use pica_convert::pica_texture::{PicaTexture, TextureFormat};
+    /// # use pica_convert::serialization::{NamedTexture, TextureContainer};
+    /// // A container that runs out of space halfway through
+    /// struct Failing;
+    ///
+    /// impl TextureContainer for Failing {
+    ///     fn name(&self) -> &'static str { "failing" }
+    ///     fn extensions(&self) -> &'static [&'static str] { &["fail"] }
+    ///     fn sniff(&self, _header: &[u8]) -> bool { false }
+    ///     fn read(&self, _reader: &mut dyn Read) -> Result<Vec<NamedTexture>, Box<dyn std::error::Error>> { Ok(Vec::new()) }
+    ///
+    ///     fn write(&self, writer: &mut dyn Write, textures: &[NamedTexture]) -> Result<(), Box<dyn std::error::Error>> {
+    ///         writer.write_all(&textures[0].texture.data()[..10])?;
+    ///         Err("No space left on device".into())
+    ///     }
+    /// }
+    ///
+    /// let texture = PicaTexture::try_new(TextureFormat::L8, 8, 8, vec![0; 64]).unwrap();
+    /// let path = std::env::temp_dir().join("pica_convert_write_file_doctest.fail");
+    ///
+    /// assert!(Failing.write_file(&path, &[NamedTexture::new("", texture)]).is_err());
+    /// assert!(!path.exists());
+    /// ```
+    fn write_file(&self, path: &Path, textures: &[NamedTexture]) -> Result<(), Box<dyn std::error::Error>> {
+        write_new_file(path, |writer| self.write(writer, textures))
+    }
+}
+
+/// The set of known [`TextureContainer`]s, resolvable by extension or content.
+///
+/// # Example
+///
+/// ```
+/// # use std::path::Path;
+/// # use pica_convert::serialization::ContainerRegistry;
+/// let registry = ContainerRegistry::default();
+///
+/// assert_eq!(registry.by_extension("CTEX").unwrap().name(), "ctex");
+/// assert_eq!(registry.for_path(Path::new("out/texture.ctex")).unwrap().name(), "ctex");
+/// assert!(registry.by_extension("png").is_none());
+///
+/// let header = br#"<?xml version="1.0" encoding="utf-8"?><NintendoWareIntermediateFile>"#;
+/// assert_eq!(registry.sniff(header).unwrap().name(), "ctex");
+/// assert!(registry.sniff(b"\x89PNG\r\n").is_none());
+/// ```
+pub struct ContainerRegistry {
+    containers: Vec<Box<dyn TextureContainer>>,
+}
+
+impl ContainerRegistry {
+    /// Creates a registry with no containers.
+    pub fn empty() -> Self {
+        Self { containers: Vec::new() }
+    }
+
+    /// Adds a container to the registry. Earlier registrations take priority.
+    pub fn register(&mut self, container: Box<dyn TextureContainer>) {
+        self.containers.push(container);
+    }
+
+    /// Puts `container` in place of the registered one with the same name,
+    /// keeping its priority, or adds it if there is none.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::serialization::{ctex::{Ctex, CtexOptions}, ContainerRegistry};
+    /// let mut registry = ContainerRegistry::default();
+    /// let count = registry.containers().count();
+    /// registry.replace(Box::new(Ctex::with_options(CtexOptions { indent: 0, ..Default::default() })));
+    ///
+    /// assert_eq!(registry.containers().count(), count);
+    /// assert_eq!(registry.by_extension("ctex").unwrap().name(), "ctex");
+    /// ```
+    pub fn replace(&mut self, container: Box<dyn TextureContainer>) {
+        match self.containers.iter_mut().find(|c| c.name() == container.name()) {
+            Some(existing) => *existing = container,
+            None => self.register(container),
+        }
+    }
+
+    /// Iterates over the registered containers.
+    pub fn containers(&self) -> impl Iterator<Item = &dyn TextureContainer> {
+        self.containers.iter().map(|c| c.as_ref())
+    }
+
+    /// Finds the container that uses the given file extension, ignoring case.
+    pub fn by_extension(&self, extension: &str) -> Option<&dyn TextureContainer> {
+        self.containers()
+            .find(|c| c.extensions().iter().any(|ext| ext.eq_ignore_ascii_case(extension)))
+    }
+
+    /// Finds the container for a path based on its extension.
+    pub fn for_path(&self, path: &Path) -> Option<&dyn TextureContainer> {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.by_extension(ext))
+    }
+
+    /// Finds the container whose content signature matches `header`.
+    pub fn sniff(&self, header: &[u8]) -> Option<&dyn TextureContainer> {
+        self.containers().find(|c| c.sniff(header))
+    }
+
+    /// Finds the container for an existing file, by extension first and then by content.
+    pub fn detect(&self, path: &Path) -> Result<&dyn TextureContainer, Box<dyn std::error::Error>> {
+        if let Some(container) = self.for_path(path) {
+            return Ok(container);
+        }
+
+        let mut header = Vec::with_capacity(256);
+        File::open(path)?.take(256).read_to_end(&mut header)?;
+        self.sniff(&header)
+            .ok_or_else(|| format!("Unrecognized texture container '{}'", path.display()).into())
+    }
+}
+
+impl Default for ContainerRegistry {
+    /// Creates a registry containing every container this crate implements.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Box::new(ctex::Ctex::default()));
+        registry
+    }
+}
+
+/// Creates the file at `path` and fills it with `write` through a buffer.
+///
+/// Any error, including one flushing the buffer, is returned, and the partly
+/// written file is removed so it can't be mistaken for a complete one. Only
+/// regular files are removed; writing to a device such as `/dev/full` just
+/// reports the error.
+///
+pub fn write_new_file(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    let result = write(&mut writer).and_then(|()| Ok(writer.flush()?));
+    if result.is_err() && fs::metadata(path).is_ok_and(|metadata| metadata.is_file()) {
+        drop(writer);
+        let _ = fs::remove_file(path);
+    }
+
+    result
+}
+
+/// Opens the file at `path` and parses it with `parse_slice` from a memory
+/// map, or with `parse_reader` from a buffered reader.
+///
+/// Only files of at least [`MMAP_THRESHOLD`] bytes are mapped, and only with
+/// the `mmap` feature; if mapping fails (on filesystems or platforms that
+/// don't support it) the file is read through the buffer instead. The map
+/// is dropped before returning, whether or not parsing succeeded.
+///
+pub(crate) fn read_mapped<T>(
+    path: &Path,
+    parse_slice: impl FnOnce(&[u8]) -> Result<T, Box<dyn std::error::Error>>,
+    parse_reader: impl FnOnce(BufReader<File>) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let file = File::open(path)?;
+
+    #[cfg(feature = "mmap")]
+    if file.metadata()?.len() >= MMAP_THRESHOLD {
+        // SAFETY: The map is only read from, and doesn't outlive this call. As
+        // with any mapped file, another process truncating it mid-parse would
+        // fault, which is the accepted cost of mapping inputs.
+        if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+            return parse_slice(&map);
+        }
+    }
+    #[cfg(not(feature = "mmap"))]
+    let _ = parse_slice;
+
+    parse_reader(BufReader::new(file))
+}
@@ -0,0 +1,116 @@
+//! Sidecar files recording what a decoded image was decoded from, so it can
+//! be encoded back into the same kind of texture.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::pica_texture::{TextureFormat, TextureLayout};
+use crate::serialization::NamedTexture;
+
+/// The texture fields a decoded image needs to go back exactly as it came.
+///
+/// The format is written by its canonical name, like
+/// [`TextureInfo`](crate::pica_texture::TextureInfo)'s.
+///
+/// # Example
+///
+/// ```
+/// # use pica_convert::pica_texture::{PicaTexture, TextureFormat};
+/// # use pica_convert::serialization::NamedTexture;
+/// # use pica_convert::sidecar::TextureSidecar;
+/// let texture = PicaTexture::try_with_mip_levels(TextureFormat::ETC1A4, 16, 16, 2, vec![0; 256 + 64]).unwrap();
+/// let sidecar = TextureSidecar::from_texture(&NamedTexture::new("COMMON_grass", texture));
+///
+/// let json = serde_json::to_string(&sidecar).unwrap();
+/// assert_eq!(json, r#"{"name":"COMMON_grass","format":"ETC1A4","mip_levels":2}"#);
+/// assert_eq!(serde_json::from_str::<TextureSidecar>(&json).unwrap(), sidecar);
+/// ```
+///
+/// A texture decoded with its sidecar, edited, and encoded again from the
+/// sidecar comes back with the same container fields:
+///
+/// ```
+/// # use image::{DynamicImage, Rgba, RgbaImage};
+/// # use pica_convert::pica_texture::{decode_texture, encode_mip_chain, DecodeOptions, EncodeOptions, TextureFormat};
+/// # use pica_convert::serialization::{ctex::Ctex, NamedTexture, TextureContainer};
+/// # use pica_convert::sidecar::TextureSidecar;
+/// let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(32, 32, |x, y| Rgba([x as u8 * 8, y as u8 * 8, 0, 255])));
+/// let texture = encode_mip_chain(&img, &TextureFormat::RGB565, 3, &EncodeOptions::default()).unwrap();
+/// let mut original = Vec::new();
+/// Ctex::default().write(&mut original, &[NamedTexture::new("UI_label", texture)]).unwrap();
+///
+/// // Decode, keeping the sidecar
+/// let read = Ctex::default().read_slice(&original).unwrap().remove(0);
+/// let json = serde_json::to_string(&TextureSidecar::from_texture(&read)).unwrap();
+/// let mut decoded = decode_texture(&read.texture, &DecodeOptions::default()).unwrap().to_rgba8();
+///
+/// // Edit the image, then encode it back the way the sidecar says
+/// decoded.put_pixel(3, 3, Rgba([255, 255, 255, 255]));
+/// let sidecar: TextureSidecar = serde_json::from_str(&json).unwrap();
+/// let texture = encode_mip_chain(&DynamicImage::ImageRgba8(decoded), &sidecar.format, sidecar.mip_levels, &EncodeOptions::default()).unwrap();
+/// let mut reencoded = Vec::new();
+/// Ctex::default().write(&mut reencoded, &[NamedTexture::new(sidecar.name, texture)]).unwrap();
+///
+/// let read_back = Ctex::default().read_slice(&reencoded).unwrap().remove(0);
+/// assert_eq!(read_back.name, "UI_label");
+/// assert_eq!(read_back.texture.format(), &TextureFormat::RGB565);
+/// assert_eq!(read_back.texture.mip_levels(), 3);
+/// assert_eq!(read_back.texture.dimensions(), (32, 32));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextureSidecar {
+    /// The texture's name in its container.
+    pub name: String,
+    #[serde(with = "crate::pica_texture::info::format_name")]
+    pub format: TextureFormat,
+    /// The number of mip levels, including the base level.
+    pub mip_levels: u32,
+    /// The order the texture's pixels were stored in, left out when tiled.
+    #[serde(default, skip_serializing_if = "is_tiled")]
+    pub layout: TextureLayout,
+}
+
+fn is_tiled(layout: &TextureLayout) -> bool {
+    *layout == TextureLayout::Tiled
+}
+
+impl TextureSidecar {
+    /// Records the fields of a texture read from a container.
+    pub fn from_texture(named: &NamedTexture) -> Self {
+        Self {
+            name: named.name.clone(),
+            format: *named.texture.format(),
+            mip_levels: named.texture.mip_levels(),
+            layout: named.texture.layout(),
+        }
+    }
+
+    /// Returns where the sidecar for `image_path` goes: beside it, as
+    /// `{stem}.texture.json`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::path::PathBuf;
+    /// # use pica_convert::sidecar::TextureSidecar;
+    /// assert_eq!(TextureSidecar::path_for("png/grass.png".as_ref()), PathBuf::from("png/grass.texture.json"));
+    /// ```
+    pub fn path_for(image_path: &Path) -> PathBuf {
+        let stem = image_path.file_stem().unwrap_or_default().to_string_lossy();
+        image_path.with_file_name(format!("{}.texture.json", stem))
+    }
+
+    /// Returns whether `path` is named like a sidecar.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use pica_convert::sidecar::TextureSidecar;
+    /// assert!(TextureSidecar::is_sidecar("png/grass.texture.json".as_ref()));
+    /// assert!(!TextureSidecar::is_sidecar("png/grass.json".as_ref()));
+    /// ```
+    pub fn is_sidecar(path: &Path) -> bool {
+        path.file_name().is_some_and(|name| name.to_string_lossy().ends_with(".texture.json"))
+    }
+}
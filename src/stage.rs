@@ -0,0 +1,191 @@
+//! Errors from processing one file, saying which file it was and at which
+//! stage of its conversion it failed.
+
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// A stage of converting one file, written by its [`name`](Stage::name) in JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// Reading the input file and parsing its image or container.
+    Load,
+    /// Checking the input, and the options it's converted with, before converting it.
+    Validate,
+    /// Encoding pixels into texture data.
+    Encode,
+    /// Writing encoded textures out in their container.
+    Serialize,
+    /// Checking an encoded texture, and what was written, against its source.
+    Verify,
+    /// Decoding texture data into pixels.
+    Decode,
+    /// Writing decoded images, and the files beside them, out.
+    Save,
+}
+
+impl Stage {
+    /// Every stage, in the order a file goes through them.
+    pub const ALL: [Stage; 7] = [Stage::Load, Stage::Validate, Stage::Encode, Stage::Serialize, Stage::Verify, Stage::Decode, Stage::Save];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Load => "load",
+            Stage::Validate => "validate",
+            Stage::Encode => "encode",
+            Stage::Serialize => "serialize",
+            Stage::Verify => "verify",
+            Stage::Decode => "decode",
+            Stage::Save => "save",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An error from processing a file, with the stage it failed at and, once
+/// known, the input and output paths.
+///
+/// Errors are tagged with their stage where they happen, using
+/// [`AtStage::at_stage`], and given their paths by whoever knows them with
+/// [`StageError::in_file`]. Displaying one gives a single line, for logs of
+/// bulk runs; the alternate form `{:#}`, which `Debug` also gives, spreads
+/// it over several lines and follows the cause's own sources.
+///
+/// # Example
+///
+/// ```
+/// # use std::path::Path;
+/// # use pica_convert::stage::{AtStage, Stage, StageError};
+/// let result: Result<(), _> = Err("invalid PNG signature").at_stage(Stage::Load);
+/// let err = result.unwrap_err().in_file(Path::new("in/grass.png"), Some(Path::new("out/grass.ctex")));
+///
+/// assert_eq!(err.stage(), Stage::Load);
+/// assert_eq!(err.to_string(), "[load] Failed to process 'in/grass.png' into 'out/grass.ctex': invalid PNG signature");
+/// assert_eq!(format!("{:#}", err), "\
+/// Failed to process 'in/grass.png'
+///   Stage:  load
+///   Input:  in/grass.png
+///   Output: out/grass.ctex
+///   Cause:  invalid PNG signature");
+///
+/// // Paths already given aren't replaced, and stages already tagged are kept
+/// let err = err.in_file(Path::new("elsewhere.png"), None);
+/// assert_eq!(err.input(), Some(Path::new("in/grass.png")));
+/// let retagged: Result<(), _> = Err::<(), Box<dyn std::error::Error>>(err.into()).at_stage(Stage::Save);
+/// assert_eq!(retagged.unwrap_err().stage(), Stage::Load);
+///
+/// // Writing to stdout says so
+/// let err = StageError::new(Stage::Serialize, "broken pipe").in_file(Path::new("a.png"), Some(Path::new("-")));
+/// assert_eq!(err.to_string(), "[serialize] Failed to process 'a.png' into stdout: broken pipe");
+/// ```
+pub struct StageError {
+    stage: Stage,
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+    cause: Box<dyn Error>,
+}
+
+impl StageError {
+    /// Creates an error at `stage`, with no paths yet.
+    pub fn new(stage: Stage, cause: impl Into<Box<dyn Error>>) -> Self {
+        StageError { stage, input: None, output: None, cause: cause.into() }
+    }
+
+    /// Records the input and output paths the error happened for, keeping
+    /// any already recorded; an error for one frame or texture of a file
+    /// already knows the output it was writing.
+    pub fn in_file(mut self, input: &Path, output: Option<&Path>) -> Self {
+        self.input.get_or_insert_with(|| input.to_path_buf());
+        if let Some(output) = output {
+            self.output.get_or_insert_with(|| output.to_path_buf());
+        }
+        self
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    pub fn input(&self) -> Option<&Path> {
+        self.input.as_deref()
+    }
+
+    pub fn output(&self) -> Option<&Path> {
+        self.output.as_deref()
+    }
+
+    /// The error that stopped the stage.
+    pub fn cause(&self) -> &(dyn Error + 'static) {
+        &*self.cause
+    }
+}
+
+/// Names a path for an error message, with `-` as stdin or stdout.
+fn describe(path: &Path, stdio: &str) -> String {
+    if path == Path::new("-") { stdio.to_string() } else { format!("'{}'", path.display()) }
+}
+
+impl fmt::Display for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let input = self.input.as_deref().map(|input| describe(input, "stdin"));
+        if f.alternate() {
+            write!(f, "Failed to process {}", input.as_deref().unwrap_or("input"))?;
+            write!(f, "\n  Stage:  {}", self.stage)?;
+            if let Some(input) = &self.input {
+                write!(f, "\n  Input:  {}", input.display())?;
+            }
+            if let Some(output) = &self.output {
+                write!(f, "\n  Output: {}", output.display())?;
+            }
+            write!(f, "\n  Cause:  {}", self.cause)?;
+            let mut source = self.cause.source();
+            while let Some(cause) = source {
+                write!(f, "\n    caused by: {}", cause)?;
+                source = cause.source();
+            }
+            return Ok(());
+        }
+
+        write!(f, "[{}] Failed to process {}", self.stage, input.as_deref().unwrap_or("input"))?;
+        if let Some(output) = &self.output {
+            write!(f, " into {}", describe(output, "stdout"))?;
+        }
+        write!(f, ": {}", self.cause)
+    }
+}
+
+impl fmt::Debug for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self)
+    }
+}
+
+impl Error for StageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.cause())
+    }
+}
+
+/// Tags the error of a result with the [`Stage`] it happened at.
+pub trait AtStage<T> {
+    /// Wraps an error in a [`StageError`] at `stage`, unless it's already
+    /// one, in which case its own, more specific, stage is kept.
+    fn at_stage(self, stage: Stage) -> Result<T, StageError>;
+}
+
+impl<T, E: Into<Box<dyn Error>>> AtStage<T> for Result<T, E> {
+    fn at_stage(self, stage: Stage) -> Result<T, StageError> {
+        self.map_err(|e| match e.into().downcast::<StageError>() {
+            Ok(tagged) => *tagged,
+            Err(e) => StageError::new(stage, e),
+        })
+    }
+}
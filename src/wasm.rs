@@ -0,0 +1,140 @@
+//! JavaScript bindings for previewing and creating ctex files in the browser.
+//!
+//! Build with `--no-default-features --features wasm` for
+//! `wasm32-unknown-unknown`; the `etc1` feature needs a C++ toolchain, so
+//! ETC1/ETC1A4 textures only decode there, unless `etc1-rust` is added to
+//! encode them with the plain Rust compressor. See
+//! `examples/wasm/index.html` for a page using these exports.
+
+use std::str::FromStr;
+
+use wasm_bindgen::prelude::*;
+
+use crate::pica_texture::{self, DecodeOptions, EncodeOptions, TextureFormat};
+use crate::serialization::ctex;
+
+/// A decoded texture, with its pixels as top-down RGBA8 rows.
+#[wasm_bindgen(getter_with_clone)]
+pub struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    /// `width * height * 4` bytes, ready for an `ImageData`.
+    pub rgba: Vec<u8>,
+}
+
+/// Options for [`encode_png_to_ctex`], mirroring [`EncodeOptions`].
+#[wasm_bindgen(js_name = EncodeOptions)]
+#[derive(Clone)]
+pub struct WasmEncodeOptions {
+    pub etc1_quality: i32,
+    pub etc1_dithering: bool,
+    pub flip_horizontal: bool,
+}
+
+#[wasm_bindgen(js_class = EncodeOptions)]
+impl WasmEncodeOptions {
+    /// Creates options with the same defaults as [`EncodeOptions::default`].
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for WasmEncodeOptions {
+    fn default() -> Self {
+        let options = EncodeOptions::default();
+        Self {
+            etc1_quality: options.etc1_quality,
+            etc1_dithering: options.etc1_dithering,
+            flip_horizontal: options.flip_horizontal,
+        }
+    }
+}
+
+impl From<WasmEncodeOptions> for EncodeOptions {
+    fn from(options: WasmEncodeOptions) -> Self {
+        Self {
+            etc1_quality: options.etc1_quality,
+            etc1_dithering: options.etc1_dithering,
+            flip_horizontal: options.flip_horizontal,
+            ..Default::default()
+        }
+    }
+}
+
+/// Decodes a ctex file into RGBA pixels.
+///
+/// # Arguments
+///
+/// * `bytes` - The contents of a ctex file.
+///
+/// # Returns
+///
+/// The decoded [`DecodedImage`], or a JavaScript `Error` if the file can't be
+/// parsed or its format isn't available.
+///
+#[wasm_bindgen]
+pub fn decode_ctex(bytes: &[u8]) -> Result<DecodedImage, JsError> {
+    decode_ctex_impl(bytes).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Encodes an image file (PNG, or any other format the `image` crate detects)
+/// into a ctex file.
+///
+/// # Arguments
+///
+/// * `bytes` - The contents of the image file.
+/// * `format` - The texture format name, such as `"rgba8888"` or `"la88"`.
+/// * `options` - The options to encode with, or `undefined` for the defaults.
+///   Passing an `EncodeOptions` object hands it over to this call, so create a
+///   new one for each call.
+///
+/// # Returns
+///
+/// The ctex file's bytes, or a JavaScript `Error` if the image can't be read
+/// or the format is unknown or unavailable.
+///
+#[wasm_bindgen]
+pub fn encode_png_to_ctex(bytes: &[u8], format: &str, options: Option<WasmEncodeOptions>) -> Result<Vec<u8>, JsError> {
+    encode_png_to_ctex_impl(bytes, format, options.unwrap_or_default().into())
+        .map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Implements [`decode_ctex`] with Rust errors, which the wrapper converts for JavaScript.
+///
+/// # Example
+///
+/// ```
+/// # use image::{DynamicImage, Rgba, RgbaImage};
+/// # use pica_convert::wasm::{decode_ctex_impl, encode_png_to_ctex_impl};
+/// let img = RgbaImage::from_fn(16, 8, |x, y| Rgba([x as u8 * 16, y as u8 * 32, 128, 255]));
+/// let mut png = Vec::new();
+/// DynamicImage::ImageRgba8(img.clone())
+///     .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+///     .unwrap();
+///
+/// let ctex = encode_png_to_ctex_impl(&png, "rgba8888", Default::default()).unwrap();
+/// let decoded = decode_ctex_impl(&ctex).unwrap();
+///
+/// assert_eq!((decoded.width, decoded.height), (16, 8));
+/// assert_eq!(&decoded.rgba, img.as_raw());
+/// ```
+pub fn decode_ctex_impl(bytes: &[u8]) -> Result<DecodedImage, Box<dyn std::error::Error>> {
+    let texture = ctex::deserialize_from_slice(bytes)?;
+    let rgba = pica_texture::decode_to_rgba(&texture, &DecodeOptions::default())?;
+
+    Ok(DecodedImage {
+        width: texture.width(),
+        height: texture.height(),
+        rgba,
+    })
+}
+
+/// Implements [`encode_png_to_ctex`] with Rust errors, which the wrapper converts for JavaScript.
+pub fn encode_png_to_ctex_impl(bytes: &[u8], format: &str, options: EncodeOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let format = TextureFormat::from_str(format)?;
+    let img = image::load_from_memory(bytes)?;
+
+    let texture = pica_texture::encode_texture(&img, &format, &options)?;
+    ctex::serialize_to_vec(&texture)
+}
@@ -0,0 +1,43 @@
+//! Helpers shared by the integration tests that run the binary.
+
+// Each test crate only uses some of these
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// Creates an empty directory for a test to work in, named after the test
+/// crate so crates running at the same time don't share one.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}_{}", env!("CARGO_CRATE_NAME"), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs the binary with `args` and waits for it to finish.
+pub fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pica-convert")).args(args).output().unwrap()
+}
+
+/// Runs the binary with `args`, writing `stdin` to its standard input.
+pub fn run_with_stdin(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pica-convert"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The binary may reject its arguments and exit before reading any of it
+    if let Err(e) = child.stdin.take().unwrap().write_all(stdin) {
+        assert_eq!(e.kind(), std::io::ErrorKind::BrokenPipe, "{}", e);
+    }
+    child.wait_with_output().unwrap()
+}
+
+/// Returns a path as a command line argument.
+pub fn path(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
@@ -0,0 +1,41 @@
+//! Checks the completion scripts the binary generates against the formats
+//! the library knows, so new formats can't be left out of them.
+
+use std::collections::HashSet;
+use std::process::Command;
+
+use pica_convert::pica_texture::TextureFormat;
+
+fn completions(shell: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_pica-convert"))
+        .args(["completions", shell])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn bash_completions_offer_every_format() {
+    let script = completions("bash");
+    let words: HashSet<&str> = script.split(|c: char| !c.is_ascii_alphanumeric() && c != '-').collect();
+
+    for format in TextureFormat::ALL {
+        assert!(words.contains(format.name()), "{} is missing", format);
+    }
+    for word in ["keep", "completions", "--output-format", "--input-path"] {
+        assert!(words.contains(word), "{} is missing", word);
+    }
+}
+
+#[test]
+fn every_shell_has_completions() {
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        assert!(completions(shell).contains("output-format"), "{}", shell);
+    }
+
+    // PowerShell's script only completes flag names, not their values
+    for shell in ["bash", "zsh", "fish"] {
+        assert!(completions(shell).contains("ETC1A4"), "{}", shell);
+    }
+}
@@ -0,0 +1,51 @@
+//! Encodes over existing ctex files through the binary, checking the
+//! `GraphicsContentCtr` Version and Namespace land in the output verbatim, in
+//! the form the replaced file gave them in.
+
+mod common;
+
+use pica_convert::pica_texture::synthetic::synthetic_image;
+
+use common::{path, run, scratch_dir};
+
+#[test]
+fn flags_are_written_verbatim() {
+    let dir = scratch_dir("flags");
+    let (input, output) = (dir.join("grass.png"), dir.join("grass.ctex"));
+    synthetic_image(8, 8, 0).save(&input).unwrap();
+
+    let result = run(&["-m", "encode", "-f", "l8", "-i", path(&input), "-o", path(&output),
+        "--ctex-version", "2.0.0-beta", "--ctex-namespace", "Field_01 & <Sky>"]);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert!(String::from_utf8_lossy(&result.stderr).contains("Warning: ctex version '2.0.0-beta' isn't a known one (1.3.0)"));
+
+    let xml = std::fs::read_to_string(&output).unwrap();
+    assert!(xml.contains("<Version>2.0.0-beta</Version>"), "{}", xml);
+    assert!(xml.contains("<Namespace>Field_01 &amp; &lt;Sky&gt;</Namespace>"), "{}", xml);
+}
+
+#[test]
+fn replaced_files_keep_their_form() {
+    let dir = scratch_dir("replace");
+    let input = dir.join("grass.png");
+    synthetic_image(8, 8, 1).save(&input).unwrap();
+    let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ctex");
+
+    // Attributes stay attributes, with the values the file had unless a flag replaces them
+    let output = dir.join("attributes.ctex");
+    std::fs::copy(fixtures.join("official_style.ctex"), &output).unwrap();
+    let result = run(&["-m", "encode", "-f", "l8", "-i", path(&input), "-o", path(&output), "--ctex-namespace", "Field_02"]);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    let xml = std::fs::read_to_string(&output).unwrap();
+    assert!(xml.contains(r#"<GraphicsContentCtr Version="1.3.0" Namespace="Field_02">"#), "{}", xml);
+    assert!(!xml.contains("<Version>"), "{}", xml);
+
+    // And elements stay elements
+    let output = dir.join("elements.ctex");
+    std::fs::copy(fixtures.join("layout_8x8_etc1a4.ctex"), &output).unwrap();
+    let result = run(&["-m", "encode", "-f", "l8", "-i", path(&input), "-o", path(&output), "--ctex-version", "1.3.0"]);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert!(!String::from_utf8_lossy(&result.stderr).contains("Warning"), "{}", String::from_utf8_lossy(&result.stderr));
+    let xml = std::fs::read_to_string(&output).unwrap();
+    assert!(xml.contains("<GraphicsContentCtr>") && xml.contains("<Version>1.3.0</Version>") && xml.contains("<Namespace></Namespace>"), "{}", xml);
+}
@@ -0,0 +1,71 @@
+//! Encodes directories holding byte-identical inputs with `--dedup` through
+//! the binary, and checks every output matches what encoding each input
+//! separately writes.
+
+mod common;
+
+use std::path::Path;
+
+use pica_convert::pica_texture::synthetic::synthetic_image;
+use pica_convert::batch::FailureManifest;
+use pica_convert::serialization::{ctex::Ctex, TextureContainer};
+
+use common::{path, run, scratch_dir};
+
+/// Lists the names of the files in `dir` other than its failure manifest, sorted.
+fn file_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name != FailureManifest::FILE_NAME)
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn duplicates_match_encoding_each_input() {
+    let dir = scratch_dir("outputs");
+    let input_dir = dir.join("in");
+    std::fs::create_dir_all(&input_dir).unwrap();
+
+    let grass = synthetic_image(16, 16, 0);
+    for name in ["grass.png", "grass_copy.png", "grass_again.png"] {
+        grass.save(input_dir.join(name)).unwrap();
+    }
+    synthetic_image(16, 16, 1).save(input_dir.join("rock.png")).unwrap();
+    for name in ["intro.gif", "intro_copy.gif"] {
+        std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/animation/three_frames.gif"), input_dir.join(name)).unwrap();
+    }
+    for name in ["broken.png", "broken_copy.png"] {
+        std::fs::write(input_dir.join(name), b"not a png").unwrap();
+    }
+
+    let (plain_dir, dedup_dir) = (dir.join("plain"), dir.join("dedup"));
+    let plain = run(&["-m", "encode", "-f", "rgb565", "--verify", "--tile-preview", "2x1", "-i", path(&input_dir), "-o", path(&plain_dir)]);
+    let dedup = run(&["-m", "encode", "-f", "rgb565", "--verify", "--tile-preview", "2x1", "--dedup", "-i", path(&input_dir), "-o", path(&dedup_dir)]);
+
+    // Every file comes out the same, named after its own input
+    let names = file_names(&plain_dir);
+    assert_eq!(names, file_names(&dedup_dir));
+    for name in &names {
+        assert_eq!(std::fs::read(plain_dir.join(name)).unwrap(), std::fs::read(dedup_dir.join(name)).unwrap(), "{}", name);
+    }
+    let textures = Ctex::default().read_file(&dedup_dir.join("grass_copy.ctex")).unwrap();
+    assert_eq!(textures[0].name, "grass_copy");
+
+    let stderr = String::from_utf8_lossy(&dedup.stderr);
+    assert!(stderr.contains("Files written from an identical input's output: 2 of 8"), "{}", stderr);
+    let copy_failure = format!("Failed to process '{}'", input_dir.join("broken_copy.png").display());
+    let line = stderr.lines().find(|line| line.contains(&copy_failure)).unwrap_or_else(|| panic!("{}", stderr));
+    assert!(line.starts_with("[load]") && line.contains("broken.png"), "{}", line);
+    assert!(stderr.contains("Verified 6 of 8 files"), "{}", stderr);
+    assert!(String::from_utf8_lossy(&plain.stderr).contains("Verified 6 of 8 files"));
+}
+
+#[test]
+fn dedup_takes_encode_mode() {
+    let dir = scratch_dir("mode");
+    let result = run(&["-m", "decode", "--dedup", "-i", path(&dir), "-o", path(&dir.join("out"))]);
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("--dedup takes encode mode"));
+}
@@ -0,0 +1,137 @@
+//! Runs inputs with degenerate, nonstandard or absurd dimensions through the
+//! binary, and checks each is rejected without a panic, or with
+//! `--allow-nonstandard`, converted where its data is all there.
+
+mod common;
+
+use std::path::Path;
+use std::process::Output;
+
+use pica_convert::pica_texture::{encode_rgba_buffer, EncodeOptions, TextureFormat};
+use pica_convert::pica_texture::synthetic::synthetic_image;
+use pica_convert::serialization::{ctex::Ctex, NamedTexture, TextureContainer};
+
+use common::{path, run, run_with_stdin, scratch_dir};
+
+const DEGENERATE: [(u32, u32); 3] = [(0, 0), (0, 8), (8, 0)];
+
+/// Writes an 8x8 ctex file to `path`, declaring `width` and `height` instead.
+fn write_ctex(path: &Path, width: u32, height: u32) {
+    let img = synthetic_image(8, 8, 0);
+    let texture = encode_rgba_buffer(img.as_raw(), 8, 8, &TextureFormat::RGBA8888, &EncodeOptions::default()).unwrap();
+    Ctex::default().write_file(path, &[NamedTexture::new("texture", texture)]).unwrap();
+    let xml = std::fs::read_to_string(path).unwrap();
+    assert!(xml.contains(r#"Width="8" Height="8""#));
+    std::fs::write(path, xml.replace(r#"Width="8" Height="8""#, &format!(r#"Width="{}" Height="{}""#, width, height))).unwrap();
+}
+
+fn assert_no_panic(result: &Output) {
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn degenerate_pngs_fail_to_load() {
+    let dir = scratch_dir("png");
+    let (input_dir, output_dir) = (dir.join("in"), dir.join("out"));
+    std::fs::create_dir_all(&input_dir).unwrap();
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/dimensions");
+    for (width, height) in DEGENERATE {
+        let name = format!("{}x{}.png", width, height);
+        std::fs::copy(fixtures.join(&name), input_dir.join(&name)).unwrap();
+    }
+    synthetic_image(8, 8, 1).save(input_dir.join("fine.png")).unwrap();
+
+    let result = run(&["-m", "encode", "-f", "rgba8888", "-i", path(&input_dir), "-o", path(&output_dir)]);
+    assert_no_panic(&result);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    for (width, height) in DEGENERATE {
+        let failure = format!("[load] Failed to process '{}'", input_dir.join(format!("{}x{}.png", width, height)).display());
+        assert!(stderr.contains(&failure), "{}", stderr);
+    }
+    assert!(output_dir.join("fine.ctex").is_file());
+}
+
+#[test]
+fn degenerate_ctex_files_are_rejected() {
+    let dir = scratch_dir("ctex");
+    let (input_dir, output_dir) = (dir.join("in"), dir.join("out"));
+    std::fs::create_dir_all(&input_dir).unwrap();
+    for (width, height) in DEGENERATE {
+        write_ctex(&input_dir.join(format!("{}x{}.ctex", width, height)), width, height);
+    }
+    write_ctex(&input_dir.join("fine.ctex"), 8, 8);
+
+    let result = run(&["-m", "decode", "-i", path(&input_dir), "-o", path(&output_dir)]);
+    assert_no_panic(&result);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    for (width, height) in DEGENERATE {
+        let message = format!("Texture dimensions {}x{} must be nonzero", width, height);
+        assert!(stderr.contains(&message), "{}", stderr);
+    }
+    assert!(output_dir.join("fine.png").is_file());
+}
+
+#[test]
+fn degenerate_raw_dumps_are_rejected() {
+    let dir = scratch_dir("raw");
+    let output = dir.join("dump.png");
+
+    for (width, height) in DEGENERATE {
+        let (width, height) = (width.to_string(), height.to_string());
+        let result = run_with_stdin(&["-m", "decode", "--input-format", "raw", "--width", &width, "--height", &height, "-f", "l8",
+            "-i", "-", "-o", path(&output)], &[0; 64]);
+        assert!(!result.status.success(), "{}x{}", width, height);
+        assert_no_panic(&result);
+        assert!(!output.exists());
+    }
+}
+
+#[test]
+fn nonstandard_dimensions_need_allow_nonstandard() {
+    let dir = scratch_dir("nonstandard");
+
+    for (width, height) in [(24, 8), (2048, 8)] {
+        let input = dir.join(format!("{}x{}.png", width, height));
+        let ctex = dir.join(format!("{}x{}.ctex", width, height));
+        let output = dir.join(format!("{}x{}_decoded.png", width, height));
+        synthetic_image(width, height, 2).save(&input).unwrap();
+
+        // Rejected in both directions without the flag, pointing at it
+        let result = run(&["-m", "encode", "-f", "rgba8888", "-i", path(&input), "-o", path(&ctex)]);
+        assert!(!result.status.success(), "{}x{}", width, height);
+        assert!(String::from_utf8_lossy(&result.stderr).contains("--allow-nonstandard"), "{}", String::from_utf8_lossy(&result.stderr));
+        assert!(!ctex.exists());
+
+        let result = run(&["-m", "encode", "-f", "rgba8888", "--allow-nonstandard", "-i", path(&input), "-o", path(&ctex)]);
+        assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+
+        let result = run(&["-m", "decode", "-i", path(&ctex), "-o", path(&output)]);
+        assert!(!result.status.success(), "{}x{}", width, height);
+        assert!(String::from_utf8_lossy(&result.stderr).contains("use --allow-nonstandard to decode it anyway"), "{}", String::from_utf8_lossy(&result.stderr));
+        assert!(!output.exists());
+
+        // And converted with it
+        let result = run(&["-m", "decode", "--allow-nonstandard", "-i", path(&ctex), "-o", path(&output)]);
+        assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+        assert_eq!(image::open(&output).unwrap().to_rgba8(), image::open(&input).unwrap().to_rgba8());
+    }
+}
+
+#[test]
+fn absurd_dimensions_are_rejected_before_allocating() {
+    let dir = scratch_dir("absurd");
+    let input = dir.join("huge.ctex");
+    let output = dir.join("huge.png");
+    write_ctex(&input, 65536, 65536);
+
+    // A 16 GiB texture backed by 256 bytes fails on its data, with or without the flag
+    for extra in [&[][..], &["--allow-nonstandard"]] {
+        let result = run(&[&["-m", "decode", "-i", path(&input), "-o", path(&output)], extra].concat());
+        assert!(!result.status.success(), "{:?}", extra);
+        assert_no_panic(&result);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        assert!(stderr.contains("is 256 bytes, but the declared Rgba8888 texture needs 17179869184"), "{}", stderr);
+        assert!(!output.exists());
+    }
+}
@@ -0,0 +1,108 @@
+//! Makes conversions fail at each stage through the binary, and checks each
+//! failure is reported with its stage, input and output.
+
+mod common;
+
+use std::path::Path;
+use std::process::Output;
+
+use pica_convert::pica_texture::{PicaTexture, TextureFormat};
+use pica_convert::pica_texture::synthetic::synthetic_image;
+use pica_convert::serialization::{ctex::Ctex, NamedTexture, TextureContainer};
+
+use common::{path, run, scratch_dir};
+
+/// Returns the line of `stderr` reporting a failure, checking it names the
+/// stage, input and output.
+fn failure_line(result: &Output, stage: &str, input: &Path, output: &Path) -> String {
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    let expected = format!("[{}] Failed to process '{}' into '{}': ", stage, input.display(), output.display());
+    stderr.lines()
+        .find(|line| line.contains(&expected))
+        .unwrap_or_else(|| panic!("no line containing {:?} in:\n{}", expected, stderr))
+        .to_string()
+}
+
+fn write_ctex(path: &Path, texture: PicaTexture) {
+    Ctex::default().write_file(path, &[NamedTexture::new("texture", texture)]).unwrap();
+}
+
+#[test]
+fn bulk_encode_failures_name_their_stage() {
+    let dir = scratch_dir("encode");
+    let (input_dir, output_dir) = (dir.join("in"), dir.join("out"));
+    std::fs::create_dir_all(&input_dir).unwrap();
+
+    // Load: not an image at all
+    std::fs::write(input_dir.join("garbage.png"), b"not a png").unwrap();
+    // Validate: dimensions the PICA doesn't support
+    synthetic_image(12, 10, 0).save(input_dir.join("odd.png")).unwrap();
+    // Serialize: a directory is in the way of the output file
+    synthetic_image(8, 8, 1).save(input_dir.join("blocked.png")).unwrap();
+    std::fs::create_dir_all(output_dir.join("blocked.ctex")).unwrap();
+    // A file that converts, so the run carries on past the failures
+    synthetic_image(8, 8, 2).save(input_dir.join("fine.png")).unwrap();
+
+    let result = run(&["-m", "encode", "-f", "rgba8888", "-i", path(&input_dir), "-o", path(&output_dir)]);
+    let line = failure_line(&result, "load", &input_dir.join("garbage.png"), &output_dir.join("garbage.ctex"));
+    assert!(line.contains("Failed to decode image"), "{}", line);
+    failure_line(&result, "validate", &input_dir.join("odd.png"), &output_dir.join("odd.ctex"));
+    failure_line(&result, "serialize", &input_dir.join("blocked.png"), &output_dir.join("blocked.ctex"));
+    assert!(output_dir.join("fine.ctex").is_file());
+
+    // Validate: a format that can't be stored the way it's asked for
+    let input = input_dir.join("fine.png");
+    let output = dir.join("linear.ctex");
+    let result = run(&["-m", "encode", "-f", "etc1", "--layout", "linear", "-i", path(&input), "-o", path(&output)]);
+    assert!(!result.status.success());
+    let line = failure_line(&result, "validate", &input, &output);
+    assert!(line.contains("can only be stored tiled"), "{}", line);
+}
+
+#[test]
+fn bulk_decode_failures_name_their_stage() {
+    let dir = scratch_dir("decode");
+    let (input_dir, output_dir) = (dir.join("in"), dir.join("out"));
+    std::fs::create_dir_all(&input_dir).unwrap();
+
+    // Load: a ctex file that isn't XML
+    std::fs::write(input_dir.join("broken.ctex"), b"<NintendoWareIntermediateFile").unwrap();
+    // Validate: dimensions the PICA doesn't support
+    write_ctex(&input_dir.join("odd.ctex"), PicaTexture::try_new(TextureFormat::L8, 12, 10, vec![0; TextureFormat::L8.expected_data_len(12, 10)]).unwrap());
+    // Decode: gas textures have no pixels to decode to
+    write_ctex(&input_dir.join("gas.ctex"), PicaTexture::try_new(TextureFormat::Gas, 8, 8, vec![0; TextureFormat::Gas.expected_data_len(8, 8)]).unwrap());
+    // Save: a directory is in the way of the decoded image
+    write_ctex(&input_dir.join("blocked.ctex"), PicaTexture::try_new(TextureFormat::L8, 8, 8, vec![0; 64]).unwrap());
+    std::fs::create_dir_all(output_dir.join("blocked.png")).unwrap();
+
+    let result = run(&["-m", "decode", "-i", path(&input_dir), "-o", path(&output_dir)]);
+    failure_line(&result, "load", &input_dir.join("broken.ctex"), &output_dir.join("broken.png"));
+    failure_line(&result, "validate", &input_dir.join("odd.ctex"), &output_dir.join("odd.png"));
+    failure_line(&result, "decode", &input_dir.join("gas.ctex"), &output_dir.join("gas.png"));
+    failure_line(&result, "save", &input_dir.join("blocked.ctex"), &output_dir.join("blocked.png"));
+}
+
+#[test]
+fn verbose_errors_spread_the_report_over_lines() {
+    let dir = scratch_dir("verbose");
+    let input = dir.join("gas.ctex");
+    write_ctex(&input, PicaTexture::try_new(TextureFormat::Gas, 8, 8, vec![0; TextureFormat::Gas.expected_data_len(8, 8)]).unwrap());
+    let output = dir.join("gas.png");
+
+    let result = run(&["-m", "decode", "-i", path(&input), "-o", path(&output)]);
+    assert!(!result.status.success());
+    failure_line(&result, "decode", &input, &output);
+
+    let result = run(&["-m", "decode", "--verbose-errors", "-i", path(&input), "-o", path(&output)]);
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    for expected in [
+        format!("Failed to process '{}'\n", input.display()),
+        "  Stage:  decode\n".to_string(),
+        format!("  Input:  {}\n", input.display()),
+        format!("  Output: {}\n", output.display()),
+        "  Cause:  ".to_string(),
+    ] {
+        assert!(stderr.contains(&expected), "no {:?} in:\n{}", expected, stderr);
+    }
+}
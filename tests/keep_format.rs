@@ -0,0 +1,76 @@
+//! Decodes textures with `--sidecar` through the binary, edits the images and
+//! encodes them back with `--output-format keep`, checking the container
+//! fields come back as they were.
+
+mod common;
+
+use std::path::Path;
+
+use image::Rgba;
+use pica_convert::pica_texture::{encode_mip_chain, EncodeOptions, TextureFormat};
+use pica_convert::pica_texture::synthetic::synthetic_image;
+use pica_convert::serialization::{ctex::Ctex, NamedTexture, TextureContainer};
+
+use common::{path, run, scratch_dir};
+
+/// Writes a 32x32 RGB565 texture with 3 mip levels, named `UI_label`.
+fn write_original(path: &Path) -> NamedTexture {
+    let img = image::DynamicImage::ImageRgba8(synthetic_image(32, 32, 4));
+    let texture = encode_mip_chain(&img, &TextureFormat::RGB565, 3, &EncodeOptions::default()).unwrap();
+    let named = NamedTexture::new("UI_label", texture);
+    Ctex::default().write_file(path, std::slice::from_ref(&named)).unwrap();
+    named
+}
+
+/// Decodes `ctex` to `png` with `extra` arguments, and paints a pixel of it white.
+fn decode_and_edit(ctex: &Path, png: &Path, extra: &[&str]) {
+    let result = run(&[&["-m", "decode", "-i", path(ctex), "-o", path(png)], extra].concat());
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    let mut img = image::open(png).unwrap().to_rgba8();
+    img.put_pixel(3, 3, Rgba([255; 4]));
+    img.save(png).unwrap();
+}
+
+#[test]
+fn keep_reuses_the_sidecar() {
+    let dir = scratch_dir("sidecar");
+    let (original, png, output) = (dir.join("original.ctex"), dir.join("edited.png"), dir.join("edited.ctex"));
+    let named = write_original(&original);
+
+    decode_and_edit(&original, &png, &["--sidecar"]);
+    assert!(dir.join("edited.texture.json").is_file());
+
+    let result = run(&["-m", "encode", "-f", "keep", "-i", path(&png), "-o", path(&output)]);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    let read = Ctex::default().read_file(&output).unwrap().remove(0);
+    assert_eq!(read.name, named.name);
+    assert_eq!(read.texture.format(), named.texture.format());
+    assert_eq!(read.texture.mip_levels(), named.texture.mip_levels());
+    assert_eq!(read.texture.dimensions(), named.texture.dimensions());
+    assert_ne!(read.texture.data(), named.texture.data());
+
+    // Explicit flags win over the sidecar
+    let result = run(&["-m", "encode", "-f", "rgba8888", "--name", "UI_renamed", "-i", path(&png), "-o", path(&output)]);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    let read = Ctex::default().read_file(&output).unwrap().remove(0);
+    assert_eq!(read.name, "UI_renamed");
+    assert_eq!(read.texture.format(), &TextureFormat::RGBA8888);
+}
+
+#[test]
+fn keep_reuses_the_reference() {
+    let dir = scratch_dir("reference");
+    let (original, png, output) = (dir.join("original.ctex"), dir.join("edited.png"), dir.join("edited.ctex"));
+    let named = write_original(&original);
+
+    decode_and_edit(&original, &png, &[]);
+    let result = run(&["-m", "encode", "-f", "keep", "-i", path(&png), "-o", path(&output)]);
+    assert!(!result.status.success(), "keep needs a sidecar or reference");
+
+    let result = run(&["-m", "encode", "-f", "keep", "--reference", path(&original), "-i", path(&png), "-o", path(&output)]);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    let read = Ctex::default().read_file(&output).unwrap().remove(0);
+    assert_eq!(read.name, named.name);
+    assert_eq!(read.texture.format(), named.texture.format());
+    assert_eq!(read.texture.mip_levels(), named.texture.mip_levels());
+}
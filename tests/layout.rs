@@ -0,0 +1,120 @@
+//! Encodes and decodes textures with `--layout` through the binary and checks
+//! the linear data, what the ctex file records, and that decoding reads it back.
+
+mod common;
+
+use pica_convert::pica_texture::{encode_rgba_buffer, EncodeOptions, PicaTexture, TextureFormat, TextureLayout};
+use pica_convert::pica_texture::synthetic::synthetic_image;
+use pica_convert::pica_texture::util::{unswizzle, unswizzle_4bpp};
+use pica_convert::serialization::{ctex::Ctex, NamedTexture, TextureContainer};
+
+use common::{path, run, scratch_dir};
+
+#[test]
+fn both_layouts_decode_to_the_same_image() {
+    let dir = scratch_dir("formats");
+    let img = synthetic_image(32, 16, 3);
+    let input = dir.join("input.png");
+    img.save(&input).unwrap();
+
+    for format in [TextureFormat::RGB565, TextureFormat::RGBA8888, TextureFormat::L4] {
+        let tiled = encode_rgba_buffer(img.as_raw(), 32, 16, &format, &EncodeOptions::default()).unwrap();
+        let mut decoded = Vec::new();
+
+        for layout in ["tiled", "linear"] {
+            let ctex = dir.join(format!("{}_{}.ctex", format, layout));
+            let png = dir.join(format!("{}_{}.png", format, layout));
+            let result = run(&["-m", "encode", "-f", format.name(), "--layout", layout, "-i", path(&input), "-o", path(&ctex)]);
+            assert!(result.status.success(), "{} {}: {}", format, layout, String::from_utf8_lossy(&result.stderr));
+
+            // Only linear textures say so, and their data is the tiled data in rows
+            let xml = std::fs::read_to_string(&ctex).unwrap();
+            let texture = Ctex::default().read_file(&ctex).unwrap().remove(0).texture;
+            if layout == "linear" {
+                assert!(xml.contains(r#"Layout="Linear""#), "{}", format);
+                assert_eq!(texture.layout(), TextureLayout::Linear);
+                let rows = match format.bits_per_pixel() {
+                    4 => unswizzle_4bpp(tiled.data(), 32, 16),
+                    bits => unswizzle(tiled.data(), 32, 16, bits as usize / 8),
+                };
+                assert_eq!(texture.data(), rows, "{}", format);
+            } else {
+                assert!(!xml.contains("Layout="), "{}", format);
+                assert_eq!(texture.data(), tiled.data(), "{}", format);
+            }
+
+            let result = run(&["-m", "decode", "-i", path(&ctex), "-o", path(&png)]);
+            assert!(result.status.success(), "{} {}: {}", format, layout, String::from_utf8_lossy(&result.stderr));
+            decoded.push(image::open(&png).unwrap().to_rgba8());
+        }
+
+        assert_eq!(decoded[0], decoded[1], "{}", format);
+    }
+}
+
+#[test]
+fn keep_reuses_the_layout() {
+    let dir = scratch_dir("keep");
+    let input = dir.join("input.png");
+    synthetic_image(16, 16, 9).save(&input).unwrap();
+    let (linear, decoded, reencoded) = (dir.join("linear.ctex"), dir.join("decoded.png"), dir.join("reencoded.ctex"));
+
+    assert!(run(&["-m", "encode", "-f", "la88", "--layout", "linear", "-i", path(&input), "-o", path(&linear)]).status.success());
+    assert!(run(&["-m", "decode", "--sidecar", "-i", path(&linear), "-o", path(&decoded)]).status.success());
+    let result = run(&["-m", "encode", "-f", "keep", "-i", path(&decoded), "-o", path(&reencoded)]);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+
+    let texture = Ctex::default().read_file(&reencoded).unwrap().remove(0).texture;
+    assert_eq!(texture.layout(), TextureLayout::Linear);
+    assert_eq!(texture.data(), Ctex::default().read_file(&linear).unwrap().remove(0).texture.data());
+}
+
+#[test]
+fn etc1_and_other_modes_reject_linear() {
+    let dir = scratch_dir("rejected");
+    let input = dir.join("input.png");
+    synthetic_image(16, 16, 1).save(&input).unwrap();
+    let output = dir.join("output.ctex");
+
+    let result = run(&["-m", "encode", "-f", "etc1", "--layout", "linear", "-i", path(&input), "-o", path(&output)]);
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("can only be stored tiled"), "{}", String::from_utf8_lossy(&result.stderr));
+    assert!(!output.exists());
+
+    let result = run(&["-m", "info", "--layout", "linear", "-i", path(&input)]);
+    assert!(String::from_utf8_lossy(&result.stderr).contains("--layout takes encode, decode or atlas mode"));
+}
+
+#[test]
+fn decode_layout_reads_rows_the_container_doesnt_mark() {
+    let dir = scratch_dir("decode");
+    let img = synthetic_image(32, 16, 6);
+    let tiled = encode_rgba_buffer(img.as_raw(), 32, 16, &TextureFormat::RGBA4444, &EncodeOptions::default()).unwrap();
+    let linear_options = EncodeOptions { layout: TextureLayout::Linear, ..Default::default() };
+    let linear = encode_rgba_buffer(img.as_raw(), 32, 16, &TextureFormat::RGBA4444, &linear_options).unwrap();
+    let expected = dir.join("expected.png");
+    pica_convert::pica_texture::decode_texture(&tiled, &Default::default()).unwrap().save(&expected).unwrap();
+
+    // A headerless dump of rows
+    let dump = dir.join("rows.bin");
+    std::fs::write(&dump, linear.data()).unwrap();
+    let output = dir.join("dump.png");
+    let result = run(&["-m", "decode", "--input-format", "raw", "--width", "32", "--height", "16", "-f", "rgba4444", "--layout", "linear",
+        "-i", path(&dump), "-o", path(&output)]);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert_eq!(image::open(&output).unwrap().to_rgba8(), image::open(&expected).unwrap().to_rgba8());
+
+    // Rows in a ctex file that says nothing about its layout
+    let unmarked = dir.join("unmarked.ctex");
+    let texture = PicaTexture::try_new(TextureFormat::RGBA4444, 32, 16, linear.data().to_vec()).unwrap();
+    Ctex::default().write_file(&unmarked, &[NamedTexture::new("rows", texture)]).unwrap();
+    let output = dir.join("unmarked.png");
+    let result = run(&["-m", "decode", "--layout", "linear", "-i", path(&unmarked), "-o", path(&output)]);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert_eq!(image::open(&output).unwrap().to_rgba8(), image::open(&expected).unwrap().to_rgba8());
+
+    // Without --layout, the rows are read as tiles and scrambled
+    let result = run(&["-m", "decode", "-i", path(&unmarked), "-o", path(&output)]);
+    assert!(result.status.success());
+    assert_ne!(image::open(&output).unwrap().to_rgba8(), image::open(&expected).unwrap().to_rgba8());
+}
@@ -0,0 +1,70 @@
+//! Encodes directories on several threads through the binary, and checks
+//! each file's messages come out together, and with `--ordered-output`, in
+//! input order.
+
+mod common;
+
+use std::path::Path;
+
+use pica_convert::pica_texture::synthetic::synthetic_image;
+
+use common::{path, run, scratch_dir};
+
+/// Fills `dir` with images that each log a resize before they're encoded,
+/// and a broken one that fails.
+fn write_inputs(dir: &Path) -> Vec<String> {
+    std::fs::create_dir_all(dir).unwrap();
+    let stems: Vec<String> = (0..24).map(|index| format!("texture_{:02}", index)).collect();
+    for (seed, stem) in stems.iter().enumerate() {
+        synthetic_image(24, 12, seed as u32).save(dir.join(format!("{}.png", stem))).unwrap();
+    }
+    std::fs::write(dir.join("texture_05_broken.png"), b"not a png").unwrap();
+    stems
+}
+
+/// Checks every input's three lines come out one after another.
+fn assert_grouped(stderr: &str, input_dir: &Path, output_dir: &Path, stems: &[String]) {
+    let lines: Vec<&str> = stderr.lines().collect();
+    for stem in stems {
+        let resize = format!("Resizing image '{}'", input_dir.join(format!("{}.png", stem)).display());
+        let start = lines.iter().position(|line| line.starts_with(&resize)).unwrap_or_else(|| panic!("no {:?} in:\n{}", resize, stderr));
+        assert_eq!(lines[start + 1], "Encoding as RGB565", "{}", stderr);
+        assert_eq!(lines[start + 2], format!("Encoded file written to '{}'", output_dir.join(format!("{}.ctex", stem)).display()), "{}", stderr);
+    }
+}
+
+#[test]
+fn each_file_logs_together() {
+    let dir = scratch_dir("grouped");
+    let (input_dir, output_dir) = (dir.join("in"), dir.join("out"));
+    let stems = write_inputs(&input_dir);
+
+    let result = run(&["-m", "encode", "-f", "rgb565", "--resize", "--threads", "4", "-i", path(&input_dir), "-o", path(&output_dir)]);
+    assert_grouped(&String::from_utf8_lossy(&result.stderr), &input_dir, &output_dir, &stems);
+}
+
+#[test]
+fn ordered_output_is_the_same_every_run() {
+    let dir = scratch_dir("ordered");
+    let (input_dir, output_dir) = (dir.join("in"), dir.join("out"));
+    let stems = write_inputs(&input_dir);
+
+    let mut logs = Vec::new();
+    for threads in ["1", "3", "8"] {
+        let result = run(&["-m", "encode", "-f", "rgb565", "--resize", "--ordered-output", "--threads", threads, "-i", path(&input_dir), "-o", path(&output_dir)]);
+        logs.push(String::from_utf8_lossy(&result.stderr).into_owned());
+    }
+    assert_eq!(logs[0], logs[1]);
+    assert_eq!(logs[0], logs[2]);
+    assert_grouped(&logs[0], &input_dir, &output_dir, &stems);
+
+    // Files are logged in the order they're listed, failures included
+    let files: Vec<&str> = logs[0].lines()
+        .filter_map(|line| line.strip_prefix("Resizing image '").or_else(|| line.strip_prefix("[load] Failed to process '")))
+        .map(|rest| &rest[..rest.find('\'').unwrap()])
+        .collect();
+    let mut sorted = files.clone();
+    sorted.sort();
+    assert_eq!(files, sorted);
+    assert_eq!(files.len(), stems.len() + 1);
+}
@@ -0,0 +1,37 @@
+//! Pads images to powers of two through the binary, and checks decoding
+//! crops them back to their original size unless `--keep-padding` is given.
+
+mod common;
+
+use pica_convert::pica_texture::synthetic::synthetic_image;
+use pica_convert::serialization::{ctex::Ctex, TextureContainer};
+
+use common::{path, run, scratch_dir};
+
+#[test]
+fn padded_images_decode_at_their_original_size() {
+    let dir = scratch_dir("round_trip");
+    let (input, ctex) = (dir.join("input.png"), dir.join("padded.ctex"));
+    let img = synthetic_image(200, 120, 5);
+    img.save(&input).unwrap();
+
+    let result = run(&["-m", "encode", "-f", "rgba8888", "--resize", "--resize-mode", "pad", "-i", path(&input), "-o", path(&ctex)]);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    let texture = Ctex::default().read_file(&ctex).unwrap().remove(0).texture;
+    assert_eq!(texture.dimensions(), (256, 128));
+
+    // Cropped back by default, so the image comes back exactly
+    let output = dir.join("cropped.png");
+    let result = run(&["-m", "decode", "-i", path(&ctex), "-o", path(&output)]);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert_eq!(image::open(&output).unwrap().to_rgba8(), img);
+
+    // Or left on its transparent canvas
+    let output = dir.join("padded.png");
+    let result = run(&["-m", "decode", "--keep-padding", "-i", path(&ctex), "-o", path(&output)]);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    let padded = image::open(&output).unwrap().to_rgba8();
+    assert_eq!(padded.dimensions(), (256, 128));
+    assert_eq!(image::imageops::crop_imm(&padded, 0, 0, 200, 120).to_image(), img);
+    assert!(padded.enumerate_pixels().filter(|(x, y, _)| *x >= 200 || *y >= 120).all(|(_, _, pixel)| pixel.0 == [0; 4]));
+}
@@ -0,0 +1,121 @@
+//! Decodes raw texture dumps through the binary, using encoder output with
+//! no container around it as the dump, and checks decoding to raw RGBA.
+
+mod common;
+
+use pica_convert::pica_texture::{decode_texture, encode_rgba_buffer, DecodeOptions, EncodeOptions, PicaTexture, TextureFormat};
+use pica_convert::pica_texture::synthetic::synthetic_image;
+use pica_convert::serialization::{ctex::Ctex, NamedTexture, TextureContainer};
+
+use common::{run_with_stdin, scratch_dir};
+
+fn encode(format: &TextureFormat) -> PicaTexture {
+    let img = synthetic_image(32, 16, 7);
+    encode_rgba_buffer(img.as_raw(), 32, 16, format, &EncodeOptions::default()).unwrap()
+}
+
+fn expected_image(texture: &PicaTexture) -> image::RgbaImage {
+    decode_texture(texture, &DecodeOptions::default()).unwrap().to_rgba8()
+}
+
+#[test]
+fn raw_dumps_decode_like_their_texture() {
+    let dir = scratch_dir("files");
+    for format in TextureFormat::ALL.into_iter().filter(|f| f.is_available() && f.can_decode()) {
+        let texture = encode(&format);
+        let dump = dir.join(format!("{}.bin", format));
+        let output = dir.join(format!("{}.png", format));
+        std::fs::write(&dump, texture.data()).unwrap();
+
+        let result = run_with_stdin(&["-m", "decode", "--input-format", "raw", "--width", "32", "--height", "16", "-f", format.name(),
+            "-i", dump.to_str().unwrap(), "-o", output.to_str().unwrap()], &[]);
+        assert!(result.status.success(), "{}: {}", format, String::from_utf8_lossy(&result.stderr));
+        assert_eq!(image::open(&output).unwrap().to_rgba8(), expected_image(&texture), "{}", format);
+    }
+}
+
+#[test]
+fn raw_dumps_decode_from_stdin() {
+    let dir = scratch_dir("stdin");
+    let texture = encode(&TextureFormat::RGB565);
+    let output = dir.join("dump.png");
+
+    let result = run_with_stdin(&["-m", "decode", "--input-format", "raw", "--width", "32", "--height", "16", "-f", "rgb565",
+        "-i", "-", "-o", output.to_str().unwrap()], texture.data());
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert_eq!(image::open(&output).unwrap().to_rgba8(), expected_image(&texture));
+}
+
+#[test]
+fn raw_dumps_must_be_the_expected_length() {
+    let dir = scratch_dir("length");
+    let texture = encode(&TextureFormat::RGBA4444);
+    let output = dir.join("dump.png");
+
+    for data in [&texture.data()[..texture.data().len() - 1], &[texture.data(), &[0]].concat()] {
+        let result = run_with_stdin(&["-m", "decode", "--input-format", "raw", "--width", "32", "--height", "16", "-f", "rgba4444",
+            "-i", "-", "-o", output.to_str().unwrap()], data);
+        assert!(!result.status.success());
+        assert!(String::from_utf8_lossy(&result.stderr).contains("takes 1024 bytes"), "{}", String::from_utf8_lossy(&result.stderr));
+    }
+    assert!(!output.exists());
+}
+
+#[test]
+fn raw_input_needs_consistent_parameters() {
+    let dir = scratch_dir("parameters");
+    let output = dir.join("dump.png");
+    let output = output.to_str().unwrap();
+
+    let cases: [(&[&str], &str); 5] = [
+        (&["-m", "decode", "--input-format", "raw", "--width", "32", "-f", "l8"], "--height"),
+        (&["-m", "decode", "--input-format", "raw", "--width", "32", "--height", "16"], "--output-format"),
+        (&["-m", "decode", "--input-format", "raw", "--width", "32", "--height", "16", "-f", "keep"], "--output-format"),
+        (&["-m", "encode", "--input-format", "raw", "--width", "32", "--height", "16", "-f", "l8"], "decode mode"),
+        (&["-m", "decode", "--width", "32", "--height", "16", "-f", "l8"], "--input-format raw"),
+    ];
+    for (args, message) in cases {
+        let result = run_with_stdin(&[args, &["-i", "-", "-o", output]].concat(), &[0; 512]);
+        assert!(!result.status.success(), "{:?}", args);
+        assert!(String::from_utf8_lossy(&result.stderr).contains(message), "{:?}: {}", args, String::from_utf8_lossy(&result.stderr));
+    }
+}
+
+#[test]
+fn raw_output_matches_the_png() {
+    let dir = scratch_dir("raw_output");
+    let input = dir.join("texture.ctex");
+    Ctex::default().write_file(&input, &[NamedTexture::new("texture", encode(&TextureFormat::LA44))]).unwrap();
+    let input = input.to_str().unwrap();
+
+    for mirror in [&[][..], &["--mirror"]] {
+        let png = dir.join("texture.png");
+        let raw = dir.join("texture.rgba");
+        for (output, extra) in [(&png, &[][..]), (&raw, &["--raw-output"])] {
+            let result = run_with_stdin(&[&["-m", "decode", "-i", input, "-o", output.to_str().unwrap()], extra, mirror].concat(), &[]);
+            assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+        }
+
+        let png = image::open(&png).unwrap().to_rgba8();
+        let raw = std::fs::read(&raw).unwrap();
+        assert_eq!(raw.len(), 32 * 16 * 4);
+        assert_eq!(raw[..4], png.get_pixel(0, 0).0, "{:?}", mirror);
+        assert_eq!(raw, png.into_raw());
+    }
+}
+
+#[test]
+fn raw_output_goes_to_stdout() {
+    let texture = encode(&TextureFormat::RGB565);
+    let result = run_with_stdin(&["-m", "decode", "--input-format", "raw", "--width", "32", "--height", "16", "-f", "rgb565",
+        "-i", "-", "-o", "-", "--raw-output"], texture.data());
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert_eq!(result.stdout, expected_image(&texture).into_raw());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("32x16"));
+
+    // Only raw pixels have a way to stdout
+    let result = run_with_stdin(&["-m", "decode", "--input-format", "raw", "--width", "32", "--height", "16", "-f", "rgb565",
+        "-i", "-", "-o", "-"], texture.data());
+    assert!(!result.status.success());
+    assert!(result.stdout.is_empty());
+}
@@ -0,0 +1,85 @@
+//! Runs bulk conversions with failing inputs through the binary, fixes them,
+//! and retries them with `--retry-failed`, checking the failure manifest
+//! each run leaves behind.
+
+mod common;
+
+use std::path::Path;
+use std::process::Output;
+
+use pica_convert::batch::FailureManifest;
+use pica_convert::pica_texture::synthetic::synthetic_image;
+use pica_convert::stage::Stage;
+
+use common::{path, run, scratch_dir};
+
+fn read_manifest(path: &Path) -> FailureManifest {
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+/// Lists the inputs whose outputs a run wrote.
+fn written(result: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&result.stderr).lines()
+        .filter_map(|line| line.strip_prefix("Encoded file written to '"))
+        .map(|rest| Path::new(&rest[..rest.find('\'').unwrap()]).file_stem().unwrap().to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn retries_convert_only_the_failed_files() {
+    let dir = scratch_dir("encode");
+    let (input_dir, output_dir) = (dir.join("in"), dir.join("out"));
+    std::fs::create_dir_all(&input_dir).unwrap();
+    synthetic_image(8, 8, 0).save(input_dir.join("good.png")).unwrap();
+    std::fs::write(input_dir.join("broken_a.png"), b"not a png").unwrap();
+    std::fs::write(input_dir.join("broken_b.png"), b"not a png either").unwrap();
+
+    let encode = ["-m", "encode", "-f", "rgb565", "-i", path(&input_dir), "-o", path(&output_dir)];
+    let manifest_path = output_dir.join(FailureManifest::FILE_NAME);
+
+    let result = run(&encode);
+    assert_eq!(written(&result), ["good"]);
+    let manifest = read_manifest(&manifest_path);
+    assert_eq!((manifest.version, manifest.mode.as_str()), (1, "encode"));
+    let failed: Vec<&Path> = manifest.failures.iter().map(|failure| failure.input.as_path()).collect();
+    assert_eq!(failed, [input_dir.join("broken_a.png"), input_dir.join("broken_b.png")]);
+    assert_eq!(manifest.failures[0].output, output_dir.join("broken_a.ctex"));
+    assert_eq!(manifest.failures[0].stage, Stage::Load);
+    assert!(manifest.failures[0].error.contains("Failed to decode image"), "{}", manifest.failures[0].error);
+
+    // The manifest isn't taken for an input when the outputs are decoded
+    let decoded = run(&["-m", "decode", "-i", path(&output_dir), "-o", path(&dir.join("png"))]);
+    assert!(!String::from_utf8_lossy(&decoded.stderr).contains(FailureManifest::FILE_NAME));
+
+    // Fixing one input, the retry converts only it and lists what still fails
+    synthetic_image(8, 8, 1).save(input_dir.join("broken_a.png")).unwrap();
+    let retry = [&encode[..], &["--retry-failed", path(&manifest_path)]].concat();
+    let result = run(&retry);
+    assert_eq!(written(&result), ["broken_a"]);
+    let manifest = read_manifest(&manifest_path);
+    assert_eq!(manifest.failures.len(), 1);
+    assert_eq!(manifest.failures[0].input, input_dir.join("broken_b.png"));
+
+    // Fixing the other, the manifest goes away
+    synthetic_image(8, 8, 2).save(input_dir.join("broken_b.png")).unwrap();
+    let result = run(&retry);
+    assert_eq!(written(&result), ["broken_b"]);
+    assert!(!manifest_path.exists());
+    assert!(output_dir.join("broken_b.ctex").is_file());
+}
+
+#[test]
+fn manifests_are_retried_in_their_own_mode() {
+    let dir = scratch_dir("mode");
+    let (input_dir, output_dir) = (dir.join("in"), dir.join("out"));
+    std::fs::create_dir_all(&input_dir).unwrap();
+    std::fs::write(input_dir.join("broken.png"), b"not a png").unwrap();
+
+    run(&["-m", "encode", "-f", "rgb565", "-i", path(&input_dir), "-o", path(&output_dir)]);
+    let manifest_path = output_dir.join(FailureManifest::FILE_NAME);
+    assert!(manifest_path.is_file());
+
+    let result = run(&["-m", "decode", "-i", path(&input_dir), "-o", path(&output_dir), "--retry-failed", path(&manifest_path)]);
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("failed in encode mode, not decode mode"));
+}
@@ -0,0 +1,141 @@
+//! Checks the byte and texel order of the uncompressed formats against how
+//! Citra reads them, as a reference independent of this crate's own decoders.
+//!
+//! `citra_texel` follows `LookupTexture` in Citra's
+//! `video_core/texture/texture_decode.cpp` and the `Decode*` helpers in
+//! `common/color.h`: rows of 8x8 tiles, texels within a tile in Morton order
+//! (`MortonInterleave`), multi-byte texels read as little-endian values, and
+//! 4-bit texels taking the low nibble first. Only the channels a format
+//! stores are compared, since Citra fills the others differently for display.
+//! Which way up the rows are shown is left to the flip options, and isn't
+//! checked here.
+
+use image::{Rgba, RgbaImage};
+use pica_convert::pica_texture::{decode_raw, DecodeOptions, TextureFormat};
+use pica_convert::pica_texture::encode::{encode_a8, encode_hl8, encode_l8, encode_la88};
+use pica_convert::pica_texture::synthetic::synthetic_image;
+
+const WIDTH: u32 = 16;
+const HEIGHT: u32 = 24;
+
+/// The texel index of (`x`, `y`) in tiled data, as Citra's `MortonInterleave`
+/// plus the offset of its tile.
+fn texel_index(x: u32, y: u32, width: u32) -> usize {
+    const X_LUT: [u32; 8] = [0x00, 0x01, 0x04, 0x05, 0x10, 0x11, 0x14, 0x15];
+    const Y_LUT: [u32; 8] = [0x00, 0x02, 0x08, 0x0A, 0x20, 0x22, 0x28, 0x2A];
+    let tile = (y / 8) * (width / 8) + x / 8;
+    (tile * 64 + X_LUT[x as usize % 8] + Y_LUT[y as usize % 8]) as usize
+}
+
+fn expand4(v: u16) -> u8 {
+    (v as u8 & 0xF) * 17
+}
+
+fn expand5(v: u16) -> u8 {
+    let v = v as u8 & 0x1F;
+    (v << 3) | (v >> 2)
+}
+
+fn expand6(v: u16) -> u8 {
+    let v = v as u8 & 0x3F;
+    (v << 2) | (v >> 4)
+}
+
+/// The texel at (`x`, `y`) as Citra decodes it, with `None` for channels the
+/// format doesn't store.
+fn citra_texel(data: &[u8], x: u32, y: u32, width: u32, format: TextureFormat) -> [Option<u8>; 4] {
+    let i = texel_index(x, y, width);
+    let u16_at = |i: usize| u16::from_le_bytes([data[i * 2], data[i * 2 + 1]]);
+    let nibble = |i: usize| (data[i / 2] >> ((i % 2) * 4)) & 0xF;
+    match format {
+        TextureFormat::RGBA8888 => {
+            let b = &data[i * 4..i * 4 + 4];
+            [Some(b[3]), Some(b[2]), Some(b[1]), Some(b[0])]
+        }
+        TextureFormat::RGB888 => {
+            let b = &data[i * 3..i * 3 + 3];
+            [Some(b[2]), Some(b[1]), Some(b[0]), None]
+        }
+        TextureFormat::RGBA5551 => {
+            let v = u16_at(i);
+            [Some(expand5(v >> 11)), Some(expand5(v >> 6)), Some(expand5(v >> 1)), Some((v & 1) as u8 * 255)]
+        }
+        TextureFormat::RGB565 => {
+            let v = u16_at(i);
+            [Some(expand5(v >> 11)), Some(expand6(v >> 5)), Some(expand5(v)), None]
+        }
+        TextureFormat::RGBA4444 => {
+            let v = u16_at(i);
+            [Some(expand4(v >> 12)), Some(expand4(v >> 8)), Some(expand4(v >> 4)), Some(expand4(v))]
+        }
+        // IA8: intensity in the high byte, alpha in the low byte
+        TextureFormat::LA88 => {
+            let (a, l) = (data[i * 2], data[i * 2 + 1]);
+            [Some(l), Some(l), Some(l), Some(a)]
+        }
+        // RG8: red in the high byte, green in the low byte
+        TextureFormat::HL8 => [Some(data[i * 2 + 1]), Some(data[i * 2]), None, None],
+        TextureFormat::L8 => [Some(data[i]), Some(data[i]), Some(data[i]), None],
+        TextureFormat::A8 => [None, None, None, Some(data[i])],
+        // IA4: intensity in the high nibble, alpha in the low nibble
+        TextureFormat::LA44 => {
+            let (l, a) = (expand4((data[i] >> 4) as u16), expand4(data[i] as u16));
+            [Some(l), Some(l), Some(l), Some(a)]
+        }
+        TextureFormat::L4 => {
+            let l = expand4(nibble(i) as u16);
+            [Some(l), Some(l), Some(l), None]
+        }
+        TextureFormat::A4 => [None, None, None, Some(expand4(nibble(i) as u16))],
+        _ => unreachable!("{} isn't an uncompressed color format", format),
+    }
+}
+
+/// Asserts every texel of `decoded` matches what Citra reads from `data`.
+fn assert_matches_citra(data: &[u8], decoded: &RgbaImage, format: TextureFormat) {
+    for (x, y, pixel) in decoded.enumerate_pixels() {
+        let expected = citra_texel(data, x, y, decoded.width(), format);
+        for (channel, (&actual, expected)) in pixel.0.iter().zip(expected).enumerate() {
+            if let Some(expected) = expected {
+                assert_eq!(actual, expected, "{} channel {} at ({}, {})", format, channel, x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn decoders_follow_citra() {
+    for format in [
+        TextureFormat::RGBA8888, TextureFormat::RGB888, TextureFormat::RGBA5551, TextureFormat::RGB565,
+        TextureFormat::RGBA4444, TextureFormat::LA88, TextureFormat::HL8, TextureFormat::L8,
+        TextureFormat::A8, TextureFormat::LA44, TextureFormat::L4, TextureFormat::A4,
+    ] {
+        let len = format.expected_data_len(WIDTH, HEIGHT);
+        let data: Vec<u8> = (0..len).map(|i| (i * 157 + i / 7) as u8).collect();
+        let decoded = decode_raw(&data, WIDTH, HEIGHT, &format, &DecodeOptions::default()).unwrap().to_rgba8();
+        assert_matches_citra(&data, &decoded, format);
+    }
+}
+
+#[test]
+fn encoders_write_what_citra_reads() {
+    let img = synthetic_image(WIDTH, HEIGHT, 7);
+    let luminance = |p: &Rgba<u8>| ((p[0] as u32 + p[1] as u32 + p[2] as u32) / 3) as u8;
+
+    for (format, data) in [
+        (TextureFormat::LA88, encode_la88(&img, WIDTH, HEIGHT)),
+        (TextureFormat::HL8, encode_hl8(&img, WIDTH, HEIGHT)),
+        (TextureFormat::L8, encode_l8(&img, WIDTH, HEIGHT)),
+        (TextureFormat::A8, encode_a8(&img, WIDTH, HEIGHT)),
+    ] {
+        for (x, y, p) in img.enumerate_pixels() {
+            let expected = match format {
+                TextureFormat::LA88 => [Some(luminance(p)), Some(luminance(p)), Some(luminance(p)), Some(p[3])],
+                TextureFormat::HL8 => [Some(p[0]), Some(p[1]), None, None],
+                TextureFormat::L8 => [Some(luminance(p)), Some(luminance(p)), Some(luminance(p)), None],
+                _ => [None, None, None, Some(p[3])],
+            };
+            assert_eq!(citra_texel(&data, x, y, WIDTH, format), expected, "{} at ({}, {})", format, x, y);
+        }
+    }
+}